@@ -0,0 +1,339 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Skeleton of an Android application rendering the triangle of the windowing chapter.
+//!
+//! Android doesn't start executables: the activity loads a shared library and calls its
+//! `android_main` function, through the glue of the `android_activity` crate. That's why this
+//! file is also compiled as part of the `chapter_code` library on Android (see `lib.rs`), which
+//! is built as a `cdylib`.
+//!
+//! The activity hands out an `ANativeWindow` when it's shown (`InitWindow`) and takes it back
+//! when it's hidden (`TerminateWindow`), for example when the user switches to another app. The
+//! surface and everything depending on it are created and destroyed accordingly, the surface
+//! being created with `VK_KHR_android_surface` from the native window.
+//!
+//! Build and run it on a device or emulator with [cargo-apk](https://crates.io/crates/cargo-apk):
+//!
+//! ```bash
+//! cargo apk run --lib --target aarch64-linux-android
+//! ```
+//!
+//! The rendering ignores the rotation of the device (`VK_KHR_swapchain` pre-transform), which a
+//! real application should handle to avoid an extra copy by the compositor.
+
+#[cfg(target_os = "android")]
+mod example {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use android_activity::{AndroidApp, MainEvent, PollEvent};
+    use chapter_code::shaders::static_triangle;
+    use chapter_code::vulkano_objects::allocators::Allocators;
+    use chapter_code::vulkano_objects::instance::get_instance;
+    use chapter_code::{vulkano_objects, Vertex2d};
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+    use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+    use vulkano::image::{ImageUsage, SwapchainImage};
+    use vulkano::instance::Instance;
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+    use vulkano::swapchain::{
+        self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
+    };
+    use vulkano::sync::future::FenceSignalFuture;
+    use vulkano::sync::{self, FlushError, GpuFuture};
+
+    type Fence = Arc<FenceSignalFuture<Box<dyn GpuFuture>>>;
+
+    /// Everything that depends on the native window, dropped when the activity takes it back.
+    struct Renderer {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        swapchain: Arc<Swapchain>,
+        allocators: Allocators,
+        command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+        fences: Vec<Option<Fence>>,
+        previous_fence_i: u32,
+        recreate_swapchain: bool,
+    }
+
+    impl Renderer {
+        fn new(instance: Arc<Instance>, app: &AndroidApp) -> Option<Self> {
+            let window = app.native_window()?;
+            let window_size = [window.width() as u32, window.height() as u32];
+
+            // The surface keeps a reference to the window, so that the window outlives it.
+            let surface = unsafe {
+                Surface::from_android(
+                    instance.clone(),
+                    window.ptr().as_ptr(),
+                    Some(Arc::new(window)),
+                )
+            }
+            .expect("failed to create a surface from the native window");
+
+            let device_extensions = DeviceExtensions {
+                khr_swapchain: true,
+                ..DeviceExtensions::empty()
+            };
+
+            let (physical_device, queue_family_index) =
+                vulkano_objects::physical_device::select_physical_device(
+                    &instance,
+                    surface.clone(),
+                    &device_extensions,
+                );
+
+            let (device, mut queues) = Device::new(
+                physical_device.clone(),
+                DeviceCreateInfo {
+                    queue_create_infos: vec![QueueCreateInfo {
+                        queue_family_index,
+                        ..Default::default()
+                    }],
+                    enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                        &physical_device,
+                        &device_extensions,
+                    ),
+                    ..Default::default()
+                },
+            )
+            .expect("failed to create device");
+
+            let queue = queues.next().unwrap();
+
+            let caps = physical_device
+                .surface_capabilities(&surface, Default::default())
+                .expect("failed to get surface capabilities");
+
+            // Android devices usually only support `Inherit`.
+            let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+            let image_format = Some(
+                physical_device
+                    .surface_formats(&surface, Default::default())
+                    .unwrap()[0]
+                    .0,
+            );
+
+            let (swapchain, images) = Swapchain::new(
+                device.clone(),
+                surface,
+                SwapchainCreateInfo {
+                    min_image_count: caps.min_image_count,
+                    image_format,
+                    image_extent: caps.current_extent.unwrap_or(window_size),
+                    image_usage: ImageUsage::COLOR_ATTACHMENT,
+                    composite_alpha,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            let mut renderer = Renderer {
+                allocators: Allocators::new(device.clone()),
+                device,
+                queue,
+                swapchain,
+                command_buffers: Vec::new(),
+                fences: vec![None; images.len()],
+                previous_fence_i: 0,
+                recreate_swapchain: false,
+            };
+            renderer.create_command_buffers(&images);
+
+            Some(renderer)
+        }
+
+        fn create_command_buffers(&mut self, images: &[Arc<SwapchainImage>]) {
+            let render_pass = vulkano_objects::render_pass::create_render_pass(
+                self.device.clone(),
+                self.swapchain.clone(),
+            )
+            .expect("failed to create render pass");
+            let framebuffers =
+                vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+                    images,
+                    render_pass.clone(),
+                );
+
+            let pipeline = vulkano_objects::pipeline::create_pipeline(
+                self.device.clone(),
+                static_triangle::vs::load(self.device.clone())
+                    .expect("failed to create shader module"),
+                static_triangle::fs::load(self.device.clone())
+                    .expect("failed to create shader module"),
+                render_pass,
+            )
+            .expect("failed to create pipeline");
+
+            let vertex_buffer = Buffer::from_iter(
+                &self.allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::VERTEX_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::Upload,
+                    ..Default::default()
+                },
+                [
+                    Vertex2d {
+                        position: [-0.5, -0.5],
+                    },
+                    Vertex2d {
+                        position: [0.0, 0.5],
+                    },
+                    Vertex2d {
+                        position: [0.5, -0.25],
+                    },
+                ],
+            )
+            .unwrap();
+
+            self.command_buffers =
+                vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+                    &self.allocators,
+                    self.queue.clone(),
+                    pipeline,
+                    &framebuffers,
+                    vertex_buffer,
+                    vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+                );
+        }
+
+        fn render(&mut self) {
+            if self.recreate_swapchain {
+                self.recreate_swapchain = false;
+
+                let caps = self
+                    .device
+                    .physical_device()
+                    .surface_capabilities(self.swapchain.surface(), Default::default())
+                    .unwrap();
+                let (new_swapchain, new_images) = self
+                    .swapchain
+                    .recreate(SwapchainCreateInfo {
+                        image_extent: caps
+                            .current_extent
+                            .unwrap_or_else(|| self.swapchain.image_extent()),
+                        ..self.swapchain.create_info()
+                    })
+                    .expect("failed to recreate swapchain");
+                self.swapchain = new_swapchain;
+                self.create_command_buffers(&new_images);
+            }
+
+            let (image_i, suboptimal, acquire_future) =
+                match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                    Ok(r) => r,
+                    Err(AcquireError::OutOfDate) => {
+                        self.recreate_swapchain = true;
+                        return;
+                    }
+                    Err(e) => panic!("failed to acquire next image: {e}"),
+                };
+
+            if suboptimal {
+                self.recreate_swapchain = true;
+            }
+
+            if let Some(image_fence) = &self.fences[image_i as usize] {
+                image_fence.wait(None).unwrap();
+            }
+
+            let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+                None => {
+                    let mut now = sync::now(self.device.clone());
+                    now.cleanup_finished();
+
+                    now.boxed()
+                }
+                Some(fence) => fence.boxed(),
+            };
+
+            let future = previous_future
+                .join(acquire_future)
+                .then_execute(
+                    self.queue.clone(),
+                    self.command_buffers[image_i as usize].clone(),
+                )
+                .unwrap()
+                .then_swapchain_present(
+                    self.queue.clone(),
+                    SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+                )
+                .boxed()
+                .then_signal_fence_and_flush();
+
+            self.fences[image_i as usize] = match future {
+                Ok(value) => Some(Arc::new(value)),
+                Err(FlushError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    None
+                }
+                Err(e) => {
+                    println!("failed to flush future: {e}");
+                    None
+                }
+            };
+
+            self.previous_fence_i = image_i;
+        }
+    }
+
+    pub fn main(app: AndroidApp) {
+        let instance = get_instance().expect("failed to create instance");
+
+        let mut renderer: Option<Renderer> = None;
+        let mut destroyed = false;
+
+        while !destroyed {
+            // Don't wake up until there's an event while there is nothing to render to.
+            let timeout = renderer.as_ref().map(|_| Duration::ZERO);
+
+            app.poll_events(timeout, |event| {
+                if let PollEvent::Main(event) = event {
+                    match event {
+                        MainEvent::InitWindow {} => {
+                            renderer = Renderer::new(instance.clone(), &app);
+                        }
+                        // The window must not be used after this event, so the surface is
+                        // destroyed right away.
+                        MainEvent::TerminateWindow {} => renderer = None,
+                        MainEvent::WindowResized {} => {
+                            if let Some(renderer) = &mut renderer {
+                                renderer.recreate_swapchain = true;
+                            }
+                        }
+                        MainEvent::Destroy => destroyed = true,
+                        _ => {}
+                    }
+                }
+            });
+
+            if let Some(renderer) = &mut renderer {
+                renderer.render();
+            }
+        }
+    }
+}
+
+/// Entry point called by the `android_activity` glue once the activity is created.
+#[cfg(target_os = "android")]
+#[no_mangle]
+fn android_main(app: android_activity::AndroidApp) {
+    example::main(app);
+}
+
+// Unused when this file is compiled as part of the library.
+#[cfg_attr(target_os = "android", allow(dead_code))]
+fn main() {
+    println!("This example only runs on Android, see the instructions at the top of the file");
+}
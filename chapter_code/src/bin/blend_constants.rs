@@ -0,0 +1,335 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Blends with a second color written by the fragment shader (dual-source blending), or with
+//! blend constants when the device doesn't support it.
+//!
+//! With dual-source blending, the fragment shader writes two colors to the same attachment, with
+//! `index = 0` and `index = 1`. The second one can only be used as a factor of the blend
+//! equation, through `Src1Color` and `Src1Alpha`. Here it's used as a per-pixel blend factor:
+//!
+//! ```text
+//! result = color0 * color1 + destination * (1 - color1)
+//! ```
+//!
+//! This needs the `dual_src_blend` feature. Without it, the same result is obtained with the
+//! blend constants, which are set on the pipeline instead of being computed by the shader and are
+//! therefore the same for every pixel:
+//!
+//! ```text
+//! result = color0 * constants + destination * (1 - constants)
+//! ```
+//!
+//! Note that Vulkan only has one set of blend constants, shared by all the attachments.
+//!
+//! The image is saved to `image.png`, and its pixels are checked against the expected blend.
+
+use chapter_code::Vertex2d;
+use image::{ImageBuffer, Rgba};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::{Device, DeviceCreateInfo, Features, QueueCreateInfo, QueueFlags};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::color_blend::{
+    AttachmentBlend, BlendFactor, BlendOp, ColorBlendState,
+};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sync::{self, GpuFuture};
+
+const SIZE: u32 = 64;
+
+// Must match the colors written by the fragment shaders.
+const COLOR0: [f32; 4] = [0.2, 0.4, 0.6, 1.0];
+const COLOR1: [f32; 4] = [0.5, 0.5, 0.5, 0.5];
+
+const BACKGROUND: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod dual_source_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0, index = 0) out vec4 color0;
+            layout(location = 0, index = 1) out vec4 color1;
+
+            void main() {
+                color0 = vec4(0.2, 0.4, 0.6, 1.0);
+                color1 = vec4(0.5, 0.5, 0.5, 0.5);
+            }
+        ",
+    }
+}
+
+mod single_source_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec4 color0;
+
+            void main() {
+                color0 = vec4(0.2, 0.4, 0.6, 1.0);
+            }
+        ",
+    }
+}
+
+/// The blended color, computed on the CPU. The alpha channel isn't blended.
+fn expected_color() -> [u8; 4] {
+    let mut color = [0; 4];
+    for i in 0..3 {
+        let blended = COLOR0[i] * COLOR1[i] + BACKGROUND[i] * (1.0 - COLOR1[i]);
+        color[i] = (blended * 255.0).round() as u8;
+    }
+    color[3] = (COLOR0[3] * 255.0).round() as u8;
+    color
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    let dual_source = physical.supported_features().dual_src_blend;
+    if !dual_source {
+        println!("Dual-source blending isn't supported, falling back to blend constants");
+    }
+
+    let queue_family_index = physical
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_features: Features {
+                dual_src_blend: dual_source,
+                ..Features::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let image = StorageImage::new(
+        &memory_allocator,
+        ImageDimensions::Dim2d {
+            width: SIZE,
+            height: SIZE,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.queue_family_index()),
+    )
+    .unwrap();
+
+    let buf = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (0..SIZE * SIZE * 4).map(|_| 0u8),
+    )
+    .expect("failed to create buffer");
+
+    // A triangle covering the whole image.
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [[-1.0, -1.0], [3.0, -1.0], [-1.0, 3.0]].map(|position| Vertex2d { position }),
+    )
+    .unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {},
+        },
+    )
+    .unwrap();
+
+    let view = ImageView::new_default(image.clone()).unwrap();
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![view],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+
+    let (fs, color_blend_state) = if dual_source {
+        let fs = dual_source_fs::load(device.clone()).expect("failed to create shader module");
+        let blend = AttachmentBlend {
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::Src1Color,
+            color_destination: BlendFactor::OneMinusSrc1Color,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::One,
+            alpha_destination: BlendFactor::Zero,
+        };
+
+        (fs, ColorBlendState::new(1).blend(blend))
+    } else {
+        let fs = single_source_fs::load(device.clone()).expect("failed to create shader module");
+        let blend = AttachmentBlend {
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::ConstantColor,
+            color_destination: BlendFactor::OneMinusConstantColor,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::One,
+            alpha_destination: BlendFactor::Zero,
+        };
+
+        (
+            fs,
+            ColorBlendState::new(1).blend(blend).blend_constants(COLOR1),
+        )
+    };
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [SIZE as f32, SIZE as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(Vertex2d::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .color_blend_state(color_blend_state)
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .unwrap();
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                // The destination of the blend.
+                clear_values: vec![Some(BACKGROUND.into())],
+                ..RenderPassBeginInfo::framebuffer(framebuffer)
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline)
+        .bind_vertex_buffers(0, vertex_buffer)
+        .draw(3, 1, 0, 0)
+        .unwrap()
+        .end_render_pass()
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buf.clone()))
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    future.wait(None).unwrap();
+
+    let buffer_content = buf.read().unwrap();
+
+    // Allow for rounding differences between implementations.
+    let expected = expected_color();
+    for pixel in buffer_content.chunks_exact(4) {
+        let matches = pixel
+            .iter()
+            .zip(expected)
+            .all(|(&actual, expected)| actual.abs_diff(expected) <= 1);
+        assert!(matches, "expected {:?}, got {:?}", expected, pixel);
+    }
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(SIZE, SIZE, &buffer_content[..]).unwrap();
+    image.save("image.png").unwrap();
+
+    println!("Everything succeeded!");
+}
@@ -58,6 +58,7 @@ fn main() {
     let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
 
     // Example operation
+    // region: example-operation
     let source_content: Vec<i32> = (0..64).collect();
     let source = Buffer::from_iter(
         &memory_allocator,
@@ -87,6 +88,7 @@ fn main() {
         destination_content,
     )
     .expect("failed to create destination buffer");
+    // endregion
 
     let command_buffer_allocator =
         StandardCommandBufferAllocator::new(device.clone(), Default::default());
@@ -0,0 +1,367 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Computes the inclusive prefix sum of an array in place, with Blelloch's work-efficient scan
+//! done in shared memory:
+//!
+//! 1. The first dispatch splits the array into blocks of 2048 `vec4`s, one per work group. Each
+//!    invocation loads two `vec4`s and scans the four values of each, then the work group scans
+//!    the block in shared memory, with an up-sweep followed by a down-sweep. The block is written
+//!    back scanned, along with its total.
+//! 2. The second dispatch adds the totals of the previous blocks to every value of a block.
+//!
+//! Each dispatch only reads and writes each value once, as all of the intermediate sums stay in
+//! shared memory. The result is checked against the same scan done on the CPU, for several sizes
+//! of arrays.
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+
+const ELEMENT_COUNTS: [u32; 3] = [256, 1024, 65536];
+
+// Must match the local sizes of the shaders.
+const LOCAL_SIZE: u32 = 1024;
+// Each invocation handles two `vec4`s.
+const VALUES_PER_WORK_GROUP: u32 = LOCAL_SIZE * 2 * 4;
+
+mod scan_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            const uint LOCAL_SIZE = 1024;
+            const uint BLOCK_SIZE = LOCAL_SIZE * 2;
+
+            layout(local_size_x = LOCAL_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) buffer Data {
+                vec4 data[];
+            };
+
+            layout(set = 0, binding = 1) writeonly buffer BlockSums {
+                float block_sums[];
+            };
+
+            // 32 KB. Only 16 KB are guaranteed, so `main` checks the device's limit first.
+            shared vec4 scratch[2048];
+
+            vec4 scan(vec4 values) {
+                values.y += values.x;
+                values.z += values.y;
+                values.w += values.z;
+                return values;
+            }
+
+            void main() {
+                uint id = gl_LocalInvocationID.x;
+                uint block_start = gl_WorkGroupID.x * BLOCK_SIZE;
+
+                // The last block may be partial, the missing values are zeros.
+                uint a = id;
+                uint b = id + LOCAL_SIZE;
+                vec4 a_values = block_start + a < data.length() ? scan(data[block_start + a])
+                                                                : vec4(0.0);
+                vec4 b_values = block_start + b < data.length() ? scan(data[block_start + b])
+                                                                : vec4(0.0);
+
+                // Each entry holds four values that are already scanned, so `.w` is their total.
+                // Combining an entry with the entries before it only needs their total, which is
+                // why the sweeps add `.w` to the whole entry.
+                scratch[a] = a_values;
+                scratch[b] = b_values;
+
+                // Up-sweep: the last entry of each subtree becomes the scan of the subtree.
+                uint offset = 1;
+                for (uint d = BLOCK_SIZE / 2; d > 0; d /= 2) {
+                    barrier();
+                    if (id < d) {
+                        uint left = offset * (2 * id + 1) - 1;
+                        uint right = offset * (2 * id + 2) - 1;
+                        scratch[right] += scratch[left].w;
+                    }
+                    offset *= 2;
+                }
+
+                barrier();
+                if (id == 0) {
+                    block_sums[gl_WorkGroupID.x] = scratch[BLOCK_SIZE - 1].w;
+                    scratch[BLOCK_SIZE - 1] = vec4(0.0);
+                }
+
+                // Down-sweep: each entry becomes the sum of the entries before it.
+                for (uint d = 1; d < BLOCK_SIZE; d *= 2) {
+                    offset /= 2;
+                    barrier();
+                    if (id < d) {
+                        uint left = offset * (2 * id + 1) - 1;
+                        uint right = offset * (2 * id + 2) - 1;
+                        vec4 left_sum = scratch[left];
+                        scratch[left] = scratch[right];
+                        scratch[right] = left_sum + scratch[right].w;
+                    }
+                }
+                barrier();
+
+                // The scan is inclusive, each value is added to the sum of the entries before it.
+                if (block_start + a < data.length()) {
+                    data[block_start + a] = a_values + scratch[a].w;
+                }
+                if (block_start + b < data.length()) {
+                    data[block_start + b] = b_values + scratch[b].w;
+                }
+            }
+        ",
+    }
+}
+
+mod add_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            const uint LOCAL_SIZE = 1024;
+            const uint BLOCK_SIZE = LOCAL_SIZE * 2;
+
+            layout(local_size_x = LOCAL_SIZE, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) buffer Data {
+                vec4 data[];
+            };
+
+            layout(set = 0, binding = 1) readonly buffer BlockSums {
+                float block_sums[];
+            };
+
+            shared float previous_blocks_sum;
+
+            void main() {
+                // There are few blocks, so their totals are summed by a single invocation.
+                if (gl_LocalInvocationID.x == 0) {
+                    float sum = 0.0;
+                    for (uint i = 0; i < gl_WorkGroupID.x; i++) {
+                        sum += block_sums[i];
+                    }
+                    previous_blocks_sum = sum;
+                }
+                barrier();
+
+                uint block_start = gl_WorkGroupID.x * BLOCK_SIZE;
+                for (uint i = gl_LocalInvocationID.x; i < BLOCK_SIZE; i += LOCAL_SIZE) {
+                    if (block_start + i < data.length()) {
+                        data[block_start + i] += previous_blocks_sum;
+                    }
+                }
+            }
+        ",
+    }
+}
+
+/// The number of reads and writes of global memory per element of the array, counting the
+/// totals of the blocks.
+fn global_accesses_per_element(element_count: u32) -> (f64, f64) {
+    let n = element_count as f64;
+    let blocks = ((element_count + VALUES_PER_WORK_GROUP - 1) / VALUES_PER_WORK_GROUP) as f64;
+
+    // Each dispatch reads and writes every value once. The first one writes the total of each
+    // block, and each work group of the second one reads the totals of the previous blocks.
+    let reads = 2.0 * n + blocks * (blocks - 1.0) / 2.0;
+    let writes = 2.0 * n + blocks;
+
+    (reads / n, writes / n)
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    // Only 128 invocations per work group are guaranteed, but most devices support 1024.
+    let properties = physical_device.properties();
+    if properties.max_compute_work_group_invocations < LOCAL_SIZE
+        || properties.max_compute_work_group_size[0] < LOCAL_SIZE
+    {
+        println!("The device doesn't support work groups of {LOCAL_SIZE} invocations");
+        return;
+    }
+    // Likewise, only 16 KB of shared memory are guaranteed, but the shader uses 32 KB.
+    if properties.max_compute_shared_memory_size < 2048 * 16 {
+        println!("The device doesn't support 32 KB of shared memory per work group");
+        return;
+    }
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let scan_shader = scan_cs::load(device.clone()).expect("failed to create shader module");
+    let scan_pipeline = ComputePipeline::new(
+        device.clone(),
+        scan_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let add_shader = add_cs::load(device.clone()).expect("failed to create shader module");
+    let add_pipeline = ComputePipeline::new(
+        device.clone(),
+        add_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    for element_count in ELEMENT_COUNTS {
+        // The values are read as `vec4`s.
+        assert_eq!(element_count % 4, 0);
+
+        // Small integers, so that every sum is exact in an `f32` whatever the order of the
+        // additions.
+        let values: Vec<f32> = (0..element_count).map(|i| (i * 7 % 10) as f32).collect();
+
+        // The scan is done in place, and the result is read back.
+        let data_buffer = Buffer::from_iter(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            values.iter().copied(),
+        )
+        .expect("failed to create buffer");
+
+        let work_group_count = (element_count + VALUES_PER_WORK_GROUP - 1) / VALUES_PER_WORK_GROUP;
+        let block_sums_buffer = Buffer::new_slice::<f32>(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            work_group_count as u64,
+        )
+        .expect("failed to create buffer");
+
+        let create_set = |pipeline: &ComputePipeline| {
+            PersistentDescriptorSet::new(
+                &descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::buffer(0, data_buffer.clone()),
+                    WriteDescriptorSet::buffer(1, block_sums_buffer.clone()),
+                ],
+            )
+            .unwrap()
+        };
+        let scan_set = create_set(&scan_pipeline);
+        let add_set = create_set(&add_pipeline);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        // vulkano inserts the barrier between the two dispatches.
+        builder
+            .bind_pipeline_compute(scan_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                scan_pipeline.layout().clone(),
+                0,
+                scan_set,
+            )
+            .dispatch([work_group_count, 1, 1])
+            .unwrap()
+            .bind_pipeline_compute(add_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                add_pipeline.layout().clone(),
+                0,
+                add_set,
+            )
+            .dispatch([work_group_count, 1, 1])
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+
+        let expected: Vec<f32> = values
+            .iter()
+            .scan(0.0, |sum, &value| {
+                *sum += value;
+                Some(*sum)
+            })
+            .collect();
+
+        assert_eq!(&data_buffer.read().unwrap()[..], &expected[..]);
+
+        let (reads, writes) = global_accesses_per_element(element_count);
+        println!(
+            "{element_count:>6} elements: {work_group_count} work group(s), {reads:.3} global \
+             memory reads and {writes:.3} writes per element"
+        );
+    }
+
+    println!("Everything succeeded!");
+}
@@ -0,0 +1,461 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Shares a storage buffer and a timeline semaphore between Vulkan and CUDA, as done when
+//! mixing CUDA inference with Vulkan rendering.
+//!
+//! vulkano allocates a buffer in exportable memory and a timeline semaphore, and exports both as
+//! file descriptors (`VK_KHR_external_memory_fd` and `VK_KHR_external_semaphore_fd`). CUDA imports
+//! them (the driver API equivalents of `cudaImportExternalMemory` and
+//! `cudaImportExternalSemaphore`) and queues a kernel that doubles every value in the buffer,
+//! after waiting for the semaphore to reach 1 and before signaling 2. vulkano then uploads the
+//! values, signals 1, waits for 2 and reads the buffer back.
+//!
+//! # Requirements
+//!
+//! This example needs the `cuda-interop` feature and only runs on Linux with an NVIDIA GPU:
+//!
+//! ```bash
+//! cargo run --bin cuda_interop --features cuda-interop
+//! ```
+//!
+//! The CUDA toolkit must be version 12.2 or later, since the bindings are generated for that
+//! version, and NVRTC (`libnvrtc.so`, part of the toolkit) has to be in the library path because
+//! the kernel is compiled at runtime. Importing timeline semaphores also needs a driver that
+//! supports CUDA 11.2 or later, which any driver shipped with the 12.2 toolkit does.
+
+#[cfg(all(feature = "cuda-interop", target_os = "linux"))]
+mod example {
+    use std::fs::File;
+    use std::mem::{self, MaybeUninit};
+    use std::os::unix::io::RawFd;
+    use std::ptr;
+    use std::sync::Arc;
+
+    use ash::vk;
+    use cudarc::driver::{sys, CudaDevice, DevicePtr, LaunchAsync, LaunchConfig};
+    use vulkano::buffer::sys::RawBuffer;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+    use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+    use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo};
+    use vulkano::device::{
+        Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags,
+    };
+    use vulkano::instance::{Instance, InstanceCreateInfo};
+    use vulkano::memory::allocator::{
+        AllocationCreateInfo, MemoryAlloc, MemoryUsage, StandardMemoryAllocator,
+    };
+    use vulkano::memory::{
+        DedicatedAllocation, DeviceMemory, ExternalMemoryHandleType, ExternalMemoryHandleTypes,
+        MemoryAllocateInfo, MemoryPropertyFlags,
+    };
+    use vulkano::sync::{self, GpuFuture};
+    use vulkano::{DeviceSize, Version, VulkanObject};
+
+    const ELEMENT_COUNT: u32 = 1024 * 1024;
+    const BUFFER_SIZE: DeviceSize = ELEMENT_COUNT as DeviceSize * 4;
+
+    // Timeline values: Vulkan signals `UPLOADED` once the buffer is filled, and CUDA signals
+    // `DOUBLED` once its kernel is done.
+    const UPLOADED: u64 = 1;
+    const DOUBLED: u64 = 2;
+
+    const KERNEL: &str = r#"
+        extern "C" __global__ void double_values(unsigned int *values, unsigned int count) {
+            unsigned int i = blockIdx.x * blockDim.x + threadIdx.x;
+            if (i < count) {
+                values[i] *= 2;
+            }
+        }
+    "#;
+
+    /// A timeline semaphore created with raw Vulkan calls, since vulkano doesn't support timeline
+    /// semaphores yet.
+    struct TimelineSemaphore {
+        device: Arc<Device>,
+        handle: vk::Semaphore,
+    }
+
+    impl TimelineSemaphore {
+        fn new_exportable(device: Arc<Device>) -> Self {
+            let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+            let mut export_info = vk::ExportSemaphoreCreateInfo::builder()
+                .handle_types(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD);
+            let create_info = vk::SemaphoreCreateInfo::builder()
+                .push_next(&mut type_info)
+                .push_next(&mut export_info);
+
+            let mut handle = vk::Semaphore::null();
+            unsafe {
+                (device.fns().v1_0.create_semaphore)(
+                    device.handle(),
+                    &*create_info,
+                    ptr::null(),
+                    &mut handle,
+                )
+            }
+            .result()
+            .expect("failed to create timeline semaphore");
+
+            TimelineSemaphore { device, handle }
+        }
+
+        fn export_fd(&self) -> RawFd {
+            let info = vk::SemaphoreGetFdInfoKHR::builder()
+                .semaphore(self.handle)
+                .handle_type(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD);
+
+            let mut fd = -1;
+            unsafe {
+                (self
+                    .device
+                    .fns()
+                    .khr_external_semaphore_fd
+                    .get_semaphore_fd_khr)(self.device.handle(), &*info, &mut fd)
+            }
+            .result()
+            .expect("failed to export timeline semaphore");
+
+            fd
+        }
+
+        fn signal(&self, value: u64) {
+            let info = vk::SemaphoreSignalInfo::builder()
+                .semaphore(self.handle)
+                .value(value);
+
+            unsafe { (self.device.fns().v1_2.signal_semaphore)(self.device.handle(), &*info) }
+                .result()
+                .expect("failed to signal timeline semaphore");
+        }
+
+        fn wait(&self, value: u64) {
+            let semaphores = [self.handle];
+            let values = [value];
+            let info = vk::SemaphoreWaitInfo::builder()
+                .semaphores(&semaphores)
+                .values(&values);
+
+            unsafe {
+                (self.device.fns().v1_2.wait_semaphores)(self.device.handle(), &*info, u64::MAX)
+            }
+            .result()
+            .expect("failed to wait on timeline semaphore");
+        }
+    }
+
+    impl Drop for TimelineSemaphore {
+        fn drop(&mut self) {
+            unsafe {
+                (self.device.fns().v1_0.destroy_semaphore)(
+                    self.device.handle(),
+                    self.handle,
+                    ptr::null(),
+                )
+            };
+        }
+    }
+
+    fn create_device(cuda_device_uuid: [u8; 16]) -> (Arc<Device>, Arc<Queue>) {
+        let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                max_api_version: Some(Version::V1_2),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create instance");
+
+        let device_extensions = DeviceExtensions {
+            khr_external_memory_fd: true,
+            khr_external_semaphore_fd: true,
+            ..DeviceExtensions::empty()
+        };
+        let features = Features {
+            timeline_semaphore: true,
+            ..Features::empty()
+        };
+
+        // CUDA can only import memory from the same physical device.
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .filter(|p| p.api_version() >= Version::V1_2)
+            .filter(|p| p.supported_extensions().contains(&device_extensions))
+            .filter(|p| p.supported_features().contains(&features))
+            .find(|p| p.properties().device_uuid == Some(cuda_device_uuid))
+            .expect("the CUDA device can't share memory and semaphores with Vulkan");
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::TRANSFER))
+            .expect("couldn't find a transfer queue family")
+            as u32;
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: device_extensions,
+                enabled_features: features,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        (device, queues.next().unwrap())
+    }
+
+    /// Creates a device-local buffer whose memory can be imported by CUDA, and returns it along
+    /// with the exported file descriptor.
+    fn create_shared_buffer(device: &Arc<Device>) -> (Subbuffer<[u32]>, File) {
+        let raw_buffer = RawBuffer::new(
+            device.clone(),
+            BufferCreateInfo {
+                size: BUFFER_SIZE,
+                usage: BufferUsage::STORAGE_BUFFER
+                    | BufferUsage::TRANSFER_SRC
+                    | BufferUsage::TRANSFER_DST,
+                external_memory_handle_types: ExternalMemoryHandleTypes::OPAQUE_FD,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create buffer");
+
+        let requirements = raw_buffer.memory_requirements();
+        let memory_type_index = device
+            .physical_device()
+            .memory_properties()
+            .memory_types
+            .iter()
+            .enumerate()
+            .position(|(i, memory_type)| {
+                requirements.memory_type_bits & (1 << i) != 0
+                    && memory_type
+                        .property_flags
+                        .intersects(MemoryPropertyFlags::DEVICE_LOCAL)
+            })
+            .expect("no device-local memory type for the buffer")
+            as u32;
+
+        let memory = DeviceMemory::allocate(
+            device.clone(),
+            MemoryAllocateInfo {
+                allocation_size: requirements.layout.size(),
+                memory_type_index,
+                export_handle_types: ExternalMemoryHandleTypes::OPAQUE_FD,
+                ..MemoryAllocateInfo::dedicated_allocation(DedicatedAllocation::Buffer(&raw_buffer))
+            },
+        )
+        .expect("failed to allocate memory");
+
+        let file = memory
+            .export_fd(ExternalMemoryHandleType::OpaqueFd)
+            .expect("failed to export memory");
+
+        let buffer = raw_buffer
+            .bind_memory(MemoryAlloc::new(memory).unwrap())
+            .map_err(|(err, _, _)| err)
+            .expect("failed to bind memory");
+
+        (Subbuffer::new(Arc::new(buffer)).reinterpret(), file)
+    }
+
+    fn cuda_device_uuid(device: &CudaDevice) -> [u8; 16] {
+        let mut uuid = MaybeUninit::uninit();
+        unsafe { sys::lib().cuDeviceGetUuid_v2(uuid.as_mut_ptr(), *device.cu_device()) }
+            .result()
+            .expect("failed to get the CUDA device UUID");
+
+        unsafe { uuid.assume_init() }.bytes.map(|byte| byte as u8)
+    }
+
+    /// Imports a timeline semaphore exported by Vulkan. CUDA takes ownership of the file
+    /// descriptor.
+    fn import_cuda_semaphore(fd: RawFd) -> sys::CUexternalSemaphore {
+        let mut description: sys::CUDA_EXTERNAL_SEMAPHORE_HANDLE_DESC = unsafe { mem::zeroed() };
+        description.type_ =
+            sys::CUexternalSemaphoreHandleType::CU_EXTERNAL_SEMAPHORE_HANDLE_TYPE_TIMELINE_SEMAPHORE_FD;
+        description.handle.fd = fd;
+
+        let mut semaphore = MaybeUninit::uninit();
+        unsafe { sys::lib().cuImportExternalSemaphore(semaphore.as_mut_ptr(), &description) }
+            .result()
+            .expect("failed to import the semaphore in CUDA");
+
+        unsafe { semaphore.assume_init() }
+    }
+
+    pub fn main() {
+        let cuda = CudaDevice::new(0).expect("failed to initialize CUDA");
+        let (device, queue) = create_device(cuda_device_uuid(&cuda));
+
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+        let (shared_buffer, memory_file) = create_shared_buffer(&device);
+        let semaphore = TimelineSemaphore::new_exportable(device.clone());
+
+        // Queue the CUDA side first. Timeline semaphores can be waited on before the signal
+        // operation is submitted, so the kernel simply waits for Vulkan.
+        let mapped_buffer = unsafe { cuda.import_external_memory(memory_file, BUFFER_SIZE) }
+            .expect("failed to import the buffer in CUDA")
+            .map_all()
+            .unwrap();
+        let cuda_semaphore = import_cuda_semaphore(semaphore.export_fd());
+
+        cuda.load_ptx(
+            cudarc::nvrtc::compile_ptx(KERNEL).expect("failed to compile the kernel"),
+            "interop",
+            &["double_values"],
+        )
+        .unwrap();
+        let double_values = cuda.get_func("interop", "double_values").unwrap();
+
+        unsafe {
+            let mut wait_params: sys::CUDA_EXTERNAL_SEMAPHORE_WAIT_PARAMS = mem::zeroed();
+            wait_params.params.fence.value = UPLOADED;
+            sys::lib()
+                .cuWaitExternalSemaphoresAsync(&cuda_semaphore, &wait_params, 1, *cuda.cu_stream())
+                .result()
+                .unwrap();
+
+            double_values
+                .launch(
+                    LaunchConfig::for_num_elems(ELEMENT_COUNT),
+                    (*mapped_buffer.device_ptr(), ELEMENT_COUNT),
+                )
+                .unwrap();
+
+            let mut signal_params: sys::CUDA_EXTERNAL_SEMAPHORE_SIGNAL_PARAMS = mem::zeroed();
+            signal_params.params.fence.value = DOUBLED;
+            sys::lib()
+                .cuSignalExternalSemaphoresAsync(
+                    &cuda_semaphore,
+                    &signal_params,
+                    1,
+                    *cuda.cu_stream(),
+                )
+                .result()
+                .unwrap();
+        }
+
+        // Upload the values with Vulkan.
+        let upload_buffer = Buffer::from_iter(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            0..ELEMENT_COUNT,
+        )
+        .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                upload_buffer,
+                shared_buffer.clone(),
+            ))
+            .unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        // Signaling from the host keeps the example short. The signal could also be part of the
+        // queue submission, so that the CPU doesn't have to wait for the upload.
+        semaphore.signal(UPLOADED);
+        semaphore.wait(DOUBLED);
+
+        // Read the result back with Vulkan.
+        let download_buffer = Buffer::new_slice::<u32>(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            ELEMENT_COUNT as DeviceSize,
+        )
+        .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                shared_buffer,
+                download_buffer.clone(),
+            ))
+            .unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(device)
+            .then_execute(queue, command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        for (i, &value) in download_buffer.read().unwrap().iter().enumerate() {
+            assert_eq!(value, i as u32 * 2, "CUDA didn't double value {}", i);
+        }
+
+        unsafe { sys::lib().cuDestroyExternalSemaphore(cuda_semaphore) }
+            .result()
+            .unwrap();
+
+        println!("Everything succeeded!");
+    }
+}
+
+#[cfg(all(feature = "cuda-interop", target_os = "linux"))]
+fn main() {
+    example::main();
+}
+
+#[cfg(all(feature = "cuda-interop", not(target_os = "linux")))]
+fn main() {
+    println!("This example is only available on Linux");
+}
+
+#[cfg(not(feature = "cuda-interop"))]
+fn main() {
+    println!("CUDA interop not compiled in");
+}
@@ -0,0 +1,313 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders a small scene into a depth-only render pass, copies the depth attachment back to the
+//! host and saves it as a grayscale `depth.png`, where near is dark and far is light.
+//!
+//! Depth values written by a perspective projection are not linear: most of the `[0, 1]` range
+//! is spent close to the near plane. Saving them as-is gives an almost white image, so they are
+//! linearized using the same near/far planes as the projection before being written.
+//!
+//! The depth format can be chosen with the first argument, either `d16` (default) or `d32`:
+//!
+//! ```bash
+//! cargo run --bin depth_readback -- d32
+//! ```
+
+use std::env;
+
+use chapter_code::Vertex3d;
+use image::{ImageBuffer, Luma};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sync::{self, GpuFuture};
+
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 1024;
+
+// Must match the constants used in the vertex shader.
+const NEAR: f32 = 0.1;
+const FAR: f32 = 10.0;
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 position;
+
+            const float near = 0.1;
+            const float far = 10.0;
+
+            void main() {
+                // Perspective projection mapping view-space z in [-near, -far] to depth [0, 1].
+                gl_Position = vec4(
+                    position.x,
+                    position.y,
+                    position.z * far / (near - far) + near * far / (near - far),
+                    -position.z
+                );
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            void main() {}
+        ",
+    }
+}
+
+/// Turns a depth value produced by the projection in `vs` back into a distance from the camera.
+fn linearize_depth(depth: f32) -> f32 {
+    NEAR * FAR / (FAR - depth * (FAR - NEAR))
+}
+
+/// Decodes the raw bytes of a depth image copied into a buffer into `[0, 1]` depth values.
+fn decode_depth(format: Format, bytes: &[u8]) -> Vec<f32> {
+    match format {
+        Format::D16_UNORM => bytes
+            .chunks_exact(2)
+            .map(|texel| u16::from_ne_bytes([texel[0], texel[1]]) as f32 / u16::MAX as f32)
+            .collect(),
+        Format::D32_SFLOAT => bytes
+            .chunks_exact(4)
+            .map(|texel| f32::from_ne_bytes([texel[0], texel[1], texel[2], texel[3]]))
+            .collect(),
+        _ => panic!("unsupported depth format {:?}", format),
+    }
+}
+
+fn main() {
+    let format = match env::args().nth(1).as_deref() {
+        None | Some("d16") => Format::D16_UNORM,
+        Some("d32") => Format::D32_SFLOAT,
+        Some(other) => panic!("unknown depth format \"{}\", expected d16 or d32", other),
+    };
+
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    let supports_format = physical
+        .format_properties(format)
+        .unwrap()
+        .optimal_tiling_features
+        .contains(FormatFeatures::DEPTH_STENCIL_ATTACHMENT | FormatFeatures::TRANSFER_SRC);
+    if !supports_format {
+        panic!(
+            "{:?} can't be used as a depth attachment on this device",
+            format
+        );
+    }
+
+    let queue_family_index = physical
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let depth_image = AttachmentImage::with_usage(
+        &memory_allocator,
+        [WIDTH, HEIGHT],
+        format,
+        ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )
+    .unwrap();
+
+    let bytes_per_texel = format.block_size().unwrap();
+    let buf = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (0..WIDTH as u64 * HEIGHT as u64 * bytes_per_texel).map(|_| 0u8),
+    )
+    .expect("failed to create buffer");
+
+    // A floor going away from the camera, and two triangles at different distances in front of it.
+    let vertices = [
+        // floor
+        [-4.0, 1.0, -0.5],
+        [4.0, 1.0, -0.5],
+        [0.0, 1.0, -9.5],
+        // far triangle
+        [-1.5, -1.0, -6.0],
+        [0.5, -1.0, -6.0],
+        [-0.5, 0.8, -6.0],
+        // near triangle
+        [-0.2, -0.4, -2.0],
+        [0.8, -0.4, -2.0],
+        [0.3, 0.4, -2.0],
+    ];
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        vertices.map(|position| Vertex3d { position }),
+    )
+    .unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            depth: {
+                load: Clear,
+                store: Store,
+                format: format,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [],
+            depth_stencil: {depth},
+        },
+    )
+    .unwrap();
+
+    let view = ImageView::new_default(depth_image.clone()).unwrap();
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![view],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [WIDTH as f32, HEIGHT as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(Vertex3d::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .unwrap();
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                // 1.0 is the far plane, so anything that isn't covered stays light
+                clear_values: vec![Some(1.0.into())],
+                ..RenderPassBeginInfo::framebuffer(framebuffer)
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline)
+        .bind_vertex_buffers(0, vertex_buffer.clone())
+        .draw(vertex_buffer.len() as u32, 1, 0, 0)
+        .unwrap()
+        .end_render_pass()
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            depth_image,
+            buf.clone(),
+        ))
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    future.wait(None).unwrap();
+
+    let buffer_content = buf.read().unwrap();
+    let pixels: Vec<u8> = decode_depth(format, &buffer_content)
+        .into_iter()
+        .map(|depth| {
+            let distance = (linearize_depth(depth) - NEAR) / (FAR - NEAR);
+            (distance.clamp(0.0, 1.0) * 255.0) as u8
+        })
+        .collect();
+
+    let image = ImageBuffer::<Luma<u8>, _>::from_raw(WIDTH, HEIGHT, pixels).unwrap();
+    image.save("depth.png").unwrap();
+
+    println!("Everything succeeded!");
+}
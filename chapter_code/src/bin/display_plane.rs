@@ -0,0 +1,302 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders the triangle of the windowing chapter directly to a display with `VK_KHR_display`,
+//! without going through a window system, as done by embedded and kiosk applications.
+//!
+//! The first display of the device is used, with its first mode, and the surface is created on
+//! the first display plane that can show that display. The swapchain has the native resolution
+//! of the mode. There is no window and therefore no input: the example renders for a few seconds,
+//! then exits.
+//!
+//! A display can only be used this way when nothing else owns it. Run the example from a virtual
+//! console rather than from a desktop session, where the compositor usually owns every display
+//! and the driver reports none or refuses to create the surface.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chapter_code::shaders::static_triangle;
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::{vulkano_objects, Vertex2d};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, QueueCreateInfo, QueueFlags};
+use vulkano::image::ImageUsage;
+use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::swapchain::display::{Display, DisplayPlane};
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano::VulkanLibrary;
+
+const DURATION: Duration = Duration::from_secs(5);
+
+fn main() {
+    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+
+    if !library.supported_extensions().khr_display {
+        println!("VK_KHR_display isn't supported by the Vulkan implementation");
+        return;
+    }
+
+    let instance = Instance::new(
+        library,
+        InstanceCreateInfo {
+            enabled_extensions: InstanceExtensions {
+                khr_surface: true,
+                khr_display: true,
+                ..InstanceExtensions::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create instance");
+
+    let device_extensions = DeviceExtensions {
+        khr_swapchain: true,
+        ..DeviceExtensions::empty()
+    };
+
+    // Displays belong to a physical device, so the device is chosen first.
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .find(|p| p.supported_extensions().contains(&device_extensions))
+        .expect("no device available");
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let display = match Display::enumerate(physical_device.clone()).next() {
+        Some(display) => display,
+        None => {
+            println!("No display available for direct rendering, is a window system using it?");
+            return;
+        }
+    };
+
+    let display_mode = match display.display_modes().next() {
+        Some(mode) => mode,
+        None => {
+            println!("The display \"{}\" has no modes", display.name());
+            return;
+        }
+    };
+
+    let plane =
+        match DisplayPlane::enumerate(physical_device.clone()).find(|p| p.supports(&display)) {
+            Some(plane) => plane,
+            None => {
+                println!(
+                    "No display plane can show the display \"{}\"",
+                    display.name()
+                );
+                return;
+            }
+        };
+
+    let [width, height] = display_mode.visible_region();
+    println!(
+        "Display \"{}\", {}x{} at {} Hz, on plane {}",
+        display.name(),
+        width,
+        height,
+        display_mode.refresh_rate() as f32 / 1000.0,
+        plane.index(),
+    );
+
+    let surface = match Surface::from_display_plane(&display_mode, &plane) {
+        Ok(surface) => surface,
+        Err(e) => {
+            println!("Failed to create a surface on the display plane: {e}");
+            return;
+        }
+    };
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(i, q)| {
+            q.queue_flags.contains(QueueFlags::GRAPHICS)
+                && physical_device
+                    .surface_support(i as u32, &surface)
+                    .unwrap_or(false)
+        })
+        .expect("couldn't find a queue family that can present to the display")
+        as u32;
+
+    // `VK_KHR_display_swapchain` is only needed to present to several displays at once, which
+    // this example doesn't do, so it's enabled when available.
+    let device_extensions = DeviceExtensions {
+        khr_display_swapchain: physical_device.supported_extensions().khr_display_swapchain,
+        ..device_extensions
+    };
+
+    let (device, mut queues) = Device::new(
+        physical_device.clone(),
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_extensions: device_extensions,
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let (swapchain, images) = {
+        let caps = physical_device
+            .surface_capabilities(&surface, Default::default())
+            .expect("failed to get surface capabilities");
+
+        let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+        let image_format = Some(
+            physical_device
+                .surface_formats(&surface, Default::default())
+                .unwrap()[0]
+                .0,
+        );
+
+        Swapchain::new(
+            device.clone(),
+            surface,
+            SwapchainCreateInfo {
+                min_image_count: caps.min_image_count,
+                image_format,
+                // There is no window to take the size from.
+                image_extent: [width, height],
+                image_usage: ImageUsage::COLOR_ATTACHMENT,
+                composite_alpha,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    };
+
+    let render_pass =
+        vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+            .expect("failed to create render pass");
+    let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+        &images,
+        render_pass.clone(),
+    );
+
+    let allocators = Allocators::new(device.clone());
+
+    let vertex_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [
+            Vertex2d {
+                position: [-0.5, -0.5],
+            },
+            Vertex2d {
+                position: [0.0, 0.5],
+            },
+            Vertex2d {
+                position: [0.5, -0.25],
+            },
+        ],
+    )
+    .unwrap();
+
+    let vs = static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+    let fs = static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+
+    let pipeline = vulkano_objects::pipeline::create_pipeline(device.clone(), vs, fs, render_pass)
+        .expect("failed to create pipeline");
+
+    let command_buffers = vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+        &allocators,
+        queue.clone(),
+        pipeline,
+        &framebuffers,
+        vertex_buffer,
+        vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+    );
+
+    let frames_in_flight = images.len();
+    let mut fences: Vec<Option<Arc<FenceSignalFuture<_>>>> = vec![None; frames_in_flight];
+    let mut previous_fence_i = 0;
+
+    // The mode of the display can't change behind our back like the size of a window, so the
+    // swapchain is never recreated.
+    let start = Instant::now();
+    while start.elapsed() < DURATION {
+        let (image_i, _, acquire_future) =
+            match swapchain::acquire_next_image(swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    println!("The swapchain is out of date, stopping");
+                    break;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if let Some(image_fence) = &fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let previous_future = match fences[previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(queue.clone(), command_buffers[image_i as usize].clone())
+            .unwrap()
+            .then_swapchain_present(
+                queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i),
+            )
+            .then_signal_fence_and_flush();
+
+        fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                println!("The swapchain is out of date, stopping");
+                break;
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        previous_fence_i = image_i;
+    }
+
+    // Wait for the last frames before the swapchain is destroyed.
+    for fence in fences.into_iter().flatten() {
+        fence.wait(None).unwrap();
+    }
+}
@@ -0,0 +1,614 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Draws a scene of spheres where both the draw commands and the per-instance data are generated
+//! on the GPU.
+//!
+//! The CPU only uploads the scene description (the center and radius of the bounding sphere of
+//! each object) and the geometry of a quad. Then, in a single command buffer:
+//!
+//! 1. A first dispatch clears one `DrawIndexedIndirectCommand` per object, so that every command
+//!    draws nothing, and resets the visible object counter.
+//! 2. A second dispatch tests each sphere against the view frustum. For each visible sphere, it
+//!    reserves a slot with an atomic counter, writes a draw command to it and writes the transform
+//!    of the instance to a buffer that is also used as a per-instance vertex buffer.
+//! 3. A single `draw_indexed_indirect` call executes all of the commands. This requires the
+//!    `multi_draw_indirect` feature, as there is more than one command.
+//!
+//! The result is saved to `image.png`, and the number of visible spheres is checked against the
+//! same test done on the CPU.
+
+use chapter_code::Vertex2d;
+use image::{ImageBuffer, Rgba};
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
+    DrawIndexedIndirectCommand, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, Features, QueueCreateInfo, QueueFlags};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sync::{self, GpuFuture};
+
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 1024;
+
+// Must match the constants used in the shaders.
+const NEAR: f32 = 0.1;
+const FAR: f32 = 20.0;
+const LOCAL_SIZE: u32 = 64;
+
+const GRID_SIZE: u32 = 16;
+const LAYERS: u32 = 4;
+const SPHERE_COUNT: u32 = GRID_SIZE * GRID_SIZE * LAYERS;
+const SPHERE_RADIUS: f32 = 0.6;
+
+/// Bounding sphere of an object of the scene, in view space.
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct Sphere {
+    center: [f32; 3],
+    radius: f32,
+}
+
+/// Per-instance data, written by the culling shader.
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct InstanceData {
+    #[format(R32G32B32A32_SFLOAT)]
+    sphere: [f32; 4],
+    #[format(R32G32B32A32_SFLOAT)]
+    color: [f32; 4],
+}
+
+mod reset_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            struct DrawCommand {
+                uint index_count;
+                uint instance_count;
+                uint first_index;
+                int vertex_offset;
+                uint first_instance;
+            };
+
+            layout(set = 0, binding = 1) writeonly buffer Commands {
+                DrawCommand commands[];
+            };
+
+            layout(set = 0, binding = 3) buffer Counter {
+                uint visible_count;
+            };
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+                if (idx == 0) {
+                    visible_count = 0u;
+                }
+                if (idx < commands.length()) {
+                    // An instance count of zero makes the command draw nothing.
+                    commands[idx] = DrawCommand(0u, 0u, 0u, 0, 0u);
+                }
+            }
+        ",
+    }
+}
+
+mod cull_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            const float near = 0.1;
+            const float far = 20.0;
+            const uint quad_index_count = 6u;
+
+            struct Sphere {
+                vec3 center;
+                float radius;
+            };
+
+            struct DrawCommand {
+                uint index_count;
+                uint instance_count;
+                uint first_index;
+                int vertex_offset;
+                uint first_instance;
+            };
+
+            struct Instance {
+                vec4 sphere;
+                vec4 color;
+            };
+
+            layout(set = 0, binding = 0) readonly buffer Scene {
+                Sphere spheres[];
+            };
+
+            layout(set = 0, binding = 1) writeonly buffer Commands {
+                DrawCommand commands[];
+            };
+
+            layout(set = 0, binding = 2) writeonly buffer Instances {
+                Instance instances[];
+            };
+
+            layout(set = 0, binding = 3) buffer Counter {
+                uint visible_count;
+            };
+
+            // The projection has a 90 degree field of view, so the side planes of the frustum
+            // are x = -z and y = -z.
+            bool is_visible(vec3 center, float radius) {
+                float side = radius * sqrt(2.0);
+                return center.z - radius < -near
+                    && center.z + radius > -far
+                    && abs(center.x) + center.z < side
+                    && abs(center.y) + center.z < side;
+            }
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+                if (idx >= spheres.length()) {
+                    return;
+                }
+
+                Sphere sphere = spheres[idx];
+                if (!is_visible(sphere.center, sphere.radius)) {
+                    return;
+                }
+
+                uint slot = atomicAdd(visible_count, 1u);
+                commands[slot] = DrawCommand(quad_index_count, 1u, 0u, 0, slot);
+
+                float hue = float(idx) / float(spheres.length());
+                vec3 color = 0.5 + 0.5 * cos(6.2831853 * (hue + vec3(0.0, 0.33, 0.67)));
+                instances[slot] = Instance(vec4(sphere.center, sphere.radius), vec4(color, 1.0));
+            }
+        ",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec4 sphere;
+            layout(location = 2) in vec4 color;
+
+            layout(location = 0) out vec2 uv;
+            layout(location = 1) out vec4 instance_color;
+
+            const float near = 0.1;
+            const float far = 20.0;
+
+            void main() {
+                // A quad facing the camera and covering the sphere.
+                vec3 view_position = sphere.xyz + vec3(position * sphere.w, 0.0);
+
+                gl_Position = vec4(
+                    view_position.x,
+                    view_position.y,
+                    view_position.z * far / (near - far) + near * far / (near - far),
+                    -view_position.z
+                );
+                uv = position;
+                instance_color = color;
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 uv;
+            layout(location = 1) in vec4 instance_color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                float distance_squared = dot(uv, uv);
+                if (distance_squared > 1.0) {
+                    discard;
+                }
+                f_color = vec4(instance_color.rgb * sqrt(1.0 - distance_squared), 1.0);
+            }
+        ",
+    }
+}
+
+/// Spheres on a grid, in layers going away from the camera. The grid is larger than what the
+/// camera sees in the closest layers, so part of the scene is culled.
+fn create_scene() -> Vec<Sphere> {
+    let half_extent = (GRID_SIZE - 1) as f32;
+
+    (0..LAYERS)
+        .flat_map(|layer| {
+            (0..GRID_SIZE * GRID_SIZE).map(move |i| Sphere {
+                center: [
+                    (i % GRID_SIZE) as f32 * 2.0 - half_extent,
+                    (i / GRID_SIZE) as f32 * 2.0 - half_extent,
+                    -3.0 - layer as f32 * 4.0,
+                ],
+                radius: SPHERE_RADIUS,
+            })
+        })
+        .collect()
+}
+
+/// Same test as `is_visible` in the culling shader.
+fn is_visible(sphere: &Sphere) -> bool {
+    let [x, y, z] = sphere.center;
+    let side = sphere.radius * 2f32.sqrt();
+
+    z - sphere.radius < -NEAR
+        && z + sphere.radius > -FAR
+        && x.abs() + z < side
+        && y.abs() + z < side
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .find(|p| {
+            p.supported_features().multi_draw_indirect
+                && p.properties().max_draw_indirect_count >= SPHERE_COUNT
+        })
+        .expect("no device supports drawing multiple indirect commands at once");
+
+    let queue_family_index = physical
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_features: Features {
+                multi_draw_indirect: true,
+                ..Features::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    // Everything prepared on the CPU: the scene and the geometry of a quad.
+
+    let scene = create_scene();
+    let scene_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        scene.iter().copied(),
+    )
+    .unwrap();
+
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|position| Vertex2d { position }),
+    )
+    .unwrap();
+
+    let index_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::INDEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [0u16, 1, 2, 2, 3, 0],
+    )
+    .unwrap();
+
+    // Everything written by the GPU. There is one command and one instance per object, of which
+    // only the first `visible_count` are used.
+
+    let indirect_buffer = Buffer::new_slice::<DrawIndexedIndirectCommand>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::INDIRECT_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        SPHERE_COUNT as u64,
+    )
+    .unwrap();
+
+    let instance_buffer = Buffer::new_slice::<InstanceData>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER | BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        SPHERE_COUNT as u64,
+    )
+    .unwrap();
+
+    let counter_buffer = Buffer::from_data(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        0u32,
+    )
+    .unwrap();
+
+    // Compute pipelines
+
+    let reset_shader = reset_cs::load(device.clone()).expect("failed to create shader module");
+    let reset_pipeline = ComputePipeline::new(
+        device.clone(),
+        reset_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let cull_shader = cull_cs::load(device.clone()).expect("failed to create shader module");
+    let cull_pipeline = ComputePipeline::new(
+        device.clone(),
+        cull_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+
+    let reset_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        reset_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(1, indirect_buffer.clone()),
+            WriteDescriptorSet::buffer(3, counter_buffer.clone()),
+        ],
+    )
+    .unwrap();
+
+    let cull_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        cull_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, scene_buffer),
+            WriteDescriptorSet::buffer(1, indirect_buffer.clone()),
+            WriteDescriptorSet::buffer(2, instance_buffer.clone()),
+            WriteDescriptorSet::buffer(3, counter_buffer.clone()),
+        ],
+    )
+    .unwrap();
+
+    // Graphics pipeline
+
+    let color_image = AttachmentImage::with_usage(
+        &memory_allocator,
+        [WIDTH, HEIGHT],
+        Format::R8G8B8A8_UNORM,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )
+    .unwrap();
+    let depth_image =
+        AttachmentImage::transient(&memory_allocator, [WIDTH, HEIGHT], Format::D16_UNORM).unwrap();
+
+    let buf = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (0..WIDTH * HEIGHT * 4).map(|_| 0u8),
+    )
+    .expect("failed to create buffer");
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: Format::D16_UNORM,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {depth},
+        },
+    )
+    .unwrap();
+
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![
+                ImageView::new_default(color_image.clone()).unwrap(),
+                ImageView::new_default(depth_image).unwrap(),
+            ],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [WIDTH as f32, HEIGHT as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    let graphics_pipeline = GraphicsPipeline::start()
+        .vertex_input_state([Vertex2d::per_vertex(), InstanceData::per_instance()])
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .unwrap();
+
+    // Two dispatches and one draw call. The barriers between the commands, including the one
+    // making the draw commands visible to the indirect draw, are inserted by vulkano.
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    let work_group_counts = [(SPHERE_COUNT + LOCAL_SIZE - 1) / LOCAL_SIZE, 1, 1];
+
+    builder
+        .bind_pipeline_compute(reset_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            reset_pipeline.layout().clone(),
+            0,
+            reset_set,
+        )
+        .dispatch(work_group_counts)
+        .unwrap()
+        .bind_pipeline_compute(cull_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            cull_pipeline.layout().clone(),
+            0,
+            cull_set,
+        )
+        .dispatch(work_group_counts)
+        .unwrap()
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into()), Some(1.0.into())],
+                ..RenderPassBeginInfo::framebuffer(framebuffer)
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(graphics_pipeline)
+        .bind_vertex_buffers(0, (vertex_buffer, instance_buffer))
+        .bind_index_buffer(index_buffer)
+        .draw_indexed_indirect(indirect_buffer)
+        .unwrap()
+        .end_render_pass()
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            color_image,
+            buf.clone(),
+        ))
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    future.wait(None).unwrap();
+
+    let visible_count = *counter_buffer.read().unwrap();
+    let expected_count = scene.iter().filter(|sphere| is_visible(sphere)).count() as u32;
+    assert_eq!(visible_count, expected_count);
+    println!("{visible_count} of {SPHERE_COUNT} spheres are visible");
+
+    let buffer_content = buf.read().unwrap();
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(WIDTH, HEIGHT, &buffer_content[..]).unwrap();
+    image.save("image.png").unwrap();
+
+    println!("Everything succeeded!");
+}
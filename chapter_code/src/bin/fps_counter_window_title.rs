@@ -0,0 +1,332 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders the triangle of the windowing chapter and shows the frame rate in the window title.
+//!
+//! The frame rate is averaged over the last frames with `FrameTimer`, and the title is only
+//! updated every few frames: changing it goes through the windowing system, which can be slow
+//! enough to show up in the frame time.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chapter_code::shaders::static_triangle;
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::{is_headless, vulkano_objects, FrameTimer, Vertex2d, HEADLESS_FRAME_COUNT};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::image::SwapchainImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::RenderPass;
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+    SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+const TITLE: &str = "Vulkano Demo";
+
+// The title is updated every `TITLE_UPDATE_INTERVAL` frames.
+const TITLE_UPDATE_INTERVAL: u64 = 30;
+// The FPS is averaged over that many frames.
+const FPS_AVERAGE_FRAMES: usize = 60;
+
+// Updating the title takes longer than this on some systems, which causes a visible stutter.
+const SLOW_TITLE_UPDATE: Duration = Duration::from_millis(1);
+
+type Fence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+struct Renderer {
+    surface: Arc<Surface>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    render_pass: Arc<RenderPass>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[Vertex2d]>,
+    pipeline: Arc<GraphicsPipeline>,
+    command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    fences: Vec<Option<Arc<Fence>>>,
+    previous_fence_i: u32,
+    recreate_swapchain: bool,
+}
+
+impl Renderer {
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+
+        let surface = WindowBuilder::new()
+            .with_title(TITLE)
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (swapchain, images) = vulkano_objects::swapchain::create_swapchain(
+            &physical_device,
+            device.clone(),
+            surface.clone(),
+        )
+        .expect("failed to create swapchain");
+
+        let render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+
+        let allocators = Allocators::new(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                Vertex2d {
+                    position: [-0.5, -0.5],
+                },
+                Vertex2d {
+                    position: [0.0, 0.5],
+                },
+                Vertex2d {
+                    position: [0.5, -0.25],
+                },
+            ],
+        )
+        .unwrap();
+
+        let vertex_shader =
+            static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+        let fragment_shader =
+            static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = vulkano_objects::pipeline::create_pipeline(
+            device.clone(),
+            vertex_shader,
+            fragment_shader,
+            render_pass.clone(),
+        )
+        .expect("failed to create pipeline");
+
+        let mut renderer = Self {
+            surface,
+            device,
+            queue,
+            swapchain,
+            render_pass,
+            allocators,
+            vertex_buffer,
+            pipeline,
+            command_buffers: Vec::new(),
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            recreate_swapchain: false,
+        };
+        renderer.create_command_buffers(&images);
+
+        renderer
+    }
+
+    fn window(&self) -> Arc<Window> {
+        self.surface
+            .object()
+            .unwrap()
+            .clone()
+            .downcast::<Window>()
+            .unwrap()
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.window().set_title(title);
+    }
+
+    fn handle_window_resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    fn create_command_buffers(&mut self, images: &[Arc<SwapchainImage>]) {
+        let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+            images,
+            self.render_pass.clone(),
+        );
+
+        self.command_buffers = vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+            &self.allocators,
+            self.queue.clone(),
+            self.pipeline.clone(),
+            &framebuffers,
+            self.vertex_buffer.clone(),
+            vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+        );
+    }
+
+    fn render(&mut self) {
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: self.window().inner_size().into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.recreate_swapchain = false;
+            self.swapchain = new_swapchain;
+            self.create_command_buffers(&new_images);
+        }
+
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(self.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(
+                self.queue.clone(),
+                self.command_buffers[image_i as usize].clone(),
+            )
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i;
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let mut renderer = Renderer::new(&event_loop);
+
+    let mut frame_timer = FrameTimer::new(FPS_AVERAGE_FRAMES);
+    let mut frame_count: u64 = 0;
+    let headless = is_headless();
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            renderer.handle_window_resize();
+        }
+        Event::MainEventsCleared => {
+            renderer.render();
+
+            frame_timer.tick();
+            frame_count += 1;
+
+            if frame_count % TITLE_UPDATE_INTERVAL == 0 {
+                if let Some(fps) = frame_timer.fps() {
+                    let title = format!("{TITLE} — {fps:.1} FPS");
+
+                    let start = Instant::now();
+                    renderer.set_title(&title);
+                    let elapsed = start.elapsed();
+
+                    if elapsed > SLOW_TITLE_UPDATE {
+                        println!(
+                            "Warning: updating the window title took {:.2} ms",
+                            elapsed.as_secs_f64() * 1000.0
+                        );
+                    }
+                }
+            }
+
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        _ => (),
+    });
+}
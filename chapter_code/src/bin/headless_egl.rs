@@ -0,0 +1,369 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders on a machine without a window system, such as a CI runner or a render farm node.
+//!
+//! Vulkan doesn't need a window system at all as long as no surface extension is enabled, so the
+//! instance is created with `get_headless_instance`. EGL is only used to pick the GPU: the devices
+//! enumerated with `EGL_EXT_device_enumeration` are opened through `EGL_EXT_platform_device`,
+//! which doesn't need an X11 or Wayland display either, and the first one whose OpenGL context
+//! reports the UUID of a Vulkan physical device is used. This is how a renderer that already
+//! drives a GPU through EGL can make sure Vulkan runs on the same one.
+//!
+//! When EGL isn't available, the first Vulkan physical device is used instead.
+//!
+//! The result is saved to `image.png`.
+
+use std::sync::Arc;
+
+use chapter_code::vulkano_objects::instance::get_headless_instance;
+use image::{ImageBuffer, Rgba};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::instance::Instance;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 1024;
+
+#[cfg(unix)]
+mod egl_device {
+    use std::ffi::{c_void, CStr};
+    use std::mem;
+    use std::ptr;
+
+    use gl::types::{GLenum, GLubyte, GLuint};
+    use khronos_egl as egl;
+
+    type Egl = egl::DynamicInstance<egl::EGL1_4>;
+    type EglDevice = *mut c_void;
+
+    const PLATFORM_DEVICE_EXT: egl::Enum = 0x313F;
+    const DEVICE_UUID_EXT: GLenum = 0x9597;
+
+    const REQUIRED_CLIENT_EXTENSIONS: &[&str] = &[
+        "EGL_EXT_device_enumeration",
+        "EGL_EXT_platform_base",
+        "EGL_EXT_platform_device",
+    ];
+
+    type QueryDevices = unsafe extern "system" fn(egl::Int, *mut EglDevice, *mut egl::Int) -> u32;
+    type GetPlatformDisplay =
+        unsafe extern "system" fn(egl::Enum, *mut c_void, *const egl::Int) -> egl::EGLDisplay;
+    type GetUnsignedBytei = unsafe extern "system" fn(GLenum, GLuint, *mut GLubyte);
+
+    /// Returns the UUIDs of the GPUs that EGL can render with, in the order EGL enumerates them.
+    pub fn device_uuids() -> Result<Vec<[u8; 16]>, String> {
+        let egl = unsafe { Egl::load_required() }.map_err(|err| err.to_string())?;
+
+        let client_extensions = egl
+            .query_string(None, egl::EXTENSIONS)
+            .map_err(|err| err.to_string())?
+            .to_string_lossy();
+        for extension in REQUIRED_CLIENT_EXTENSIONS {
+            if !client_extensions.split(' ').any(|e| e == *extension) {
+                return Err(format!("{} isn't supported", extension));
+            }
+        }
+
+        let query_devices: QueryDevices = unsafe { load(&egl, "eglQueryDevicesEXT") }?;
+        let get_platform_display: GetPlatformDisplay =
+            unsafe { load(&egl, "eglGetPlatformDisplayEXT") }?;
+
+        let mut count = 0;
+        unsafe { query_devices(0, ptr::null_mut(), &mut count) };
+        let mut devices = vec![ptr::null_mut(); count as usize];
+        unsafe { query_devices(count, devices.as_mut_ptr(), &mut count) };
+
+        let uuids = devices
+            .into_iter()
+            .filter_map(|device| {
+                let display = unsafe {
+                    get_platform_display(PLATFORM_DEVICE_EXT, device, [egl::NONE].as_ptr())
+                };
+                if display.is_null() {
+                    return None;
+                }
+
+                let display = unsafe { egl::Display::from_ptr(display) };
+                let uuid = query_uuid(&egl, display);
+                let _ = egl.terminate(display);
+                uuid
+            })
+            .collect();
+
+        Ok(uuids)
+    }
+
+    // Creates an OpenGL context on the display and asks it for the UUID of its device.
+    fn query_uuid(egl: &Egl, display: egl::Display) -> Option<[u8; 16]> {
+        egl.initialize(display).ok()?;
+        egl.bind_api(egl::OPENGL_API).ok()?;
+
+        // Device displays have no window system, so only pbuffer configs exist.
+        let config = egl
+            .choose_first_config(
+                display,
+                &[
+                    egl::SURFACE_TYPE,
+                    egl::PBUFFER_BIT,
+                    egl::RENDERABLE_TYPE,
+                    egl::OPENGL_BIT,
+                    egl::NONE,
+                ],
+            )
+            .ok()??;
+        let context = egl
+            .create_context(
+                display,
+                config,
+                None,
+                &[
+                    egl::CONTEXT_MAJOR_VERSION,
+                    3,
+                    egl::CONTEXT_MINOR_VERSION,
+                    3,
+                    egl::CONTEXT_OPENGL_PROFILE_MASK,
+                    egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                    egl::CONTEXT_OPENGL_FORWARD_COMPATIBLE,
+                    egl::TRUE as egl::Int,
+                    egl::NONE,
+                ],
+            )
+            .ok()?;
+
+        let uuid = egl
+            .make_current(display, None, None, Some(context))
+            .ok()
+            .and_then(|()| {
+                gl::load_with(|name| {
+                    egl.get_proc_address(name)
+                        .map_or(ptr::null(), |f| f as *const c_void)
+                });
+
+                // The UUID is part of `GL_EXT_memory_object`, which every driver that can share
+                // memory with Vulkan supports.
+                if !has_extension("GL_EXT_memory_object") {
+                    return None;
+                }
+                let get_unsigned_bytei: GetUnsignedBytei =
+                    unsafe { load(egl, "glGetUnsignedBytei_vEXT") }.ok()?;
+
+                let mut uuid = [0; 16];
+                unsafe { get_unsigned_bytei(DEVICE_UUID_EXT, 0, uuid.as_mut_ptr()) };
+                Some(uuid)
+            });
+
+        let _ = egl.make_current(display, None, None, None);
+        let _ = egl.destroy_context(display, context);
+        uuid
+    }
+
+    fn has_extension(name: &str) -> bool {
+        unsafe {
+            let mut count = 0;
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+
+            (0..count as GLuint).any(|i| {
+                let extension = CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, i) as *const _);
+                extension.to_bytes() == name.as_bytes()
+            })
+        }
+    }
+
+    // `F` must be the function pointer type matching the signature of `name`.
+    unsafe fn load<F>(egl: &Egl, name: &str) -> Result<F, String> {
+        let function = egl
+            .get_proc_address(name)
+            .ok_or_else(|| format!("{} is not available", name))?;
+        Ok(mem::transmute_copy(&function))
+    }
+}
+
+#[cfg(not(unix))]
+mod egl_device {
+    pub fn device_uuids() -> Result<Vec<[u8; 16]>, String> {
+        Err("EGL is only used on Unix platforms".to_string())
+    }
+}
+
+fn select_physical_device(instance: &Arc<Instance>) -> Arc<PhysicalDevice> {
+    let mut physical_devices: Vec<_> = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .collect();
+
+    match egl_device::device_uuids() {
+        Ok(uuids) => {
+            let position = uuids.iter().find_map(|uuid| {
+                physical_devices
+                    .iter()
+                    .position(|p| p.properties().device_uuid.as_ref() == Some(uuid))
+            });
+
+            match position {
+                Some(i) => {
+                    println!("Using the first EGL device");
+                    return physical_devices.swap_remove(i);
+                }
+                None => println!("No EGL device matches a Vulkan physical device"),
+            }
+        }
+        Err(err) => println!("Can't enumerate EGL devices: {}", err),
+    }
+
+    println!("Using the first Vulkan physical device");
+    physical_devices
+        .into_iter()
+        .next()
+        .expect("no devices available")
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+            void main() {
+                vec2 uv = (gl_GlobalInvocationID.xy + vec2(0.5)) / vec2(imageSize(img));
+                float rings = 0.5 + 0.5 * cos(length(uv - vec2(0.5)) * 60.0);
+                imageStore(img, ivec2(gl_GlobalInvocationID.xy), vec4(uv, rings, 1.0));
+            }
+        ",
+    }
+}
+
+fn main() {
+    let instance = get_headless_instance().expect("failed to create instance");
+    let physical_device = select_physical_device(&instance);
+    println!("Rendering on {}", physical_device.properties().device_name);
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|q| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let compute_pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let image = StorageImage::new(
+        &memory_allocator,
+        ImageDimensions::Dim2d {
+            width: WIDTH,
+            height: HEIGHT,
+            array_layers: 1,
+        },
+        Format::R8G8B8A8_UNORM,
+        Some(queue.queue_family_index()),
+    )
+    .unwrap();
+    let view = ImageView::new_default(image.clone()).unwrap();
+
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let layout = compute_pipeline.layout().set_layouts().get(0).unwrap();
+    let set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        layout.clone(),
+        [WriteDescriptorSet::image_view(0, view)],
+    )
+    .unwrap();
+
+    let buf = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (0..WIDTH * HEIGHT * 4).map(|_| 0u8),
+    )
+    .expect("failed to create buffer");
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+    builder
+        .bind_pipeline_compute(compute_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            compute_pipeline.layout().clone(),
+            0,
+            set,
+        )
+        .dispatch([WIDTH / 8, HEIGHT / 8, 1])
+        .unwrap()
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buf.clone()))
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    let buffer_content = buf.read().unwrap();
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(WIDTH, HEIGHT, &buffer_content[..]).unwrap();
+    image.save("image.png").unwrap();
+
+    println!("Everything succeeded!");
+}
@@ -0,0 +1,801 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Culls the objects hidden behind an occluder with a hierarchical depth buffer (Hi-Z), over a few
+//! frames where the occluder moves in front of a grid of cubes.
+//!
+//! Each frame, in a single command buffer:
+//!
+//! 1. A compute pass tests the bounding box of each object against the Hi-Z of the previous frame.
+//!    The box is projected to the screen, and the Hi-Z level where it covers at most 2x2 texels is
+//!    sampled with `textureLod`. The object is hidden if its nearest depth is farther than the
+//!    farthest depth of these texels. The visible objects are appended to a list, and counted in
+//!    the instance count of an indirect draw command.
+//! 2. The visible objects are drawn with `draw_indirect`.
+//! 3. The Hi-Z is rebuilt from the depth buffer. Each texel of a level holds the farthest depth of
+//!    the 2x2 texels below it, the first level being half the size of the depth buffer.
+//!
+//! The Hi-Z levels are computed with a compute shader rather than by blitting each level to the
+//! next. A blit of a depth image can only use nearest filtering, which keeps one of the four
+//! depths instead of the farthest, and would cull objects that are partly visible.
+//!
+//! As the Hi-Z comes from the previous frame, an object that becomes visible is only drawn one
+//! frame late. The number of culled objects is printed for each frame, and the last frame is
+//! saved to `image.png`.
+
+use std::sync::Arc;
+
+use chapter_code::Vertex3d;
+use image::{ImageBuffer, Rgba};
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, ClearColorImageInfo, CommandBufferUsage, CopyBufferInfo,
+    CopyImageToBufferInfo, DrawIndirectCommand, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::view::{ImageView, ImageViewAbstract, ImageViewCreateInfo};
+use vulkano::image::{
+    AttachmentImage, ImageAspects, ImageCreateFlags, ImageDimensions, ImageLayout,
+    ImageSubresourceRange, ImageUsage, ImmutableImage, MipmapsCount,
+};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sampler::{
+    Filter, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, LOD_CLAMP_NONE,
+};
+use vulkano::sync::{self, GpuFuture};
+
+const SIZE: u32 = 512;
+const HI_Z_SIZE: u32 = SIZE / 2;
+const HI_Z_LEVELS: u32 = HI_Z_SIZE.ilog2() + 1;
+const DEPTH_FORMAT: Format = Format::D32_SFLOAT;
+
+// Must match the local sizes of the shaders.
+const CULL_LOCAL_SIZE: u32 = 64;
+const DOWNSAMPLE_LOCAL_SIZE: u32 = 8;
+
+const FRAME_COUNT: u32 = 9;
+const GRID_SIZE: u32 = 16;
+const OBJECT_COUNT: u32 = 1 + GRID_SIZE * GRID_SIZE;
+
+/// Bounding box of an object of the scene, in view space. The `w` components are unused, they
+/// give the struct the same layout as in the shaders.
+#[derive(BufferContents, Clone, Copy)]
+#[repr(C)]
+struct Object {
+    center: [f32; 4],
+    half_extent: [f32; 4],
+}
+
+mod cull_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            const float near = 0.1;
+            const float far = 20.0;
+
+            struct Object {
+                vec4 center;
+                vec4 half_extent;
+            };
+
+            struct DrawCommand {
+                uint vertex_count;
+                uint instance_count;
+                uint first_vertex;
+                uint first_instance;
+            };
+
+            layout(set = 0, binding = 0) readonly buffer Objects {
+                Object objects[];
+            };
+
+            layout(set = 0, binding = 1) uniform sampler2D hi_z;
+
+            layout(set = 0, binding = 2) buffer Command {
+                DrawCommand command;
+            };
+
+            layout(set = 0, binding = 3) writeonly buffer Visible {
+                uint visible[];
+            };
+
+            vec4 project(vec3 position) {
+                return vec4(
+                    position.x,
+                    position.y,
+                    position.z * far / (near - far) + near * far / (near - far),
+                    -position.z
+                );
+            }
+
+            bool is_occluded(Object object) {
+                vec2 uv_min = vec2(1.0);
+                vec2 uv_max = vec2(0.0);
+                float nearest_depth = 1.0;
+
+                for (uint i = 0; i < 8; i++) {
+                    vec3 corner_sign = vec3(
+                        (i & 1u) != 0u ? 1.0 : -1.0,
+                        (i & 2u) != 0u ? 1.0 : -1.0,
+                        (i & 4u) != 0u ? 1.0 : -1.0
+                    );
+                    vec4 clip = project(object.center.xyz + object.half_extent.xyz * corner_sign);
+
+                    // The box crosses the near plane, it is too close to be tested.
+                    if (clip.w < near) {
+                        return false;
+                    }
+
+                    vec3 ndc = clip.xyz / clip.w;
+                    vec2 uv = ndc.xy * 0.5 + 0.5;
+                    uv_min = min(uv_min, uv);
+                    uv_max = max(uv_max, uv);
+                    nearest_depth = min(nearest_depth, ndc.z);
+                }
+
+                uv_min = clamp(uv_min, 0.0, 1.0);
+                uv_max = clamp(uv_max, 0.0, 1.0);
+
+                // The level where the box is at most one texel wide, so that it covers at most
+                // 2x2 texels.
+                vec2 size = (uv_max - uv_min) * vec2(textureSize(hi_z, 0));
+                float level = clamp(
+                    ceil(log2(max(max(size.x, size.y), 1.0))),
+                    0.0,
+                    float(textureQueryLevels(hi_z) - 1)
+                );
+
+                float farthest_depth = max(
+                    max(
+                        textureLod(hi_z, uv_min, level).r,
+                        textureLod(hi_z, vec2(uv_max.x, uv_min.y), level).r
+                    ),
+                    max(
+                        textureLod(hi_z, vec2(uv_min.x, uv_max.y), level).r,
+                        textureLod(hi_z, uv_max, level).r
+                    )
+                );
+
+                return nearest_depth > farthest_depth;
+            }
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+                if (idx >= objects.length() || is_occluded(objects[idx])) {
+                    return;
+                }
+
+                uint slot = atomicAdd(command.instance_count, 1u);
+                visible[slot] = idx;
+            }
+        ",
+    }
+}
+
+mod downsample_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            // Either the depth buffer or the previous level of the Hi-Z.
+            layout(set = 0, binding = 0) uniform sampler2D src;
+
+            layout(set = 0, binding = 1, r32f) writeonly uniform image2D dst;
+
+            void main() {
+                ivec2 coord = ivec2(gl_GlobalInvocationID.xy);
+                if (any(greaterThanEqual(coord, imageSize(dst)))) {
+                    return;
+                }
+
+                // The farthest of the 2x2 texels, so that the Hi-Z never hides what is in front
+                // of it.
+                ivec2 src_coord = coord * 2;
+                float depth = max(
+                    max(
+                        texelFetch(src, src_coord, 0).r,
+                        texelFetch(src, src_coord + ivec2(1, 0), 0).r
+                    ),
+                    max(
+                        texelFetch(src, src_coord + ivec2(0, 1), 0).r,
+                        texelFetch(src, src_coord + ivec2(1, 1), 0).r
+                    )
+                );
+                imageStore(dst, coord, vec4(depth));
+            }
+        ",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            const float near = 0.1;
+            const float far = 20.0;
+
+            struct Object {
+                vec4 center;
+                vec4 half_extent;
+            };
+
+            layout(location = 0) in vec3 position;
+
+            layout(location = 0) out vec3 color;
+
+            layout(set = 0, binding = 0) readonly buffer Objects {
+                Object objects[];
+            };
+
+            layout(set = 0, binding = 1) readonly buffer Visible {
+                uint visible[];
+            };
+
+            void main() {
+                uint idx = visible[gl_InstanceIndex];
+                Object object = objects[idx];
+                vec3 view_position = object.center.xyz + position * object.half_extent.xyz;
+
+                gl_Position = vec4(
+                    view_position.x,
+                    view_position.y,
+                    view_position.z * far / (near - far) + near * far / (near - far),
+                    -view_position.z
+                );
+
+                // The occluder is gray, and the faces of the cubes are shaded differently.
+                float hue = float(idx) / float(objects.length());
+                vec3 object_color = idx == 0
+                    ? vec3(0.5)
+                    : 0.5 + 0.5 * cos(6.2831853 * (hue + vec3(0.0, 0.33, 0.67)));
+                color = object_color * (0.7 + 0.3 * dot(position, vec3(0.2, 0.3, 0.5)));
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(color, 1.0);
+            }
+        ",
+    }
+}
+
+/// The 36 vertices of a cube going from -1 to 1, as triangles. Nothing is culled, so the winding
+/// doesn't matter.
+fn cube_vertices() -> Vec<Vertex3d> {
+    let mut vertices = Vec::with_capacity(36);
+
+    for axis in 0..3 {
+        for side in [-1.0, 1.0] {
+            let corner = |u: f32, v: f32| {
+                let mut position = [0.0; 3];
+                position[axis] = side;
+                position[(axis + 1) % 3] = u;
+                position[(axis + 2) % 3] = v;
+                Vertex3d { position }
+            };
+
+            vertices.extend([
+                corner(-1.0, -1.0),
+                corner(1.0, -1.0),
+                corner(1.0, 1.0),
+                corner(1.0, 1.0),
+                corner(-1.0, 1.0),
+                corner(-1.0, -1.0),
+            ]);
+        }
+    }
+
+    vertices
+}
+
+/// The occluder, moving from left to right in front of the grid over the frames.
+fn occluder(frame: u32) -> Object {
+    Object {
+        center: [frame as f32 - 4.0, 0.0, -4.0, 0.0],
+        half_extent: [1.5, 1.5, 0.2, 0.0],
+    }
+}
+
+/// The occluder followed by a grid of small cubes behind it.
+fn create_scene() -> Vec<Object> {
+    let half_extent = (GRID_SIZE - 1) as f32 / 2.0;
+
+    let cubes = (0..GRID_SIZE * GRID_SIZE).map(|i| Object {
+        center: [
+            (i % GRID_SIZE) as f32 - half_extent,
+            (i / GRID_SIZE) as f32 - half_extent,
+            -12.0,
+            0.0,
+        ],
+        half_extent: [0.3, 0.3, 0.3, 0.0],
+    });
+
+    [occluder(0)].into_iter().chain(cubes).collect()
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    let supports_depth_format = physical
+        .format_properties(DEPTH_FORMAT)
+        .unwrap()
+        .optimal_tiling_features
+        .contains(FormatFeatures::DEPTH_STENCIL_ATTACHMENT | FormatFeatures::SAMPLED_IMAGE);
+    if !supports_depth_format {
+        panic!("{DEPTH_FORMAT:?} can't be used as a sampled depth attachment on this device");
+    }
+
+    let queue_family_index = physical
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    // Buffers
+
+    // Host-visible, as the occluder is moved from the CPU between the frames.
+    let objects_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        create_scene(),
+    )
+    .unwrap();
+
+    let vertices = cube_vertices();
+    let vertex_count = vertices.len() as u32;
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        vertices,
+    )
+    .unwrap();
+
+    // Copied to the draw command at the start of each frame, so that it draws nothing until the
+    // culling shader adds the visible objects.
+    let initial_command_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [DrawIndirectCommand {
+            vertex_count,
+            instance_count: 0,
+            first_vertex: 0,
+            first_instance: 0,
+        }],
+    )
+    .unwrap();
+
+    // Read back after each frame to count the visible objects.
+    let indirect_buffer = Buffer::new_slice::<DrawIndirectCommand>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER
+                | BufferUsage::INDIRECT_BUFFER
+                | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        1,
+    )
+    .unwrap();
+
+    let visible_buffer = Buffer::new_slice::<u32>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        OBJECT_COUNT as u64,
+    )
+    .unwrap();
+
+    let buf = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (0..SIZE * SIZE * 4).map(|_| 0u8),
+    )
+    .expect("failed to create buffer");
+
+    // Images
+
+    let color_image = AttachmentImage::with_usage(
+        &memory_allocator,
+        [SIZE, SIZE],
+        Format::R8G8B8A8_UNORM,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    )
+    .unwrap();
+    let depth_image =
+        AttachmentImage::sampled(&memory_allocator, [SIZE, SIZE], DEPTH_FORMAT).unwrap();
+    let depth_view = ImageView::new_default(depth_image).unwrap();
+
+    // Written by the downsampling shader and sampled by the culling shader, so it is kept in the
+    // general layout.
+    let (hi_z, hi_z_init) = ImmutableImage::uninitialized(
+        &memory_allocator,
+        ImageDimensions::Dim2d {
+            width: HI_Z_SIZE,
+            height: HI_Z_SIZE,
+            array_layers: 1,
+        },
+        Format::R32_SFLOAT,
+        MipmapsCount::Specific(HI_Z_LEVELS),
+        ImageUsage::STORAGE | ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+        ImageCreateFlags::empty(),
+        ImageLayout::General,
+        Some(queue_family_index),
+    )
+    .unwrap();
+
+    // The whole chain, for the culling shader, and one view per level, for the downsampling
+    // shader.
+    let hi_z_view = ImageView::new_default(hi_z.clone()).unwrap();
+    let hi_z_level_views: Vec<_> = (0..HI_Z_LEVELS)
+        .map(|level| {
+            ImageView::new(
+                hi_z.clone(),
+                ImageViewCreateInfo {
+                    subresource_range: ImageSubresourceRange {
+                        aspects: ImageAspects::COLOR,
+                        mip_levels: level..level + 1,
+                        array_layers: 0..1,
+                    },
+                    ..ImageViewCreateInfo::from_image(&hi_z)
+                },
+            )
+            .unwrap()
+        })
+        .collect();
+
+    let sampler = Sampler::new(
+        device.clone(),
+        SamplerCreateInfo {
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            address_mode: [SamplerAddressMode::ClampToEdge; 3],
+            lod: 0.0..=LOD_CLAMP_NONE,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // Compute pipelines
+
+    let cull_shader = cull_cs::load(device.clone()).expect("failed to create shader module");
+    let cull_pipeline = ComputePipeline::new(
+        device.clone(),
+        cull_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let downsample_shader =
+        downsample_cs::load(device.clone()).expect("failed to create shader module");
+    let downsample_pipeline = ComputePipeline::new(
+        device.clone(),
+        downsample_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let cull_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        cull_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, objects_buffer.clone()),
+            WriteDescriptorSet::image_view_sampler(1, hi_z_view, sampler.clone()),
+            WriteDescriptorSet::buffer(2, indirect_buffer.clone()),
+            WriteDescriptorSet::buffer(3, visible_buffer.clone()),
+        ],
+    )
+    .unwrap();
+
+    // The first level is downsampled from the depth buffer, the others from the previous level.
+    let downsample_sets: Vec<_> = (0..HI_Z_LEVELS as usize)
+        .map(|level| {
+            let src: Arc<dyn ImageViewAbstract> = match level {
+                0 => depth_view.clone(),
+                _ => hi_z_level_views[level - 1].clone(),
+            };
+
+            PersistentDescriptorSet::new(
+                &descriptor_set_allocator,
+                downsample_pipeline.layout().set_layouts()[0].clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, src, sampler.clone()),
+                    WriteDescriptorSet::image_view(1, hi_z_level_views[level].clone()),
+                ],
+            )
+            .unwrap()
+        })
+        .collect();
+
+    // Graphics pipeline
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: Store,
+                format: DEPTH_FORMAT,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {depth},
+        },
+    )
+    .unwrap();
+
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![
+                ImageView::new_default(color_image.clone()).unwrap(),
+                depth_view,
+            ],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [SIZE as f32, SIZE as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    let graphics_pipeline = GraphicsPipeline::start()
+        .vertex_input_state(Vertex3d::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .unwrap();
+
+    let draw_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        graphics_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, objects_buffer.clone()),
+            WriteDescriptorSet::buffer(1, visible_buffer),
+        ],
+    )
+    .unwrap();
+
+    // The Hi-Z starts out at the far plane, so that nothing is culled in the first frame.
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+    builder
+        .clear_color_image(ClearColorImageInfo {
+            clear_value: [1.0, 0.0, 0.0, 0.0].into(),
+            ..ClearColorImageInfo::image(hi_z_init)
+        })
+        .unwrap();
+    let command_buffer = builder.build().unwrap();
+
+    sync::now(device.clone())
+        .then_execute(queue.clone(), command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    // Frames. The barriers between the commands are inserted by vulkano.
+
+    for frame in 0..FRAME_COUNT {
+        // The previous frame has finished executing, the buffer isn't in use.
+        objects_buffer.write().unwrap()[0] = occluder(frame);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(
+                initial_command_buffer.clone(),
+                indirect_buffer.clone(),
+            ))
+            .unwrap()
+            .bind_pipeline_compute(cull_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                cull_pipeline.layout().clone(),
+                0,
+                cull_set.clone(),
+            )
+            .dispatch([(OBJECT_COUNT + CULL_LOCAL_SIZE - 1) / CULL_LOCAL_SIZE, 1, 1])
+            .unwrap()
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into()), Some(1.0.into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(graphics_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                graphics_pipeline.layout().clone(),
+                0,
+                draw_set.clone(),
+            )
+            .bind_vertex_buffers(0, vertex_buffer.clone())
+            .draw_indirect(indirect_buffer.clone())
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+            .bind_pipeline_compute(downsample_pipeline.clone());
+
+        for (level, set) in downsample_sets.iter().enumerate() {
+            let level_size = HI_Z_SIZE >> level;
+            let work_group_count = (level_size + DOWNSAMPLE_LOCAL_SIZE - 1) / DOWNSAMPLE_LOCAL_SIZE;
+
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    downsample_pipeline.layout().clone(),
+                    0,
+                    set.clone(),
+                )
+                .dispatch([work_group_count, work_group_count, 1])
+                .unwrap();
+        }
+
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                color_image.clone(),
+                buf.clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+
+        let visible_count = indirect_buffer.read().unwrap()[0].instance_count;
+        println!(
+            "Frame {frame}: {} of {OBJECT_COUNT} objects culled",
+            OBJECT_COUNT - visible_count
+        );
+    }
+
+    let buffer_content = buf.read().unwrap();
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(SIZE, SIZE, &buffer_content[..]).unwrap();
+    image.save("image.png").unwrap();
+
+    println!("Everything succeeded!");
+}
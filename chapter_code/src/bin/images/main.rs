@@ -3,7 +3,10 @@ mod mandelbrot;
 
 use chapter_code::select_example_to_run;
 
-const EXAMPLES: [&str; 2] = ["image_clear", "mandelbrot"];
+const EXAMPLES: [(&str, &str); 2] = [
+    ("image_clear", "fills an image with a solid color"),
+    ("mandelbrot", "draws a fractal with a compute shader"),
+];
 
 fn execute_example(selection: &str) {
     println!("Running '{}'", selection);
@@ -19,5 +22,5 @@ fn execute_example(selection: &str) {
 }
 
 fn main() {
-    select_example_to_run(&EXAMPLES.to_vec(), execute_example);
+    select_example_to_run(&EXAMPLES, execute_example);
 }
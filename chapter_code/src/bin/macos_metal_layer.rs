@@ -0,0 +1,358 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders the triangle of the windowing chapter on macOS, creating the surface from a
+//! `CAMetalLayer` with `VK_EXT_metal_surface` instead of going through vulkano-win.
+//!
+//! winit creates the `NSWindow`. A `CAMetalLayer` is then attached to its content view, which is
+//! what vulkano-win does internally, and the surface is created from that layer.
+//!
+//! # MoltenVK
+//!
+//! macOS has no native Vulkan driver: Vulkan calls are translated to Metal at runtime by MoltenVK,
+//! which presents by drawing into the `CAMetalLayer`. This is different from a native Metal
+//! backend (like the one of wgpu), which talks to Metal directly and has no translation layer,
+//! and therefore supports exactly what the GPU supports. Through MoltenVK, some Vulkan features
+//! are emulated or missing (`VK_KHR_portability_subset`).
+//!
+//! When MoltenVK comes from the Vulkan SDK, the loader and the driver are found through the
+//! environment set up by the SDK's `setup-env.sh`. To use a MoltenVK build directly instead,
+//! point the dynamic loader at it, for example:
+//!
+//! ```bash
+//! DYLD_LIBRARY_PATH=/path/to/MoltenVK/dylib/macOS cargo run --bin macos_metal_layer
+//! ```
+
+#[cfg(target_os = "macos")]
+mod example {
+    use std::sync::Arc;
+
+    use chapter_code::shaders::static_triangle;
+    use chapter_code::vulkano_objects::allocators::Allocators;
+    use chapter_code::vulkano_objects::instance::get_instance;
+    use chapter_code::{vulkano_objects, Vertex2d};
+    use core_graphics_types::geometry::CGSize;
+    use metal::MetalLayer;
+    use objc::runtime::{Object, YES};
+    use objc::{msg_send, sel, sel_impl};
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, QueueCreateInfo};
+    use vulkano::image::ImageUsage;
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+    use vulkano::swapchain::{
+        self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+        SwapchainPresentInfo,
+    };
+    use vulkano::sync::future::FenceSignalFuture;
+    use vulkano::sync::{self, FlushError, GpuFuture};
+    use winit::dpi::PhysicalSize;
+    use winit::event::{Event, WindowEvent};
+    use winit::event_loop::{ControlFlow, EventLoop};
+    use winit::window::{Window, WindowBuilder};
+
+    /// Makes a new `CAMetalLayer` the backing layer of the window's content view.
+    fn attach_metal_layer(window: &Window) -> MetalLayer {
+        let ns_view = match window.raw_window_handle() {
+            RawWindowHandle::AppKit(handle) => handle.ns_view as *mut Object,
+            _ => unreachable!("winit always creates AppKit windows on macOS"),
+        };
+
+        let layer = MetalLayer::new();
+        layer.set_edge_antialiasing_mask(0);
+        layer.set_presents_with_transaction(false);
+        layer.remove_all_animations();
+        resize_metal_layer(&layer, window.inner_size(), window.scale_factor());
+
+        unsafe {
+            let _: () = msg_send![ns_view, setWantsLayer: YES];
+            let _: () = msg_send![ns_view, setLayer: metal_layer_ptr(&layer)];
+        }
+
+        layer
+    }
+
+    /// Keeps the size of the layer's drawables in sync with the window, in pixels.
+    fn resize_metal_layer(layer: &MetalLayer, size: PhysicalSize<u32>, scale_factor: f64) {
+        layer.set_contents_scale(scale_factor);
+        layer.set_drawable_size(CGSize::new(size.width as f64, size.height as f64));
+    }
+
+    fn metal_layer_ptr(layer: &MetalLayer) -> *mut Object {
+        // A reference to a `MetalLayerRef` is a pointer to the Objective-C object.
+        &**layer as *const _ as *mut Object
+    }
+
+    pub fn main() {
+        let instance = get_instance().expect("failed to create instance");
+
+        let event_loop = EventLoop::new();
+        let window = Arc::new(
+            WindowBuilder::new()
+                .with_title("vulkano macos_metal_layer")
+                .build(&event_loop)
+                .unwrap(),
+        );
+
+        let layer = attach_metal_layer(&window);
+
+        // The view retains the layer, and the surface keeps the window alive.
+        let surface = unsafe {
+            Surface::from_metal(
+                instance.clone(),
+                metal_layer_ptr(&layer),
+                Some(window.clone()),
+            )
+        }
+        .expect("failed to create a surface from the Metal layer");
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (mut swapchain, images) = {
+            let caps = physical_device
+                .surface_capabilities(&surface, Default::default())
+                .expect("failed to get surface capabilities");
+
+            let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+            let image_format = Some(
+                physical_device
+                    .surface_formats(&surface, Default::default())
+                    .unwrap()[0]
+                    .0,
+            );
+
+            Swapchain::new(
+                device.clone(),
+                surface,
+                SwapchainCreateInfo {
+                    min_image_count: caps.min_image_count,
+                    image_format,
+                    image_extent: window.inner_size().into(),
+                    image_usage: ImageUsage::COLOR_ATTACHMENT,
+                    composite_alpha,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+        let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+            &images,
+            render_pass.clone(),
+        );
+
+        let allocators = Allocators::new(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                Vertex2d {
+                    position: [-0.5, -0.5],
+                },
+                Vertex2d {
+                    position: [0.0, 0.5],
+                },
+                Vertex2d {
+                    position: [0.5, -0.25],
+                },
+            ],
+        )
+        .unwrap();
+
+        let vs = static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+        let fs = static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline =
+            vulkano_objects::pipeline::create_pipeline(device.clone(), vs, fs, render_pass.clone())
+                .expect("failed to create pipeline");
+
+        let mut command_buffers =
+            vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+                &allocators,
+                queue.clone(),
+                pipeline.clone(),
+                &framebuffers,
+                vertex_buffer.clone(),
+                vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+            );
+
+        let mut window_resized = false;
+        let mut recreate_swapchain = false;
+
+        let frames_in_flight = images.len();
+        let mut fences: Vec<Option<Arc<FenceSignalFuture<_>>>> = vec![None; frames_in_flight];
+        let mut previous_fence_i = 0;
+
+        event_loop.run(move |event, _, control_flow| match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(new_size),
+                ..
+            } => {
+                resize_metal_layer(&layer, new_size, window.scale_factor());
+                window_resized = true;
+            }
+            // Moving the window to a display with a different density changes its size in pixels.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    },
+                ..
+            } => {
+                resize_metal_layer(&layer, *new_inner_size, scale_factor);
+                window_resized = true;
+            }
+            Event::MainEventsCleared => {
+                if window_resized || recreate_swapchain {
+                    window_resized = false;
+                    recreate_swapchain = false;
+
+                    let new_dimensions = window.inner_size();
+
+                    let (new_swapchain, new_images) =
+                        match swapchain.recreate(SwapchainCreateInfo {
+                            image_extent: new_dimensions.into(),
+                            ..swapchain.create_info()
+                        }) {
+                            Ok(r) => r,
+                            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                            Err(e) => panic!("failed to recreate swapchain: {e}"),
+                        };
+                    swapchain = new_swapchain;
+                    let new_framebuffers =
+                        vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+                            &new_images,
+                            render_pass.clone(),
+                        );
+
+                    // The viewport is set from the framebuffers when recording, so the pipeline
+                    // stays the same whatever the new size.
+                    command_buffers =
+                        vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+                            &allocators,
+                            queue.clone(),
+                            pipeline.clone(),
+                            &new_framebuffers,
+                            vertex_buffer.clone(),
+                            vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+                        );
+                }
+
+                let (image_i, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None) {
+                        Ok(r) => r,
+                        Err(AcquireError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            return;
+                        }
+                        Err(e) => panic!("failed to acquire next image: {e}"),
+                    };
+
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                if let Some(image_fence) = &fences[image_i as usize] {
+                    image_fence.wait(None).unwrap();
+                }
+
+                let previous_future = match fences[previous_fence_i as usize].clone() {
+                    None => {
+                        let mut now = sync::now(device.clone());
+                        now.cleanup_finished();
+
+                        now.boxed()
+                    }
+                    Some(fence) => fence.boxed(),
+                };
+
+                let future = previous_future
+                    .join(acquire_future)
+                    .then_execute(queue.clone(), command_buffers[image_i as usize].clone())
+                    .unwrap()
+                    .then_swapchain_present(
+                        queue.clone(),
+                        SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i),
+                    )
+                    .then_signal_fence_and_flush();
+
+                fences[image_i as usize] = match future {
+                    Ok(value) => Some(Arc::new(value)),
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        None
+                    }
+                    Err(e) => {
+                        println!("failed to flush future: {e}");
+                        None
+                    }
+                };
+
+                previous_fence_i = image_i;
+            }
+            _ => (),
+        });
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    println!("This example is only available on macOS");
+}
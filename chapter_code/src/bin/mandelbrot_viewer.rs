@@ -0,0 +1,510 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Draws the fractal of the `mandelbrot` example in a window, where the arrow keys pan the view
+//! and + and - zoom it.
+//!
+//! Instead of being exported once to a file, the image is computed again every frame, with the
+//! center and the scale of the view given to the compute shader as push constants. The compute
+//! shader can't write to the swapchain images directly, as their sRGB formats usually don't
+//! support storage, so it writes to a storage image of the same size that is then blitted to the
+//! swapchain image, converting the format on the way.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::{is_headless, vulkano_objects, HEADLESS_FRAME_COUNT};
+use glam::Vec2;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
+};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImageUsage, StorageImage, SwapchainImage};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+    SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+            // The point of the complex plane at the center of the image, and the height of the
+            // part of the plane that the image shows.
+            layout(push_constant) uniform PushConstants {
+                vec2 center;
+                float scale;
+            } view;
+
+            void main() {
+                ivec2 size = imageSize(img);
+                // The image is rarely a multiple of the workgroup size.
+                if (any(greaterThanEqual(gl_GlobalInvocationID.xy, uvec2(size)))) {
+                    return;
+                }
+
+                vec2 pixel = gl_GlobalInvocationID.xy + vec2(0.5);
+                vec2 offset = (pixel - vec2(size) / 2.0) / float(size.y);
+                vec2 c = view.center + offset * view.scale;
+
+                vec2 z = vec2(0.0, 0.0);
+                float i;
+                for (i = 0.0; i < 1.0; i += 0.005) {
+                    z = vec2(
+                        z.x * z.x - z.y * z.y + c.x,
+                        z.y * z.x + z.x * z.y + c.y
+                    );
+
+                    if (length(z) > 4.0) {
+                        break;
+                    }
+                }
+
+                vec4 to_write = vec4(vec3(i), 1.0);
+                imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+            }
+        ",
+    }
+}
+
+// The view of the `mandelbrot` example.
+const START_CENTER: Vec2 = Vec2::new(-1.0, 0.0);
+const START_SCALE: f32 = 2.0;
+
+// Past this, neighboring pixels are the same `f32` and the image becomes blocky.
+const MIN_SCALE: f32 = 1e-5;
+const MAX_SCALE: f32 = 8.0;
+
+// How far the view moves per second, relative to its height, and how many times it zooms per
+// second.
+const PAN_SPEED: f32 = 0.5;
+const ZOOM_SPEED: f32 = 2.0;
+
+type Fence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+// The keys that are held down.
+#[derive(Default)]
+struct Keys {
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    zoom_in: bool,
+    zoom_out: bool,
+}
+
+// 1 if only the `positive` key is held, -1 if only the `negative` one is, and 0 otherwise.
+fn axis(positive: bool, negative: bool) -> f32 {
+    match (positive, negative) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    }
+}
+
+struct View {
+    center: Vec2,
+    scale: f32,
+}
+
+impl View {
+    fn new() -> Self {
+        Self {
+            center: START_CENTER,
+            scale: START_SCALE,
+        }
+    }
+
+    fn update(&mut self, keys: &Keys, seconds_passed: f32) {
+        let direction = Vec2::new(axis(keys.right, keys.left), axis(keys.down, keys.up));
+        self.center += direction * PAN_SPEED * self.scale * seconds_passed;
+
+        let zoom = ZOOM_SPEED.powf(axis(keys.zoom_in, keys.zoom_out) * seconds_passed);
+        self.scale = (self.scale / zoom).clamp(MIN_SCALE, MAX_SCALE);
+    }
+
+    fn push_constants(&self) -> cs::PushConstants {
+        cs::PushConstants {
+            center: self.center.into(),
+            scale: self.scale,
+        }
+    }
+}
+
+// The image the compute shader writes to for one of the swapchain images.
+struct Target {
+    swapchain_image: Arc<SwapchainImage>,
+    storage_image: Arc<StorageImage>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+}
+
+struct Renderer {
+    surface: Arc<Surface>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    allocators: Allocators,
+    pipeline: Arc<ComputePipeline>,
+    targets: Vec<Target>,
+    fences: Vec<Option<Arc<Fence>>>,
+    previous_fence_i: u32,
+    recreate_swapchain: bool,
+}
+
+impl Renderer {
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+
+        let surface = WindowBuilder::new()
+            .with_title("Mandelbrot viewer")
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        // Every implementation has a queue family supporting both, and in practice it is the
+        // graphics one.
+        assert!(
+            physical_device.queue_family_properties()[queue_family_index as usize]
+                .queue_flags
+                .contains(QueueFlags::COMPUTE),
+            "the graphics queue family doesn't support compute"
+        );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        // The images are only blitted to, so unlike the ones of `create_swapchain` they don't
+        // need to be color attachments.
+        let (swapchain, images) = {
+            let caps = physical_device
+                .surface_capabilities(&surface, Default::default())
+                .expect("failed to get surface capabilities");
+            assert!(
+                caps.supported_usage_flags
+                    .contains(ImageUsage::TRANSFER_DST),
+                "the surface doesn't support blitting to its images"
+            );
+
+            let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+            let (image_format, image_color_space) =
+                vulkano_objects::swapchain::select_surface_format(&physical_device, &surface)
+                    .expect("failed to query surface formats");
+
+            Swapchain::new(
+                device.clone(),
+                surface.clone(),
+                SwapchainCreateInfo {
+                    min_image_count: caps.min_image_count,
+                    image_format: Some(image_format),
+                    image_color_space,
+                    image_extent: window(&surface).inner_size().into(),
+                    image_usage: ImageUsage::TRANSFER_DST,
+                    composite_alpha,
+                    ..Default::default()
+                },
+            )
+            .expect("failed to create swapchain")
+        };
+
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = vulkano_objects::compute::create_compute_pipeline(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+        )
+        .expect("failed to create compute pipeline");
+
+        let allocators = Allocators::new(device.clone());
+
+        let mut renderer = Self {
+            surface,
+            device,
+            queue,
+            swapchain,
+            allocators,
+            pipeline,
+            targets: Vec::new(),
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            recreate_swapchain: false,
+        };
+        renderer.create_targets(images);
+
+        renderer
+    }
+
+    fn handle_window_resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    // The storage images have the size of the swapchain images, so they are created again with
+    // them.
+    fn create_targets(&mut self, swapchain_images: Vec<Arc<SwapchainImage>>) {
+        let [width, height] = self.swapchain.image_extent();
+        let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
+
+        self.targets = swapchain_images
+            .into_iter()
+            .map(|swapchain_image| {
+                let storage_image = StorageImage::new(
+                    &self.allocators.memory,
+                    ImageDimensions::Dim2d {
+                        width,
+                        height,
+                        array_layers: 1,
+                    },
+                    Format::R8G8B8A8_UNORM,
+                    Some(self.queue.queue_family_index()),
+                )
+                .unwrap();
+                let view = ImageView::new_default(storage_image.clone()).unwrap();
+                let descriptor_set = PersistentDescriptorSet::new(
+                    &self.allocators.descriptor_set,
+                    layout.clone(),
+                    [WriteDescriptorSet::image_view(0, view)],
+                )
+                .unwrap();
+
+                Target {
+                    swapchain_image,
+                    storage_image,
+                    descriptor_set,
+                }
+            })
+            .collect();
+    }
+
+    fn create_command_buffer(&self, target: &Target, view: &View) -> PrimaryAutoCommandBuffer {
+        let [width, height] = self.swapchain.image_extent();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.allocators.command_buffer,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                target.descriptor_set.clone(),
+            )
+            .push_constants(self.pipeline.layout().clone(), 0, view.push_constants())
+            // Rounded up, the shader skips the invocations outside of the image.
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap()
+            .blit_image(BlitImageInfo::images(
+                target.storage_image.clone(),
+                target.swapchain_image.clone(),
+            ))
+            .unwrap();
+
+        builder.build().unwrap()
+    }
+
+    fn render(&mut self, view: &View) {
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: window(&self.surface).inner_size().into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.recreate_swapchain = false;
+            self.swapchain = new_swapchain;
+            self.create_targets(new_images);
+        }
+
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        // The storage image of the target is written again, so the GPU must be done reading it.
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let command_buffer = self.create_command_buffer(&self.targets[image_i as usize], view);
+
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(self.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i;
+    }
+}
+
+fn window(surface: &Surface) -> Arc<Window> {
+    surface
+        .object()
+        .unwrap()
+        .clone()
+        .downcast::<Window>()
+        .unwrap()
+}
+
+fn main() {
+    println!("Press the arrow keys to move, + and - to zoom, and ESC to quit");
+
+    let event_loop = EventLoop::new();
+    let mut renderer = Renderer::new(&event_loop);
+
+    let mut view = View::new();
+    let mut keys = Keys::default();
+    let mut previous_frame_time = Instant::now();
+
+    let headless = is_headless();
+    let mut frame_count = 0;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            renderer.handle_window_resize();
+        }
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(key_code),
+                            state,
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => {
+            let pressed = state == ElementState::Pressed;
+            match key_code {
+                VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+                VirtualKeyCode::Left => keys.left = pressed,
+                VirtualKeyCode::Right => keys.right = pressed,
+                VirtualKeyCode::Up => keys.up = pressed,
+                VirtualKeyCode::Down => keys.down = pressed,
+                // `+` is on the same key as `=` on most layouts.
+                VirtualKeyCode::Plus | VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+                    keys.zoom_in = pressed
+                }
+                VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => keys.zoom_out = pressed,
+                _ => {}
+            }
+        }
+        Event::MainEventsCleared => {
+            let this_frame_time = Instant::now();
+            let seconds_passed = (this_frame_time - previous_frame_time).as_secs_f32();
+            previous_frame_time = this_frame_time;
+
+            view.update(&keys, seconds_passed);
+            renderer.render(&view);
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        _ => (),
+    });
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Stress test for the `StandardMemoryAllocator` near the limits of device memory.
+//!
+//! The example allocates 256 MB device-local buffers until the allocator reports that the device
+//! is out of memory, frees all of them and checks that the same number of allocations succeeds a
+//! second time. It then frees half of the buffers and checks that they can be allocated again. If
+//! memory was leaked anywhere along the way, one of those checks fails.
+//!
+//! This may make other applications using the GPU misbehave while it runs.
+
+use vulkano::buffer::{
+    Buffer, BufferCreateInfo, BufferError, BufferMemory, BufferUsage, Subbuffer,
+};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::memory::{MemoryHeapFlags, MemoryPropertyFlags};
+use vulkano::DeviceSize;
+
+const CHUNK_SIZE: DeviceSize = 256 * 1024 * 1024;
+
+// Upper bound in case the driver keeps handing out memory past the size of the heap (for example
+// by paging to system memory).
+const MAX_EXTRA_CHUNKS: DeviceSize = 4;
+
+fn device_local_heap_size(physical_device: &PhysicalDevice) -> DeviceSize {
+    physical_device
+        .memory_properties()
+        .memory_heaps
+        .iter()
+        .filter(|heap| heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size)
+        .max()
+        .expect("no device-local memory heap")
+}
+
+fn is_device_local(physical_device: &PhysicalDevice, buffer: &Subbuffer<[u8]>) -> bool {
+    let memory_type_index = match buffer.buffer().memory() {
+        BufferMemory::Normal(allocation) => allocation.device_memory().memory_type_index(),
+        _ => return false,
+    };
+
+    physical_device.memory_properties().memory_types[memory_type_index as usize]
+        .property_flags
+        .intersects(MemoryPropertyFlags::DEVICE_LOCAL)
+}
+
+/// Tries to allocate one chunk. Returns `None` once the device-local memory is exhausted.
+fn allocate_chunk(
+    physical_device: &PhysicalDevice,
+    memory_allocator: &StandardMemoryAllocator,
+) -> Option<Subbuffer<[u8]>> {
+    let result = Buffer::new_slice::<u8>(
+        memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        CHUNK_SIZE,
+    );
+
+    match result {
+        // `DeviceOnly` only prefers device-local memory, the allocator falls back to other memory
+        // types when the device-local heap is full. Treat that the same as running out.
+        Ok(buffer) if is_device_local(physical_device, &buffer) => Some(buffer),
+        Ok(_) => None,
+        Err(BufferError::AllocError(e)) => {
+            println!("  allocation failed: {}", e);
+            None
+        }
+        Err(e) => panic!("failed to create buffer: {}", e),
+    }
+}
+
+/// Allocates chunks until the device runs out of memory.
+fn allocate_until_exhausted(
+    physical_device: &PhysicalDevice,
+    memory_allocator: &StandardMemoryAllocator,
+    max_chunks: DeviceSize,
+) -> Vec<Subbuffer<[u8]>> {
+    let mut buffers = Vec::new();
+
+    while (buffers.len() as DeviceSize) < max_chunks {
+        match allocate_chunk(physical_device, memory_allocator) {
+            Some(buffer) => buffers.push(buffer),
+            None => break,
+        }
+    }
+
+    buffers
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    let (device, _queues) = Device::new(
+        physical_device.clone(),
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let heap_size = device_local_heap_size(&physical_device);
+    let max_chunks = heap_size / CHUNK_SIZE + MAX_EXTRA_CHUNKS;
+    println!(
+        "Device-local heap of {} MB, allocating {} MB chunks",
+        heap_size / (1024 * 1024),
+        CHUNK_SIZE / (1024 * 1024),
+    );
+
+    // First pass: find out how many chunks fit.
+    let buffers = allocate_until_exhausted(&physical_device, &memory_allocator, max_chunks);
+    let first_count = buffers.len();
+    println!("First pass: {} chunks allocated", first_count);
+    assert!(first_count > 0, "couldn't allocate a single chunk");
+    drop(buffers);
+
+    // Second pass: everything was freed, so the same amount has to fit again.
+    let mut buffers = allocate_until_exhausted(&physical_device, &memory_allocator, max_chunks);
+    let second_count = buffers.len();
+    println!("Second pass: {} chunks allocated", second_count);
+    assert_eq!(
+        first_count, second_count,
+        "memory wasn't fully released after the first pass"
+    );
+
+    // Free every other chunk and allocate the same number again.
+    let mut i = 0;
+    buffers.retain(|_| {
+        i += 1;
+        i % 2 == 0
+    });
+    let freed = second_count - buffers.len();
+    println!("Freed {} chunks, reallocating them", freed);
+
+    for n in 0..freed {
+        let buffer = allocate_chunk(&physical_device, &memory_allocator)
+            .unwrap_or_else(|| panic!("failed to reallocate freed chunk {} of {}", n + 1, freed));
+        buffers.push(buffer);
+    }
+    assert_eq!(buffers.len(), second_count);
+    drop(buffers);
+
+    println!("Everything succeeded!");
+}
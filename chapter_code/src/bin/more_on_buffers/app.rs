@@ -1,10 +1,28 @@
 use std::time::Duration;
 
-use chapter_code::game_objects::Square;
-use winit::event::{ElementState, VirtualKeyCode};
+use chapter_code::game_objects::{Square, SquareGrid};
+use chapter_code::vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR;
+use chapter_code::FpsCounter;
+use glam::Vec2;
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, MouseButton, VirtualKeyCode};
 use winit::event_loop::EventLoop;
 
-use crate::render::RenderLoop;
+use crate::render::{RenderLoop, WINDOW_TITLE};
+
+const GRID_COLUMNS: u32 = 10;
+const GRID_ROWS: u32 = 10;
+
+// How long the frame rate shown in the window title is averaged over.
+const FPS_INTERVAL: Duration = Duration::from_millis(500);
+
+// The background colors that C cycles through.
+const CLEAR_COLORS: [[f32; 4]; 4] = [
+    DEFAULT_CLEAR_COLOR,
+    [0.0, 0.0, 0.0, 1.0],
+    [0.1, 0.2, 0.4, 1.0],
+    [0.9, 0.9, 0.9, 1.0],
+];
 
 #[derive(Default, PartialEq)]
 pub enum KeyState {
@@ -21,24 +39,57 @@ struct Keys {
     w: KeyState,
     s: KeyState,
     d: KeyState,
+    q: KeyState,
+    e: KeyState,
+    plus: KeyState,
+    minus: KeyState,
     space: KeyState,
+    c: KeyState,
+    f11: KeyState,
+    f12: KeyState,
+    left_mouse_button: KeyState,
+}
+
+// 1 if only the `positive` key is pressed, -1 if only the `negative` one is, and 0 otherwise, so
+// that opposite keys cancel each other out.
+fn axis(positive: &KeyState, negative: &KeyState) -> f32 {
+    match (positive, negative) {
+        (Pressed, Released) => 1.0,
+        (Released, Pressed) => -1.0,
+        _ => 0.0,
+    }
 }
 
 pub struct App {
     render_loop: RenderLoop,
     square: Square,
+    grid: SquareGrid,
     keys: Keys,
+    fps_counter: FpsCounter,
+    cursor_position: PhysicalPosition<f64>,
+    clear_color_i: usize,
 }
 
 impl App {
     pub fn start(event_loop: &EventLoop<()>) -> Self {
         println!("Welcome to the movable square example!");
-        println!("Press WASD to move and SPACE to change color");
+        println!("Press WASD to move the squares and SPACE to change their tint");
+        println!("Press C to change the background color");
+        println!("Press Q and E to rotate the squares, and + and - to scale them");
+        println!("Drag the squares with the left mouse button");
+        println!("Press F11 to toggle fullscreen and F12 to save a screenshot");
+        println!("Press ESC to quit");
+
+        let grid = SquareGrid::new(GRID_COLUMNS, GRID_ROWS);
 
         Self {
-            render_loop: RenderLoop::new(event_loop),
+            render_loop: RenderLoop::new(event_loop, &grid),
             square: Square::new(),
+            grid,
             keys: Keys::default(),
+            fps_counter: FpsCounter::new(WINDOW_TITLE, FPS_INTERVAL),
+            cursor_position: PhysicalPosition::default(),
+            clear_color_i: 0,
         }
     }
 
@@ -46,23 +97,27 @@ impl App {
         let seconds_passed = (duration_since_last_update.as_micros() as f32) / 1000000.0;
 
         self.update_movement(seconds_passed);
+        self.grid.update(seconds_passed);
+
+        self.render_loop.update(&self.square, &self.grid);
 
-        self.render_loop.update(&self.square);
+        let gpu_time = self.render_loop.take_gpu_time();
+        if let Some(title) = self
+            .fps_counter
+            .record_frame_with_gpu_time(*duration_since_last_update, gpu_time)
+        {
+            self.render_loop.set_title(title);
+        }
     }
 
     fn update_movement(&mut self, seconds_passed: f32) {
-        if self.keys.w == Pressed && self.keys.s == Released {
-            self.square.move_up(seconds_passed)
-        }
-        if self.keys.s == Pressed && self.keys.w == Released {
-            self.square.move_down(seconds_passed)
-        }
-        if self.keys.a == Pressed && self.keys.d == Released {
-            self.square.move_left(seconds_passed)
-        }
-        if self.keys.d == Pressed && self.keys.a == Released {
-            self.square.move_right(seconds_passed)
-        }
+        let keys = &self.keys;
+        let direction = Vec2::new(axis(&keys.d, &keys.a), axis(&keys.s, &keys.w));
+
+        self.square.move_in_direction(direction, seconds_passed);
+        self.square.rotate(axis(&keys.e, &keys.q), seconds_passed);
+        self.square
+            .grow(axis(&keys.plus, &keys.minus), seconds_passed);
     }
 
     pub fn handle_keyboard_input(&mut self, key_code: VirtualKeyCode, state: ElementState) {
@@ -78,14 +133,75 @@ impl App {
                 }
                 self.keys.space = state;
             }
+            VirtualKeyCode::C => {
+                if state == Pressed && self.keys.c == Released {
+                    self.clear_color_i = (self.clear_color_i + 1) % CLEAR_COLORS.len();
+                    self.render_loop
+                        .set_clear_color(CLEAR_COLORS[self.clear_color_i]);
+                }
+                self.keys.c = state;
+            }
+            VirtualKeyCode::F11 => {
+                if state == Pressed && self.keys.f11 == Released {
+                    self.render_loop.toggle_fullscreen();
+                }
+                self.keys.f11 = state;
+            }
+            VirtualKeyCode::F12 => {
+                if state == Pressed && self.keys.f12 == Released {
+                    self.render_loop.request_screenshot();
+                }
+                self.keys.f12 = state;
+            }
             VirtualKeyCode::W => self.keys.w = state,
             VirtualKeyCode::A => self.keys.a = state,
             VirtualKeyCode::S => self.keys.s = state,
             VirtualKeyCode::D => self.keys.d = state,
+            VirtualKeyCode::Q => self.keys.q = state,
+            VirtualKeyCode::E => self.keys.e = state,
+            // `+` is on the same key as `=` on most layouts.
+            VirtualKeyCode::Plus | VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+                self.keys.plus = state
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => self.keys.minus = state,
             _ => {}
         }
     }
 
+    pub fn handle_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor_position = position;
+
+        if self.keys.left_mouse_button == Pressed {
+            self.move_square_to_cursor();
+        }
+    }
+
+    pub fn handle_mouse_input(&mut self, button: MouseButton, state: ElementState) {
+        if button != MouseButton::Left {
+            return;
+        }
+
+        self.keys.left_mouse_button = match state {
+            ElementState::Pressed => Pressed,
+            ElementState::Released => Released,
+        };
+
+        if self.keys.left_mouse_button == Pressed {
+            self.move_square_to_cursor();
+        }
+    }
+
+    fn move_square_to_cursor(&mut self) {
+        if let Some(position) = self.render_loop.cursor_to_world(self.cursor_position) {
+            self.square.position = position;
+        }
+    }
+
+    /// Marks every key as released, as if the user let go of all of them.
+    pub fn reset_keys(&mut self) {
+        self.keys = Keys::default();
+    }
+
     pub fn handle_window_resize(&mut self) {
         self.render_loop.handle_window_resize()
     }
@@ -1,18 +1,28 @@
 pub mod app;
 pub mod render;
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use winit::event::{Event, WindowEvent};
+use chapter_code::{is_headless, RenderStats, HEADLESS_FRAME_COUNT};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 use crate::app::App;
 
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
 fn main() {
     let event_loop = EventLoop::new();
     let mut app = App::start(&event_loop);
 
     let mut previous_frame_time = Instant::now();
+
+    let mut render_stats = RenderStats::new();
+    let mut previous_stats_time = Instant::now();
+
+    let headless = is_headless();
+    let mut frame_count = 0;
+
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             event: WindowEvent::CloseRequested,
@@ -26,6 +36,22 @@ fn main() {
         } => {
             app.handle_window_resize();
         }
+        // The app has no access to the control flow, so quitting is handled here.
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
         Event::WindowEvent {
             event: WindowEvent::KeyboardInput { input, .. },
             ..
@@ -34,6 +60,25 @@ fn main() {
                 app.handle_keyboard_input(key_code, input.state)
             }
         }
+        Event::WindowEvent {
+            event: WindowEvent::CursorMoved { position, .. },
+            ..
+        } => {
+            app.handle_cursor_moved(position);
+        }
+        Event::WindowEvent {
+            event: WindowEvent::MouseInput { button, state, .. },
+            ..
+        } => {
+            app.handle_mouse_input(button, state);
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Focused(false),
+            ..
+        } => {
+            // key releases that happen while the window isn't focused are never received
+            app.reset_keys();
+        }
         Event::MainEventsCleared => {
             let this_frame_time = Instant::now();
             let duration_from_last_frame = this_frame_time - previous_frame_time;
@@ -41,6 +86,22 @@ fn main() {
             app.update(&duration_from_last_frame);
 
             previous_frame_time = this_frame_time;
+
+            render_stats.record_frame(duration_from_last_frame);
+            if this_frame_time - previous_stats_time >= STATS_INTERVAL {
+                println!(
+                    "Frame time: p50 {} us, p95 {} us, p99 {} us",
+                    render_stats.percentile_frame_time_us(50.0),
+                    render_stats.percentile_frame_time_us(95.0),
+                    render_stats.p99_frame_time_us(),
+                );
+                previous_stats_time = this_frame_time;
+            }
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
         }
         _ => (),
     });
@@ -2,3 +2,4 @@ mod render_loop;
 mod renderer;
 
 pub use render_loop::RenderLoop;
+pub use renderer::WINDOW_TITLE;
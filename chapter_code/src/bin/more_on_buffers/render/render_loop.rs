@@ -1,44 +1,70 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use chapter_code::game_objects::Square;
+use chapter_code::game_objects::{Square, SquareGrid};
 use vulkano::swapchain::AcquireError;
 use vulkano::sync::{FlushError, GpuFuture};
+use winit::dpi::PhysicalPosition;
 use winit::event_loop::EventLoop;
 
 use crate::render::renderer::{Fence, Renderer};
 
+/// How many frames the CPU can submit before waiting for the GPU to finish the oldest one,
+/// independently of how many images the swapchain has. There is an instance buffer for each.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+// Vulkano creates and cycles the semaphores of each frame itself, as part of the futures: the
+// acquire future signals one when the image is ready, and presenting waits for the one signaled
+// when the command buffer is done. Only the fences need to be kept here.
+//
+// The command buffer is recorded again every frame, so nothing is tied to the swapchain images
+// and there is no need to wait for the last frame that rendered to the acquired one.
 pub struct RenderLoop {
     renderer: Renderer,
     recreate_swapchain: bool,
     window_resized: bool,
-    fences: Vec<Option<Arc<Fence>>>,
-    previous_fence_i: u32,
+    // The fence of the last submission of each frame in flight, waited for before reusing it.
+    frame_fences: Vec<Option<Arc<Fence>>>,
+    frame_i: usize,
+    previous_fence: Option<Arc<Fence>>,
+    // Measured when the fence of a frame has been waited for, until taken by `take_gpu_time`.
+    gpu_time: Option<Duration>,
 }
 
 impl RenderLoop {
-    pub fn new(event_loop: &EventLoop<()>) -> Self {
-        let renderer = Renderer::initialize(event_loop);
-        let frames_in_flight = renderer.get_image_count();
-        let fences: Vec<Option<Arc<Fence>>> = vec![None; frames_in_flight];
-
+    pub fn new(event_loop: &EventLoop<()>, grid: &SquareGrid) -> Self {
         Self {
-            renderer,
+            renderer: Renderer::initialize(event_loop, grid),
             recreate_swapchain: false,
             window_resized: false,
-            fences,
-            previous_fence_i: 0,
+            frame_fences: vec![None; MAX_FRAMES_IN_FLIGHT],
+            frame_i: 0,
+            previous_fence: None,
+            gpu_time: None,
         }
     }
 
-    pub fn update(&mut self, triangle: &Square) {
-        if self.window_resized {
-            self.window_resized = false;
-            self.recreate_swapchain = false;
-            self.renderer.handle_window_resize();
+    pub fn update(&mut self, triangle: &Square, grid: &SquareGrid) {
+        // Nothing is rendered until the window is restored. The pending resize is kept, so that the
+        // swapchain is recreated then.
+        if self.renderer.is_minimized() {
+            return;
         }
-        if self.recreate_swapchain {
-            self.recreate_swapchain = false;
-            self.renderer.recreate_swapchain();
+
+        // However many resize events there were since the last frame, the swapchain is recreated
+        // only once, for the current size of the window. If that size isn't supported (yet), it is
+        // tried again next frame.
+        if self.window_resized || self.recreate_swapchain {
+            let recreated = if self.window_resized {
+                self.renderer.handle_window_resize()
+            } else {
+                self.renderer.recreate_swapchain()
+            };
+
+            if recreated {
+                self.window_resized = false;
+                self.recreate_swapchain = false;
+            }
         }
 
         let (image_i, suboptimal, acquire_future) = match self.renderer.acquire_swapchain_image() {
@@ -54,15 +80,21 @@ impl RenderLoop {
             self.recreate_swapchain = true;
         }
 
-        if let Some(image_fence) = &self.fences[image_i as usize] {
-            image_fence.wait(None).unwrap();
+        // a new uniform buffer is used every frame, so it can be written to before the wait
+        let uniform_set = self.renderer.update_uniform(triangle);
+
+        if let Some(frame_fence) = &self.frame_fences[self.frame_i] {
+            frame_fence.wait(None).unwrap();
+
+            // The previous frame of this slot is done, so its timestamps can be read.
+            self.gpu_time = self.renderer.gpu_frame_time(self.frame_i);
         }
 
         // logic that uses the GPU resources that are currently not used (have been waited upon)
-        self.renderer.update_uniform(image_i, triangle);
+        self.renderer.update_instances(self.frame_i, grid);
 
         let something_needs_all_gpu_resources = false;
-        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+        let previous_future = match self.previous_fence.clone() {
             None => self.renderer.synchronize().boxed(),
             Some(fence) => {
                 if something_needs_all_gpu_resources {
@@ -76,11 +108,15 @@ impl RenderLoop {
             // logic that can use every GPU resource (the GPU is sleeping)
         }
 
-        let result = self
-            .renderer
-            .flush_next_future(previous_future, acquire_future, image_i);
+        let result = self.renderer.flush_next_future(
+            previous_future,
+            acquire_future,
+            image_i,
+            self.frame_i,
+            uniform_set,
+        );
 
-        self.fences[image_i as usize] = match result {
+        let fence = match result {
             Ok(fence) => Some(Arc::new(fence)),
             Err(FlushError::OutOfDate) => {
                 self.recreate_swapchain = true;
@@ -92,9 +128,39 @@ impl RenderLoop {
             }
         };
 
-        self.previous_fence_i = image_i;
+        self.frame_fences[self.frame_i] = fence.clone();
+        self.previous_fence = fence;
+        self.frame_i = (self.frame_i + 1) % MAX_FRAMES_IN_FLIGHT;
+    }
+
+    /// The time the GPU took to render the last frame that finished since the previous call, if
+    /// any, and if timestamps are supported.
+    pub fn take_gpu_time(&mut self) -> Option<Duration> {
+        self.gpu_time.take()
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.renderer.request_screenshot();
+    }
+
+    pub fn cursor_to_world(&self, position: PhysicalPosition<f64>) -> Option<[f32; 2]> {
+        self.renderer.cursor_to_world(position)
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.renderer.set_clear_color(clear_color);
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        self.renderer.toggle_fullscreen();
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.renderer.set_title(title);
     }
 
+    /// Only marks the swapchain as needing to be recreated, which the next `update` does, so that
+    /// the resize events sent while dragging the edge of the window don't each recreate it.
     pub fn handle_window_resize(&mut self) {
         // impacts the next update
         self.window_resized = true;
@@ -1,55 +1,74 @@
+use std::iter;
 use std::sync::Arc;
+use std::time::Duration;
 
-use chapter_code::game_objects::Square;
+use chapter_code::game_objects::{Camera, Square, SquareGrid, GRID_SQUARE_SCALE};
 use chapter_code::models::SquareModel;
 use chapter_code::shaders::movable_square;
 use chapter_code::vulkano_objects::allocators::Allocators;
 use chapter_code::vulkano_objects::buffers::Buffers;
-use chapter_code::{vulkano_objects, Vertex2d};
-use vulkano::command_buffer::{CommandBufferExecFuture, PrimaryAutoCommandBuffer};
+use chapter_code::vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR;
+use chapter_code::vulkano_objects::texture::Texture;
+use chapter_code::{vulkano_objects, GpuTimer, Instance2d, Vertex2d};
+use vulkano::buffer::Subbuffer;
+use vulkano::descriptor_set::PersistentDescriptorSet;
 use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
-use vulkano::image::SwapchainImage;
+use vulkano::image::{ImageUsage, SampleCount, SwapchainImage};
+use vulkano::instance::debug::DebugUtilsMessenger;
 use vulkano::instance::Instance;
-use vulkano::pipeline::graphics::viewport::Viewport;
 use vulkano::pipeline::{GraphicsPipeline, Pipeline};
 use vulkano::render_pass::{Framebuffer, RenderPass};
-use vulkano::shader::ShaderModule;
 use vulkano::swapchain::{
     self, AcquireError, PresentFuture, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo,
     SwapchainCreationError, SwapchainPresentInfo,
 };
-use vulkano::sync::future::{FenceSignalFuture, JoinFuture, NowFuture};
+use vulkano::sync::future::{FenceSignalFuture, NowFuture};
 use vulkano::sync::{self, FlushError, GpuFuture};
 use vulkano_win::VkSurfaceBuild;
-use winit::dpi::LogicalSize;
+use winit::dpi::{LogicalSize, PhysicalPosition};
 use winit::event_loop::EventLoop;
 use winit::window::{Window, WindowBuilder};
 
-pub type Fence = FenceSignalFuture<
-    PresentFuture<CommandBufferExecFuture<JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>>>,
->;
+use crate::render::render_loop::MAX_FRAMES_IN_FLIGHT;
+
+// Number of samples per pixel used to smooth the edges of the square. The closest count supported
+// by the device is used if it doesn't support this one.
+const MSAA_SAMPLES: u32 = 4;
+
+pub const WINDOW_TITLE: &str = "Movable Square";
+
+// Multiplied with the color of each square.
+const TEXTURE_PNG: &[u8] = include_bytes!("../../../../assets/square.png");
+
+// The future is boxed before presenting, as a screenshot adds a command buffer to some frames.
+pub type Fence = FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>>>;
 
 pub struct Renderer {
     _instance: Arc<Instance>,
+    _debug_messenger: Option<DebugUtilsMessenger>,
     window: Arc<Window>,
     device: Arc<Device>,
     queue: Arc<Queue>,
     swapchain: Arc<Swapchain>,
     images: Vec<Arc<SwapchainImage>>,
+    samples: SampleCount,
     render_pass: Arc<RenderPass>,
     framebuffers: Vec<Arc<Framebuffer>>,
     allocators: Allocators,
-    buffers: Buffers<Vertex2d, movable_square::vs::Data>,
-    vertex_shader: Arc<ShaderModule>,
-    fragment_shader: Arc<ShaderModule>,
-    viewport: Viewport,
+    buffers: Buffers<Vertex2d, movable_square::vs::Data, Instance2d>,
+    camera: Camera,
     pipeline: Arc<GraphicsPipeline>,
-    command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    screenshot_requested: bool,
+    clear_color: [f32; 4],
+    // One for each frame in flight, or none if the queue family doesn't support timestamps.
+    gpu_timers: Vec<GpuTimer>,
 }
 
 impl Renderer {
-    pub fn initialize(event_loop: &EventLoop<()>) -> Self {
-        let instance = vulkano_objects::instance::get_instance();
+    pub fn initialize(event_loop: &EventLoop<()>, grid: &SquareGrid) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+        let debug_messenger = vulkano_objects::instance::create_debug_messenger(&instance);
 
         let surface = WindowBuilder::new()
             .build_vk_surface(event_loop, instance.clone())
@@ -62,7 +81,7 @@ impl Renderer {
             .downcast::<Window>()
             .unwrap();
 
-        window.set_title("Movable Square");
+        window.set_title(WINDOW_TITLE);
         window.set_inner_size(LogicalSize::new(600.0f32, 600.0));
 
         let device_extensions = DeviceExtensions {
@@ -77,29 +96,55 @@ impl Renderer {
                 &device_extensions,
             );
 
+        let transfer_queue_family_index =
+            vulkano_objects::physical_device::select_transfer_queue_family(
+                &physical_device,
+                queue_family_index,
+            );
+
         let (device, mut queues) = Device::new(
             physical_device.clone(),
             DeviceCreateInfo {
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
-                enabled_extensions: device_extensions, // new
+                queue_create_infos: iter::once(queue_family_index)
+                    .chain(transfer_queue_family_index)
+                    .map(|queue_family_index| QueueCreateInfo {
+                        queue_family_index,
+                        ..Default::default()
+                    })
+                    .collect(),
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ), // new
                 ..Default::default()
             },
         )
         .expect("failed to create device");
 
         let queue = queues.next().unwrap();
+        // The staging buffers are copied on the transfer queue if there is one.
+        let transfer_queue = queues.next().unwrap_or_else(|| queue.clone());
 
         let (swapchain, images) =
-            vulkano_objects::swapchain::create_swapchain(&physical_device, device.clone(), surface);
+            vulkano_objects::swapchain::create_swapchain(&physical_device, device.clone(), surface)
+                .expect("failed to create swapchain");
+
+        let samples =
+            vulkano_objects::physical_device::select_sample_count(&physical_device, MSAA_SAMPLES);
 
-        let render_pass =
-            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone());
-        let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+        let allocators = Allocators::new(device.clone());
+
+        let render_pass = vulkano_objects::render_pass::create_render_pass_with_msaa(
+            device.clone(),
+            swapchain.clone(),
+            samples,
+        )
+        .expect("failed to create render pass");
+        let framebuffers = vulkano_objects::swapchain::create_framebuffers_with_msaa(
             &images,
             render_pass.clone(),
+            &allocators.memory,
+            samples,
         );
 
         let vertex_shader =
@@ -107,96 +152,135 @@ impl Renderer {
         let fragment_shader =
             movable_square::fs::load(device.clone()).expect("failed to create shader module");
 
-        let viewport = Viewport {
-            origin: [0.0, 0.0],
-            dimensions: window.inner_size().into(),
-            depth_range: 0.0..1.0,
-        };
+        let mut camera = Camera::orthographic_2d(1.0);
+        camera.set_viewport_size(window.inner_size().into());
 
-        let pipeline = vulkano_objects::pipeline::create_pipeline(
+        let pipeline = vulkano_objects::pipeline::create_instanced_pipeline(
             device.clone(),
-            vertex_shader.clone(),
-            fragment_shader.clone(),
+            vertex_shader,
+            fragment_shader,
             render_pass.clone(),
-            viewport.clone(),
-        );
+        )
+        .expect("failed to create pipeline");
 
-        let allocators = Allocators::new(device.clone());
+        let texture = Texture::from_png(&allocators, queue.clone(), TEXTURE_PNG);
 
         let buffers = Buffers::initialize_device_local::<SquareModel>(
             &allocators,
             pipeline.layout().set_layouts().get(0).unwrap().clone(),
-            images.len(),
-            queue.clone(),
-        );
+            MAX_FRAMES_IN_FLIGHT,
+            Some(&texture),
+            &instances(grid),
+            transfer_queue,
+            queue_family_index,
+        )
+        .expect("failed to create buffers");
 
-        let command_buffers = vulkano_objects::command_buffers::create_simple_command_buffers(
-            &allocators,
-            queue.clone(),
-            pipeline.clone(),
-            &framebuffers,
-            &buffers,
-        );
+        let gpu_timers: Vec<_> = (0..MAX_FRAMES_IN_FLIGHT)
+            .map_while(|_| GpuTimer::new(device.clone(), queue_family_index))
+            .collect();
+        if gpu_timers.is_empty() {
+            println!("Warning: the queue doesn't support timestamps, the GPU time won't be shown");
+        }
 
         Self {
             _instance: instance,
+            _debug_messenger: debug_messenger,
             window,
             device,
             queue,
             swapchain,
             images,
+            samples,
             render_pass,
             framebuffers,
             allocators,
             buffers,
-            vertex_shader,
-            fragment_shader,
-            viewport,
+            camera,
             pipeline,
-            command_buffers,
+            screenshot_requested: false,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            gpu_timers,
         }
     }
 
-    pub fn recreate_swapchain(&mut self) {
+    /// Whether the window is minimized, in which case nothing can be rendered to it.
+    pub fn is_minimized(&self) -> bool {
+        let size = self.window.inner_size();
+        size.width == 0 || size.height == 0
+    }
+
+    /// Recreates the swapchain for the current size of the window. Returns `false` if it can't be
+    /// done for that size, like while the window is minimized, in which case the old one is kept.
+    ///
+    /// The old swapchain isn't destroyed right away: the frames in flight still rendering to its
+    /// images hold the last references to it, and it is dropped along with them.
+    pub fn recreate_swapchain(&mut self) -> bool {
+        // A swapchain can't have images of size zero.
+        if self.is_minimized() {
+            return false;
+        }
+
         let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
             image_extent: self.window.inner_size().into(),
             ..self.swapchain.create_info()
         }) {
             Ok(r) => r,
-            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return false,
             Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
         };
 
         self.swapchain = new_swapchain;
-        self.framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+        self.framebuffers = vulkano_objects::swapchain::create_framebuffers_with_msaa(
             &new_images,
             self.render_pass.clone(),
+            &self.allocators.memory,
+            self.samples,
         );
+        self.images = new_images;
+
+        true
     }
 
-    pub fn handle_window_resize(&mut self) {
-        self.recreate_swapchain();
-        self.viewport.dimensions = self.window.inner_size().into();
+    /// Same as `recreate_swapchain`, but the camera is updated for the new size as well. The
+    /// pipeline's viewport is dynamic and set from the framebuffer when the command buffer is
+    /// recorded, so nothing else needs to be.
+    pub fn handle_window_resize(&mut self) -> bool {
+        if !self.recreate_swapchain() {
+            return false;
+        }
 
-        self.pipeline = vulkano_objects::pipeline::create_pipeline(
-            self.device.clone(),
-            self.vertex_shader.clone(),
-            self.fragment_shader.clone(),
-            self.render_pass.clone(),
-            self.viewport.clone(),
-        );
+        self.camera
+            .set_viewport_size(self.window.inner_size().into());
 
-        self.command_buffers = vulkano_objects::command_buffers::create_simple_command_buffers(
-            &self.allocators,
-            self.queue.clone(),
-            self.pipeline.clone(),
-            &self.framebuffers,
-            &self.buffers,
-        );
+        true
+    }
+
+    /// The position in the world of the cursor at `position` in the window, as given by winit's
+    /// `CursorMoved` event, or `None` if the window is minimized.
+    pub fn cursor_to_world(&self, position: PhysicalPosition<f64>) -> Option<[f32; 2]> {
+        let size = self.window.inner_size();
+
+        self.camera
+            .cursor_to_world(
+                [position.x as f32, position.y as f32],
+                [size.width as f32, size.height as f32],
+            )
+            .map(|point| [point.x, point.y])
+    }
+
+    /// Sets the color the window is cleared with, from the next frame on, as the command buffers
+    /// are recorded every frame.
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        chapter_code::toggle_fullscreen(&self.window);
     }
 
-    pub fn get_image_count(&self) -> usize {
-        self.images.len()
+    pub fn set_title(&self, title: &str) {
+        self.window.set_title(title);
     }
 
     pub fn acquire_swapchain_image(
@@ -213,32 +297,132 @@ impl Renderer {
     }
 
     pub fn flush_next_future(
-        &self,
+        &mut self,
         previous_future: Box<dyn GpuFuture>,
         swapchain_acquire_future: SwapchainAcquireFuture,
         image_i: u32,
+        frame_i: usize,
+        uniform_set: Arc<PersistentDescriptorSet>,
     ) -> Result<Fence, FlushError> {
-        previous_future
+        let command_buffer = vulkano_objects::command_buffers::create_simple_command_buffer(
+            &self.allocators,
+            self.queue.clone(),
+            self.pipeline.clone(),
+            self.framebuffers[image_i as usize].clone(),
+            &self.buffers,
+            frame_i,
+            uniform_set,
+            self.clear_color,
+            self.gpu_timers.get(frame_i),
+        );
+
+        let mut future = previous_future
             .join(swapchain_acquire_future)
-            .then_execute(
-                self.queue.clone(),
-                self.command_buffers[image_i as usize].clone(),
-            )
+            .then_execute(self.queue.clone(), command_buffer)
             .unwrap()
+            .boxed();
+
+        // The image can only be copied between its rendering and its presentation.
+        let screenshot = if self.screenshot_requested {
+            self.screenshot_requested = false;
+
+            let (copy_command_buffer, buffer) = vulkano_objects::screenshot::create_screenshot_copy(
+                &self.allocators,
+                &self.queue,
+                self.images[image_i as usize].clone(),
+            );
+            future = future
+                .then_execute(self.queue.clone(), copy_command_buffer)
+                .unwrap()
+                .boxed();
+
+            Some(buffer)
+        } else {
+            None
+        };
+
+        let fence = future
             .then_swapchain_present(
                 self.queue.clone(),
                 SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
             )
-            .then_signal_fence_and_flush()
+            .then_signal_fence_and_flush()?;
+
+        if let Some(buffer) = screenshot {
+            fence.wait(None).unwrap();
+            self.save_screenshot(&buffer);
+        }
+
+        Ok(fence)
+    }
+
+    /// Saves the next frame to a PNG file, if the swapchain images can be copied and have a format
+    /// that `vulkano_objects::screenshot` handles.
+    pub fn request_screenshot(&mut self) {
+        let format = self.swapchain.image_format();
+
+        if !self
+            .swapchain
+            .image_usage()
+            .intersects(ImageUsage::TRANSFER_SRC)
+        {
+            println!("The swapchain images can't be copied, no screenshot taken");
+        } else if !vulkano_objects::screenshot::supports_screenshots(format) {
+            println!("Screenshots of {:?} images aren't supported", format);
+        } else {
+            self.screenshot_requested = true;
+        }
+    }
+
+    fn save_screenshot(&self, buffer: &Subbuffer<[u8]>) {
+        let path = vulkano_objects::screenshot::save_screenshot(
+            buffer,
+            self.swapchain.image_extent(),
+            self.swapchain.image_format(),
+        );
+
+        println!("Saved {path}");
     }
 
-    pub fn update_uniform(&self, index: u32, square: &Square) {
-        let mut uniform_content = self.buffers.uniforms[index as usize]
-            .0
+    /// Writes the uniforms of `square` to a new uniform buffer, and returns the descriptor set to
+    /// give to `flush_next_future`.
+    pub fn update_uniform(&self, square: &Square) -> Arc<PersistentDescriptorSet> {
+        let mvp = self.camera.view_projection() * square.model_matrix();
+        let data = movable_square::vs::Data {
+            mvp: mvp.to_cols_array_2d(),
+            color: square.color.into(),
+        };
+
+        self.buffers
+            .create_uniform_descriptor_set(&self.allocators, data)
+            .expect("failed to create the uniform descriptor set")
+    }
+
+    /// The time the GPU took to render the last frame submitted as the frame in flight `frame_i`,
+    /// or `None` if timestamps aren't supported. Waits for the frame to finish rendering, so it
+    /// should only be called once its fence has been waited for.
+    pub fn gpu_frame_time(&self, frame_i: usize) -> Option<Duration> {
+        self.gpu_timers.get(frame_i).map(GpuTimer::elapsed)
+    }
+
+    pub fn update_instances(&self, frame_i: usize, grid: &SquareGrid) {
+        let mut instance_content = self.buffers.instances[frame_i]
             .write()
-            .unwrap_or_else(|e| panic!("Failed to write to uniform buffer\n{}", e));
+            .unwrap_or_else(|e| panic!("Failed to write to instance buffer\n{}", e));
 
-        uniform_content.color = square.color.into();
-        uniform_content.position = square.position;
+        for (instance, new_instance) in instance_content.iter_mut().zip(instances(grid)) {
+            *instance = new_instance;
+        }
     }
 }
+
+fn instances(grid: &SquareGrid) -> Vec<Instance2d> {
+    grid.squares
+        .iter()
+        .map(|square| Instance2d {
+            offset: square.position,
+            scale: GRID_SQUARE_SCALE,
+            color: square.color,
+        })
+        .collect()
+}
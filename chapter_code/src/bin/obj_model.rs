@@ -0,0 +1,396 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders a rotating cube loaded from a Wavefront OBJ file, colored by the position of each
+//! point of its surface, with a perspective camera and a depth buffer.
+//!
+//! The file is embedded in the executable and parsed by `models::parse_obj` when the buffers are
+//! created, through the `Model` trait like the hard-coded `SquareModel`. Its sides are quads,
+//! which the parser splits into triangles. Replacing `assets/cube.obj` with another model, like
+//! the Utah teapot, works as long as it fits in the view.
+
+use std::f32::consts::{FRAC_PI_4, TAU};
+use std::sync::Arc;
+use std::time::Instant;
+
+use chapter_code::game_objects::{Camera, Projection};
+use chapter_code::models::{parse_obj, Model};
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::{is_headless, vulkano_objects, Vertex3d, HEADLESS_FRAME_COUNT};
+use glam::{Mat4, Vec3};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::image::SwapchainImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, RenderPass};
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+    SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 position;
+
+            layout(push_constant) uniform PushConstants {
+                mat4 mvp;
+            } push_constants;
+
+            layout(location = 0) out vec3 color;
+
+            void main() {
+                // The model fits in [-0.5, 0.5] on each axis.
+                color = position + 0.5;
+                gl_Position = push_constants.mvp * vec4(position, 1.0);
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(color, 1.0);
+            }
+        ",
+    }
+}
+
+const CUBE_OBJ: &str = include_str!("../../assets/cube.obj");
+
+// Time it takes the cube to make a full turn around the vertical axis, in seconds.
+const PERIOD: f32 = 6.0;
+
+struct CubeModel;
+
+// The file is small enough that parsing it once for the vertices and once for the indices doesn't
+// matter.
+impl Model<Vertex3d, vs::PushConstants, u32> for CubeModel {
+    fn get_indices() -> Vec<u32> {
+        parse_obj(CUBE_OBJ).1
+    }
+
+    fn get_vertices() -> Vec<Vertex3d> {
+        parse_obj(CUBE_OBJ).0
+    }
+
+    fn get_initial_uniform_data() -> vs::PushConstants {
+        vs::PushConstants {
+            mvp: Mat4::IDENTITY.to_cols_array_2d(),
+        }
+    }
+}
+
+type Fence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+struct Renderer {
+    surface: Arc<Surface>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    render_pass: Arc<RenderPass>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[Vertex3d]>,
+    index_buffer: Subbuffer<[u32]>,
+    pipeline: Arc<GraphicsPipeline>,
+    camera: Camera,
+    fences: Vec<Option<Arc<Fence>>>,
+    previous_fence_i: u32,
+    recreate_swapchain: bool,
+}
+
+impl Renderer {
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+
+        let surface = WindowBuilder::new()
+            .with_title("OBJ model")
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (swapchain, images) = vulkano_objects::swapchain::create_swapchain(
+            &physical_device,
+            device.clone(),
+            surface.clone(),
+        )
+        .expect("failed to create swapchain");
+
+        let render_pass = vulkano_objects::render_pass::create_render_pass_with_depth(
+            device.clone(),
+            swapchain.clone(),
+        )
+        .expect("failed to create render pass");
+
+        let allocators = Allocators::new(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            CubeModel::get_vertices(),
+        )
+        .unwrap();
+
+        let index_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::INDEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            CubeModel::get_indices(),
+        )
+        .unwrap();
+
+        let vertex_shader = vs::load(device.clone()).expect("failed to create shader module");
+        let fragment_shader = fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = vulkano_objects::pipeline::create_pipeline_with_depth(
+            device.clone(),
+            vertex_shader,
+            fragment_shader,
+            render_pass.clone(),
+        )
+        .expect("failed to create pipeline");
+
+        let mut renderer = Self {
+            surface,
+            device,
+            queue,
+            swapchain,
+            render_pass,
+            framebuffers: Vec::new(),
+            allocators,
+            vertex_buffer,
+            index_buffer,
+            pipeline,
+            camera: Camera {
+                eye: Vec3::new(0.0, -1.0, 2.0),
+                target: Vec3::ZERO,
+                up: Vec3::Y,
+                projection: Projection::Perspective { fov_y: FRAC_PI_4 },
+                aspect_ratio: 1.0,
+                near: 0.1,
+                far: 10.0,
+            },
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            recreate_swapchain: false,
+        };
+        renderer.create_framebuffers(&images);
+
+        renderer
+    }
+
+    fn window(&self) -> Arc<Window> {
+        self.surface
+            .object()
+            .unwrap()
+            .clone()
+            .downcast::<Window>()
+            .unwrap()
+    }
+
+    fn handle_window_resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    // The command buffers are recorded every frame, so only what they use is created here.
+    fn create_framebuffers(&mut self, images: &[Arc<SwapchainImage>]) {
+        self.framebuffers = vulkano_objects::swapchain::create_framebuffers_with_depth(
+            images,
+            self.render_pass.clone(),
+            &self.allocators.memory,
+        );
+        self.camera
+            .set_viewport_size(self.window().inner_size().into());
+    }
+
+    fn render(&mut self, model_matrix: Mat4) {
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: self.window().inner_size().into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.recreate_swapchain = false;
+            self.swapchain = new_swapchain;
+            self.create_framebuffers(&new_images);
+        }
+
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let push_constants = vs::PushConstants {
+            mvp: (self.camera.view_projection() * model_matrix).to_cols_array_2d(),
+        };
+        let command_buffer = vulkano_objects::command_buffers::create_push_constants_command_buffer(
+            &self.allocators,
+            self.queue.clone(),
+            self.pipeline.clone(),
+            self.framebuffers[image_i as usize].clone(),
+            self.vertex_buffer.clone(),
+            self.index_buffer.clone(),
+            push_constants,
+            vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+        );
+
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(self.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i;
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let mut renderer = Renderer::new(&event_loop);
+
+    let start = Instant::now();
+
+    let headless = is_headless();
+    let mut frame_count = 0;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            renderer.handle_window_resize();
+        }
+        Event::MainEventsCleared => {
+            let angle = start.elapsed().as_secs_f32() / PERIOD * TAU;
+            // Also tilted back and forth, so that the top and bottom sides show.
+            let model_matrix =
+                Mat4::from_rotation_y(angle) * Mat4::from_rotation_x(0.5 * angle.sin());
+
+            renderer.render(model_matrix);
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        _ => (),
+    });
+}
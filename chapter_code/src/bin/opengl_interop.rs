@@ -0,0 +1,580 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Shares a texture between Vulkan and OpenGL with `VK_KHR_external_memory_fd` and
+//! `GL_EXT_memory_object_fd`, as an application moving from OpenGL to Vulkan one part at a time
+//! might do.
+//!
+//! vulkano allocates an image in exportable memory and gives its file descriptor to OpenGL, which
+//! creates a texture on top of the same memory and draws into it. OpenGL then signals a semaphore
+//! shared the same way (`GL_EXT_semaphore_fd`), and once vulkano has waited on it, a compute
+//! shader samples the texture and the result is saved to `image.png`.
+//!
+//! The OpenGL context is created with EGL and doesn't need a window. Both APIs must run on the
+//! same driver and device, which is why the Vulkan device is chosen by comparing its UUID with the
+//! one reported by OpenGL.
+
+#[cfg(unix)]
+mod opengl {
+    use std::ffi::{c_void, CStr};
+    use std::mem;
+    use std::os::unix::io::RawFd;
+    use std::ptr;
+
+    use gl::types::{GLenum, GLint, GLsizei, GLubyte, GLuint, GLuint64};
+    use khronos_egl as egl;
+
+    const TEXTURE_TILING_EXT: GLenum = 0x9580;
+    const DEDICATED_MEMORY_OBJECT_EXT: GLenum = 0x9581;
+    const OPTIMAL_TILING_EXT: GLenum = 0x9584;
+    const HANDLE_TYPE_OPAQUE_FD_EXT: GLenum = 0x9586;
+    const LAYOUT_GENERAL_EXT: GLenum = 0x958D;
+    const DEVICE_UUID_EXT: GLenum = 0x9597;
+
+    const REQUIRED_EXTENSIONS: &[&str] = &["GL_EXT_memory_object_fd", "GL_EXT_semaphore_fd"];
+
+    // The `gl` crate only contains core OpenGL, so the functions of the extensions are loaded by
+    // hand.
+    struct ExtFunctions {
+        get_unsigned_bytei_v: unsafe extern "system" fn(GLenum, GLuint, *mut GLubyte),
+        create_memory_objects: unsafe extern "system" fn(GLsizei, *mut GLuint),
+        memory_object_parameteriv: unsafe extern "system" fn(GLuint, GLenum, *const GLint),
+        import_memory_fd: unsafe extern "system" fn(GLuint, GLuint64, GLenum, GLint),
+        tex_storage_mem_2d:
+            unsafe extern "system" fn(GLenum, GLsizei, GLenum, GLsizei, GLsizei, GLuint, GLuint64),
+        gen_semaphores: unsafe extern "system" fn(GLsizei, *mut GLuint),
+        import_semaphore_fd: unsafe extern "system" fn(GLuint, GLenum, GLint),
+        signal_semaphore: unsafe extern "system" fn(
+            GLuint,
+            GLuint,
+            *const GLuint,
+            GLuint,
+            *const GLuint,
+            *const GLenum,
+        ),
+    }
+
+    impl ExtFunctions {
+        unsafe fn load(egl: &egl::DynamicInstance<egl::EGL1_4>) -> Self {
+            ExtFunctions {
+                get_unsigned_bytei_v: load(egl, "glGetUnsignedBytei_vEXT"),
+                create_memory_objects: load(egl, "glCreateMemoryObjectsEXT"),
+                memory_object_parameteriv: load(egl, "glMemoryObjectParameterivEXT"),
+                import_memory_fd: load(egl, "glImportMemoryFdEXT"),
+                tex_storage_mem_2d: load(egl, "glTexStorageMem2DEXT"),
+                gen_semaphores: load(egl, "glGenSemaphoresEXT"),
+                import_semaphore_fd: load(egl, "glImportSemaphoreFdEXT"),
+                signal_semaphore: load(egl, "glSignalSemaphoreEXT"),
+            }
+        }
+    }
+
+    // `F` must be the function pointer type matching the signature of `name`.
+    unsafe fn load<F>(egl: &egl::DynamicInstance<egl::EGL1_4>, name: &str) -> F {
+        let function = egl
+            .get_proc_address(name)
+            .unwrap_or_else(|| panic!("{} is not available", name));
+        mem::transmute_copy(&function)
+    }
+
+    /// An OpenGL context without a window, current on the calling thread.
+    pub struct Context {
+        egl: egl::DynamicInstance<egl::EGL1_4>,
+        display: egl::Display,
+        context: egl::Context,
+        ext: ExtFunctions,
+    }
+
+    impl Context {
+        pub fn new() -> Self {
+            let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }
+                .expect("failed to load libEGL");
+
+            let display = egl
+                .get_display(egl::DEFAULT_DISPLAY)
+                .expect("no EGL display");
+            egl.initialize(display).expect("failed to initialize EGL");
+            egl.bind_api(egl::OPENGL_API)
+                .expect("EGL doesn't support OpenGL");
+
+            let config = egl
+                .choose_first_config(display, &[egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::NONE])
+                .unwrap()
+                .expect("no EGL config supports OpenGL");
+            let context = egl
+                .create_context(
+                    display,
+                    config,
+                    None,
+                    &[
+                        egl::CONTEXT_MAJOR_VERSION,
+                        4,
+                        egl::CONTEXT_MINOR_VERSION,
+                        5,
+                        egl::CONTEXT_OPENGL_PROFILE_MASK,
+                        egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                        egl::NONE,
+                    ],
+                )
+                .expect("failed to create an OpenGL 4.5 context");
+
+            // Everything is drawn into textures, so no surface is needed
+            // (`EGL_KHR_surfaceless_context`).
+            egl.make_current(display, None, None, Some(context))
+                .expect("failed to make the OpenGL context current");
+
+            gl::load_with(|name| {
+                egl.get_proc_address(name)
+                    .map_or(ptr::null(), |f| f as *const c_void)
+            });
+
+            for extension in REQUIRED_EXTENSIONS {
+                assert!(
+                    has_extension(extension),
+                    "OpenGL doesn't support {}",
+                    extension
+                );
+            }
+
+            let ext = unsafe { ExtFunctions::load(&egl) };
+
+            Context {
+                egl,
+                display,
+                context,
+                ext,
+            }
+        }
+
+        /// The UUID of the device OpenGL runs on, comparable with Vulkan's `device_uuid`.
+        pub fn device_uuid(&self) -> [u8; 16] {
+            let mut uuid = [0; 16];
+            unsafe { (self.ext.get_unsigned_bytei_v)(DEVICE_UUID_EXT, 0, uuid.as_mut_ptr()) };
+            uuid
+        }
+
+        /// Creates an RGBA8 texture using the memory behind `fd`. OpenGL takes ownership of the
+        /// file descriptor.
+        pub fn import_texture(&self, fd: RawFd, size: u64, width: u32, height: u32) -> GLuint {
+            unsafe {
+                let mut memory_object = 0;
+                (self.ext.create_memory_objects)(1, &mut memory_object);
+                // vulkano uses a dedicated allocation for exportable images.
+                let dedicated = gl::TRUE as GLint;
+                (self.ext.memory_object_parameteriv)(
+                    memory_object,
+                    DEDICATED_MEMORY_OBJECT_EXT,
+                    &dedicated,
+                );
+                (self.ext.import_memory_fd)(memory_object, size, HANDLE_TYPE_OPAQUE_FD_EXT, fd);
+
+                let mut texture = 0;
+                gl::CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+                gl::TextureParameteri(texture, TEXTURE_TILING_EXT, OPTIMAL_TILING_EXT as GLint);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                (self.ext.tex_storage_mem_2d)(
+                    gl::TEXTURE_2D,
+                    1,
+                    gl::RGBA8,
+                    width as GLsizei,
+                    height as GLsizei,
+                    memory_object,
+                    0,
+                );
+                check_error("importing the texture");
+
+                texture
+            }
+        }
+
+        /// Draws a yellow square in the top-left quarter of a blue texture.
+        pub fn draw(&self, texture: GLuint, width: u32, height: u32) {
+            let (width, height) = (width as GLsizei, height as GLsizei);
+
+            unsafe {
+                let mut framebuffer = 0;
+                gl::CreateFramebuffers(1, &mut framebuffer);
+                gl::NamedFramebufferTexture(framebuffer, gl::COLOR_ATTACHMENT0, texture, 0);
+                assert_eq!(
+                    gl::CheckNamedFramebufferStatus(framebuffer, gl::FRAMEBUFFER),
+                    gl::FRAMEBUFFER_COMPLETE
+                );
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl::Viewport(0, 0, width, height);
+
+                gl::ClearColor(0.0, 0.0, 1.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+
+                // OpenGL's origin is the bottom-left corner, so the top rows come last.
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(width / 8, height / 2 + height / 8, width / 4, height / 4);
+                gl::ClearColor(1.0, 1.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+                gl::Disable(gl::SCISSOR_TEST);
+
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::DeleteFramebuffers(1, &framebuffer);
+                check_error("drawing");
+            }
+        }
+
+        /// Imports the semaphore behind `fd` and signals it once the drawing commands are done,
+        /// leaving `texture` in the `GENERAL` layout that vulkano expects. OpenGL takes ownership
+        /// of the file descriptor.
+        pub fn signal(&self, fd: RawFd, texture: GLuint) {
+            unsafe {
+                let mut semaphore = 0;
+                (self.ext.gen_semaphores)(1, &mut semaphore);
+                (self.ext.import_semaphore_fd)(semaphore, HANDLE_TYPE_OPAQUE_FD_EXT, fd);
+
+                let layout = LAYOUT_GENERAL_EXT;
+                (self.ext.signal_semaphore)(semaphore, 0, ptr::null(), 1, &texture, &layout);
+                // The signal operation has to reach the driver before Vulkan waits on it.
+                gl::Flush();
+                check_error("signaling the semaphore");
+            }
+        }
+    }
+
+    impl Drop for Context {
+        fn drop(&mut self) {
+            self.egl
+                .make_current(self.display, None, None, None)
+                .unwrap();
+            self.egl
+                .destroy_context(self.display, self.context)
+                .unwrap();
+            self.egl.terminate(self.display).unwrap();
+        }
+    }
+
+    fn has_extension(name: &str) -> bool {
+        unsafe {
+            let mut count = 0;
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+
+            (0..count as GLuint).any(|i| {
+                let extension = CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, i) as *const _);
+                extension.to_bytes() == name.as_bytes()
+            })
+        }
+    }
+
+    fn check_error(operation: &str) {
+        let error = unsafe { gl::GetError() };
+        assert_eq!(
+            error,
+            gl::NO_ERROR,
+            "OpenGL error {:#x} while {}",
+            error,
+            operation
+        );
+    }
+}
+
+#[cfg(unix)]
+mod example {
+    use std::os::unix::io::IntoRawFd;
+    use std::sync::Arc;
+
+    use image::{ImageBuffer, Rgba};
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+    use vulkano::command_buffer::{
+        AutoCommandBufferBuilder, ClearColorImageInfo, CommandBufferUsage, SemaphoreSubmitInfo,
+        SubmitInfo,
+    };
+    use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+    use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+    use vulkano::device::{
+        Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
+    };
+    use vulkano::format::Format;
+    use vulkano::image::view::ImageView;
+    use vulkano::image::{ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+    use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+    use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+    use vulkano::sampler::{Sampler, SamplerCreateInfo};
+    use vulkano::sync::fence::{Fence, FenceCreateInfo};
+    use vulkano::sync::semaphore::{
+        ExternalSemaphoreHandleType, ExternalSemaphoreHandleTypes, Semaphore, SemaphoreCreateInfo,
+    };
+    use vulkano::sync::{self, GpuFuture};
+
+    use super::opengl;
+
+    const WIDTH: u32 = 1024;
+    const HEIGHT: u32 = 1024;
+
+    mod cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 460
+
+                layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+                layout(set = 0, binding = 0) uniform sampler2D shared_texture;
+                layout(set = 0, binding = 1) writeonly buffer Pixels {
+                    uint pixels[];
+                };
+
+                void main() {
+                    uvec2 size = uvec2(textureSize(shared_texture, 0));
+                    vec2 uv = (vec2(gl_GlobalInvocationID.xy) + vec2(0.5)) / vec2(size);
+                    // The texture was drawn by OpenGL, whose first row is the bottom one.
+                    uv.y = 1.0 - uv.y;
+
+                    vec4 color = texture(shared_texture, uv);
+                    pixels[gl_GlobalInvocationID.y * size.x + gl_GlobalInvocationID.x] =
+                        packUnorm4x8(color);
+                }
+            ",
+        }
+    }
+
+    fn create_device(gl_device_uuid: [u8; 16]) -> (Arc<Device>, Arc<Queue>) {
+        let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                enabled_extensions: InstanceExtensions {
+                    khr_external_memory_capabilities: true,
+                    khr_external_semaphore_capabilities: true,
+                    khr_get_physical_device_properties2: true,
+                    ..InstanceExtensions::empty()
+                },
+                ..Default::default()
+            },
+        )
+        .expect("failed to create instance");
+
+        let device_extensions = DeviceExtensions {
+            khr_external_memory: true,
+            khr_external_memory_fd: true,
+            khr_external_semaphore: true,
+            khr_external_semaphore_fd: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .filter(|p| p.supported_extensions().contains(&device_extensions))
+            .find(|p| p.properties().device_uuid == Some(gl_device_uuid))
+            .expect("the device used by OpenGL can't share memory with Vulkan");
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::COMPUTE))
+            .expect("couldn't find a compute queue family") as u32;
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: device_extensions,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        (device, queues.next().unwrap())
+    }
+
+    /// Waits on `semaphore` on the GPU and on the fence of that submission on the CPU.
+    fn wait_semaphore(device: Arc<Device>, queue: &Arc<Queue>, semaphore: Arc<Semaphore>) {
+        let fence = Arc::new(Fence::new(device, FenceCreateInfo::default()).unwrap());
+        let submit_info = SubmitInfo {
+            wait_semaphores: vec![SemaphoreSubmitInfo::semaphore(semaphore)],
+            ..Default::default()
+        };
+
+        queue
+            .with(|mut q| unsafe { q.submit_unchecked([submit_info], Some(fence.clone())) })
+            .unwrap();
+
+        fence.wait(None).unwrap();
+    }
+
+    pub fn main() {
+        // OpenGL decides which device is used, Vulkan has to follow.
+        let gl_context = opengl::Context::new();
+        let (device, queue) = create_device(gl_context.device_uuid());
+
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+
+        let image = StorageImage::new_with_exportable_fd(
+            &memory_allocator,
+            ImageDimensions::Dim2d {
+                width: WIDTH,
+                height: HEIGHT,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            ImageUsage::SAMPLED | ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
+            ImageCreateFlags::empty(),
+            [queue.queue_family_index()],
+        )
+        .unwrap();
+
+        // vulkano considers the content of an image it has never used undefined. Clearing it once
+        // moves it to the `GENERAL` layout, which is what OpenGL will leave it in.
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder
+            .clear_color_image(ClearColorImageInfo::image(image.clone()))
+            .unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        // Draw with OpenGL.
+        let memory_fd = image
+            .export_posix_fd()
+            .expect("failed to export memory")
+            .into_raw_fd();
+        let texture = gl_context.import_texture(memory_fd, image.mem_size(), WIDTH, HEIGHT);
+        gl_context.draw(texture, WIDTH, HEIGHT);
+
+        let semaphore = Arc::new(
+            Semaphore::new(
+                device.clone(),
+                SemaphoreCreateInfo {
+                    export_handle_types: ExternalSemaphoreHandleTypes::OPAQUE_FD,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        );
+        let semaphore_fd = unsafe { semaphore.export_fd(ExternalSemaphoreHandleType::OpaqueFd) }
+            .expect("failed to export semaphore")
+            .into_raw_fd();
+        gl_context.signal(semaphore_fd, texture);
+
+        wait_semaphore(device.clone(), &queue, semaphore);
+
+        // Sample what OpenGL has drawn.
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+        let compute_pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+        .expect("failed to create compute pipeline");
+
+        let pixels = Buffer::from_iter(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            (0..WIDTH * HEIGHT).map(|_| 0u32),
+        )
+        .expect("failed to create buffer");
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo::simple_repeat_linear_no_mipmap(),
+        )
+        .unwrap();
+        let view = ImageView::new_default(image).unwrap();
+
+        let layout = compute_pipeline.layout().set_layouts().get(0).unwrap();
+        let set = PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(0, view, sampler),
+                WriteDescriptorSet::buffer(1, pixels.clone()),
+            ],
+        )
+        .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder
+            .bind_pipeline_compute(compute_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                compute_pipeline.layout().clone(),
+                0,
+                set,
+            )
+            .dispatch([WIDTH / 8, HEIGHT / 8, 1])
+            .unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(device)
+            .then_execute(queue, command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let bytes: Vec<u8> = pixels
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|pixel| pixel.to_le_bytes())
+            .collect();
+
+        // The top-left quarter contains the yellow square.
+        let top_left = &bytes[((HEIGHT / 4 * WIDTH + WIDTH / 4) * 4) as usize..][..4];
+        assert_eq!(
+            top_left,
+            [255, 255, 0, 255],
+            "Vulkan doesn't see OpenGL's drawing"
+        );
+
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(WIDTH, HEIGHT, bytes).unwrap();
+        image.save("image.png").unwrap();
+
+        println!("Everything succeeded!");
+    }
+}
+
+#[cfg(unix)]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("This example is only available on Unix platforms");
+}
@@ -0,0 +1,560 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Moves particles with a compute shader and draws them as points, with a single buffer used as
+//! both a storage buffer by the compute shader and a vertex buffer by the graphics pipeline.
+//!
+//! Each frame, the command buffer first dispatches the compute shader, which writes the new
+//! positions and velocities of the particles, and then draws them. The vertex shader must not read
+//! the particles before the compute shader is done writing them, which takes a pipeline barrier
+//! from the compute shader writes to the vertex attribute reads. `AutoCommandBufferBuilder` sees
+//! that the draw reads the buffer that the dispatch wrote, and inserts that barrier itself.
+//!
+//! The particles stay on the GPU the whole time: they are uploaded once to device-local memory,
+//! and the host never reads or writes them again.
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::{is_headless, vulkano_objects, HEADLESS_FRAME_COUNT};
+use rand::Rng;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, PrimaryAutoCommandBuffer,
+    PrimaryCommandBufferAbstract, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
+};
+use vulkano::image::SwapchainImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, RenderPass, Subpass};
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+    SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano::DeviceSize;
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+const PARTICLE_COUNT: u32 = 16384;
+
+// Must match `local_size_x` in the compute shader.
+const LOCAL_SIZE: u32 = 64;
+
+// A frame that takes longer, for example while the window is being moved, doesn't make the
+// particles jump.
+const MAX_DELTA_TIME: f32 = 0.1;
+
+// The layout of a particle is the same in the storage buffer, where it follows the `std430`
+// rules of the compute shader, and as a vertex.
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct Particle {
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    velocity: [f32; 2],
+}
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            struct Particle {
+                vec2 position;
+                vec2 velocity;
+            };
+
+            layout(set = 0, binding = 0) buffer Particles {
+                Particle particles[];
+            };
+
+            layout(push_constant) uniform PushConstants {
+                float delta_time;
+            };
+
+            void main() {
+                uint i = gl_GlobalInvocationID.x;
+                if (i >= particles.length()) {
+                    return;
+                }
+
+                Particle particle = particles[i];
+
+                // Pulled towards the center, so that the particles orbit around it.
+                particle.velocity -= particle.position * delta_time;
+                particle.position += particle.velocity * delta_time;
+
+                // Bounces off the edges of the window.
+                if (abs(particle.position.x) > 1.0) {
+                    particle.position.x = sign(particle.position.x);
+                    particle.velocity.x = -particle.velocity.x;
+                }
+                if (abs(particle.position.y) > 1.0) {
+                    particle.position.y = sign(particle.position.y);
+                    particle.velocity.y = -particle.velocity.y;
+                }
+
+                particles[i] = particle;
+            }
+        ",
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec2 velocity;
+
+            layout(location = 0) out vec3 color;
+
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+                // Larger points need the `large_points` feature.
+                gl_PointSize = 1.0;
+
+                // From blue for the slowest particles to orange for the fastest ones.
+                float speed = clamp(length(velocity), 0.0, 1.0);
+                color = mix(vec3(0.2, 0.4, 1.0), vec3(1.0, 0.6, 0.2), speed);
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(color, 1.0);
+            }
+        ",
+    }
+}
+
+type Fence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+// Particles spread over a disc, moving around its center.
+fn initial_particles() -> Vec<Particle> {
+    let mut rng = rand::thread_rng();
+
+    (0..PARTICLE_COUNT)
+        .map(|_| {
+            let angle = rng.gen_range(0.0..TAU);
+            let distance = rng.gen_range(0.1..0.8f32);
+            let (sin, cos) = angle.sin_cos();
+            let speed = distance * rng.gen_range(0.8..1.2);
+
+            Particle {
+                position: [distance * cos, distance * sin],
+                velocity: [-speed * sin, speed * cos],
+            }
+        })
+        .collect()
+}
+
+// Uploads the particles to a device-local buffer that is both a storage and a vertex buffer.
+fn create_particle_buffer(allocators: &Allocators, queue: Arc<Queue>) -> Subbuffer<[Particle]> {
+    let particles = initial_particles();
+
+    let buffer = Buffer::new_slice::<Particle>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER
+                | BufferUsage::VERTEX_BUFFER
+                | BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        particles.len() as DeviceSize,
+    )
+    .expect("failed to create buffer");
+
+    let staging_buffer = Buffer::from_iter(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        particles,
+    )
+    .expect("failed to create buffer");
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &allocators.command_buffer,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+    builder
+        .copy_buffer(CopyBufferInfo::buffers(staging_buffer, buffer.clone()))
+        .unwrap();
+
+    builder
+        .build()
+        .unwrap()
+        .execute(queue)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    buffer
+}
+
+struct Renderer {
+    surface: Arc<Surface>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    render_pass: Arc<RenderPass>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    allocators: Allocators,
+    particles: Subbuffer<[Particle]>,
+    compute_pipeline: Arc<ComputePipeline>,
+    particles_set: Arc<PersistentDescriptorSet>,
+    graphics_pipeline: Arc<GraphicsPipeline>,
+    fences: Vec<Option<Arc<Fence>>>,
+    previous_fence_i: u32,
+    recreate_swapchain: bool,
+}
+
+impl Renderer {
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+
+        let surface = WindowBuilder::new()
+            .with_title("Particles")
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        // The dispatch and the draw are recorded in the same command buffer, so the queue must
+        // support both. Every implementation has such a family, and in practice it is the
+        // graphics one.
+        assert!(
+            physical_device.queue_family_properties()[queue_family_index as usize]
+                .queue_flags
+                .contains(QueueFlags::COMPUTE),
+            "the graphics queue family doesn't support compute"
+        );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (swapchain, images) = vulkano_objects::swapchain::create_swapchain(
+            &physical_device,
+            device.clone(),
+            surface.clone(),
+        )
+        .expect("failed to create swapchain");
+
+        let render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+
+        let allocators = Allocators::new(device.clone());
+        let particles = create_particle_buffer(&allocators, queue.clone());
+
+        let cs = cs::load(device.clone()).expect("failed to create shader module");
+        let compute_pipeline = vulkano_objects::compute::create_compute_pipeline(
+            device.clone(),
+            cs.entry_point("main").unwrap(),
+        )
+        .expect("failed to create compute pipeline");
+        let particles_set = vulkano_objects::compute::create_storage_buffer_descriptor_set(
+            &allocators.descriptor_set,
+            &compute_pipeline,
+            particles.clone(),
+        )
+        .expect("failed to create descriptor set");
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        // The helpers of `vulkano_objects::pipeline` draw triangles, so the pipeline is built here
+        // to draw each vertex as a point instead.
+        let graphics_pipeline = GraphicsPipeline::start()
+            .vertex_input_state(Particle::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::PointList))
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .expect("failed to create graphics pipeline");
+
+        let mut renderer = Self {
+            surface,
+            device,
+            queue,
+            swapchain,
+            render_pass,
+            framebuffers: Vec::new(),
+            allocators,
+            particles,
+            compute_pipeline,
+            particles_set,
+            graphics_pipeline,
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            recreate_swapchain: false,
+        };
+        renderer.create_framebuffers(&images);
+
+        renderer
+    }
+
+    fn window(&self) -> Arc<Window> {
+        self.surface
+            .object()
+            .unwrap()
+            .clone()
+            .downcast::<Window>()
+            .unwrap()
+    }
+
+    fn handle_window_resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    fn create_framebuffers(&mut self, images: &[Arc<SwapchainImage>]) {
+        self.framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+            images,
+            self.render_pass.clone(),
+        );
+    }
+
+    // Moves the particles by `delta_time` seconds, and then draws them to `framebuffer`.
+    fn create_command_buffer(
+        &self,
+        framebuffer: Arc<Framebuffer>,
+        delta_time: f32,
+    ) -> PrimaryAutoCommandBuffer {
+        let [width, height] = framebuffer.extent();
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.allocators.command_buffer,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        builder
+            .bind_pipeline_compute(self.compute_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.compute_pipeline.layout().clone(),
+                0,
+                self.particles_set.clone(),
+            )
+            .push_constants(
+                self.compute_pipeline.layout().clone(),
+                0,
+                cs::PushConstants { delta_time },
+            )
+            .dispatch([(PARTICLE_COUNT + LOCAL_SIZE - 1) / LOCAL_SIZE, 1, 1])
+            .unwrap()
+            // The barrier between the dispatch and the draw is inserted here by vulkano.
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [viewport])
+            .bind_pipeline_graphics(self.graphics_pipeline.clone())
+            .bind_vertex_buffers(0, self.particles.clone())
+            .draw(PARTICLE_COUNT, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        builder.build().unwrap()
+    }
+
+    fn render(&mut self, delta_time: f32) {
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: self.window().inner_size().into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.recreate_swapchain = false;
+            self.swapchain = new_swapchain;
+            self.create_framebuffers(&new_images);
+        }
+
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let command_buffer =
+            self.create_command_buffer(self.framebuffers[image_i as usize].clone(), delta_time);
+
+        // Every frame writes the same particles, so each one is submitted after the previous one,
+        // which makes the GPU finish moving the particles of a frame before the next frame does.
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(self.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i;
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let mut renderer = Renderer::new(&event_loop);
+
+    let mut previous_frame_time = Instant::now();
+
+    let headless = is_headless();
+    let mut frame_count = 0;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            renderer.handle_window_resize();
+        }
+        Event::MainEventsCleared => {
+            let this_frame_time = Instant::now();
+            let delta_time = (this_frame_time - previous_frame_time).as_secs_f32();
+            previous_frame_time = this_frame_time;
+
+            renderer.render(delta_time.min(MAX_DELTA_TIME));
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        _ => (),
+    });
+}
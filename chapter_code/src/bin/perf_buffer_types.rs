@@ -0,0 +1,356 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Measures the bandwidth of host-visible and device-local buffers, for the ways they are usually
+//! accessed:
+//!
+//! 1. the CPU writing to a host-visible buffer,
+//! 2. the GPU reading a host-visible buffer from a compute shader,
+//! 3. the CPU writing to a host-visible staging buffer, then the GPU copying it to a device-local
+//!    buffer,
+//! 4. the GPU reading a device-local buffer from a compute shader.
+//!
+//! The GPU work is timed with timestamp queries and the CPU work with `Instant`. The third test
+//! adds up both. The results are printed and saved to `bandwidth_results.csv`.
+//!
+//! The results depend a lot on the kind of device. On a discrete GPU, host-visible memory is
+//! usually in system RAM, and the GPU reads it over PCIe, far slower than its own memory. On an
+//! integrated GPU, all of the memory is system RAM, and the two kinds of buffers perform about the
+//! same. On a discrete GPU with resizable BAR, vulkano may also put the host-visible buffer in
+//! device-local memory, making the second test about as fast as the fourth.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chapter_code::GpuTimer;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDeviceType;
+use vulkano::device::{Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+
+const MB: u64 = 1024 * 1024;
+const BUFFER_SIZES: [u64; 3] = [MB, 64 * MB, 512 * MB];
+
+// A buffer bound as a storage buffer can't be larger than `max_storage_buffer_range`, which is
+// at least 128 MB, so the large buffers are read in chunks.
+const CHUNK_SIZE: u64 = 64 * MB;
+
+// Must match the local size of the shader.
+const LOCAL_SIZE: u32 = 256;
+const WORK_GROUP_COUNT: u32 = 1024;
+
+const RESULTS_PATH: &str = "bandwidth_results.csv";
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 256, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer Input {
+                uint values[];
+            };
+
+            layout(set = 0, binding = 1) writeonly buffer Sums {
+                uint sums[];
+            };
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+                uint stride = gl_NumWorkGroups.x * gl_WorkGroupSize.x;
+
+                // Consecutive invocations read consecutive values, and the sum is written so
+                // that the reads can't be optimized away.
+                uint sum = 0;
+                for (uint i = idx; i < values.length(); i += stride) {
+                    sum += values[i];
+                }
+                sums[idx] = sum;
+            }
+        ",
+    }
+}
+
+struct Benchmark {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    pipeline: Arc<ComputePipeline>,
+    sums_buffer: Subbuffer<[u32]>,
+    gpu_timer: GpuTimer,
+}
+
+impl Benchmark {
+    /// Executes the recorded commands and returns the time the GPU took.
+    fn execute(
+        &self,
+        record: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+    ) -> Duration {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        self.gpu_timer.begin(&mut builder);
+        record(&mut builder);
+        self.gpu_timer.end(&mut builder);
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+
+        self.gpu_timer.elapsed()
+    }
+
+    fn gpu_read(&self, buffer: &Subbuffer<[u32]>) -> Duration {
+        // The descriptor sets are created beforehand, so that only the reads are measured.
+        let sets: Vec<_> = (0..buffer.size())
+            .step_by(CHUNK_SIZE as usize)
+            .map(|offset| {
+                let end = (offset + CHUNK_SIZE).min(buffer.size());
+                let chunk = buffer.clone().slice(offset / 4..end / 4);
+
+                PersistentDescriptorSet::new(
+                    &self.descriptor_set_allocator,
+                    self.pipeline.layout().set_layouts()[0].clone(),
+                    [
+                        WriteDescriptorSet::buffer(0, chunk),
+                        WriteDescriptorSet::buffer(1, self.sums_buffer.clone()),
+                    ],
+                )
+                .unwrap()
+            })
+            .collect();
+
+        self.execute(|builder| {
+            builder.bind_pipeline_compute(self.pipeline.clone());
+
+            for set in sets {
+                builder
+                    .bind_descriptor_sets(
+                        PipelineBindPoint::Compute,
+                        self.pipeline.layout().clone(),
+                        0,
+                        set,
+                    )
+                    .dispatch([WORK_GROUP_COUNT, 1, 1])
+                    .unwrap();
+            }
+        })
+    }
+
+    fn gpu_copy(&self, src: &Subbuffer<[u32]>, dst: &Subbuffer<[u32]>) -> Duration {
+        self.execute(|builder| {
+            builder
+                .copy_buffer(CopyBufferInfo::buffers(src.clone(), dst.clone()))
+                .unwrap();
+        })
+    }
+}
+
+/// Fills a host-visible buffer from the CPU, including the flush of the memory when it isn't
+/// host-coherent.
+fn cpu_write(buffer: &Subbuffer<[u32]>) -> Duration {
+    let start = Instant::now();
+
+    let mut content = buffer.write().unwrap();
+    for (i, value) in content.iter_mut().enumerate() {
+        *value = i as u32;
+    }
+    drop(content);
+
+    start.elapsed()
+}
+
+fn gigabytes_per_second(size: u64, time: Duration) -> f64 {
+    size as f64 / time.as_secs_f64() / 1e9
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    let device_type = physical_device.properties().device_type;
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        device_type,
+    );
+    match device_type {
+        PhysicalDeviceType::DiscreteGpu => println!(
+            "Discrete GPU: host-visible memory is usually system RAM, accessed by the GPU over PCIe"
+        ),
+        PhysicalDeviceType::IntegratedGpu => println!(
+            "Integrated GPU: the CPU and the GPU share the same memory, expect similar results"
+        ),
+        _ => {}
+    }
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let gpu_timer = match GpuTimer::new(device.clone(), queue_family_index) {
+        Some(gpu_timer) => gpu_timer,
+        None => {
+            println!("The queue doesn't support timestamps, the GPU can't be measured");
+            return;
+        }
+    };
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let sums_buffer = Buffer::new_slice::<u32>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        (WORK_GROUP_COUNT * LOCAL_SIZE) as u64,
+    )
+    .unwrap();
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let benchmark = Benchmark {
+        command_buffer_allocator: StandardCommandBufferAllocator::new(
+            device.clone(),
+            Default::default(),
+        ),
+        descriptor_set_allocator: StandardDescriptorSetAllocator::new(device.clone()),
+        device,
+        queue,
+        pipeline,
+        sums_buffer,
+        gpu_timer,
+    };
+
+    let mut csv = String::from("size_mb,test,gb_per_s\n");
+
+    println!();
+    println!("{:>7} | {:<36} | {:>8}", "size", "test", "GB/s");
+    println!("{:-<7}-+-{:-<36}-+-{:-<8}", "", "", "");
+
+    for size in BUFFER_SIZES {
+        let create_buffer = |usage, memory_usage| {
+            Buffer::new_slice::<u32>(
+                &memory_allocator,
+                BufferCreateInfo {
+                    usage,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: memory_usage,
+                    ..Default::default()
+                },
+                size / 4,
+            )
+        };
+
+        // The host-visible buffer is also the staging buffer of the third test.
+        let buffers = create_buffer(
+            BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC,
+            MemoryUsage::Upload,
+        )
+        .and_then(|host_visible| {
+            let device_local = create_buffer(
+                BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_DST,
+                MemoryUsage::DeviceOnly,
+            )?;
+            Ok((host_visible, device_local))
+        });
+        let (host_visible, device_local) = match buffers {
+            Ok(buffers) => buffers,
+            Err(e) => {
+                println!("{:>4} MB | failed to allocate the buffers: {e}", size / MB);
+                continue;
+            }
+        };
+
+        let cpu_write_time = cpu_write(&host_visible);
+        let host_visible_read_time = benchmark.gpu_read(&host_visible);
+        let upload_time =
+            cpu_write(&host_visible) + benchmark.gpu_copy(&host_visible, &device_local);
+        let device_local_read_time = benchmark.gpu_read(&device_local);
+
+        let results = [
+            ("CPU write to host-visible", cpu_write_time),
+            ("GPU read from host-visible", host_visible_read_time),
+            ("CPU write + GPU copy to device-local", upload_time),
+            ("GPU read from device-local", device_local_read_time),
+        ];
+
+        for (test, time) in results {
+            let bandwidth = gigabytes_per_second(size, time);
+            println!("{:>4} MB | {:<36} | {:>8.2}", size / MB, test, bandwidth);
+            writeln!(csv, "{},{},{:.3}", size / MB, test, bandwidth).unwrap();
+        }
+    }
+
+    fs::write(RESULTS_PATH, csv).expect("failed to write the results");
+    println!();
+    println!("Results saved to {RESULTS_PATH}");
+}
@@ -0,0 +1,898 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Measures the time taken to record 10,000 dispatches that each use a different storage buffer
+//! range, with the descriptor update strategies available on the device:
+//!
+//! - creating a `PersistentDescriptorSet` before each dispatch, as done when the descriptors
+//!   change every frame,
+//! - binding descriptor sets created once beforehand,
+//! - pushing the descriptors with `VK_KHR_push_descriptor`, without any descriptor set,
+//! - with `VK_EXT_descriptor_buffer`, writing the descriptors to a buffer beforehand and only
+//!   setting an offset in that buffer before each dispatch.
+//!
+//! vulkano doesn't support descriptor buffers, so they are compared with raw Vulkan calls to
+//! `vkCmdSetDescriptorBufferOffsetsEXT` and to `vkCmdBindDescriptorSets` with the same sets as the
+//! second strategy. The command buffers are only recorded, never executed.
+//!
+//! The memory used by the descriptor pools is measured by creating a pool with allocation
+//! callbacks that count the bytes the driver allocates. Push descriptors and descriptor buffers
+//! don't need pools at all, the latter using device memory instead.
+//!
+//! The descriptor buffer and memory measurements need the `perf-descriptor-sets` feature:
+//!
+//! ```bash
+//! cargo run --release --bin perf_descriptor_sets --features perf-descriptor-sets
+//! ```
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Features, QueueCreateInfo, QueueFlags,
+};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::Version;
+
+const BIND_COUNT: u32 = 10_000;
+
+// Each dispatch uses its own range of the data buffer. 256 bytes is the largest
+// `min_storage_buffer_offset_alignment` allowed, so the offsets are always aligned.
+const RANGE_SIZE: u64 = 256;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer Data {
+                uint values[];
+            };
+
+            shared uint sink[64];
+
+            void main() {
+                // The buffer is only read, so that vulkano doesn't add barriers between the
+                // dispatches.
+                sink[gl_LocalInvocationIndex] = values[gl_LocalInvocationIndex];
+            }
+        ",
+    }
+}
+
+#[cfg(feature = "perf-descriptor-sets")]
+mod raw {
+    use std::alloc::{self, Layout};
+    use std::collections::HashMap;
+    use std::ffi::{c_void, CString};
+    use std::mem;
+    use std::ptr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use ash::vk;
+    use vulkano::buffer::Subbuffer;
+    use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+    use vulkano::device::Device;
+    use vulkano::memory::MemoryPropertyFlags;
+    use vulkano::pipeline::{ComputePipeline, Pipeline};
+    use vulkano::shader::ShaderModule;
+    use vulkano::VulkanObject;
+
+    use super::{BIND_COUNT, RANGE_SIZE};
+
+    /// Host allocation callbacks keeping track of the memory allocated by the driver.
+    #[derive(Default)]
+    struct AllocationCounter {
+        // The layout of each live allocation, needed to free it.
+        allocations: Mutex<HashMap<usize, Layout>>,
+    }
+
+    impl AllocationCounter {
+        fn allocated_bytes(&self) -> usize {
+            self.allocations
+                .lock()
+                .unwrap()
+                .values()
+                .map(Layout::size)
+                .sum()
+        }
+
+        fn callbacks(&self) -> vk::AllocationCallbacks {
+            vk::AllocationCallbacks {
+                p_user_data: self as *const _ as *mut c_void,
+                pfn_allocation: Some(allocation),
+                pfn_reallocation: Some(reallocation),
+                pfn_free: Some(free),
+                pfn_internal_allocation: None,
+                pfn_internal_free: None,
+            }
+        }
+    }
+
+    unsafe extern "system" fn allocation(
+        p_user_data: *mut c_void,
+        size: usize,
+        alignment: usize,
+        _allocation_scope: vk::SystemAllocationScope,
+    ) -> *mut c_void {
+        let counter = &*(p_user_data as *const AllocationCounter);
+
+        let layout = match Layout::from_size_align(size, alignment) {
+            Ok(layout) if size > 0 => layout,
+            _ => return ptr::null_mut(),
+        };
+
+        let memory = alloc::alloc(layout);
+        if !memory.is_null() {
+            counter
+                .allocations
+                .lock()
+                .unwrap()
+                .insert(memory as usize, layout);
+        }
+
+        memory as *mut c_void
+    }
+
+    unsafe extern "system" fn reallocation(
+        p_user_data: *mut c_void,
+        p_original: *mut c_void,
+        size: usize,
+        alignment: usize,
+        allocation_scope: vk::SystemAllocationScope,
+    ) -> *mut c_void {
+        if p_original.is_null() {
+            return allocation(p_user_data, size, alignment, allocation_scope);
+        }
+        if size == 0 {
+            free(p_user_data, p_original);
+            return ptr::null_mut();
+        }
+
+        let counter = &*(p_user_data as *const AllocationCounter);
+        let original_size = counter.allocations.lock().unwrap()[&(p_original as usize)].size();
+
+        // On failure, the original allocation must be left untouched.
+        let memory = allocation(p_user_data, size, alignment, allocation_scope);
+        if !memory.is_null() {
+            ptr::copy_nonoverlapping(
+                p_original as *const u8,
+                memory as *mut u8,
+                original_size.min(size),
+            );
+            free(p_user_data, p_original);
+        }
+
+        memory
+    }
+
+    unsafe extern "system" fn free(p_user_data: *mut c_void, p_memory: *mut c_void) {
+        if p_memory.is_null() {
+            return;
+        }
+
+        let counter = &*(p_user_data as *const AllocationCounter);
+        let layout = counter
+            .allocations
+            .lock()
+            .unwrap()
+            .remove(&(p_memory as usize));
+        if let Some(layout) = layout {
+            alloc::dealloc(p_memory as *mut u8, layout);
+        }
+    }
+
+    /// The host memory allocated by the driver for a descriptor pool holding `set_count` sets of
+    /// the pipeline's layout, including the sets themselves.
+    ///
+    /// Drivers may also allocate memory without going through the callbacks, so this is a lower
+    /// bound.
+    pub fn descriptor_pool_memory(
+        device: &Device,
+        pipeline: &ComputePipeline,
+        set_count: u32,
+    ) -> usize {
+        let fns = &device.fns().v1_0;
+        let counter = AllocationCounter::default();
+        let callbacks = counter.callbacks();
+
+        let pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: set_count,
+        };
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(set_count)
+            .pool_sizes(std::slice::from_ref(&pool_size));
+        let mut pool = vk::DescriptorPool::null();
+        unsafe {
+            (fns.create_descriptor_pool)(device.handle(), &*pool_info, &callbacks, &mut pool)
+        }
+        .result()
+        .expect("failed to create descriptor pool");
+
+        let set_layouts = vec![pipeline.layout().set_layouts()[0].handle(); set_count as usize];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts);
+        let mut sets = vec![vk::DescriptorSet::null(); set_count as usize];
+        unsafe {
+            (fns.allocate_descriptor_sets)(device.handle(), &*allocate_info, sets.as_mut_ptr())
+        }
+        .result()
+        .expect("failed to allocate descriptor sets");
+
+        let allocated_bytes = counter.allocated_bytes();
+
+        // The sets are freed along with the pool.
+        unsafe { (fns.destroy_descriptor_pool)(device.handle(), pool, &callbacks) };
+
+        allocated_bytes
+    }
+
+    /// A command buffer that is recorded with raw Vulkan calls and never submitted.
+    struct RawCommandBuffer {
+        device: Arc<Device>,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+    }
+
+    impl RawCommandBuffer {
+        fn begin(device: Arc<Device>, queue_family_index: u32) -> Self {
+            let fns = &device.fns().v1_0;
+
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(queue_family_index);
+            let mut command_pool = vk::CommandPool::null();
+            unsafe {
+                (fns.create_command_pool)(
+                    device.handle(),
+                    &*pool_info,
+                    ptr::null(),
+                    &mut command_pool,
+                )
+            }
+            .result()
+            .expect("failed to create command pool");
+
+            let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let mut command_buffer = vk::CommandBuffer::null();
+            unsafe {
+                (fns.allocate_command_buffers)(
+                    device.handle(),
+                    &*allocate_info,
+                    &mut command_buffer,
+                )
+            }
+            .result()
+            .expect("failed to allocate command buffer");
+
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            unsafe { (fns.begin_command_buffer)(command_buffer, &*begin_info) }
+                .result()
+                .unwrap();
+
+            RawCommandBuffer {
+                device,
+                command_pool,
+                command_buffer,
+            }
+        }
+
+        fn end(&self) {
+            let fns = &self.device.fns().v1_0;
+            unsafe { (fns.end_command_buffer)(self.command_buffer) }
+                .result()
+                .unwrap();
+        }
+    }
+
+    impl Drop for RawCommandBuffer {
+        fn drop(&mut self) {
+            let fns = &self.device.fns().v1_0;
+            unsafe {
+                (fns.destroy_command_pool)(self.device.handle(), self.command_pool, ptr::null())
+            };
+        }
+    }
+
+    /// Records a dispatch after each `vkCmdBindDescriptorSets` call, with sets created by
+    /// vulkano.
+    pub fn bind_descriptor_sets(
+        device: Arc<Device>,
+        queue_family_index: u32,
+        pipeline: &ComputePipeline,
+        sets: &[Arc<PersistentDescriptorSet>],
+    ) -> Duration {
+        let fns = &device.fns().v1_0;
+        let commands = RawCommandBuffer::begin(device.clone(), queue_family_index);
+
+        let start = Instant::now();
+        unsafe {
+            (fns.cmd_bind_pipeline)(
+                commands.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.handle(),
+            );
+
+            for set in sets {
+                (fns.cmd_bind_descriptor_sets)(
+                    commands.command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.layout().handle(),
+                    0,
+                    1,
+                    &set.inner().handle(),
+                    0,
+                    ptr::null(),
+                );
+                (fns.cmd_dispatch)(commands.command_buffer, 1, 1, 1);
+            }
+        }
+        commands.end();
+
+        start.elapsed()
+    }
+
+    pub struct DescriptorBufferResult {
+        pub recording: Duration,
+        /// The size of the buffer holding the descriptors of every dispatch.
+        pub buffer_size: u64,
+    }
+
+    fn descriptor_buffer_properties(
+        device: &Device,
+    ) -> vk::PhysicalDeviceDescriptorBufferPropertiesEXT {
+        let physical_device = device.physical_device();
+        let fns = &physical_device.instance().fns().v1_1;
+
+        let mut properties = vk::PhysicalDeviceDescriptorBufferPropertiesEXT::default();
+        let mut properties2 = vk::PhysicalDeviceProperties2 {
+            p_next: &mut properties as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        unsafe {
+            (fns.get_physical_device_properties2)(physical_device.handle(), &mut properties2)
+        };
+
+        properties
+    }
+
+    /// Records a dispatch after each `vkCmdSetDescriptorBufferOffsetsEXT` call. The descriptors
+    /// of every dispatch are written to a descriptor buffer beforehand.
+    ///
+    /// The device must have been created with `VK_EXT_descriptor_buffer` and the
+    /// `descriptor_buffer` and `buffer_device_address` features.
+    pub fn set_descriptor_buffer_offsets(
+        device: Arc<Device>,
+        queue_family_index: u32,
+        shader: &ShaderModule,
+        data_buffer: &Subbuffer<[u32]>,
+    ) -> DescriptorBufferResult {
+        let fns = &device.fns();
+        let instance_fns = &device.physical_device().instance().fns().v1_0;
+        let ext_fns = vk::ExtDescriptorBufferFn::load(|name| unsafe {
+            mem::transmute((instance_fns.get_device_proc_addr)(
+                device.handle(),
+                name.as_ptr(),
+            ))
+        });
+        let properties = descriptor_buffer_properties(&device);
+
+        // The layouts and the pipeline must be created for descriptor buffers, which vulkano
+        // can't do.
+
+        let binding = vk::DescriptorSetLayoutBinding {
+            binding: 0,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            ..Default::default()
+        };
+        let set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(vk::DescriptorSetLayoutCreateFlags::DESCRIPTOR_BUFFER_EXT)
+            .bindings(std::slice::from_ref(&binding));
+        let mut set_layout = vk::DescriptorSetLayout::null();
+        unsafe {
+            (fns.v1_0.create_descriptor_set_layout)(
+                device.handle(),
+                &*set_layout_info,
+                ptr::null(),
+                &mut set_layout,
+            )
+        }
+        .result()
+        .expect("failed to create descriptor set layout");
+
+        let pipeline_layout_info =
+            vk::PipelineLayoutCreateInfo::builder().set_layouts(std::slice::from_ref(&set_layout));
+        let mut pipeline_layout = vk::PipelineLayout::null();
+        unsafe {
+            (fns.v1_0.create_pipeline_layout)(
+                device.handle(),
+                &*pipeline_layout_info,
+                ptr::null(),
+                &mut pipeline_layout,
+            )
+        }
+        .result()
+        .expect("failed to create pipeline layout");
+
+        let entry_point = CString::new("main").unwrap();
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader.handle())
+            .name(&entry_point)
+            .build();
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .flags(vk::PipelineCreateFlags::DESCRIPTOR_BUFFER_EXT)
+            .stage(stage)
+            .layout(pipeline_layout);
+        let mut pipeline = vk::Pipeline::null();
+        unsafe {
+            (fns.v1_0.create_compute_pipelines)(
+                device.handle(),
+                vk::PipelineCache::null(),
+                1,
+                &*pipeline_info,
+                ptr::null(),
+                &mut pipeline,
+            )
+        }
+        .result()
+        .expect("failed to create compute pipeline");
+
+        // One set per dispatch, each starting at a multiple of the required alignment.
+
+        let mut layout_size = 0;
+        let mut binding_offset = 0;
+        unsafe {
+            (ext_fns.get_descriptor_set_layout_size_ext)(
+                device.handle(),
+                set_layout,
+                &mut layout_size,
+            );
+            (ext_fns.get_descriptor_set_layout_binding_offset_ext)(
+                device.handle(),
+                set_layout,
+                0,
+                &mut binding_offset,
+            );
+        }
+        let alignment = properties.descriptor_buffer_offset_alignment;
+        let set_stride = (layout_size + alignment - 1) / alignment * alignment;
+        let buffer_size = set_stride * BIND_COUNT as u64;
+
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(buffer_size)
+            .usage(
+                vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let mut descriptor_buffer = vk::Buffer::null();
+        unsafe {
+            (fns.v1_0.create_buffer)(
+                device.handle(),
+                &*buffer_info,
+                ptr::null(),
+                &mut descriptor_buffer,
+            )
+        }
+        .result()
+        .expect("failed to create descriptor buffer");
+
+        let mut requirements = vk::MemoryRequirements::default();
+        unsafe {
+            (fns.v1_0.get_buffer_memory_requirements)(
+                device.handle(),
+                descriptor_buffer,
+                &mut requirements,
+            )
+        };
+
+        let memory_type_index = device
+            .physical_device()
+            .memory_properties()
+            .memory_types
+            .iter()
+            .enumerate()
+            .position(|(i, memory_type)| {
+                requirements.memory_type_bits & (1 << i) != 0
+                    && memory_type.property_flags.contains(
+                        MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+                    )
+            })
+            .expect("no host-visible memory type for the descriptor buffer")
+            as u32;
+
+        let mut allocate_flags =
+            vk::MemoryAllocateFlagsInfo::builder().flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut allocate_flags);
+        let mut memory = vk::DeviceMemory::null();
+        unsafe {
+            (fns.v1_0.allocate_memory)(device.handle(), &*allocate_info, ptr::null(), &mut memory)
+        }
+        .result()
+        .expect("failed to allocate memory for the descriptor buffer");
+
+        let mut mapped = ptr::null_mut();
+        unsafe {
+            (fns.v1_0.bind_buffer_memory)(device.handle(), descriptor_buffer, memory, 0)
+                .result()
+                .unwrap();
+            (fns.v1_0.map_memory)(
+                device.handle(),
+                memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+                &mut mapped,
+            )
+            .result()
+            .unwrap();
+        }
+
+        let data_address = data_buffer.device_address().unwrap().get();
+        for i in 0..BIND_COUNT as u64 {
+            let address_info = vk::DescriptorAddressInfoEXT {
+                address: data_address + i * RANGE_SIZE,
+                range: RANGE_SIZE,
+                ..Default::default()
+            };
+            let get_info = vk::DescriptorGetInfoEXT {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                data: vk::DescriptorDataEXT {
+                    p_storage_buffer: &address_info,
+                },
+                ..Default::default()
+            };
+            unsafe {
+                (ext_fns.get_descriptor_ext)(
+                    device.handle(),
+                    &get_info,
+                    properties.storage_buffer_descriptor_size,
+                    (mapped as *mut u8).add((i * set_stride + binding_offset) as usize)
+                        as *mut c_void,
+                )
+            };
+        }
+
+        let address_info = vk::BufferDeviceAddressInfo::builder().buffer(descriptor_buffer);
+        let descriptor_buffer_address =
+            unsafe { (fns.v1_2.get_buffer_device_address)(device.handle(), &*address_info) };
+
+        // Only the recording is measured, like for the other strategies.
+
+        let commands = RawCommandBuffer::begin(device.clone(), queue_family_index);
+        let binding_info = vk::DescriptorBufferBindingInfoEXT {
+            address: descriptor_buffer_address,
+            usage: vk::BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT,
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        unsafe {
+            (fns.v1_0.cmd_bind_pipeline)(
+                commands.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline,
+            );
+            (ext_fns.cmd_bind_descriptor_buffers_ext)(commands.command_buffer, 1, &binding_info);
+
+            for i in 0..BIND_COUNT as u64 {
+                let buffer_index = 0;
+                let offset = i * set_stride;
+                (ext_fns.cmd_set_descriptor_buffer_offsets_ext)(
+                    commands.command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline_layout,
+                    0,
+                    1,
+                    &buffer_index,
+                    &offset,
+                );
+                (fns.v1_0.cmd_dispatch)(commands.command_buffer, 1, 1, 1);
+            }
+        }
+        commands.end();
+        let recording = start.elapsed();
+
+        drop(commands);
+        unsafe {
+            (fns.v1_0.unmap_memory)(device.handle(), memory);
+            (fns.v1_0.destroy_buffer)(device.handle(), descriptor_buffer, ptr::null());
+            (fns.v1_0.free_memory)(device.handle(), memory, ptr::null());
+            (fns.v1_0.destroy_pipeline)(device.handle(), pipeline, ptr::null());
+            (fns.v1_0.destroy_pipeline_layout)(device.handle(), pipeline_layout, ptr::null());
+            (fns.v1_0.destroy_descriptor_set_layout)(device.handle(), set_layout, ptr::null());
+        }
+
+        DescriptorBufferResult {
+            recording,
+            buffer_size,
+        }
+    }
+}
+
+/// Measures the time taken to record a command buffer, from its creation to `build`.
+fn time_recording(
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue_family_index: u32,
+    record: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
+) -> Duration {
+    let start = Instant::now();
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue_family_index,
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+    record(&mut builder);
+    builder.build().unwrap();
+
+    start.elapsed()
+}
+
+fn print_row(strategy: &str, time: Duration) {
+    println!(
+        "{:<38} | {:>10.2} | {:>13}",
+        strategy,
+        time.as_secs_f64() * 1000.0,
+        time.as_nanos() / BIND_COUNT as u128,
+    );
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let push_descriptors = physical_device.supported_extensions().khr_push_descriptor;
+    if !push_descriptors {
+        println!("VK_KHR_push_descriptor isn't supported");
+    }
+
+    // Getting the address of the descriptor buffer needs `vkGetBufferDeviceAddress`, part of
+    // Vulkan 1.2.
+    let descriptor_buffers = cfg!(feature = "perf-descriptor-sets")
+        && physical_device.api_version() >= Version::V1_2
+        && physical_device.supported_extensions().ext_descriptor_buffer
+        && physical_device.supported_features().descriptor_buffer
+        && physical_device.supported_features().buffer_device_address;
+    if !cfg!(feature = "perf-descriptor-sets") {
+        println!(
+            "Descriptor buffers and descriptor pool memory aren't measured, enable the \
+             perf-descriptor-sets feature"
+        );
+    } else if !descriptor_buffers {
+        println!("VK_EXT_descriptor_buffer isn't supported");
+    }
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_extensions: DeviceExtensions {
+                khr_push_descriptor: push_descriptors,
+                ext_descriptor_buffer: descriptor_buffers,
+                ..DeviceExtensions::empty()
+            },
+            enabled_features: Features {
+                descriptor_buffer: descriptor_buffers,
+                buffer_device_address: descriptor_buffers,
+                ..Features::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    // The queue is never used, the command buffers are only recorded.
+    let _queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let data_usage = if descriptor_buffers {
+        BufferUsage::STORAGE_BUFFER | BufferUsage::SHADER_DEVICE_ADDRESS
+    } else {
+        BufferUsage::STORAGE_BUFFER
+    };
+    let data_buffer = Buffer::new_slice::<u32>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: data_usage,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        BIND_COUNT as u64 * RANGE_SIZE / 4,
+    )
+    .unwrap();
+
+    let range = |i: u32| -> Subbuffer<[u32]> {
+        let start = i as u64 * RANGE_SIZE / 4;
+        data_buffer.clone().slice(start..start + RANGE_SIZE / 4)
+    };
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let set_layout = pipeline.layout().set_layouts()[0].clone();
+
+    let create_set = |i: u32| {
+        PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            set_layout.clone(),
+            [WriteDescriptorSet::buffer(0, range(i))],
+        )
+        .unwrap()
+    };
+
+    println!();
+    println!(
+        "{BIND_COUNT} dispatches, each with its own descriptor:\n\n{:<38} | {:>10} | {:>13}",
+        "strategy", "total (ms)", "per bind (ns)"
+    );
+    println!("{:-<38}-+-{:-<10}-+-{:-<13}", "", "", "");
+
+    let time = time_recording(&command_buffer_allocator, queue_family_index, |builder| {
+        builder.bind_pipeline_compute(pipeline.clone());
+        for i in 0..BIND_COUNT {
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    create_set(i),
+                )
+                .dispatch([1, 1, 1])
+                .unwrap();
+        }
+    });
+    print_row("vulkano, set created before each bind", time);
+
+    let sets: Vec<_> = (0..BIND_COUNT).map(create_set).collect();
+    let time = time_recording(&command_buffer_allocator, queue_family_index, |builder| {
+        builder.bind_pipeline_compute(pipeline.clone());
+        for set in &sets {
+            builder
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Compute,
+                    pipeline.layout().clone(),
+                    0,
+                    set.clone(),
+                )
+                .dispatch([1, 1, 1])
+                .unwrap();
+        }
+    });
+    print_row("vulkano, sets created beforehand", time);
+
+    if push_descriptors {
+        let push_pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |set_layouts| set_layouts[0].push_descriptor = true,
+        )
+        .expect("failed to create compute pipeline");
+
+        let time = time_recording(&command_buffer_allocator, queue_family_index, |builder| {
+            builder.bind_pipeline_compute(push_pipeline.clone());
+            for i in 0..BIND_COUNT {
+                builder
+                    .push_descriptor_set(
+                        PipelineBindPoint::Compute,
+                        push_pipeline.layout().clone(),
+                        0,
+                        [WriteDescriptorSet::buffer(0, range(i))],
+                    )
+                    .dispatch([1, 1, 1])
+                    .unwrap();
+            }
+        });
+        print_row("vulkano, push descriptors", time);
+    }
+
+    #[cfg(feature = "perf-descriptor-sets")]
+    {
+        let time = raw::bind_descriptor_sets(device.clone(), queue_family_index, &pipeline, &sets);
+        print_row("raw, vkCmdBindDescriptorSets", time);
+
+        let descriptor_buffer = descriptor_buffers.then(|| {
+            let result = raw::set_descriptor_buffer_offsets(
+                device.clone(),
+                queue_family_index,
+                &shader,
+                &data_buffer,
+            );
+            print_row("raw, vkCmdSetDescriptorBufferOffsetsEXT", result.recording);
+            result
+        });
+
+        println!();
+        println!(
+            "Memory for {BIND_COUNT} descriptors:\n\n{:<38} | {:>14} | {:>14}",
+            "strategy", "pool (bytes)", "buffer (bytes)"
+        );
+        println!("{:-<38}-+-{:-<14}-+-{:-<14}", "", "", "");
+        println!(
+            "{:<38} | {:>14} | {:>14}",
+            "descriptor sets",
+            raw::descriptor_pool_memory(&device, &pipeline, BIND_COUNT),
+            0,
+        );
+        if push_descriptors {
+            println!("{:<38} | {:>14} | {:>14}", "push descriptors", 0, 0);
+        }
+        if let Some(result) = descriptor_buffer {
+            println!(
+                "{:<38} | {:>14} | {:>14}",
+                "descriptor buffer", 0, result.buffer_size
+            );
+        }
+    }
+}
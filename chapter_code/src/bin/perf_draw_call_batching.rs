@@ -0,0 +1,401 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Measures the cost of draw calls, by drawing the same quad N times in three ways:
+//!
+//! - naive: N `draw_indexed` calls drawing one instance each,
+//! - instanced: one `draw_indexed` call drawing N instances,
+//! - indirect: one `draw_indexed_indirect` call with N commands in a buffer, which needs the
+//!   `multi_draw_indirect` feature when N is more than 1.
+//!
+//! For each, the time taken to record the command buffer is measured on the CPU, and the time
+//! taken to execute it is measured on the GPU with timestamp queries. The recording time includes
+//! the work done by vulkano for each command, which is part of the cost of a draw call for an
+//! application using it.
+//!
+//! The quad is small, so that the GPU time is dominated by the processing of the draw calls
+//! rather than by the fragments.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chapter_code::{GpuTimer, Vertex2d};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, DrawIndexedIndirectCommand, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::device::{Device, DeviceCreateInfo, Features, Queue, QueueCreateInfo, QueueFlags};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+use vulkano::sync::{self, GpuFuture};
+
+const SIZE: u32 = 512;
+const QUAD_HALF_SIZE: f32 = 0.02;
+const DRAW_COUNTS: [u32; 5] = [1, 10, 100, 1000, 10000];
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(1.0, 0.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Method {
+    Naive,
+    Instanced,
+    Indirect,
+}
+
+impl Method {
+    fn name(self) -> &'static str {
+        match self {
+            Method::Naive => "naive",
+            Method::Instanced => "instanced",
+            Method::Indirect => "indirect",
+        }
+    }
+}
+
+struct Measurement {
+    recording: Duration,
+    /// `None` if the queue doesn't support timestamps.
+    execution: Option<Duration>,
+}
+
+struct Benchmark {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: StandardMemoryAllocator,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffer: Arc<Framebuffer>,
+    vertex_buffer: Subbuffer<[Vertex2d]>,
+    index_buffer: Subbuffer<[u16]>,
+    gpu_timer: Option<GpuTimer>,
+    max_indirect_draw_count: u32,
+}
+
+impl Benchmark {
+    /// Returns `None` if the method can't draw `draw_count` quads on this device.
+    fn measure(&self, method: Method, draw_count: u32) -> Option<Measurement> {
+        if let Method::Indirect = method {
+            if draw_count > self.max_indirect_draw_count {
+                return None;
+            }
+        }
+
+        // The indirect commands are prepared beforehand, like the vertex buffer: only the
+        // recording of the commands is measured.
+        let indirect_buffer = Buffer::from_iter(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::INDIRECT_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            (0..draw_count).map(|_| DrawIndexedIndirectCommand {
+                index_count: 6,
+                instance_count: 1,
+                first_index: 0,
+                vertex_offset: 0,
+                first_instance: 0,
+            }),
+        )
+        .unwrap();
+
+        let start = Instant::now();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.begin(&mut builder);
+        }
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(self.framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .bind_index_buffer(self.index_buffer.clone());
+
+        match method {
+            Method::Naive => {
+                for _ in 0..draw_count {
+                    builder.draw_indexed(6, 1, 0, 0, 0).unwrap();
+                }
+            }
+            Method::Instanced => {
+                builder.draw_indexed(6, draw_count, 0, 0, 0).unwrap();
+            }
+            Method::Indirect => {
+                builder.draw_indexed_indirect(indirect_buffer).unwrap();
+            }
+        }
+
+        builder.end_render_pass().unwrap();
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.end(&mut builder);
+        }
+
+        let command_buffer = builder.build().unwrap();
+        let recording = start.elapsed();
+
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+
+        let execution = self.gpu_timer.as_ref().map(GpuTimer::elapsed);
+
+        Some(Measurement {
+            recording,
+            execution,
+        })
+    }
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    // Without `multi_draw_indirect`, an indirect draw can only execute one command.
+    let multi_draw_indirect = physical_device.supported_features().multi_draw_indirect;
+    let max_indirect_draw_count = if multi_draw_indirect {
+        physical_device.properties().max_draw_indirect_count
+    } else {
+        println!("multi_draw_indirect isn't supported, indirect draws are limited to 1 command");
+        1
+    };
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_features: Features {
+                multi_draw_indirect,
+                ..Features::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let gpu_timer = GpuTimer::new(device.clone(), queue_family_index);
+    if gpu_timer.is_none() {
+        println!("The queue doesn't support timestamps, only the recording time is measured");
+    }
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|[x, y]| Vertex2d {
+            position: [x * QUAD_HALF_SIZE, y * QUAD_HALF_SIZE],
+        }),
+    )
+    .unwrap();
+
+    let index_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::INDEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [0u16, 1, 2, 2, 3, 0],
+    )
+    .unwrap();
+
+    let image = AttachmentImage::with_usage(
+        &memory_allocator,
+        [SIZE, SIZE],
+        Format::R8G8B8A8_UNORM,
+        ImageUsage::COLOR_ATTACHMENT,
+    )
+    .unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {},
+        },
+    )
+    .unwrap();
+
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![ImageView::new_default(image).unwrap()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [SIZE as f32, SIZE as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(Vertex2d::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .unwrap();
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let benchmark = Benchmark {
+        device,
+        queue,
+        memory_allocator,
+        command_buffer_allocator,
+        pipeline,
+        framebuffer,
+        vertex_buffer,
+        index_buffer,
+        gpu_timer,
+        max_indirect_draw_count,
+    };
+
+    // The first submission pays for the lazy initialization done by the driver.
+    benchmark.measure(Method::Naive, 1);
+
+    println!();
+    println!(
+        "{:>6} | {:<9} | {:>14} | {:>14}",
+        "quads", "method", "recording (us)", "GPU (us)"
+    );
+    println!("{:-<6}-+-{:-<9}-+-{:-<14}-+-{:-<14}", "", "", "", "");
+
+    for draw_count in DRAW_COUNTS {
+        for method in [Method::Naive, Method::Instanced, Method::Indirect] {
+            let (recording, execution) = match benchmark.measure(method, draw_count) {
+                Some(measurement) => (
+                    measurement.recording.as_micros().to_string(),
+                    measurement
+                        .execution
+                        .map_or("n/a".to_owned(), |time| time.as_micros().to_string()),
+                ),
+                None => ("unsupported".to_owned(), "unsupported".to_owned()),
+            };
+
+            println!(
+                "{:>6} | {:<9} | {:>14} | {:>14}",
+                draw_count,
+                method.name(),
+                recording,
+                execution
+            );
+        }
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Measures the cost of changing the pipeline between draws, by drawing 10,000 small quads:
+//!
+//! 1. with the same pipeline for every draw,
+//! 2. alternating between two pipelines that only differ by their fragment shader,
+//! 3. alternating between two pipelines with different vertex layouts, which also means binding
+//!    a different vertex buffer with each pipeline.
+//!
+//! The time taken by the GPU is measured with timestamp queries, and the time spent in
+//! `bind_pipeline_graphics` on the CPU with `Instant`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chapter_code::{GpuTimer, Vertex2d};
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+};
+use vulkano::device::{Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexBufferDescription};
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::{self, GpuFuture};
+
+const SIZE: u32 = 256;
+const QUAD_HALF_SIZE: f32 = 0.02;
+const DRAW_COUNT: u32 = 10_000;
+
+/// A vertex with a different layout than `Vertex2d`.
+#[derive(BufferContents, Vertex)]
+#[repr(C)]
+struct ColoredVertex {
+    #[format(R32G32_SFLOAT)]
+    position: [f32; 2],
+    #[format(R32G32B32_SFLOAT)]
+    color: [f32; 3],
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod red_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(1.0, 0.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod green_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(0.0, 1.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod colored_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec3 color;
+
+            layout(location = 0) out vec3 out_color;
+
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+                out_color = color;
+            }
+        ",
+    }
+}
+
+mod colored_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 in_color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(in_color, 1.0);
+            }
+        ",
+    }
+}
+
+/// A pipeline along with the vertex buffer matching its vertex layout.
+#[derive(Clone)]
+struct PipelineState {
+    pipeline: Arc<GraphicsPipeline>,
+    vertex_buffer: Subbuffer<[u8]>,
+}
+
+struct Measurement {
+    gpu: Duration,
+    /// Total time spent in `bind_pipeline_graphics`.
+    cpu_bind: Duration,
+    bind_count: u32,
+}
+
+struct Benchmark {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    framebuffer: Arc<Framebuffer>,
+    gpu_timer: GpuTimer,
+}
+
+impl Benchmark {
+    /// Draws `DRAW_COUNT` quads, cycling through `states`. The pipeline and the vertex buffer are
+    /// only bound when they change.
+    fn measure(&self, states: &[PipelineState]) -> Measurement {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        self.gpu_timer.begin(&mut builder);
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(self.framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap();
+
+        let mut cpu_bind = Duration::ZERO;
+        let mut bind_count = 0;
+
+        let mut current: Option<&PipelineState> = None;
+        for i in 0..DRAW_COUNT as usize {
+            let state = &states[i % states.len()];
+
+            if !current.is_some_and(|c| Arc::ptr_eq(&c.pipeline, &state.pipeline)) {
+                let start = Instant::now();
+                builder.bind_pipeline_graphics(state.pipeline.clone());
+                cpu_bind += start.elapsed();
+                bind_count += 1;
+            }
+
+            if !current.is_some_and(|c| {
+                Arc::ptr_eq(c.vertex_buffer.buffer(), state.vertex_buffer.buffer())
+            }) {
+                builder.bind_vertex_buffers(0, state.vertex_buffer.clone());
+            }
+
+            current = Some(state);
+            builder.draw(6, 1, 0, 0).unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+        self.gpu_timer.end(&mut builder);
+
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+
+        Measurement {
+            gpu: self.gpu_timer.elapsed(),
+            cpu_bind,
+            bind_count,
+        }
+    }
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    vertex_input_state: VertexBufferDescription,
+    render_pass: Arc<RenderPass>,
+) -> Arc<GraphicsPipeline> {
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [SIZE as f32, SIZE as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    GraphicsPipeline::start()
+        .vertex_input_state(vertex_input_state)
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)
+        .unwrap()
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let gpu_timer = match GpuTimer::new(device.clone(), queue_family_index) {
+        Some(gpu_timer) => gpu_timer,
+        None => {
+            println!("The queue doesn't support timestamps, the GPU can't be measured");
+            return;
+        }
+    };
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    // Two triangles rather than an index buffer, so that only the vertex buffer changes along
+    // with the vertex layout.
+    let quad = [
+        [-1.0, -1.0],
+        [1.0, -1.0],
+        [1.0, 1.0],
+        [1.0, 1.0],
+        [-1.0, 1.0],
+        [-1.0, -1.0],
+    ]
+    .map(|[x, y]| [x * QUAD_HALF_SIZE, y * QUAD_HALF_SIZE]);
+
+    let vertex_buffer_info = BufferCreateInfo {
+        usage: BufferUsage::VERTEX_BUFFER,
+        ..Default::default()
+    };
+    let vertex_allocation_info = AllocationCreateInfo {
+        usage: MemoryUsage::Upload,
+        ..Default::default()
+    };
+
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        vertex_buffer_info.clone(),
+        vertex_allocation_info.clone(),
+        quad.map(|position| Vertex2d { position }),
+    )
+    .unwrap();
+
+    let colored_vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        vertex_buffer_info,
+        vertex_allocation_info,
+        quad.map(|position| ColoredVertex {
+            position,
+            color: [0.0, 0.0, 1.0],
+        }),
+    )
+    .unwrap();
+
+    let image = AttachmentImage::with_usage(
+        &memory_allocator,
+        [SIZE, SIZE],
+        Format::R8G8B8A8_UNORM,
+        ImageUsage::COLOR_ATTACHMENT,
+    )
+    .unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: Format::R8G8B8A8_UNORM,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {},
+        },
+    )
+    .unwrap();
+
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![ImageView::new_default(image).unwrap()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let red_fs = red_fs::load(device.clone()).expect("failed to create shader module");
+    let green_fs = green_fs::load(device.clone()).expect("failed to create shader module");
+    let colored_vs = colored_vs::load(device.clone()).expect("failed to create shader module");
+    let colored_fs = colored_fs::load(device.clone()).expect("failed to create shader module");
+
+    let red = PipelineState {
+        pipeline: create_pipeline(
+            device.clone(),
+            vs.clone(),
+            red_fs,
+            Vertex2d::per_vertex(),
+            render_pass.clone(),
+        ),
+        vertex_buffer: vertex_buffer.clone().into_bytes(),
+    };
+    let green = PipelineState {
+        pipeline: create_pipeline(
+            device.clone(),
+            vs,
+            green_fs,
+            Vertex2d::per_vertex(),
+            render_pass.clone(),
+        ),
+        vertex_buffer: vertex_buffer.into_bytes(),
+    };
+    let colored = PipelineState {
+        pipeline: create_pipeline(
+            device.clone(),
+            colored_vs,
+            colored_fs,
+            ColoredVertex::per_vertex(),
+            render_pass,
+        ),
+        vertex_buffer: colored_vertex_buffer.into_bytes(),
+    };
+
+    let benchmark = Benchmark {
+        command_buffer_allocator: StandardCommandBufferAllocator::new(
+            device.clone(),
+            Default::default(),
+        ),
+        device,
+        queue,
+        framebuffer,
+        gpu_timer,
+    };
+
+    // The first submission pays for the lazy initialization done by the driver.
+    benchmark.measure(&[red.clone()]);
+
+    let scenarios = [
+        ("same pipeline", vec![red.clone()]),
+        ("different fragment shaders", vec![red.clone(), green]),
+        ("different vertex layouts", vec![red, colored]),
+    ];
+
+    println!();
+    println!(
+        "{DRAW_COUNT} draws:\n\n{:<26} | {:>8} | {:>14} | {:>16}",
+        "scenario", "GPU (us)", "pipeline binds", "CPU per bind (ns)"
+    );
+    println!("{:-<26}-+-{:-<8}-+-{:-<14}-+-{:-<16}", "", "", "", "");
+
+    for (name, states) in scenarios {
+        let measurement = benchmark.measure(&states);
+        println!(
+            "{:<26} | {:>8} | {:>14} | {:>16}",
+            name,
+            measurement.gpu.as_micros(),
+            measurement.bind_count,
+            measurement.cpu_bind.as_nanos() / measurement.bind_count as u128,
+        );
+    }
+
+    println!(
+        "
+How expensive a pipeline change is depends on the GPU:
+
+- On desktop GPUs, draws are executed as they come. A pipeline change can make the GPU wait for
+  the previous draws before loading the new state. Changing the vertex layout changes how the
+  vertices are fetched, which can need more of the pipeline to be reconfigured than changing the
+  fragment shader.
+- On mobile GPUs, which are tile-based, draws are first sorted into tiles and the fragments are
+  shaded later, tile by tile. Every state change has to be replayed for each tile that the draws
+  touch, and the drivers run on slower CPUs, so sorting draws by pipeline matters more there.
+
+On every GPU, pipelines should be sorted so that they change as rarely as possible."
+    );
+}
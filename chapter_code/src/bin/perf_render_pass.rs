@@ -0,0 +1,437 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Compares render passes with dynamic rendering, by rendering 1000 frames of a triangle:
+//!
+//! 1. with a render pass object and a framebuffer, begun with `vkCmdBeginRenderPass`,
+//! 2. with dynamic rendering, where the attachments are given when rendering begins with
+//!    `vkCmdBeginRendering` (or `vkCmdBeginRenderingKHR` before Vulkan 1.3), and the pipeline is
+//!    created with the formats of the attachments instead of a render pass.
+//!
+//! The time spent recording each frame is measured on the CPU with `Instant`, and the time taken
+//! by the GPU with timestamp queries. Dynamic rendering needs Vulkan 1.3 or the
+//! `VK_KHR_dynamic_rendering` extension, and only the render pass is measured without them.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chapter_code::{GpuTimer, Vertex2d};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    RenderingAttachmentInfo, RenderingInfo, SubpassContents,
+};
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags,
+};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::render_pass::{
+    PipelineRenderPassType, PipelineRenderingCreateInfo,
+};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, LoadOp, StoreOp, Subpass};
+use vulkano::shader::ShaderModule;
+use vulkano::sync::{self, GpuFuture};
+use vulkano::Version;
+
+const SIZE: u32 = 1024;
+const FORMAT: Format = Format::R8G8B8A8_UNORM;
+const FRAME_COUNT: u32 = 1000;
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+
+            void main() {
+                gl_Position = vec4(position, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = vec4(1.0, 0.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+/// How the rendering of a frame begins and ends.
+enum Rendering {
+    RenderPass {
+        framebuffer: Arc<Framebuffer>,
+        pipeline: Arc<GraphicsPipeline>,
+    },
+    Dynamic {
+        image_view: Arc<ImageView<AttachmentImage>>,
+        pipeline: Arc<GraphicsPipeline>,
+    },
+}
+
+impl Rendering {
+    fn name(&self) -> &'static str {
+        match self {
+            Rendering::RenderPass { .. } => "render pass",
+            Rendering::Dynamic { .. } => "dynamic rendering",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Measurement {
+    recording: Duration,
+    gpu: Duration,
+}
+
+struct Benchmark {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    vertex_buffer: Subbuffer<[Vertex2d]>,
+    gpu_timer: GpuTimer,
+}
+
+impl Benchmark {
+    /// Records and executes a single frame.
+    fn frame(&self, rendering: &Rendering) -> Measurement {
+        let start = Instant::now();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        self.gpu_timer.begin(&mut builder);
+        self.record(&mut builder, rendering);
+        self.gpu_timer.end(&mut builder);
+
+        let command_buffer = builder.build().unwrap();
+        let recording = start.elapsed();
+
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+
+        Measurement {
+            recording,
+            gpu: self.gpu_timer.elapsed(),
+        }
+    }
+
+    fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        rendering: &Rendering,
+    ) {
+        let clear_value = Some([0.0, 0.0, 1.0, 1.0].into());
+
+        let pipeline = match rendering {
+            Rendering::RenderPass {
+                framebuffer,
+                pipeline,
+            } => {
+                builder
+                    .begin_render_pass(
+                        RenderPassBeginInfo {
+                            clear_values: vec![clear_value],
+                            ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                        },
+                        SubpassContents::Inline,
+                    )
+                    .unwrap();
+                pipeline
+            }
+            Rendering::Dynamic {
+                image_view,
+                pipeline,
+            } => {
+                // The attachment is given inline, there is no render pass or framebuffer.
+                builder
+                    .begin_rendering(RenderingInfo {
+                        color_attachments: vec![Some(RenderingAttachmentInfo {
+                            load_op: LoadOp::Clear,
+                            store_op: StoreOp::Store,
+                            clear_value,
+                            ..RenderingAttachmentInfo::image_view(image_view.clone())
+                        })],
+                        ..Default::default()
+                    })
+                    .unwrap();
+                pipeline
+            }
+        };
+
+        builder
+            .bind_pipeline_graphics(pipeline.clone())
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap();
+
+        match rendering {
+            Rendering::RenderPass { .. } => builder.end_render_pass().unwrap(),
+            Rendering::Dynamic { .. } => builder.end_rendering().unwrap(),
+        };
+    }
+
+    /// Renders `FRAME_COUNT` frames and returns the total times.
+    fn measure(&self, rendering: &Rendering) -> Measurement {
+        // The first submission pays for the lazy initialization done by the driver.
+        self.frame(rendering);
+
+        (0..FRAME_COUNT).fold(Measurement::default(), |total, _| {
+            let frame = self.frame(rendering);
+            Measurement {
+                recording: total.recording + frame.recording,
+                gpu: total.gpu + frame.gpu,
+            }
+        })
+    }
+}
+
+/// Creates the pipeline for a subpass of a render pass, or for dynamic rendering with the formats
+/// of the attachments.
+fn create_pipeline(
+    device: Arc<Device>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    render_pass: impl Into<PipelineRenderPassType>,
+) -> Arc<GraphicsPipeline> {
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [SIZE as f32, SIZE as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    GraphicsPipeline::start()
+        .vertex_input_state(Vertex2d::per_vertex())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .render_pass(render_pass)
+        .build(device)
+        .unwrap()
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+        .expect("couldn't find a graphical queue family") as u32;
+
+    // Dynamic rendering is part of Vulkan 1.3. Before that, `VK_KHR_dynamic_rendering` depends on
+    // extensions that are part of Vulkan 1.2.
+    let core_dynamic_rendering = physical_device.api_version() >= Version::V1_3;
+    let dynamic_rendering = physical_device.supported_features().dynamic_rendering
+        && (core_dynamic_rendering
+            || physical_device.api_version() >= Version::V1_2
+                && physical_device.supported_extensions().khr_dynamic_rendering);
+    if !dynamic_rendering {
+        println!("Dynamic rendering isn't supported, only the render pass is measured");
+    }
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_extensions: DeviceExtensions {
+                khr_dynamic_rendering: dynamic_rendering && !core_dynamic_rendering,
+                ..DeviceExtensions::empty()
+            },
+            enabled_features: Features {
+                dynamic_rendering,
+                ..Features::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let gpu_timer = match GpuTimer::new(device.clone(), queue_family_index) {
+        Some(gpu_timer) => gpu_timer,
+        None => {
+            println!("The queue doesn't support timestamps, the GPU can't be measured");
+            return;
+        }
+    };
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let vertex_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        [[-0.5, -0.5], [0.0, 0.5], [0.5, -0.25]].map(|position| Vertex2d { position }),
+    )
+    .unwrap();
+
+    let image = AttachmentImage::with_usage(
+        &memory_allocator,
+        [SIZE, SIZE],
+        FORMAT,
+        ImageUsage::COLOR_ATTACHMENT,
+    )
+    .unwrap();
+    let image_view = ImageView::new_default(image).unwrap();
+
+    let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: FORMAT,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {},
+        },
+    )
+    .unwrap();
+
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![image_view.clone()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vs = vs::load(device.clone()).expect("failed to create shader module");
+    let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+    let mut renderings = vec![Rendering::RenderPass {
+        framebuffer,
+        pipeline: create_pipeline(
+            device.clone(),
+            &vs,
+            &fs,
+            Subpass::from(render_pass, 0).unwrap(),
+        ),
+    }];
+
+    if dynamic_rendering {
+        renderings.push(Rendering::Dynamic {
+            image_view,
+            pipeline: create_pipeline(
+                device.clone(),
+                &vs,
+                &fs,
+                PipelineRenderingCreateInfo {
+                    color_attachment_formats: vec![Some(FORMAT)],
+                    ..Default::default()
+                },
+            ),
+        });
+    }
+
+    let benchmark = Benchmark {
+        command_buffer_allocator: StandardCommandBufferAllocator::new(
+            device.clone(),
+            Default::default(),
+        ),
+        device,
+        queue,
+        vertex_buffer,
+        gpu_timer,
+    };
+
+    let results: Vec<_> = renderings
+        .iter()
+        .map(|rendering| (rendering.name(), benchmark.measure(rendering)))
+        .collect();
+
+    println!();
+    println!(
+        "{FRAME_COUNT} frames:\n\n{:<17} | {:>24} | {:>18}",
+        "rendering", "recording per frame (us)", "GPU per frame (us)"
+    );
+    println!("{:-<17}-+-{:-<24}-+-{:-<18}", "", "", "");
+
+    let per_frame = |time: Duration| time.as_secs_f64() * 1e6 / FRAME_COUNT as f64;
+
+    for (name, measurement) in &results {
+        println!(
+            "{:<17} | {:>24.2} | {:>18.2}",
+            name,
+            per_frame(measurement.recording),
+            per_frame(measurement.gpu),
+        );
+    }
+
+    if let [(_, render_pass), (_, dynamic)] = &results[..] {
+        let ratio = |dynamic: Duration, render_pass: Duration| {
+            dynamic.as_secs_f64() / render_pass.as_secs_f64()
+        };
+
+        println!();
+        println!(
+            "Dynamic rendering takes {:.2}x the recording time and {:.2}x the GPU time of the \
+             render pass",
+            ratio(dynamic.recording, render_pass.recording),
+            ratio(dynamic.gpu, render_pass.gpu),
+        );
+    }
+}
@@ -0,0 +1,481 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Measures the GPU cost of synchronizing compute dispatches that read and write the same buffer.
+//! 10,000 dispatches are recorded in a row, with one of these between each of them:
+//!
+//! 1. nothing, which is wrong, as each dispatch races with the previous one,
+//! 2. a full barrier, from all commands to all commands and for every memory access,
+//! 3. a minimal barrier, from the compute shader writes to the compute shader reads and writes,
+//! 4. an event, set after a dispatch and waited on before the next one.
+//!
+//! The dispatches are timed with timestamp queries, and the overhead of each synchronization
+//! operation is the difference with the first case. As the first case is a data race, it is only
+//! compiled in debug builds. The GPU timings don't depend on the build profile, so the benchmark
+//! should be run without `--release`:
+//!
+//! ```bash
+//! cargo run --bin perf_synchronization --features perf-synchronization
+//! ```
+//!
+//! vulkano always inserts the barriers that are needed, and doesn't expose events in command
+//! buffers it records, so the command buffers are recorded with raw Vulkan calls.
+
+#[cfg(feature = "perf-synchronization")]
+mod example {
+    use std::ptr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ash::vk;
+    use chapter_code::GpuTimer;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+    use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+    use vulkano::device::{Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags};
+    use vulkano::instance::{Instance, InstanceCreateInfo};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+    use vulkano::pipeline::{ComputePipeline, Pipeline};
+    use vulkano::{VulkanLibrary, VulkanObject};
+
+    const ITERATIONS: u32 = 10_000;
+
+    // The dispatches are kept small, so that the synchronization is a large part of their cost.
+    // Must match the local size of the shader.
+    const LOCAL_SIZE: u32 = 64;
+    const WORK_GROUP_COUNT: u32 = 16;
+
+    mod cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 460
+
+                layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+                layout(set = 0, binding = 0) buffer Data {
+                    uint data[];
+                };
+
+                void main() {
+                    uint idx = gl_GlobalInvocationID.x;
+                    data[idx] += 1;
+                }
+            ",
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    enum Synchronization {
+        #[cfg(debug_assertions)]
+        None,
+        FullBarrier,
+        MinimalBarrier,
+        Event,
+    }
+
+    impl Synchronization {
+        fn name(self) -> &'static str {
+            match self {
+                #[cfg(debug_assertions)]
+                Synchronization::None => "none (wrong)",
+                Synchronization::FullBarrier => "full barrier",
+                Synchronization::MinimalBarrier => "minimal barrier",
+                Synchronization::Event => "event",
+            }
+        }
+    }
+
+    const SYNCHRONIZATIONS: [Synchronization; if cfg!(debug_assertions) { 4 } else { 3 }] = [
+        #[cfg(debug_assertions)]
+        Synchronization::None,
+        Synchronization::FullBarrier,
+        Synchronization::MinimalBarrier,
+        Synchronization::Event,
+    ];
+
+    /// A command buffer recorded and submitted with raw Vulkan calls, so that vulkano doesn't
+    /// add barriers of its own.
+    struct RawCommandBuffer {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+    }
+
+    impl RawCommandBuffer {
+        fn begin(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+            let fns = &device.fns().v1_0;
+
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(queue.queue_family_index());
+            let mut command_pool = vk::CommandPool::null();
+            unsafe {
+                (fns.create_command_pool)(
+                    device.handle(),
+                    &*pool_info,
+                    ptr::null(),
+                    &mut command_pool,
+                )
+            }
+            .result()
+            .expect("failed to create command pool");
+
+            let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let mut command_buffer = vk::CommandBuffer::null();
+            unsafe {
+                (fns.allocate_command_buffers)(
+                    device.handle(),
+                    &*allocate_info,
+                    &mut command_buffer,
+                )
+            }
+            .result()
+            .expect("failed to allocate command buffer");
+
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            unsafe { (fns.begin_command_buffer)(command_buffer, &*begin_info) }
+                .result()
+                .unwrap();
+
+            RawCommandBuffer {
+                device,
+                queue,
+                command_pool,
+                command_buffer,
+            }
+        }
+
+        fn submit_and_wait(self) {
+            let fns = &self.device.fns().v1_0;
+
+            unsafe { (fns.end_command_buffer)(self.command_buffer) }
+                .result()
+                .unwrap();
+
+            let command_buffers = [self.command_buffer];
+            let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+            self.queue
+                .with(|_queue| unsafe {
+                    (fns.queue_submit)(self.queue.handle(), 1, &*submit_info, vk::Fence::null())
+                        .result()?;
+                    (fns.queue_wait_idle)(self.queue.handle()).result()
+                })
+                .expect("failed to submit the command buffer");
+        }
+    }
+
+    impl Drop for RawCommandBuffer {
+        fn drop(&mut self) {
+            let fns = &self.device.fns().v1_0;
+            unsafe {
+                (fns.destroy_command_pool)(self.device.handle(), self.command_pool, ptr::null())
+            };
+        }
+    }
+
+    struct Benchmark {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        pipeline: Arc<ComputePipeline>,
+        descriptor_set: Arc<PersistentDescriptorSet>,
+        event: vk::Event,
+        gpu_timer: GpuTimer,
+    }
+
+    impl Benchmark {
+        /// Records the dispatches, with `synchronization` after each of them, and returns the
+        /// time the GPU took to execute them.
+        fn measure(&self, synchronization: Synchronization) -> Duration {
+            let fns = &self.device.fns().v1_0;
+            let commands = RawCommandBuffer::begin(self.device.clone(), self.queue.clone());
+            let command_buffer = commands.command_buffer;
+            let query_pool = self.gpu_timer.query_pool().handle();
+
+            // The accesses of each dispatch that must happen after the previous dispatch.
+            let memory_barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                .build();
+
+            // Safety: the buffer and the queries aren't in use, as the previous command buffer
+            // has finished executing.
+            unsafe {
+                (fns.cmd_reset_query_pool)(command_buffer, query_pool, 0, 2);
+                (fns.cmd_write_timestamp)(
+                    command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    query_pool,
+                    0,
+                );
+
+                (fns.cmd_bind_pipeline)(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline.handle(),
+                );
+                (fns.cmd_bind_descriptor_sets)(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline.layout().handle(),
+                    0,
+                    1,
+                    &self.descriptor_set.inner().handle(),
+                    0,
+                    ptr::null(),
+                );
+
+                for _ in 0..ITERATIONS {
+                    (fns.cmd_dispatch)(command_buffer, WORK_GROUP_COUNT, 1, 1);
+
+                    match synchronization {
+                        #[cfg(debug_assertions)]
+                        Synchronization::None => {}
+                        Synchronization::FullBarrier => {
+                            let memory_barrier = vk::MemoryBarrier::builder()
+                                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                                .dst_access_mask(
+                                    vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                                );
+                            (fns.cmd_pipeline_barrier)(
+                                command_buffer,
+                                vk::PipelineStageFlags::ALL_COMMANDS,
+                                vk::PipelineStageFlags::ALL_COMMANDS,
+                                vk::DependencyFlags::empty(),
+                                1,
+                                &*memory_barrier,
+                                0,
+                                ptr::null(),
+                                0,
+                                ptr::null(),
+                            );
+                        }
+                        Synchronization::MinimalBarrier => {
+                            (fns.cmd_pipeline_barrier)(
+                                command_buffer,
+                                vk::PipelineStageFlags::COMPUTE_SHADER,
+                                vk::PipelineStageFlags::COMPUTE_SHADER,
+                                vk::DependencyFlags::empty(),
+                                1,
+                                &memory_barrier,
+                                0,
+                                ptr::null(),
+                                0,
+                                ptr::null(),
+                            );
+                        }
+                        Synchronization::Event => {
+                            // The event is never reset. Setting it again is allowed, and the wait
+                            // covers every command before the last time it was set.
+                            (fns.cmd_set_event)(
+                                command_buffer,
+                                self.event,
+                                vk::PipelineStageFlags::COMPUTE_SHADER,
+                            );
+                            (fns.cmd_wait_events)(
+                                command_buffer,
+                                1,
+                                &self.event,
+                                vk::PipelineStageFlags::COMPUTE_SHADER,
+                                vk::PipelineStageFlags::COMPUTE_SHADER,
+                                1,
+                                &memory_barrier,
+                                0,
+                                ptr::null(),
+                                0,
+                                ptr::null(),
+                            );
+                        }
+                    }
+                }
+
+                (fns.cmd_write_timestamp)(
+                    command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    query_pool,
+                    1,
+                );
+            }
+
+            commands.submit_and_wait();
+
+            self.gpu_timer.elapsed()
+        }
+    }
+
+    impl Drop for Benchmark {
+        fn drop(&mut self) {
+            let fns = &self.device.fns().v1_0;
+            unsafe { (fns.destroy_event)(self.device.handle(), self.event, ptr::null()) };
+        }
+    }
+
+    fn nanoseconds_per_iteration(time: Duration) -> f64 {
+        time.as_nanos() as f64 / ITERATIONS as f64
+    }
+
+    pub fn main() {
+        let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(library, InstanceCreateInfo::default())
+            .expect("failed to create instance");
+
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .next()
+            .expect("no devices available");
+
+        println!(
+            "Using device: {} (type: {:?})",
+            physical_device.properties().device_name,
+            physical_device.properties().device_type,
+        );
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::COMPUTE))
+            .expect("couldn't find a compute queue family") as u32;
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let gpu_timer = match GpuTimer::new(device.clone(), queue_family_index) {
+            Some(gpu_timer) => gpu_timer,
+            None => {
+                println!("The queue doesn't support timestamps, the GPU can't be measured");
+                return;
+            }
+        };
+
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+
+        let data_buffer = Buffer::new_slice::<u32>(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            (WORK_GROUP_COUNT * LOCAL_SIZE) as u64,
+        )
+        .unwrap();
+
+        let shader = cs::load(device.clone()).expect("failed to create shader module");
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            None,
+            |_| {},
+        )
+        .expect("failed to create compute pipeline");
+
+        let descriptor_set = PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::buffer(0, data_buffer)],
+        )
+        .unwrap();
+
+        let event_info = vk::EventCreateInfo::builder();
+        let mut event = vk::Event::null();
+        unsafe {
+            (device.fns().v1_0.create_event)(device.handle(), &*event_info, ptr::null(), &mut event)
+        }
+        .result()
+        .expect("failed to create event");
+
+        let benchmark = Benchmark {
+            device,
+            queue,
+            pipeline,
+            descriptor_set,
+            event,
+            gpu_timer,
+        };
+
+        // The first submission pays for the lazy initialization done by the driver.
+        benchmark.measure(SYNCHRONIZATIONS[0]);
+
+        let results = SYNCHRONIZATIONS
+            .map(|synchronization| (synchronization, benchmark.measure(synchronization)));
+
+        #[cfg(debug_assertions)]
+        let baseline = Some(results[0].1);
+        #[cfg(not(debug_assertions))]
+        let baseline = None::<Duration>;
+
+        println!();
+        println!(
+            "{:<15} | {:>18} | {:>20}",
+            "synchronization", "per iteration (ns)", "overhead per op (ns)"
+        );
+        println!("{:-<15}-+-{:-<18}-+-{:-<20}", "", "", "");
+
+        for (synchronization, time) in results {
+            let overhead = match baseline {
+                Some(baseline) => format!(
+                    "{:.1}",
+                    nanoseconds_per_iteration(time) - nanoseconds_per_iteration(baseline)
+                ),
+                None => "-".to_owned(),
+            };
+            println!(
+                "{:<15} | {:>18.1} | {:>20}",
+                synchronization.name(),
+                nanoseconds_per_iteration(time),
+                overhead,
+            );
+        }
+
+        println!();
+        if baseline.is_none() {
+            println!(
+                "The unsynchronized baseline is only compiled in debug builds, run without \
+                 --release to get the overhead of each synchronization operation"
+            );
+        } else {
+            println!(
+                "The unsynchronized baseline is wrong on purpose: its dispatches race with each \
+                 other and only serve as a reference for the timings"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "perf-synchronization")]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(feature = "perf-synchronization"))]
+fn main() {
+    println!("The synchronization benchmark needs the `perf-synchronization` feature");
+}
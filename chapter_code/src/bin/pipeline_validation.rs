@@ -0,0 +1,518 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Misuses the API on purpose and checks that the validation layer reports it, to make sure that
+//! validation is actually working on a machine.
+//!
+//! Three mistakes are made, each followed by a check that the debug messenger callback received
+//! an error:
+//!
+//! 1. An image is cleared in the `TransferDstOptimal` layout without being transitioned to it
+//!    first (an invalid image layout error when the commands are submitted).
+//! 2. A buffer is filled and then copied from without a barrier in between (a read-after-write
+//!    hazard, reported by the synchronization validation).
+//! 3. A graphics pipeline is created with a vertex shader that has two inputs, but with a vertex
+//!    input state that only provides one of them.
+//!
+//! vulkano would otherwise insert the missing layout transition and barrier, and refuse to create
+//! the pipeline, so these mistakes are made with raw Vulkan calls.
+//!
+//! This needs the Khronos validation layer (part of the Vulkan SDK) and the `pipeline-validation`
+//! feature. Nothing is done unless `--validate` is passed:
+//!
+//! ```bash
+//! cargo run --bin pipeline_validation --features pipeline-validation -- --validate
+//! ```
+
+#[cfg(feature = "pipeline-validation")]
+mod example {
+    use std::env;
+    use std::ffi::CString;
+    use std::ptr;
+    use std::slice;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use ash::vk;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::device::{Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags};
+    use vulkano::format::Format;
+    use vulkano::image::{
+        ImageAccess, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage,
+    };
+    use vulkano::instance::debug::{
+        DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+        DebugUtilsMessengerCreateInfo,
+    };
+    use vulkano::instance::{
+        Instance, InstanceCreateInfo, InstanceExtensions, ValidationFeatureEnable,
+    };
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+    use vulkano::pipeline::layout::PipelineLayoutCreateInfo;
+    use vulkano::pipeline::PipelineLayout;
+    use vulkano::{VulkanLibrary, VulkanObject};
+
+    const VALIDATE_FLAG: &str = "--validate";
+    const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+    const IMAGE_SIZE: u32 = 64;
+
+    mod vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 position;
+                layout(location = 1) in vec3 color;
+
+                layout(location = 0) out vec3 out_color;
+
+                void main() {
+                    gl_Position = vec4(position, 0.0, 1.0);
+                    out_color = color;
+                }
+            ",
+        }
+    }
+
+    mod fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec3 in_color;
+
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = vec4(in_color, 1.0);
+                }
+            ",
+        }
+    }
+
+    /// A command buffer recorded and submitted with raw Vulkan calls, so that vulkano doesn't
+    /// add what's missing on purpose.
+    struct RawCommandBuffer {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+    }
+
+    impl RawCommandBuffer {
+        fn begin(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+            let fns = &device.fns().v1_0;
+
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(queue.queue_family_index());
+            let mut command_pool = vk::CommandPool::null();
+            unsafe {
+                (fns.create_command_pool)(
+                    device.handle(),
+                    &*pool_info,
+                    ptr::null(),
+                    &mut command_pool,
+                )
+            }
+            .result()
+            .expect("failed to create command pool");
+
+            let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let mut command_buffer = vk::CommandBuffer::null();
+            unsafe {
+                (fns.allocate_command_buffers)(
+                    device.handle(),
+                    &*allocate_info,
+                    &mut command_buffer,
+                )
+            }
+            .result()
+            .expect("failed to allocate command buffer");
+
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            unsafe { (fns.begin_command_buffer)(command_buffer, &*begin_info) }
+                .result()
+                .unwrap();
+
+            RawCommandBuffer {
+                device,
+                queue,
+                command_pool,
+                command_buffer,
+            }
+        }
+
+        fn submit_and_wait(self) {
+            let fns = &self.device.fns().v1_0;
+
+            unsafe { (fns.end_command_buffer)(self.command_buffer) }
+                .result()
+                .unwrap();
+
+            let command_buffers = [self.command_buffer];
+            let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+            self.queue
+                .with(|_queue| unsafe {
+                    (fns.queue_submit)(self.queue.handle(), 1, &*submit_info, vk::Fence::null())
+                        .result()?;
+                    (fns.queue_wait_idle)(self.queue.handle()).result()
+                })
+                .expect("failed to submit the command buffer");
+        }
+    }
+
+    impl Drop for RawCommandBuffer {
+        fn drop(&mut self) {
+            let fns = &self.device.fns().v1_0;
+            unsafe {
+                (fns.destroy_command_pool)(self.device.handle(), self.command_pool, ptr::null())
+            };
+        }
+    }
+
+    /// Checks that the validation layer reported an error since the previous check.
+    fn check_reported(error_reported: &AtomicBool, mistake: &str) {
+        assert!(
+            error_reported.swap(false, Ordering::SeqCst),
+            "the validation layer didn't report {mistake}",
+        );
+        println!("Reported as expected: {mistake}\n");
+    }
+
+    pub fn main() {
+        if !env::args().skip(1).any(|arg| arg == VALIDATE_FLAG) {
+            println!("Pass {VALIDATE_FLAG} to misuse the API and check that validation catches it");
+            return;
+        }
+
+        let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+
+        let has_validation_layer = library
+            .layer_properties()
+            .unwrap()
+            .any(|layer| layer.name() == VALIDATION_LAYER);
+        if !has_validation_layer {
+            println!("{VALIDATION_LAYER} isn't installed, install the Vulkan SDK to run this");
+            return;
+        }
+
+        // Hazards are only reported when the synchronization validation is enabled.
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                enabled_extensions: InstanceExtensions {
+                    ext_debug_utils: true,
+                    ext_validation_features: true,
+                    ..InstanceExtensions::empty()
+                },
+                enabled_layers: vec![VALIDATION_LAYER.to_owned()],
+                enabled_validation_features: vec![
+                    ValidationFeatureEnable::SynchronizationValidation,
+                ],
+                ..Default::default()
+            },
+        )
+        .expect("failed to create instance");
+
+        let error_reported = Arc::new(AtomicBool::new(false));
+
+        let _messenger = {
+            let error_reported = error_reported.clone();
+
+            unsafe {
+                DebugUtilsMessenger::new(
+                    instance.clone(),
+                    DebugUtilsMessengerCreateInfo {
+                        message_severity: DebugUtilsMessageSeverity::ERROR
+                            | DebugUtilsMessageSeverity::WARNING,
+                        message_type: DebugUtilsMessageType::GENERAL
+                            | DebugUtilsMessageType::VALIDATION
+                            | DebugUtilsMessageType::PERFORMANCE,
+                        ..DebugUtilsMessengerCreateInfo::user_callback(Arc::new(move |msg| {
+                            println!("[{:?}] {}", msg.severity, msg.description);
+
+                            if msg.severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+                                error_reported.store(true, Ordering::SeqCst);
+                            }
+                        }))
+                    },
+                )
+            }
+            .expect("failed to create debug messenger")
+        };
+
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .next()
+            .expect("no devices available");
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::GRAPHICS))
+            .expect("couldn't find a graphical queue family")
+            as u32;
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+        let fns = &device.fns().v1_0;
+
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+        // Anything reported until now is unrelated to the mistakes below.
+        error_reported.store(false, Ordering::SeqCst);
+
+        // 1. Clearing an image that is still in the `Undefined` layout it was created with.
+
+        let image = StorageImage::with_usage(
+            &memory_allocator,
+            ImageDimensions::Dim2d {
+                width: IMAGE_SIZE,
+                height: IMAGE_SIZE,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            ImageUsage::TRANSFER_DST,
+            ImageCreateFlags::empty(),
+            [queue_family_index],
+        )
+        .unwrap();
+
+        let commands = RawCommandBuffer::begin(device.clone(), queue.clone());
+        let clear_color = vk::ClearColorValue {
+            float32: [0.0, 0.0, 1.0, 1.0],
+        };
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        unsafe {
+            (fns.cmd_clear_color_image)(
+                commands.command_buffer,
+                image.inner().image.handle(),
+                // The image should have been transitioned to this layout with a barrier first.
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &clear_color,
+                1,
+                &range,
+            )
+        };
+        commands.submit_and_wait();
+
+        check_reported(&error_reported, "a missing image layout transition");
+
+        // 2. Reading a buffer right after writing it, without a barrier.
+
+        let create_buffer = || {
+            Buffer::new_slice::<u32>(
+                &memory_allocator,
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::DeviceOnly,
+                    ..Default::default()
+                },
+                1024,
+            )
+            .unwrap()
+        };
+        let source = create_buffer();
+        let destination = create_buffer();
+
+        let commands = RawCommandBuffer::begin(device.clone(), queue.clone());
+        let region = vk::BufferCopy {
+            src_offset: source.offset(),
+            dst_offset: destination.offset(),
+            size: source.size(),
+        };
+        unsafe {
+            (fns.cmd_fill_buffer)(
+                commands.command_buffer,
+                source.buffer().handle(),
+                source.offset(),
+                source.size(),
+                42,
+            );
+            // A barrier from the transfer write to the transfer read is needed here.
+            (fns.cmd_copy_buffer)(
+                commands.command_buffer,
+                source.buffer().handle(),
+                destination.buffer().handle(),
+                1,
+                &region,
+            );
+        }
+        commands.submit_and_wait();
+
+        check_reported(&error_reported, "a read-after-write hazard");
+
+        // 3. Creating a pipeline whose vertex input state doesn't provide every vertex shader
+        // input.
+
+        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: Format::R8G8B8A8_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+        .unwrap();
+
+        let layout =
+            PipelineLayout::new(device.clone(), PipelineLayoutCreateInfo::default()).unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let entry_point = CString::new("main").unwrap();
+
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vs.handle())
+                .name(&entry_point)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(fs.handle())
+                .name(&entry_point)
+                .build(),
+        ];
+
+        // Only `position` is provided, `color` (location 1) is missing.
+        let vertex_binding = vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: 2 * std::mem::size_of::<f32>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        };
+        let vertex_attribute = vk::VertexInputAttributeDescription {
+            location: 0,
+            binding: 0,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: 0,
+        };
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(slice::from_ref(&vertex_binding))
+            .vertex_attribute_descriptions(slice::from_ref(&vertex_attribute));
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: IMAGE_SIZE as f32,
+            height: IMAGE_SIZE as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent: vk::Extent2D {
+                width: IMAGE_SIZE,
+                height: IMAGE_SIZE,
+            },
+        };
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(slice::from_ref(&viewport))
+            .scissors(slice::from_ref(&scissor));
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .line_width(1.0);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+        let blend_attachment = vk::PipelineColorBlendAttachmentState {
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+            ..Default::default()
+        };
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(slice::from_ref(&blend_attachment));
+
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .color_blend_state(&color_blend_state)
+            .layout(layout.handle())
+            .render_pass(render_pass.handle())
+            .subpass(0);
+
+        let mut pipeline = vk::Pipeline::null();
+        let result = unsafe {
+            (fns.create_graphics_pipelines)(
+                device.handle(),
+                vk::PipelineCache::null(),
+                1,
+                &*pipeline_info,
+                ptr::null(),
+                &mut pipeline,
+            )
+        };
+        // Drivers aren't required to fail, the validation layer is what catches this.
+        if result == vk::Result::SUCCESS {
+            unsafe { (fns.destroy_pipeline)(device.handle(), pipeline, ptr::null()) };
+        }
+
+        check_reported(
+            &error_reported,
+            "a vertex shader input missing from the vertex input",
+        );
+
+        println!("Everything succeeded!");
+    }
+}
+
+#[cfg(feature = "pipeline-validation")]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(feature = "pipeline-validation"))]
+fn main() {
+    println!("Pipeline validation not compiled in");
+}
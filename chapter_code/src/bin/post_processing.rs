@@ -0,0 +1,456 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders the triangle of the windowing chapter with a post-processing effect, which turns the
+//! image to grayscale and darkens its corners.
+//!
+//! The frame is drawn in two passes. The first one renders the scene as usual, but to an
+//! off-screen image instead of the swapchain image. The second one draws a single triangle covering
+//! the swapchain image, whose fragment shader samples the off-screen image at the same position and
+//! applies the effect.
+//!
+//! The off-screen image is written as a color attachment by the first pass and sampled by the
+//! second, which needs a layout transition and a barrier in between. These are inserted by
+//! vulkano's command buffer builder.
+
+use std::sync::Arc;
+
+use chapter_code::shaders::static_triangle;
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::vulkano_objects::swapchain::OffscreenTarget;
+use chapter_code::{is_headless, vulkano_objects, Vertex2d, HEADLESS_FRAME_COUNT};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
+    SubpassContents,
+};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::image::SwapchainImage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, RenderPass};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+    SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+// Draws the triangle covering the framebuffer that `create_fullscreen_pipeline` expects, with the
+// coordinates of each pixel in the off-screen image.
+mod post_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) out vec2 uv;
+
+            void main() {
+                // (0, 0), (2, 0) and (0, 2), the parts outside of the framebuffer are clipped.
+                uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+                gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+mod post_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 uv;
+
+            layout(location = 0) out vec4 f_color;
+
+            layout(set = 0, binding = 0) uniform sampler2D scene;
+
+            void main() {
+                vec3 color = texture(scene, uv).rgb;
+
+                // Weighted by how bright each primary color looks.
+                float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+
+                // 1 at the center, down to 0.5 in the corners.
+                vec2 from_center = uv - vec2(0.5);
+                float vignette = 1.0 - dot(from_center, from_center);
+
+                f_color = vec4(vec3(luminance * vignette), 1.0);
+            }
+        ",
+    }
+}
+
+type Fence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+struct Renderer {
+    surface: Arc<Surface>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    scene_render_pass: Arc<RenderPass>,
+    post_render_pass: Arc<RenderPass>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[Vertex2d]>,
+    scene_pipeline: Arc<GraphicsPipeline>,
+    post_pipeline: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    fences: Vec<Option<Arc<Fence>>>,
+    previous_fence_i: u32,
+    recreate_swapchain: bool,
+}
+
+impl Renderer {
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+
+        let surface = WindowBuilder::new()
+            .with_title("Post-processing")
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (swapchain, images) = vulkano_objects::swapchain::create_swapchain(
+            &physical_device,
+            device.clone(),
+            surface.clone(),
+        )
+        .expect("failed to create swapchain");
+
+        // Both passes draw to a single color attachment with the format of the swapchain images,
+        // so they are created the same way. A scene with depth testing would give the first one a
+        // depth attachment as well.
+        let scene_render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+        let post_render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+
+        let allocators = Allocators::new(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                Vertex2d {
+                    position: [-0.5, -0.5],
+                },
+                Vertex2d {
+                    position: [0.0, 0.5],
+                },
+                Vertex2d {
+                    position: [0.5, -0.25],
+                },
+            ],
+        )
+        .unwrap();
+
+        let scene_pipeline = vulkano_objects::pipeline::create_pipeline(
+            device.clone(),
+            static_triangle::vs::load(device.clone()).expect("failed to create shader module"),
+            static_triangle::fs::load(device.clone()).expect("failed to create shader module"),
+            scene_render_pass.clone(),
+        )
+        .expect("failed to create pipeline");
+
+        let post_pipeline = vulkano_objects::pipeline::create_fullscreen_pipeline(
+            device.clone(),
+            post_vs::load(device.clone()).expect("failed to create shader module"),
+            post_fs::load(device.clone()).expect("failed to create shader module"),
+            post_render_pass.clone(),
+        )
+        .expect("failed to create pipeline");
+
+        // The off-screen images have the size of the swapchain images, so each pixel samples a
+        // single texel and the default nearest filtering is enough.
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::default()).unwrap();
+
+        let mut renderer = Self {
+            surface,
+            device,
+            queue,
+            swapchain,
+            scene_render_pass,
+            post_render_pass,
+            allocators,
+            vertex_buffer,
+            scene_pipeline,
+            post_pipeline,
+            sampler,
+            command_buffers: Vec::new(),
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            recreate_swapchain: false,
+        };
+        renderer.create_command_buffers(&images);
+
+        renderer
+    }
+
+    fn window(&self) -> Arc<Window> {
+        self.surface
+            .object()
+            .unwrap()
+            .clone()
+            .downcast::<Window>()
+            .unwrap()
+    }
+
+    fn handle_window_resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    // The off-screen targets are created again with the swapchain images, as they have their size.
+    // The command buffers keep them alive, so they aren't stored.
+    fn create_command_buffers(&mut self, images: &[Arc<SwapchainImage>]) {
+        let targets = vulkano_objects::swapchain::create_offscreen_targets(
+            images,
+            self.scene_render_pass.clone(),
+            &self.allocators.memory,
+        );
+        let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+            images,
+            self.post_render_pass.clone(),
+        );
+
+        self.command_buffers = targets
+            .iter()
+            .zip(framebuffers)
+            .map(|(target, framebuffer)| self.create_command_buffer(target, framebuffer))
+            .collect();
+    }
+
+    // Draws the scene to `target`, and then `target` to `framebuffer` with the effect applied.
+    fn create_command_buffer(
+        &self,
+        target: &OffscreenTarget,
+        framebuffer: Arc<Framebuffer>,
+    ) -> Arc<PrimaryAutoCommandBuffer> {
+        let [width, height] = framebuffer.extent();
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        let layout = self.post_pipeline.layout().set_layouts().get(0).unwrap();
+        let scene_set = PersistentDescriptorSet::new(
+            &self.allocators.descriptor_set,
+            layout.clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                target.view.clone(),
+                self.sampler.clone(),
+            )],
+        )
+        .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.allocators.command_buffer,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::MultipleSubmit,
+        )
+        .unwrap();
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(
+                        vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR.into(),
+                    )],
+                    ..RenderPassBeginInfo::framebuffer(target.framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [viewport.clone()])
+            .bind_pipeline_graphics(self.scene_pipeline.clone())
+            .bind_vertex_buffers(0, self.vertex_buffer.clone())
+            .draw(self.vertex_buffer.len() as u32, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+            // Every pixel is drawn by the full-screen triangle, so what it is cleared with is
+            // never seen.
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [viewport])
+            .bind_pipeline_graphics(self.post_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.post_pipeline.layout().clone(),
+                0,
+                scene_set,
+            )
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        Arc::new(builder.build().unwrap())
+    }
+
+    fn render(&mut self) {
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: self.window().inner_size().into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.recreate_swapchain = false;
+            self.swapchain = new_swapchain;
+            self.create_command_buffers(&new_images);
+        }
+
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(self.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(
+                self.queue.clone(),
+                self.command_buffers[image_i as usize].clone(),
+            )
+            .unwrap()
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i;
+    }
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let mut renderer = Renderer::new(&event_loop);
+
+    let headless = is_headless();
+    let mut frame_count = 0;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            renderer.handle_window_resize();
+        }
+        Event::MainEventsCleared => {
+            renderer.render();
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        _ => (),
+    });
+}
@@ -0,0 +1,175 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Runs a headless loop of "frames", each submitting a compute dispatch and waiting for it, and
+//! collects their frame times with `RenderStats`.
+//!
+//! Besides the average, the p50, p95 and p99 frame times are printed: a few slow frames barely
+//! move the average, but they are what makes the rendering stutter. The same statistics are
+//! printed by the `more_on_buffers` example while it renders to a window.
+
+use std::time::Instant;
+
+use chapter_code::RenderStats;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use vulkano::VulkanLibrary;
+
+// As many frames as `RenderStats` keeps, so that the statistics are over all of them.
+const FRAME_COUNT: usize = 1000;
+
+// Must match the local size of the shader.
+const LOCAL_SIZE: u32 = 64;
+const WORK_GROUP_COUNT: u32 = 1024;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) buffer Data {
+                uint data[];
+            } buf;
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+                buf.data[idx] = buf.data[idx] * 1664525 + 1013904223;
+            }
+        ",
+    }
+}
+
+fn main() {
+    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .position(|q| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let data_buffer = Buffer::new_slice::<u32>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::DeviceOnly,
+            ..Default::default()
+        },
+        (WORK_GROUP_COUNT * LOCAL_SIZE) as u64,
+    )
+    .expect("failed to create buffer");
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+    let descriptor_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        pipeline.layout().set_layouts()[0].clone(),
+        [WriteDescriptorSet::buffer(0, data_buffer)],
+    )
+    .expect("failed to create descriptor set");
+
+    let mut render_stats = RenderStats::new();
+    let mut previous_frame_time = Instant::now();
+
+    for _ in 0..FRAME_COUNT {
+        // Recorded every frame, like the command buffers of a render loop whose content changes.
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue_family_index,
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set.clone(),
+            )
+            .dispatch([WORK_GROUP_COUNT, 1, 1])
+            .unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .expect("failed to flush future")
+            .wait(None)
+            .unwrap();
+
+        let this_frame_time = Instant::now();
+        render_stats.record_frame(this_frame_time - previous_frame_time);
+        previous_frame_time = this_frame_time;
+    }
+
+    println!(
+        "{} frames: average {} us, p50 {} us, p95 {} us, p99 {} us",
+        render_stats.frame_count(),
+        render_stats.average_frame_time_us(),
+        render_stats.percentile_frame_time_us(50.0),
+        render_stats.percentile_frame_time_us(95.0),
+        render_stats.p99_frame_time_us(),
+    );
+}
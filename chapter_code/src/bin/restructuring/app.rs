@@ -1,20 +1,80 @@
+use std::time::{Duration, Instant};
+
+use chapter_code::vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR;
+use chapter_code::FpsCounter;
+use winit::event::{ElementState, VirtualKeyCode};
 use winit::event_loop::EventLoop;
 
-use crate::render::RenderLoop;
+use crate::render::{Drawable, RenderLoop, WINDOW_TITLE};
+
+// How long the frame rate shown in the window title is averaged over.
+const FPS_INTERVAL: Duration = Duration::from_millis(500);
+
+// The background colors that C cycles through.
+const CLEAR_COLORS: [[f32; 4]; 4] = [
+    DEFAULT_CLEAR_COLOR,
+    [0.0, 0.0, 0.0, 1.0],
+    [0.1, 0.2, 0.4, 1.0],
+    [0.9, 0.9, 0.9, 1.0],
+];
 
-pub struct App {
-    render_loop: RenderLoop,
+pub struct App<D: Drawable> {
+    render_loop: RenderLoop<D>,
+    fps_counter: FpsCounter,
+    previous_frame_time: Instant,
+    clear_color_i: usize,
+    // Held keys are repeated, but the actions only happen when the keys are first pressed.
+    f11_pressed: bool,
+    c_pressed: bool,
 }
 
-impl App {
+impl<D: Drawable> App<D> {
     pub fn start(event_loop: &EventLoop<()>) -> Self {
+        println!("Press F11 to toggle fullscreen and C to change the background color");
+        println!("Press ESC to quit");
+
         Self {
             render_loop: RenderLoop::new(event_loop),
+            fps_counter: FpsCounter::new(WINDOW_TITLE, FPS_INTERVAL),
+            previous_frame_time: Instant::now(),
+            clear_color_i: 0,
+            f11_pressed: false,
+            c_pressed: false,
         }
     }
 
     pub fn update(&mut self) {
         self.render_loop.update();
+
+        let this_frame_time = Instant::now();
+        let frame_time = this_frame_time - self.previous_frame_time;
+        self.previous_frame_time = this_frame_time;
+
+        if let Some(title) = self.fps_counter.record_frame(frame_time) {
+            self.render_loop.set_title(title);
+        }
+    }
+
+    pub fn handle_keyboard_input(&mut self, key_code: VirtualKeyCode, state: ElementState) {
+        let pressed = state == ElementState::Pressed;
+
+        match key_code {
+            VirtualKeyCode::F11 => {
+                if pressed && !self.f11_pressed {
+                    self.render_loop.toggle_fullscreen();
+                }
+                self.f11_pressed = pressed;
+            }
+            VirtualKeyCode::C => {
+                if pressed && !self.c_pressed {
+                    self.clear_color_i = (self.clear_color_i + 1) % CLEAR_COLORS.len();
+                    self.render_loop
+                        .set_clear_color(CLEAR_COLORS[self.clear_color_i]);
+                }
+                self.c_pressed = pressed;
+            }
+            _ => {}
+        }
     }
 
     pub fn handle_window_resize(&mut self) {
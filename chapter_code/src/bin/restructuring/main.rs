@@ -1,14 +1,35 @@
 pub mod app;
+pub mod models;
 pub mod render;
 
-use winit::event::{Event, WindowEvent};
+use std::env;
+
+use chapter_code::{is_headless, HEADLESS_FRAME_COUNT};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 
 use crate::app::App;
+use crate::models::{Square, Triangle};
+use crate::render::Drawable;
+
+/// Command line flag drawing a square instead of a triangle, with the same renderer.
+const SQUARE_FLAG: &str = "--square";
 
 fn main() {
     let event_loop = EventLoop::new();
-    let mut app = App::start(&event_loop);
+
+    if env::args().skip(1).any(|arg| arg == SQUARE_FLAG) {
+        run::<Square>(event_loop)
+    } else {
+        run::<Triangle>(event_loop)
+    }
+}
+
+fn run<D: Drawable + 'static>(event_loop: EventLoop<()>) -> ! {
+    let mut app = App::<D>::start(&event_loop);
+
+    let headless = is_headless();
+    let mut frame_count = 0;
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
@@ -23,8 +44,37 @@ fn main() {
         } => {
             app.handle_window_resize();
         }
+        // The app has no access to the control flow, so quitting is handled here.
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { input, .. },
+            ..
+        } => {
+            if let Some(key_code) = input.virtual_keycode {
+                app.handle_keyboard_input(key_code, input.state)
+            }
+        }
         Event::MainEventsCleared => {
             app.update();
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
         }
         _ => (),
     });
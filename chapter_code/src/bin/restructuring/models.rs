@@ -0,0 +1,87 @@
+#[cfg(feature = "shader-hot-reload")]
+use std::path::Path;
+use std::sync::Arc;
+
+use chapter_code::shaders::static_triangle;
+use chapter_code::Vertex2d;
+use vulkano::device::Device;
+use vulkano::shader::ShaderModule;
+
+use crate::render::Drawable;
+
+pub struct Triangle;
+
+impl Drawable for Triangle {
+    type Vertex = Vertex2d;
+
+    fn get_vertices() -> Vec<Vertex2d> {
+        vec![
+            Vertex2d {
+                position: [-0.5, -0.5],
+            },
+            Vertex2d {
+                position: [0.0, 0.5],
+            },
+            Vertex2d {
+                position: [0.5, -0.25],
+            },
+        ]
+    }
+
+    fn load_shaders(device: Arc<Device>) -> (Arc<ShaderModule>, Arc<ShaderModule>) {
+        load_static_triangle_shaders(device)
+    }
+
+    #[cfg(feature = "shader-hot-reload")]
+    fn shader_paths() -> (&'static Path, &'static Path) {
+        static_triangle_shader_paths()
+    }
+}
+
+/// Drawn as two triangles, as there are no indices to share the vertices of the diagonal.
+pub struct Square;
+
+impl Drawable for Square {
+    type Vertex = Vertex2d;
+
+    fn get_vertices() -> Vec<Vertex2d> {
+        [
+            [-0.5, -0.5],
+            [0.5, -0.5],
+            [-0.5, 0.5],
+            [0.5, -0.5],
+            [0.5, 0.5],
+            [-0.5, 0.5],
+        ]
+        .into_iter()
+        .map(|position| Vertex2d { position })
+        .collect()
+    }
+
+    fn load_shaders(device: Arc<Device>) -> (Arc<ShaderModule>, Arc<ShaderModule>) {
+        load_static_triangle_shaders(device)
+    }
+
+    #[cfg(feature = "shader-hot-reload")]
+    fn shader_paths() -> (&'static Path, &'static Path) {
+        static_triangle_shader_paths()
+    }
+}
+
+// Despite their name, these draw any shape in a single color.
+fn load_static_triangle_shaders(device: Arc<Device>) -> (Arc<ShaderModule>, Arc<ShaderModule>) {
+    let vertex_shader =
+        static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+    let fragment_shader =
+        static_triangle::fs::load(device).expect("failed to create shader module");
+
+    (vertex_shader, fragment_shader)
+}
+
+#[cfg(feature = "shader-hot-reload")]
+fn static_triangle_shader_paths() -> (&'static Path, &'static Path) {
+    (
+        Path::new(static_triangle::VERTEX_SHADER_PATH),
+        Path::new(static_triangle::FRAGMENT_SHADER_PATH),
+    )
+}
@@ -0,0 +1,21 @@
+#[cfg(feature = "shader-hot-reload")]
+use std::path::Path;
+use std::sync::Arc;
+
+use vulkano::device::Device;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::shader::ShaderModule;
+
+/// A model drawn by `Renderer` as a list of triangles, without indices or uniforms, along with the
+/// shaders drawing it. The vertex shader's inputs must match the fields of `Self::Vertex`.
+pub trait Drawable {
+    type Vertex: Vertex;
+
+    fn get_vertices() -> Vec<Self::Vertex>;
+    /// Returns the vertex and fragment shaders, in that order.
+    fn load_shaders(device: Arc<Device>) -> (Arc<ShaderModule>, Arc<ShaderModule>);
+    /// Returns the paths of the GLSL sources of the shaders, in the same order, which are compiled
+    /// again when they are edited.
+    #[cfg(feature = "shader-hot-reload")]
+    fn shader_paths() -> (&'static Path, &'static Path);
+}
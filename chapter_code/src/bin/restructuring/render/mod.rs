@@ -1,4 +1,7 @@
+mod drawable;
 mod render_loop;
 mod renderer;
 
+pub use drawable::Drawable;
 pub use render_loop::RenderLoop;
+pub use renderer::WINDOW_TITLE;
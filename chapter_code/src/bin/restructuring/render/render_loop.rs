@@ -5,39 +5,74 @@ use vulkano::sync::{FlushError, GpuFuture};
 use winit::event_loop::EventLoop;
 
 use crate::render::renderer::{Fence, Renderer};
+use crate::render::Drawable;
 
-pub struct RenderLoop {
-    renderer: Renderer,
+/// How many frames the CPU can submit before waiting for the GPU to finish the oldest one,
+/// independently of how many images the swapchain has.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+// Vulkano creates and cycles the semaphores of each frame itself, as part of the futures: the
+// acquire future signals one when the image is ready, and presenting waits for the one signaled
+// when the command buffer is done. Only the fences need to be kept here.
+pub struct RenderLoop<D: Drawable> {
+    renderer: Renderer<D>,
     recreate_swapchain: bool,
     window_resized: bool,
-    fences: Vec<Option<Arc<Fence>>>,
-    previous_fence_i: u32,
+    // The fence of the last submission of each frame in flight, waited for before reusing it.
+    frame_fences: Vec<Option<Arc<Fence>>>,
+    // The fence of the last frame that rendered to each swapchain image. The command buffers are
+    // recorded once for each image, and one can't be submitted again until its last submission is
+    // done, which the frame fence doesn't guarantee when there are more images than frames.
+    image_fences: Vec<Option<Arc<Fence>>>,
+    frame_i: usize,
+    previous_fence: Option<Arc<Fence>>,
 }
 
-impl RenderLoop {
+impl<D: Drawable> RenderLoop<D> {
     pub fn new(event_loop: &EventLoop<()>) -> Self {
         let renderer = Renderer::initialize(event_loop);
-        let frames_in_flight = renderer.get_image_count();
-        let fences: Vec<Option<Arc<Fence>>> = vec![None; frames_in_flight];
+        let image_count = renderer.get_image_count();
 
         Self {
             renderer,
             recreate_swapchain: false,
             window_resized: false,
-            fences,
-            previous_fence_i: 0,
+            frame_fences: vec![None; MAX_FRAMES_IN_FLIGHT],
+            image_fences: vec![None; image_count],
+            frame_i: 0,
+            previous_fence: None,
         }
     }
 
     pub fn update(&mut self) {
-        if self.window_resized {
-            self.window_resized = false;
-            self.recreate_swapchain = false;
-            self.renderer.handle_window_resize();
+        // Nothing is rendered until the window is restored. The pending resize is kept, so that the
+        // swapchain is recreated then.
+        if self.renderer.is_minimized() {
+            return;
         }
-        if self.recreate_swapchain {
-            self.recreate_swapchain = false;
-            self.renderer.recreate_swapchain();
+
+        // However many resize events there were since the last frame, the swapchain is recreated
+        // only once, for the current size of the window. If that size isn't supported (yet), it is
+        // tried again next frame.
+        if self.window_resized || self.recreate_swapchain {
+            let recreated = if self.window_resized {
+                self.renderer.handle_window_resize()
+            } else {
+                self.renderer.recreate_swapchain()
+            };
+
+            if recreated {
+                self.window_resized = false;
+                self.recreate_swapchain = false;
+                self.reset_image_fences();
+            }
+        }
+
+        #[cfg(feature = "shader-hot-reload")]
+        self.renderer.reload_shaders();
+
+        if let Some(frame_fence) = &self.frame_fences[self.frame_i] {
+            frame_fence.wait(None).unwrap();
         }
 
         let (image_i, suboptimal, acquire_future) = match self.renderer.acquire_swapchain_image() {
@@ -53,14 +88,14 @@ impl RenderLoop {
             self.recreate_swapchain = true;
         }
 
-        if let Some(image_fence) = &self.fences[image_i as usize] {
+        if let Some(image_fence) = &self.image_fences[image_i as usize] {
             image_fence.wait(None).unwrap();
         }
 
         // logic that uses the GPU resources that are currently not used (have been waited upon)
 
         let something_needs_all_gpu_resources = false;
-        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+        let previous_future = match self.previous_fence.clone() {
             None => self.renderer.synchronize().boxed(),
             Some(fence) => {
                 if something_needs_all_gpu_resources {
@@ -78,7 +113,7 @@ impl RenderLoop {
             .renderer
             .flush_next_future(previous_future, acquire_future, image_i);
 
-        self.fences[image_i as usize] = match result {
+        let fence = match result {
             Ok(fence) => Some(Arc::new(fence)),
             Err(FlushError::OutOfDate) => {
                 self.recreate_swapchain = true;
@@ -90,9 +125,31 @@ impl RenderLoop {
             }
         };
 
-        self.previous_fence_i = image_i;
+        self.frame_fences[self.frame_i] = fence.clone();
+        self.image_fences[image_i as usize] = fence.clone();
+        self.previous_fence = fence;
+        self.frame_i = (self.frame_i + 1) % MAX_FRAMES_IN_FLIGHT;
+    }
+
+    // The new swapchain may not have as many images as the old one.
+    fn reset_image_fences(&mut self) {
+        self.image_fences = vec![None; self.renderer.get_image_count()];
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.renderer.set_clear_color(clear_color);
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        self.renderer.toggle_fullscreen();
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.renderer.set_title(title);
     }
 
+    /// Only marks the swapchain as needing to be recreated, which the next `update` does, so that
+    /// the resize events sent while dragging the edge of the window don't each recreate it.
     pub fn handle_window_resize(&mut self) {
         // impacts the next update
         self.window_resized = true;
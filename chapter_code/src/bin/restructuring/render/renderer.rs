@@ -1,18 +1,21 @@
+use std::env;
 use std::sync::Arc;
+use std::time::Instant;
 
-use chapter_code::shaders::static_triangle;
+use chapter_code::vulkano_objects;
 use chapter_code::vulkano_objects::allocators::Allocators;
-use chapter_code::{vulkano_objects, Vertex2d};
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use chapter_code::vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR;
+#[cfg(feature = "shader-hot-reload")]
+use chapter_code::ShaderReloader;
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::command_buffer::{CommandBufferExecFuture, PrimaryAutoCommandBuffer};
 use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
 use vulkano::image::SwapchainImage;
+use vulkano::instance::debug::DebugUtilsMessenger;
 use vulkano::instance::Instance;
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
-use vulkano::pipeline::graphics::viewport::Viewport;
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::render_pass::{Framebuffer, RenderPass};
-use vulkano::shader::ShaderModule;
 use vulkano::swapchain::{
     self, AcquireError, PresentFuture, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo,
     SwapchainCreationError, SwapchainPresentInfo,
@@ -23,12 +26,21 @@ use vulkano_win::VkSurfaceBuild;
 use winit::event_loop::EventLoop;
 use winit::window::{Window, WindowBuilder};
 
+use crate::render::Drawable;
+
+pub const WINDOW_TITLE: &str = "Restructuring";
+
+// Saved in the temporary directory, so that it doesn't clutter the current one.
+const PIPELINE_CACHE_FILE: &str = "vulkano-restructuring-pipeline-cache.bin";
+
 pub type Fence = FenceSignalFuture<
     PresentFuture<CommandBufferExecFuture<JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>>>,
 >;
 
-pub struct Renderer {
+/// Draws the `Drawable` `D` in a window.
+pub struct Renderer<D: Drawable> {
     _instance: Arc<Instance>,
+    _debug_messenger: Option<DebugUtilsMessenger>,
     window: Arc<Window>,
     device: Arc<Device>,
     queue: Arc<Queue>,
@@ -37,19 +49,22 @@ pub struct Renderer {
     render_pass: Arc<RenderPass>,
     framebuffers: Vec<Arc<Framebuffer>>,
     allocators: Allocators,
-    vertex_buffer: Subbuffer<[Vertex2d]>,
-    vertex_shader: Arc<ShaderModule>,
-    fragment_shader: Arc<ShaderModule>,
-    viewport: Viewport,
+    vertex_buffer: Subbuffer<[D::Vertex]>,
     pipeline: Arc<GraphicsPipeline>,
     command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    clear_color: [f32; 4],
+    #[cfg(feature = "shader-hot-reload")]
+    shader_reloader: ShaderReloader,
 }
 
-impl Renderer {
+impl<D: Drawable> Renderer<D> {
     pub fn initialize(event_loop: &EventLoop<()>) -> Self {
-        let instance = vulkano_objects::instance::get_instance();
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+        let debug_messenger = vulkano_objects::instance::create_debug_messenger(&instance);
 
         let surface = WindowBuilder::new()
+            .with_title(WINDOW_TITLE)
             .build_vk_surface(event_loop, instance.clone())
             .unwrap();
 
@@ -80,7 +95,10 @@ impl Renderer {
                         queue_family_index,
                         ..Default::default()
                     }],
-                    enabled_extensions: device_extensions,
+                    enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                        &physical_device,
+                        &device_extensions,
+                    ),
                     ..Default::default()
                 },
             )
@@ -90,37 +108,43 @@ impl Renderer {
         let queue = queues.next().unwrap();
 
         let (swapchain, images) =
-            vulkano_objects::swapchain::create_swapchain(&physical_device, device.clone(), surface);
+            vulkano_objects::swapchain::create_swapchain(&physical_device, device.clone(), surface)
+                .expect("failed to create swapchain");
 
         let render_pass =
-            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone());
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
         let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
             &images,
             render_pass.clone(),
         );
 
-        let vertex_shader =
-            static_triangle::vs::load(device.clone()).expect("failed to create shader module");
-        let fragment_shader =
-            static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+        let (vertex_shader, fragment_shader) = D::load_shaders(device.clone());
 
-        let viewport = Viewport {
-            origin: [0.0, 0.0],
-            dimensions: window.inner_size().into(),
-            depth_range: 0.0..1.0,
-        };
+        let pipeline_cache_path = env::temp_dir().join(PIPELINE_CACHE_FILE);
+        let pipeline_cache = vulkano_objects::pipeline_cache::load_pipeline_cache(
+            device.clone(),
+            &pipeline_cache_path,
+        );
 
-        let pipeline = vulkano_objects::pipeline::create_pipeline(
+        let start = Instant::now();
+        let pipeline = vulkano_objects::pipeline::create_pipeline_with_cache::<D::Vertex>(
             device.clone(),
-            vertex_shader.clone(),
-            fragment_shader.clone(),
+            vertex_shader,
+            fragment_shader,
             render_pass.clone(),
-            viewport.clone(),
-        );
+            Some(pipeline_cache.clone()),
+        )
+        .expect("failed to create pipeline");
+        println!("Created the pipeline in {:.2?}", start.elapsed());
+
+        // The pipeline is only created once, so the cache can be saved right away, in case the
+        // example doesn't exit cleanly.
+        vulkano_objects::pipeline_cache::save_pipeline_cache(&pipeline_cache, &pipeline_cache_path);
 
         let allocators = Allocators::new(device.clone());
 
-        let vertex_buffer = create_vertex_buffer(&allocators);
+        let vertex_buffer = create_vertex_buffer(&allocators, D::get_vertices());
 
         let command_buffers = vulkano_objects::command_buffers::create_only_vertex_command_buffers(
             &allocators,
@@ -128,10 +152,18 @@ impl Renderer {
             pipeline.clone(),
             &framebuffers,
             vertex_buffer.clone(),
+            DEFAULT_CLEAR_COLOR,
         );
 
+        #[cfg(feature = "shader-hot-reload")]
+        let shader_reloader = {
+            let (vertex_path, fragment_path) = D::shader_paths();
+            ShaderReloader::new(device.clone(), vertex_path, fragment_path)
+        };
+
         Self {
             _instance: instance,
+            _debug_messenger: debug_messenger,
             window,
             device,
             queue,
@@ -141,21 +173,37 @@ impl Renderer {
             framebuffers,
             allocators,
             vertex_buffer,
-            vertex_shader,
-            fragment_shader,
-            viewport,
             pipeline,
             command_buffers,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            #[cfg(feature = "shader-hot-reload")]
+            shader_reloader,
         }
     }
 
-    pub fn recreate_swapchain(&mut self) {
+    /// Whether the window is minimized, in which case nothing can be rendered to it.
+    pub fn is_minimized(&self) -> bool {
+        let size = self.window.inner_size();
+        size.width == 0 || size.height == 0
+    }
+
+    /// Recreates the swapchain for the current size of the window. Returns `false` if it can't be
+    /// done for that size, like while the window is minimized, in which case the old one is kept.
+    ///
+    /// The old swapchain isn't destroyed right away: the frames in flight still rendering to its
+    /// images hold the last references to it, and it is dropped along with them.
+    pub fn recreate_swapchain(&mut self) -> bool {
+        // A swapchain can't have images of size zero.
+        if self.is_minimized() {
+            return false;
+        }
+
         let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
             image_extent: self.window.inner_size().into(),
             ..self.swapchain.create_info()
         }) {
             Ok(r) => r,
-            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+            Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return false,
             Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
         };
 
@@ -164,27 +212,71 @@ impl Renderer {
             &new_images,
             self.render_pass.clone(),
         );
-    }
-
-    pub fn handle_window_resize(&mut self) {
-        self.recreate_swapchain();
-        self.viewport.dimensions = self.window.inner_size().into();
+        self.images = new_images;
+        self.create_command_buffers();
 
-        self.pipeline = vulkano_objects::pipeline::create_pipeline(
-            self.device.clone(),
-            self.vertex_shader.clone(),
-            self.fragment_shader.clone(),
-            self.render_pass.clone(),
-            self.viewport.clone(),
-        );
+        true
+    }
 
+    // The frames in flight keep the command buffers they were submitted with alive, so these can be
+    // replaced at any time.
+    fn create_command_buffers(&mut self) {
         self.command_buffers = vulkano_objects::command_buffers::create_only_vertex_command_buffers(
             &self.allocators,
             self.queue.clone(),
             self.pipeline.clone(),
             &self.framebuffers,
             self.vertex_buffer.clone(),
+            self.clear_color,
+        );
+    }
+
+    /// Rebuilds the pipeline and the command buffers if the shaders were edited. If they don't
+    /// compile or don't fit the pipeline, the error is printed and the current ones are kept.
+    #[cfg(feature = "shader-hot-reload")]
+    pub fn reload_shaders(&mut self) {
+        let (vertex_shader, fragment_shader) = match self.shader_reloader.reload_if_changed() {
+            Some(shaders) => shaders,
+            None => return,
+        };
+
+        let pipeline = vulkano_objects::pipeline::create_pipeline_with_cache::<D::Vertex>(
+            self.device.clone(),
+            vertex_shader,
+            fragment_shader,
+            self.render_pass.clone(),
+            None,
         );
+
+        match pipeline {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.create_command_buffers();
+            }
+            Err(e) => println!("Warning: {}\nKeeping the previous pipeline", e),
+        }
+    }
+
+    /// Sets the color the window is cleared with before drawing. The command buffers are recorded
+    /// in advance, so they are recorded again with the new color.
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+        self.create_command_buffers();
+    }
+
+    // The pipeline's viewport is dynamic and set from the framebuffers when the command buffers are
+    // recorded, so only the swapchain and what depends on it need to be recreated.
+    /// Same as `recreate_swapchain`.
+    pub fn handle_window_resize(&mut self) -> bool {
+        self.recreate_swapchain()
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        chapter_code::toggle_fullscreen(&self.window);
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.window.set_title(title);
     }
 
     pub fn get_image_count(&self) -> usize {
@@ -225,17 +317,10 @@ impl Renderer {
     }
 }
 
-pub fn create_vertex_buffer(allocators: &Allocators) -> Subbuffer<[Vertex2d]> {
-    let vertex1 = Vertex2d {
-        position: [-0.5, -0.5],
-    };
-    let vertex2 = Vertex2d {
-        position: [0.0, 0.5],
-    };
-    let vertex3 = Vertex2d {
-        position: [0.5, -0.25],
-    };
-
+pub fn create_vertex_buffer<V: BufferContents>(
+    allocators: &Allocators,
+    vertices: Vec<V>,
+) -> Subbuffer<[V]> {
     Buffer::from_iter(
         &allocators.memory,
         BufferCreateInfo {
@@ -246,7 +331,7 @@ pub fn create_vertex_buffer(allocators: &Allocators) -> Subbuffer<[Vertex2d]> {
             usage: MemoryUsage::Upload,
             ..Default::default()
         },
-        vec![vertex1, vertex2, vertex3].into_iter(),
+        vertices,
     )
     .unwrap()
 }
@@ -0,0 +1,286 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Reads past the end of a storage buffer in a compute shader, with the `robust_buffer_access2`
+//! feature of `VK_EXT_robustness2` enabled.
+//!
+//! Without robustness, an out-of-bounds read returns an undefined value, or worse. The core
+//! `robust_buffer_access` feature only guarantees that it returns some value from inside the
+//! buffer or zero. With `robust_buffer_access2`, it's guaranteed to return zero. The shader copies
+//! 16 elements from a buffer of 10, and the last 6 are checked to be zero.
+//!
+//! The bounds checks aren't free, so the example then compares the time taken by a number of
+//! in-bounds dispatches on a device with the feature and on a device without it.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags,
+};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+
+const INPUT_LEN: u32 = 10;
+const READ_COUNT: u32 = 16;
+
+const BENCHMARK_LEN: u32 = 1 << 20;
+const BENCHMARK_DISPATCHES: u32 = 1000;
+
+// Must match the local size of the shader.
+const LOCAL_SIZE: u32 = 64;
+
+mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer Input {
+                uint values[];
+            };
+
+            layout(set = 0, binding = 1) writeonly buffer Output {
+                uint copied[];
+            };
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+
+                // Only the writes are bounds checked: the reads go as far as the output.
+                if (idx < copied.length()) {
+                    copied[idx] = values[idx];
+                }
+            }
+        ",
+    }
+}
+
+fn create_device(
+    physical_device: Arc<PhysicalDevice>,
+    queue_family_index: u32,
+    robust: bool,
+) -> (Arc<Device>, Arc<Queue>) {
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            enabled_extensions: DeviceExtensions {
+                ext_robustness2: robust,
+                ..DeviceExtensions::empty()
+            },
+            // `robust_buffer_access2` can only be enabled along with `robust_buffer_access`.
+            enabled_features: Features {
+                robust_buffer_access: robust,
+                robust_buffer_access2: robust,
+                ..Features::empty()
+            },
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    (device, queues.next().unwrap())
+}
+
+/// Copies the first `output_len` elements of a buffer holding `1..=input_len` with the shader,
+/// `dispatch_count` times. Returns the output and the time taken by the GPU work.
+fn run_copy(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    input_len: u32,
+    output_len: u32,
+    dispatch_count: u32,
+) -> (Vec<u32>, Duration) {
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    // None of the values are zero, so that the zeros of the output can only come from
+    // out-of-bounds reads.
+    let input_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        1..=input_len,
+    )
+    .expect("failed to create buffer");
+
+    let output_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        (0..output_len).map(|_| u32::MAX),
+    )
+    .expect("failed to create buffer");
+
+    let shader = cs::load(device.clone()).expect("failed to create shader module");
+    let pipeline = ComputePipeline::new(
+        device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+    let set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, input_buffer),
+            WriteDescriptorSet::buffer(1, output_buffer.clone()),
+        ],
+    )
+    .unwrap();
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    builder
+        .bind_pipeline_compute(pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            pipeline.layout().clone(),
+            0,
+            set,
+        );
+
+    let work_group_count = (output_len + LOCAL_SIZE - 1) / LOCAL_SIZE;
+    for _ in 0..dispatch_count {
+        builder.dispatch([work_group_count, 1, 1]).unwrap();
+    }
+
+    let command_buffer = builder.build().unwrap();
+
+    // Only the execution is timed, not the recording.
+    let start = Instant::now();
+    let future = sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    future.wait(None).unwrap();
+    let elapsed = start.elapsed();
+
+    let output = output_buffer.read().unwrap().to_vec();
+    (output, elapsed)
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = match instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .find(|p| {
+            p.supported_extensions().ext_robustness2 && p.supported_features().robust_buffer_access2
+        }) {
+        Some(physical_device) => physical_device,
+        None => {
+            println!("No device supports robust_buffer_access2 from VK_EXT_robustness2");
+            return;
+        }
+    };
+
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let (robust_device, robust_queue) =
+        create_device(physical_device.clone(), queue_family_index, true);
+    let (device, queue) = create_device(physical_device, queue_family_index, false);
+
+    let (output, _) = run_copy(
+        robust_device.clone(),
+        robust_queue.clone(),
+        INPUT_LEN,
+        READ_COUNT,
+        1,
+    );
+    println!("Read {READ_COUNT} elements from a buffer of {INPUT_LEN}: {output:?}");
+
+    let (in_bounds, out_of_bounds) = output.split_at(INPUT_LEN as usize);
+    assert!(in_bounds.iter().copied().eq(1..=INPUT_LEN));
+    assert!(out_of_bounds.iter().all(|&value| value == 0));
+
+    // The reads are all in bounds here, so the results are defined on both devices and only the
+    // cost of the bounds checks is measured.
+    let (_, robust_time) = run_copy(
+        robust_device,
+        robust_queue,
+        BENCHMARK_LEN,
+        BENCHMARK_LEN,
+        BENCHMARK_DISPATCHES,
+    );
+    let (_, time) = run_copy(
+        device,
+        queue,
+        BENCHMARK_LEN,
+        BENCHMARK_LEN,
+        BENCHMARK_DISPATCHES,
+    );
+
+    let throughput = |time: Duration| BENCHMARK_DISPATCHES as f64 / time.as_secs_f64();
+    println!(
+        "{BENCHMARK_DISPATCHES} dispatches of {BENCHMARK_LEN} elements:\n  \
+         without robust_buffer_access2: {:?} ({:.0} dispatches/s)\n  \
+         with robust_buffer_access2: {:?} ({:.0} dispatches/s)\n  \
+         overhead: {:.1}%",
+        time,
+        throughput(time),
+        robust_time,
+        throughput(robust_time),
+        (robust_time.as_secs_f64() / time.as_secs_f64() - 1.0) * 100.0,
+    );
+
+    println!("Everything succeeded!");
+}
@@ -0,0 +1,421 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders the triangle of the windowing chapter, and saves a screenshot when F12 is pressed.
+//!
+//! A swapchain image can only be accessed between the moment it's acquired and the moment it's
+//! presented. The screenshot is therefore taken in the frame following the key press: the image
+//! is copied to a buffer right after being rendered, in the same submission, and before being
+//! presented.
+//!
+//! The copy needs the image in the `TransferSrcOptimal` layout, while the presentation needs it in
+//! `PresentSrcKHR`. vulkano tracks the layout of swapchain images: the command buffer transitions
+//! the image to `TransferSrcOptimal` before the copy, and back to `PresentSrcKHR` at its end, with
+//! the barriers needed to wait for the rendering. For that to be valid, the swapchain images must
+//! be created with the `TRANSFER_SRC` usage.
+
+use std::sync::Arc;
+
+use chapter_code::shaders::static_triangle;
+use chapter_code::vulkano_objects::allocators::Allocators;
+use chapter_code::{is_headless, vulkano_objects, Vertex2d, HEADLESS_FRAME_COUNT};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::image::{ImageUsage, SwapchainImage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::RenderPass;
+use vulkano::swapchain::{
+    self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError,
+    SwapchainPresentInfo,
+};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+type Fence = FenceSignalFuture<Box<dyn GpuFuture>>;
+
+struct Renderer {
+    surface: Arc<Surface>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    swapchain: Arc<Swapchain>,
+    images: Vec<Arc<SwapchainImage>>,
+    render_pass: Arc<RenderPass>,
+    allocators: Allocators,
+    vertex_buffer: Subbuffer<[Vertex2d]>,
+    pipeline: Arc<GraphicsPipeline>,
+    command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    fences: Vec<Option<Arc<Fence>>>,
+    previous_fence_i: u32,
+    recreate_swapchain: bool,
+    screenshot_requested: bool,
+}
+
+impl Renderer {
+    fn new(event_loop: &EventLoop<()>) -> Self {
+        let instance =
+            vulkano_objects::instance::get_instance().expect("failed to create instance");
+
+        let surface = WindowBuilder::new()
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (swapchain, images) = create_swapchain(&physical_device, device.clone(), &surface);
+
+        let render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+
+        let allocators = Allocators::new(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                Vertex2d {
+                    position: [-0.5, -0.5],
+                },
+                Vertex2d {
+                    position: [0.0, 0.5],
+                },
+                Vertex2d {
+                    position: [0.5, -0.25],
+                },
+            ],
+        )
+        .unwrap();
+
+        let vertex_shader =
+            static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+        let fragment_shader =
+            static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline = vulkano_objects::pipeline::create_pipeline(
+            device.clone(),
+            vertex_shader,
+            fragment_shader,
+            render_pass.clone(),
+        )
+        .expect("failed to create pipeline");
+
+        let mut renderer = Self {
+            surface,
+            device,
+            queue,
+            swapchain,
+            images: Vec::new(),
+            render_pass,
+            allocators,
+            vertex_buffer,
+            pipeline,
+            command_buffers: Vec::new(),
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            recreate_swapchain: false,
+            screenshot_requested: false,
+        };
+        renderer.create_command_buffers(images);
+
+        renderer
+    }
+
+    fn window(&self) -> Arc<Window> {
+        self.surface
+            .object()
+            .unwrap()
+            .clone()
+            .downcast::<Window>()
+            .unwrap()
+    }
+
+    /// Saves a screenshot of the next frame.
+    fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    fn handle_window_resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    fn create_command_buffers(&mut self, images: Vec<Arc<SwapchainImage>>) {
+        let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+            &images,
+            self.render_pass.clone(),
+        );
+
+        self.command_buffers = vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+            &self.allocators,
+            self.queue.clone(),
+            self.pipeline.clone(),
+            &framebuffers,
+            self.vertex_buffer.clone(),
+            vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+        );
+        self.images = images;
+    }
+
+    fn render(&mut self) {
+        if self.recreate_swapchain {
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: self.window().inner_size().into(),
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.recreate_swapchain = false;
+            self.swapchain = new_swapchain;
+            self.create_command_buffers(new_images);
+        }
+
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+
+        let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
+            None => {
+                let mut now = sync::now(self.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+        };
+
+        let mut future = previous_future
+            .join(acquire_future)
+            .then_execute(
+                self.queue.clone(),
+                self.command_buffers[image_i as usize].clone(),
+            )
+            .unwrap()
+            .boxed();
+
+        let screenshot = if self.screenshot_requested {
+            self.screenshot_requested = false;
+
+            let (copy_command_buffer, buffer) = self.create_screenshot_copy(image_i);
+            future = future
+                .then_execute(self.queue.clone(), copy_command_buffer)
+                .unwrap()
+                .boxed();
+
+            Some(buffer)
+        } else {
+            None
+        };
+
+        let future = future
+            .then_swapchain_present(
+                self.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i),
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => {
+                if let Some(buffer) = screenshot {
+                    value.wait(None).unwrap();
+                    self.save_screenshot(&buffer);
+                }
+
+                Some(Arc::new(value))
+            }
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i;
+    }
+
+    fn create_screenshot_copy(
+        &self,
+        image_i: u32,
+    ) -> (Arc<PrimaryAutoCommandBuffer>, Subbuffer<[u8]>) {
+        vulkano_objects::screenshot::create_screenshot_copy(
+            &self.allocators,
+            &self.queue,
+            self.images[image_i as usize].clone(),
+        )
+    }
+
+    fn save_screenshot(&self, buffer: &Subbuffer<[u8]>) {
+        let path = vulkano_objects::screenshot::save_screenshot(
+            buffer,
+            self.swapchain.image_extent(),
+            self.swapchain.image_format(),
+        );
+
+        println!("Saved {path}");
+    }
+}
+
+fn create_swapchain(
+    physical_device: &Arc<PhysicalDevice>,
+    device: Arc<Device>,
+    surface: &Arc<Surface>,
+) -> (Arc<Swapchain>, Vec<Arc<SwapchainImage>>) {
+    let caps = physical_device
+        .surface_capabilities(surface, Default::default())
+        .expect("failed to get surface capabilities");
+
+    // The screenshot code only handles formats with 8-bit RGBA or BGRA pixels.
+    let (image_format, _) = physical_device
+        .surface_formats(surface, Default::default())
+        .unwrap()
+        .into_iter()
+        .find(|&(format, _)| vulkano_objects::screenshot::supports_screenshots(format))
+        .expect("no 8-bit RGBA or BGRA surface format");
+
+    let window = surface
+        .object()
+        .unwrap()
+        .clone()
+        .downcast::<Window>()
+        .unwrap();
+
+    Swapchain::new(
+        device,
+        surface.clone(),
+        SwapchainCreateInfo {
+            min_image_count: caps.min_image_count,
+            image_format: Some(image_format),
+            image_extent: window.inner_size().into(),
+            // `TRANSFER_SRC` is what allows copying the images for screenshots.
+            image_usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+            composite_alpha: caps.supported_composite_alpha.into_iter().next().unwrap(),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let mut renderer = Renderer::new(&event_loop);
+
+    // Holding the key down sends repeated presses, only the first one takes a screenshot.
+    let mut screenshot_key_down = false;
+
+    // Without anyone to press F12, the first frame is saved.
+    let headless = is_headless();
+    if headless {
+        renderer.request_screenshot();
+    }
+    let mut frame_count = 0;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        } => {
+            renderer.handle_window_resize();
+        }
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::F12),
+                            state,
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => match state {
+            ElementState::Pressed if !screenshot_key_down => {
+                screenshot_key_down = true;
+                renderer.request_screenshot();
+            }
+            ElementState::Pressed => {}
+            ElementState::Released => screenshot_key_down = false,
+        },
+        Event::MainEventsCleared => {
+            renderer.render();
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        _ => (),
+    });
+}
@@ -0,0 +1,351 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Shares a semaphore between two processes with `VK_KHR_external_semaphore`.
+//!
+//! The parent process fills a buffer on the GPU, writes the result to a file that plays the role
+//! of shared memory, exports a semaphore as a file descriptor (Linux) or a `HANDLE` (Windows) and
+//! starts a child process that inherits it. The parent then signals the semaphore, and the child
+//! imports it, waits on it on its own queue and checks that the shared file was populated.
+//!
+//! Opaque handles can only be imported by the same driver on the same physical device, which is
+//! why the child picks the device the same way the parent does.
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs::File;
+    use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+    use vulkano::device::DeviceExtensions;
+    use vulkano::sync::semaphore::{
+        ExternalSemaphoreHandleType, ExternalSemaphoreHandleTypes, ImportSemaphoreFdInfo, Semaphore,
+    };
+
+    pub const HANDLE_TYPE: ExternalSemaphoreHandleType = ExternalSemaphoreHandleType::OpaqueFd;
+    pub const HANDLE_TYPES: ExternalSemaphoreHandleTypes = ExternalSemaphoreHandleTypes::OPAQUE_FD;
+
+    pub fn device_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            khr_external_semaphore: true,
+            khr_external_semaphore_fd: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    /// Exports the semaphore and makes the file descriptor inheritable by child processes.
+    /// Returns the file descriptor number to pass to the child.
+    pub fn export(semaphore: &Semaphore) -> String {
+        let file = unsafe { semaphore.export_fd(HANDLE_TYPE) }.expect("failed to export semaphore");
+        let fd = file.into_raw_fd();
+
+        // Drivers open the file descriptor with `O_CLOEXEC`, which would close it in the child.
+        let result = unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        assert_ne!(result, -1, "failed to clear FD_CLOEXEC");
+
+        fd.to_string()
+    }
+
+    pub fn import(semaphore: &Semaphore, handle: &str) {
+        let fd = handle.parse().expect("invalid file descriptor");
+        let file = unsafe { File::from_raw_fd(fd) };
+
+        unsafe {
+            semaphore.import_fd(ImportSemaphoreFdInfo {
+                file: Some(file),
+                ..ImportSemaphoreFdInfo::handle_type(HANDLE_TYPE)
+            })
+        }
+        .expect("failed to import semaphore");
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use vulkano::device::DeviceExtensions;
+    use vulkano::sync::semaphore::{
+        ExternalSemaphoreHandleType, ExternalSemaphoreHandleTypes, ImportSemaphoreWin32HandleInfo,
+        Semaphore,
+    };
+    use windows_sys::Win32::Foundation::{SetHandleInformation, HANDLE_FLAG_INHERIT};
+
+    pub const HANDLE_TYPE: ExternalSemaphoreHandleType = ExternalSemaphoreHandleType::OpaqueWin32;
+    pub const HANDLE_TYPES: ExternalSemaphoreHandleTypes =
+        ExternalSemaphoreHandleTypes::OPAQUE_WIN32;
+
+    pub fn device_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            khr_external_semaphore: true,
+            khr_external_semaphore_win32: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    /// Exports the semaphore and makes the handle inheritable by child processes.
+    /// Returns the handle value to pass to the child.
+    pub fn export(semaphore: &Semaphore) -> String {
+        let handle = unsafe { semaphore.export_win32_handle(HANDLE_TYPE) }
+            .expect("failed to export semaphore");
+
+        let result =
+            unsafe { SetHandleInformation(handle as _, HANDLE_FLAG_INHERIT, HANDLE_FLAG_INHERIT) };
+        assert_ne!(result, 0, "failed to make the handle inheritable");
+
+        (handle as usize).to_string()
+    }
+
+    pub fn import(semaphore: &Semaphore, handle: &str) {
+        let handle: usize = handle.parse().expect("invalid handle");
+
+        unsafe {
+            semaphore.import_win32_handle(ImportSemaphoreWin32HandleInfo {
+                handle: handle as _,
+                ..ImportSemaphoreWin32HandleInfo::handle_type(HANDLE_TYPE)
+            })
+        }
+        .expect("failed to import semaphore");
+    }
+}
+
+#[cfg(any(target_os = "linux", windows))]
+mod example {
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    use std::sync::Arc;
+
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+    use vulkano::command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, SemaphoreSubmitInfo, SubmitInfo,
+    };
+    use vulkano::device::{Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags};
+    use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+    use vulkano::sync::fence::{Fence, FenceCreateInfo};
+    use vulkano::sync::semaphore::{ExternalSemaphoreInfo, Semaphore, SemaphoreCreateInfo};
+    use vulkano::sync::{self, GpuFuture};
+
+    use super::platform;
+
+    pub const CHILD_ARG: &str = "--child";
+    const FILL_VALUE: u32 = 0xC0FFEE;
+    const ELEMENT_COUNT: u64 = 1024;
+
+    fn shared_file_path() -> PathBuf {
+        env::temp_dir().join("vulkano_semaphore_sharing.bin")
+    }
+
+    fn create_device() -> (Arc<Device>, Arc<Queue>) {
+        let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                enabled_extensions: InstanceExtensions {
+                    khr_external_semaphore_capabilities: true,
+                    khr_get_physical_device_properties2: true,
+                    ..InstanceExtensions::empty()
+                },
+                ..Default::default()
+            },
+        )
+        .expect("failed to create instance");
+
+        let device_extensions = platform::device_extensions();
+
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .find(|p| p.supported_extensions().contains(&device_extensions))
+            .expect("no device supports exporting semaphores");
+
+        let properties = physical_device
+            .external_semaphore_properties(ExternalSemaphoreInfo::handle_type(
+                platform::HANDLE_TYPE,
+            ))
+            .unwrap();
+        assert!(
+            properties.exportable && properties.importable,
+            "{:?} semaphores can't be shared on this device",
+            platform::HANDLE_TYPE
+        );
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .enumerate()
+            .position(|(_, q)| q.queue_flags.contains(QueueFlags::GRAPHICS))
+            .expect("couldn't find a graphical queue family")
+            as u32;
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: device_extensions,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        (device, queues.next().unwrap())
+    }
+
+    /// Submits a batch without command buffers that only waits on or signals `semaphore`, and waits
+    /// for it to complete.
+    fn submit_semaphore_operation(
+        device: Arc<Device>,
+        queue: &Arc<Queue>,
+        semaphore: Arc<Semaphore>,
+        wait: bool,
+    ) {
+        let fence = Arc::new(Fence::new(device, FenceCreateInfo::default()).unwrap());
+
+        let semaphores = vec![SemaphoreSubmitInfo::semaphore(semaphore)];
+        let submit_info = if wait {
+            SubmitInfo {
+                wait_semaphores: semaphores,
+                ..Default::default()
+            }
+        } else {
+            SubmitInfo {
+                signal_semaphores: semaphores,
+                ..Default::default()
+            }
+        };
+
+        queue
+            .with(|mut q| unsafe { q.submit_unchecked([submit_info], Some(fence.clone())) })
+            .unwrap();
+
+        fence.wait(None).unwrap();
+    }
+
+    pub fn parent() {
+        let (device, queue) = create_device();
+
+        // The "render": fill a buffer on the GPU and read it back.
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+        let buffer = Buffer::new_slice::<u32>(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            ELEMENT_COUNT,
+        )
+        .unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder.fill_buffer(buffer.clone(), FILL_VALUE).unwrap();
+        let command_buffer = builder.build().unwrap();
+
+        sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let bytes: Vec<u8> = buffer
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        fs::write(shared_file_path(), bytes).expect("failed to write the shared file");
+
+        let semaphore = Arc::new(
+            Semaphore::new(
+                device.clone(),
+                SemaphoreCreateInfo {
+                    export_handle_types: platform::HANDLE_TYPES,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        );
+        let handle = platform::export(&semaphore);
+
+        let mut child = Command::new(env::current_exe().unwrap())
+            .arg(CHILD_ARG)
+            .arg(handle)
+            .stdin(Stdio::piped())
+            .spawn()
+            .expect("failed to start the child process");
+
+        // A binary semaphore must have its signal operation submitted before a wait on it is
+        // submitted, so the child only proceeds once it reads from the pipe.
+        submit_semaphore_operation(device, &queue, semaphore, false);
+        child.stdin.take().unwrap().write_all(b"signaled").unwrap();
+
+        let status = child.wait().unwrap();
+        let _ = fs::remove_file(shared_file_path());
+        assert!(status.success(), "the child process failed");
+
+        println!("Everything succeeded!");
+    }
+
+    pub fn child(handle: &str) {
+        let (device, queue) = create_device();
+
+        let semaphore =
+            Arc::new(Semaphore::new(device.clone(), SemaphoreCreateInfo::default()).unwrap());
+        platform::import(&semaphore, handle);
+
+        let mut message = String::new();
+        std::io::stdin().read_to_string(&mut message).unwrap();
+        assert_eq!(message, "signaled");
+
+        submit_semaphore_operation(device, &queue, semaphore, true);
+
+        let bytes = fs::read(shared_file_path()).expect("failed to read the shared file");
+        assert_eq!(bytes.len() as u64, ELEMENT_COUNT * 4);
+        for value in bytes.chunks_exact(4) {
+            assert_eq!(
+                u32::from_ne_bytes([value[0], value[1], value[2], value[3]]),
+                FILL_VALUE
+            );
+        }
+
+        println!("Child: semaphore waited on and shared data verified");
+    }
+}
+
+#[cfg(any(target_os = "linux", windows))]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(String::as_str) {
+        Some(example::CHILD_ARG) => example::child(args.get(2).expect("missing semaphore handle")),
+        _ => example::parent(),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn main() {
+    println!("This example is only available on Linux and Windows");
+}
@@ -0,0 +1,346 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Removes the elements of an array that don't pass a predicate (stream compaction), with two
+//! compute passes:
+//!
+//! 1. The first pass writes a mask (1 if the element passes the predicate, 0 otherwise) and the
+//!    exclusive prefix sum of the mask, which is the index of each surviving element in the
+//!    output. It runs as a single work group: each invocation handles a contiguous chunk of the
+//!    array, and the sums of the chunks are scanned in shared memory. The total is the number of
+//!    surviving elements.
+//! 2. The second pass scatters the surviving elements to the index given by the prefix sum.
+//!
+//! The result is checked against the same compaction done on the CPU.
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+
+const ELEMENT_COUNT: u32 = 65536;
+
+// Must match the local sizes of the shaders.
+const SCATTER_LOCAL_SIZE: u32 = 64;
+
+mod scan_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            // Every device supports work groups of at least 128 invocations.
+            const uint INVOCATIONS = 128;
+
+            layout(local_size_x = INVOCATIONS, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer Input {
+                uint values[];
+            };
+
+            layout(set = 0, binding = 1) writeonly buffer Mask {
+                uint mask[];
+            };
+
+            layout(set = 0, binding = 2) writeonly buffer PrefixSum {
+                uint prefix_sum[];
+            };
+
+            layout(set = 0, binding = 3) writeonly buffer Count {
+                uint count;
+            };
+
+            shared uint chunk_sums[INVOCATIONS];
+
+            bool predicate(uint value) {
+                return value % 3u == 0u;
+            }
+
+            void main() {
+                uint id = gl_LocalInvocationID.x;
+                uint chunk_size = (values.length() + INVOCATIONS - 1) / INVOCATIONS;
+                uint chunk_start = id * chunk_size;
+                uint chunk_end = min(chunk_start + chunk_size, values.length());
+
+                uint chunk_sum = 0;
+                for (uint i = chunk_start; i < chunk_end; i++) {
+                    chunk_sum += predicate(values[i]) ? 1u : 0u;
+                }
+
+                // Inclusive scan of the sums of the chunks (Hillis-Steele).
+                chunk_sums[id] = chunk_sum;
+                barrier();
+                for (uint offset = 1; offset < INVOCATIONS; offset *= 2) {
+                    uint value = id >= offset ? chunk_sums[id - offset] : 0u;
+                    barrier();
+                    chunk_sums[id] += value;
+                    barrier();
+                }
+
+                if (id == INVOCATIONS - 1) {
+                    count = chunk_sums[id];
+                }
+
+                // Exclusive scan of the mask inside the chunk, starting at the sum of the
+                // previous chunks.
+                uint sum = chunk_sums[id] - chunk_sum;
+                for (uint i = chunk_start; i < chunk_end; i++) {
+                    uint passes = predicate(values[i]) ? 1u : 0u;
+                    mask[i] = passes;
+                    prefix_sum[i] = sum;
+                    sum += passes;
+                }
+            }
+        ",
+    }
+}
+
+mod scatter_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer Input {
+                uint values[];
+            };
+
+            layout(set = 0, binding = 1) readonly buffer Mask {
+                uint mask[];
+            };
+
+            layout(set = 0, binding = 2) readonly buffer PrefixSum {
+                uint prefix_sum[];
+            };
+
+            layout(set = 0, binding = 3) writeonly buffer Output {
+                uint compacted[];
+            };
+
+            void main() {
+                uint idx = gl_GlobalInvocationID.x;
+                if (idx < values.length() && mask[idx] == 1) {
+                    compacted[prefix_sum[idx]] = values[idx];
+                }
+            }
+        ",
+    }
+}
+
+/// Must be the same predicate as in `scan_cs`.
+fn predicate(value: u32) -> bool {
+    value % 3 == 0
+}
+
+fn main() {
+    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+    let instance =
+        Instance::new(library, InstanceCreateInfo::default()).expect("failed to create instance");
+
+    let physical_device = instance
+        .enumerate_physical_devices()
+        .expect("could not enumerate devices")
+        .next()
+        .expect("no devices available");
+
+    let queue_family_index = physical_device
+        .queue_family_properties()
+        .iter()
+        .enumerate()
+        .position(|(_, q)| q.queue_flags.contains(QueueFlags::COMPUTE))
+        .expect("couldn't find a compute queue family") as u32;
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .expect("failed to create device");
+
+    let queue = queues.next().unwrap();
+
+    let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+    let input_buffer = Buffer::from_iter(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+        0..ELEMENT_COUNT,
+    )
+    .expect("failed to create buffer");
+
+    let create_device_buffer = || {
+        Buffer::new_slice::<u32>(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::DeviceOnly,
+                ..Default::default()
+            },
+            ELEMENT_COUNT as u64,
+        )
+        .expect("failed to create buffer")
+    };
+    let mask_buffer = create_device_buffer();
+    let prefix_sum_buffer = create_device_buffer();
+
+    // The output is as large as the input, as all of the elements could pass.
+    let output_buffer = Buffer::new_slice::<u32>(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        ELEMENT_COUNT as u64,
+    )
+    .expect("failed to create buffer");
+
+    let count_buffer = Buffer::from_data(
+        &memory_allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        0u32,
+    )
+    .expect("failed to create buffer");
+
+    let scan_shader = scan_cs::load(device.clone()).expect("failed to create shader module");
+    let scan_pipeline = ComputePipeline::new(
+        device.clone(),
+        scan_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let scatter_shader = scatter_cs::load(device.clone()).expect("failed to create shader module");
+    let scatter_pipeline = ComputePipeline::new(
+        device.clone(),
+        scatter_shader.entry_point("main").unwrap(),
+        &(),
+        None,
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+
+    let scan_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        scan_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, input_buffer.clone()),
+            WriteDescriptorSet::buffer(1, mask_buffer.clone()),
+            WriteDescriptorSet::buffer(2, prefix_sum_buffer.clone()),
+            WriteDescriptorSet::buffer(3, count_buffer.clone()),
+        ],
+    )
+    .unwrap();
+
+    let scatter_set = PersistentDescriptorSet::new(
+        &descriptor_set_allocator,
+        scatter_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::buffer(0, input_buffer.clone()),
+            WriteDescriptorSet::buffer(1, mask_buffer),
+            WriteDescriptorSet::buffer(2, prefix_sum_buffer),
+            WriteDescriptorSet::buffer(3, output_buffer.clone()),
+        ],
+    )
+    .unwrap();
+
+    let command_buffer_allocator =
+        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    // vulkano inserts the barrier between the two passes.
+    builder
+        .bind_pipeline_compute(scan_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            scan_pipeline.layout().clone(),
+            0,
+            scan_set,
+        )
+        .dispatch([1, 1, 1])
+        .unwrap()
+        .bind_pipeline_compute(scatter_pipeline.clone())
+        .bind_descriptor_sets(
+            PipelineBindPoint::Compute,
+            scatter_pipeline.layout().clone(),
+            0,
+            scatter_set,
+        )
+        .dispatch([ELEMENT_COUNT / SCATTER_LOCAL_SIZE, 1, 1])
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap();
+    future.wait(None).unwrap();
+
+    let expected: Vec<u32> = input_buffer
+        .read()
+        .unwrap()
+        .iter()
+        .copied()
+        .filter(|&value| predicate(value))
+        .collect();
+
+    let count = *count_buffer.read().unwrap() as usize;
+    assert_eq!(count, expected.len());
+
+    let output = output_buffer.read().unwrap();
+    assert_eq!(&output[..count], &expected[..]);
+
+    println!("{count} of {ELEMENT_COUNT} elements passed the predicate");
+    println!("Everything succeeded!");
+}
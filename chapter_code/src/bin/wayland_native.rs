@@ -0,0 +1,526 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Renders the triangle of the windowing chapter in a window created directly with
+//! `wayland-client`, without winit or vulkano-win.
+//!
+//! The `wl_surface` is turned into an `xdg_toplevel` window and given to vulkano with
+//! `Surface::from_wayland`. Unlike X11 or Windows, the size of a Wayland surface is chosen by the
+//! client (its surface capabilities report an undefined extent), so the swapchain takes the size
+//! suggested by the compositor in its configure events, or a default one.
+//!
+//! Instead of rendering as fast as possible, each frame waits for the `wl_surface::frame`
+//! callback of the previous one. The compositor sends it when it's a good time to draw again, and
+//! doesn't send it at all while the window is hidden.
+//!
+//! This example needs the `wayland-native` feature and a Wayland compositor:
+//!
+//! ```bash
+//! cargo run --bin wayland_native --features wayland-native
+//! ```
+
+#[cfg(all(target_os = "linux", feature = "wayland-native"))]
+mod wayland {
+    use std::ffi::c_void;
+
+    use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    use wayland_client::protocol::wl_callback::{self, WlCallback};
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+    use wayland_client::protocol::wl_registry::WlRegistry;
+    use wayland_client::protocol::wl_surface::WlSurface;
+    use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+    use wayland_protocols::xdg::shell::client::xdg_surface::{self, XdgSurface};
+    use wayland_protocols::xdg::shell::client::xdg_toplevel::{self, XdgToplevel};
+    use wayland_protocols::xdg::shell::client::xdg_wm_base::{self, XdgWmBase};
+
+    // Used until the compositor suggests a size.
+    const DEFAULT_SIZE: [u32; 2] = [800, 600];
+
+    #[derive(Default)]
+    struct State {
+        configured: bool,
+        size: Option<[u32; 2]>,
+        resized: bool,
+        frame_done: bool,
+        closed: bool,
+    }
+
+    /// A `wl_surface` shown as an `xdg_toplevel` window, without any toolkit.
+    pub struct Window {
+        connection: Connection,
+        queue: EventQueue<State>,
+        state: State,
+        surface: WlSurface,
+        _xdg_surface: XdgSurface,
+        _toplevel: XdgToplevel,
+    }
+
+    impl Window {
+        /// Connects to the compositor from `WAYLAND_DISPLAY` and opens a window.
+        pub fn new(title: &str) -> Result<Self, String> {
+            let connection = Connection::connect_to_env()
+                .map_err(|err| format!("failed to connect to a Wayland compositor: {}", err))?;
+            let (globals, mut queue) = registry_queue_init::<State>(&connection)
+                .map_err(|err| format!("failed to list the Wayland globals: {}", err))?;
+            let qh = queue.handle();
+
+            let compositor: WlCompositor = globals
+                .bind(&qh, 4..=5, ())
+                .map_err(|err| format!("wl_compositor unavailable: {}", err))?;
+            let wm_base: XdgWmBase = globals
+                .bind(&qh, 1..=1, ())
+                .map_err(|err| format!("xdg_wm_base unavailable: {}", err))?;
+
+            let surface = compositor.create_surface(&qh, ());
+            let xdg_surface = wm_base.get_xdg_surface(&surface, &qh, ());
+            let toplevel = xdg_surface.get_toplevel(&qh, ());
+            toplevel.set_title(title.to_owned());
+
+            // The first commit, without a buffer, asks the compositor for a configure event.
+            surface.commit();
+
+            let mut state = State {
+                frame_done: true,
+                ..Default::default()
+            };
+            while !state.configured {
+                queue
+                    .blocking_dispatch(&mut state)
+                    .map_err(|err| format!("failed to dispatch Wayland events: {}", err))?;
+            }
+            state.resized = false;
+
+            Ok(Window {
+                connection,
+                queue,
+                state,
+                surface,
+                _xdg_surface: xdg_surface,
+                _toplevel: toplevel,
+            })
+        }
+
+        pub fn display_ptr(&self) -> *mut c_void {
+            self.connection.backend().display_ptr().cast()
+        }
+
+        pub fn surface_ptr(&self) -> *mut c_void {
+            self.surface.id().as_ptr().cast()
+        }
+
+        /// The size of the window. Wayland lets the client choose it, unless the compositor asks
+        /// for a specific one.
+        pub fn size(&self) -> [u32; 2] {
+            self.state.size.unwrap_or(DEFAULT_SIZE)
+        }
+
+        /// Returns whether the compositor changed the size since the last call.
+        pub fn take_resized(&mut self) -> bool {
+            std::mem::take(&mut self.state.resized)
+        }
+
+        pub fn is_closed(&self) -> bool {
+            self.state.closed
+        }
+
+        /// Blocks until the compositor is ready for a new frame. While the window is hidden the
+        /// compositor stops sending frame callbacks, so nothing is rendered.
+        pub fn wait_for_frame(&mut self) -> Result<(), String> {
+            while !self.state.frame_done && !self.state.closed {
+                self.queue
+                    .blocking_dispatch(&mut self.state)
+                    .map_err(|err| format!("failed to dispatch Wayland events: {}", err))?;
+            }
+
+            // Also handle the events that arrived in the meantime, such as a resize.
+            self.queue
+                .dispatch_pending(&mut self.state)
+                .map_err(|err| format!("failed to dispatch Wayland events: {}", err))?;
+
+            Ok(())
+        }
+
+        /// Asks to be notified when the compositor wants the next frame. Must be called right
+        /// before presenting, so that the request is part of the commit done by the presentation.
+        pub fn request_frame(&mut self) -> Result<(), String> {
+            self.state.frame_done = false;
+            self.surface.frame(&self.queue.handle(), ());
+            self.connection
+                .flush()
+                .map_err(|err| format!("failed to flush the Wayland connection: {}", err))
+        }
+
+        /// Forgets the last frame request, for when nothing was presented after it.
+        pub fn cancel_frame(&mut self) {
+            self.state.frame_done = true;
+        }
+    }
+
+    impl Dispatch<WlRegistry, GlobalListContents> for State {
+        fn event(
+            _: &mut Self,
+            _: &WlRegistry,
+            _: <WlRegistry as Proxy>::Event,
+            _: &GlobalListContents,
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<XdgWmBase, ()> for State {
+        fn event(
+            _: &mut Self,
+            wm_base: &XdgWmBase,
+            event: xdg_wm_base::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let xdg_wm_base::Event::Ping { serial } = event {
+                wm_base.pong(serial);
+            }
+        }
+    }
+
+    impl Dispatch<XdgSurface, ()> for State {
+        fn event(
+            state: &mut Self,
+            xdg_surface: &XdgSurface,
+            event: xdg_surface::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let xdg_surface::Event::Configure { serial } = event {
+                xdg_surface.ack_configure(serial);
+                state.configured = true;
+            }
+        }
+    }
+
+    impl Dispatch<XdgToplevel, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &XdgToplevel,
+            event: xdg_toplevel::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                // A size of 0 leaves the choice to the client.
+                xdg_toplevel::Event::Configure { width, height, .. } if width > 0 && height > 0 => {
+                    let size = [width as u32, height as u32];
+                    if state.size != Some(size) {
+                        state.size = Some(size);
+                        state.resized = true;
+                    }
+                }
+                xdg_toplevel::Event::Close => state.closed = true,
+                _ => {}
+            }
+        }
+    }
+
+    impl Dispatch<WlCallback, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &WlCallback,
+            event: wl_callback::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let wl_callback::Event::Done { .. } = event {
+                state.frame_done = true;
+            }
+        }
+    }
+
+    wayland_client::delegate_noop!(State: ignore WlCompositor);
+    wayland_client::delegate_noop!(State: ignore WlSurface);
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland-native"))]
+mod example {
+    use std::sync::Arc;
+
+    use chapter_code::shaders::static_triangle;
+    use chapter_code::vulkano_objects::allocators::Allocators;
+    use chapter_code::{vulkano_objects, Vertex2d};
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, QueueCreateInfo};
+    use vulkano::image::ImageUsage;
+    use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+    use vulkano::swapchain::{
+        self, AcquireError, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
+    };
+    use vulkano::sync::future::FenceSignalFuture;
+    use vulkano::sync::{self, FlushError, GpuFuture};
+
+    use crate::wayland::Window;
+
+    pub fn main() {
+        // Declared first so that it's dropped last: the Vulkan surface must be destroyed before
+        // the `wl_surface`.
+        let mut window = match Window::new("vulkano wayland_native") {
+            Ok(window) => window,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+
+        let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                enabled_extensions: InstanceExtensions {
+                    khr_surface: true,
+                    khr_wayland_surface: true,
+                    ..InstanceExtensions::empty()
+                },
+                ..Default::default()
+            },
+        )
+        .expect("failed to create instance");
+
+        let surface = unsafe {
+            Surface::from_wayland(
+                instance.clone(),
+                window.display_ptr(),
+                window.surface_ptr(),
+                None,
+            )
+        }
+        .expect("failed to create a surface from the Wayland surface");
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) =
+            vulkano_objects::physical_device::select_physical_device(
+                &instance,
+                surface.clone(),
+                &device_extensions,
+            );
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: vulkano_objects::physical_device::enabled_device_extensions(
+                    &physical_device,
+                    &device_extensions,
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let (mut swapchain, images) = {
+            let caps = physical_device
+                .surface_capabilities(&surface, Default::default())
+                .expect("failed to get surface capabilities");
+
+            let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+            let image_format = Some(
+                physical_device
+                    .surface_formats(&surface, Default::default())
+                    .unwrap()[0]
+                    .0,
+            );
+
+            Swapchain::new(
+                device.clone(),
+                surface,
+                SwapchainCreateInfo {
+                    min_image_count: caps.min_image_count,
+                    image_format,
+                    // `caps.current_extent` is `None` on Wayland.
+                    image_extent: window.size(),
+                    image_usage: ImageUsage::COLOR_ATTACHMENT,
+                    composite_alpha,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let render_pass =
+            vulkano_objects::render_pass::create_render_pass(device.clone(), swapchain.clone())
+                .expect("failed to create render pass");
+        let framebuffers = vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+            &images,
+            render_pass.clone(),
+        );
+
+        let allocators = Allocators::new(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &allocators.memory,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [
+                Vertex2d {
+                    position: [-0.5, -0.5],
+                },
+                Vertex2d {
+                    position: [0.0, 0.5],
+                },
+                Vertex2d {
+                    position: [0.5, -0.25],
+                },
+            ],
+        )
+        .unwrap();
+
+        let vs = static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+        let fs = static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+
+        let pipeline =
+            vulkano_objects::pipeline::create_pipeline(device.clone(), vs, fs, render_pass.clone())
+                .expect("failed to create pipeline");
+
+        let mut command_buffers =
+            vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+                &allocators,
+                queue.clone(),
+                pipeline.clone(),
+                &framebuffers,
+                vertex_buffer.clone(),
+                vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+            );
+
+        let mut recreate_swapchain = false;
+
+        let frames_in_flight = images.len();
+        let mut fences: Vec<Option<Arc<FenceSignalFuture<_>>>> = vec![None; frames_in_flight];
+        let mut previous_fence_i = 0;
+
+        loop {
+            window.wait_for_frame().unwrap();
+            if window.is_closed() {
+                break;
+            }
+
+            let window_resized = window.take_resized();
+            if window_resized || recreate_swapchain {
+                recreate_swapchain = false;
+
+                let new_dimensions = window.size();
+
+                let (new_swapchain, new_images) = swapchain
+                    .recreate(SwapchainCreateInfo {
+                        image_extent: new_dimensions,
+                        ..swapchain.create_info()
+                    })
+                    .expect("failed to recreate swapchain");
+                swapchain = new_swapchain;
+                let new_framebuffers =
+                    vulkano_objects::swapchain::create_framebuffers_from_swapchain_images(
+                        &new_images,
+                        render_pass.clone(),
+                    );
+
+                command_buffers =
+                    vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+                        &allocators,
+                        queue.clone(),
+                        pipeline.clone(),
+                        &new_framebuffers,
+                        vertex_buffer.clone(),
+                        vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+                    );
+            }
+
+            let (image_i, suboptimal, acquire_future) =
+                match swapchain::acquire_next_image(swapchain.clone(), None) {
+                    Ok(r) => r,
+                    Err(AcquireError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        continue;
+                    }
+                    Err(e) => panic!("failed to acquire next image: {e}"),
+                };
+
+            if suboptimal {
+                recreate_swapchain = true;
+            }
+
+            if let Some(image_fence) = &fences[image_i as usize] {
+                image_fence.wait(None).unwrap();
+            }
+
+            let previous_future = match fences[previous_fence_i as usize].clone() {
+                None => {
+                    let mut now = sync::now(device.clone());
+                    now.cleanup_finished();
+
+                    now.boxed()
+                }
+                Some(fence) => fence.boxed(),
+            };
+
+            // The present below commits the surface, along with this request.
+            window.request_frame().unwrap();
+
+            let future = previous_future
+                .join(acquire_future)
+                .then_execute(queue.clone(), command_buffers[image_i as usize].clone())
+                .unwrap()
+                .then_swapchain_present(
+                    queue.clone(),
+                    SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i),
+                )
+                .then_signal_fence_and_flush();
+
+            fences[image_i as usize] = match future {
+                Ok(value) => Some(Arc::new(value)),
+                Err(FlushError::OutOfDate) => {
+                    recreate_swapchain = true;
+                    window.cancel_frame();
+                    None
+                }
+                Err(e) => {
+                    println!("failed to flush future: {e}");
+                    window.cancel_frame();
+                    None
+                }
+            };
+
+            previous_fence_i = image_i;
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland-native"))]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(all(target_os = "linux", feature = "wayland-native")))]
+fn main() {
+    println!("This example is only available on Linux, with the `wayland-native` feature");
+}
@@ -0,0 +1,781 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Shares an image between vulkano and wgpu in the same process with `VK_KHR_external_memory`.
+//!
+//! vulkano renders the triangle from the "Graphics pipeline" chapter into an image whose memory
+//! is exported as a file descriptor (Linux) or a `HANDLE` (Windows). wgpu, running on its own
+//! Vulkan device, imports that memory into a texture through its `hal::vulkan` backend, samples
+//! it on a fullscreen triangle and applies a tint. The texture never goes through the CPU; only
+//! wgpu's final output is read back and saved to `image.png`.
+//!
+//! This needs the `wgpu-interop` feature:
+//!
+//! ```bash
+//! cargo run --bin wgpu_interop --features wgpu-interop
+//! ```
+//!
+//! Both wgpu and vulkano expose their Vulkan objects as `ash` types, so the `ash` dependency has
+//! to be the version that vulkano uses.
+
+#[cfg(all(feature = "wgpu-interop", target_os = "linux"))]
+mod platform {
+    use std::ffi::CStr;
+    use std::os::unix::io::{IntoRawFd, RawFd};
+
+    use ash::vk;
+    use vulkano::device::DeviceExtensions;
+    use vulkano::memory::{DeviceMemory, ExternalMemoryHandleType, ExternalMemoryHandleTypes};
+
+    pub type Handle = RawFd;
+
+    pub const HANDLE_TYPES: ExternalMemoryHandleTypes = ExternalMemoryHandleTypes::OPAQUE_FD;
+    pub const ASH_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+        vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+
+    pub fn device_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            khr_external_memory: true,
+            khr_external_memory_fd: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    pub fn wgpu_device_extensions() -> [&'static CStr; 2] {
+        [
+            vk::KhrExternalMemoryFn::name(),
+            vk::KhrExternalMemoryFdFn::name(),
+        ]
+    }
+
+    pub fn export(memory: &DeviceMemory) -> Handle {
+        memory
+            .export_fd(ExternalMemoryHandleType::OpaqueFd)
+            .expect("failed to export memory")
+            .into_raw_fd()
+    }
+
+    /// Allocates memory for `image` on the wgpu device, backed by the exported memory. The driver
+    /// takes ownership of the file descriptor.
+    pub unsafe fn import(
+        device: &ash::Device,
+        handle: Handle,
+        image: vk::Image,
+        allocation_size: vk::DeviceSize,
+        memory_type_index: u32,
+    ) -> vk::DeviceMemory {
+        let mut import_info = vk::ImportMemoryFdInfoKHR::builder()
+            .handle_type(ASH_HANDLE_TYPE)
+            .fd(handle);
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder().image(image);
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(allocation_size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+
+        device
+            .allocate_memory(&allocate_info, None)
+            .expect("failed to import memory")
+    }
+}
+
+#[cfg(all(feature = "wgpu-interop", windows))]
+mod platform {
+    use std::ffi::CStr;
+
+    use ash::vk;
+    use vulkano::device::DeviceExtensions;
+    use vulkano::memory::{DeviceMemory, ExternalMemoryHandleTypes};
+    use vulkano::VulkanObject;
+
+    pub type Handle = vk::HANDLE;
+
+    pub const HANDLE_TYPES: ExternalMemoryHandleTypes = ExternalMemoryHandleTypes::OPAQUE_WIN32;
+    pub const ASH_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+        vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+
+    pub fn device_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            khr_external_memory: true,
+            khr_external_memory_win32: true,
+            ..DeviceExtensions::empty()
+        }
+    }
+
+    pub fn wgpu_device_extensions() -> [&'static CStr; 2] {
+        [
+            vk::KhrExternalMemoryFn::name(),
+            vk::KhrExternalMemoryWin32Fn::name(),
+        ]
+    }
+
+    pub fn export(memory: &DeviceMemory) -> Handle {
+        // vulkano doesn't wrap `vkGetMemoryWin32HandleKHR`, so it is called directly.
+        let device = memory.device();
+        let info = vk::MemoryGetWin32HandleInfoKHR::builder()
+            .memory(memory.handle())
+            .handle_type(ASH_HANDLE_TYPE);
+        let mut handle = std::ptr::null_mut();
+
+        unsafe {
+            (device
+                .fns()
+                .khr_external_memory_win32
+                .get_memory_win32_handle_khr)(device.handle(), &*info, &mut handle)
+        }
+        .result()
+        .expect("failed to export memory");
+
+        handle
+    }
+
+    /// Allocates memory for `image` on the wgpu device, backed by the exported memory. Unlike
+    /// file descriptors, the handle stays owned by the application.
+    pub unsafe fn import(
+        device: &ash::Device,
+        handle: Handle,
+        image: vk::Image,
+        allocation_size: vk::DeviceSize,
+        memory_type_index: u32,
+    ) -> vk::DeviceMemory {
+        let mut import_info = vk::ImportMemoryWin32HandleInfoKHR::builder()
+            .handle_type(ASH_HANDLE_TYPE)
+            .handle(handle);
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder().image(image);
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(allocation_size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+
+        device
+            .allocate_memory(&allocate_info, None)
+            .expect("failed to import memory")
+    }
+}
+
+#[cfg(all(feature = "wgpu-interop", any(target_os = "linux", windows)))]
+mod example {
+    use std::sync::Arc;
+
+    use ash::vk;
+    use image::{ImageBuffer, Rgba};
+    use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+    use vulkano::command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+    };
+    use vulkano::device::physical::PhysicalDevice;
+    use vulkano::device::{Device, DeviceCreateInfo, Queue, QueueCreateInfo, QueueFlags};
+    use vulkano::format::Format;
+    use vulkano::image::sys::{Image, ImageCreateInfo, RawImage};
+    use vulkano::image::view::ImageView;
+    use vulkano::image::{ImageDimensions, ImageUsage};
+    use vulkano::instance::{Instance, InstanceCreateInfo};
+    use vulkano::memory::allocator::{
+        AllocationCreateInfo, MemoryAlloc, MemoryUsage, StandardMemoryAllocator,
+    };
+    use vulkano::memory::{
+        DedicatedAllocation, DeviceMemory, MemoryAllocateInfo, MemoryPropertyFlags,
+    };
+    use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+    use vulkano::pipeline::graphics::vertex_input::Vertex;
+    use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+    use vulkano::pipeline::GraphicsPipeline;
+    use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, Subpass};
+    use vulkano::sync::{self, GpuFuture};
+    use wgpu_hal::api::Vulkan;
+
+    use super::platform;
+
+    const WIDTH: u32 = 1024;
+    const HEIGHT: u32 = 1024;
+
+    #[derive(BufferContents, Vertex)]
+    #[repr(C)]
+    struct MyVertex {
+        #[format(R32G32_SFLOAT)]
+        position: [f32; 2],
+    }
+
+    mod vs {
+        vulkano_shaders::shader! {
+            ty: "vertex",
+            src: r"
+                #version 460
+
+                layout(location = 0) in vec2 position;
+
+                void main() {
+                    gl_Position = vec4(position, 0.0, 1.0);
+                }
+            ",
+        }
+    }
+
+    mod fs {
+        vulkano_shaders::shader! {
+            ty: "fragment",
+            src: r"
+                #version 460
+
+                layout(location = 0) out vec4 f_color;
+
+                void main() {
+                    f_color = vec4(1.0, 0.0, 0.0, 1.0);
+                }
+            ",
+        }
+    }
+
+    // Draws a triangle covering the whole target and samples the shared texture with a tint.
+    const TINT_SHADER: &str = r"
+        @group(0) @binding(0) var shared_texture: texture_2d<f32>;
+        @group(0) @binding(1) var shared_sampler: sampler;
+
+        struct VertexOutput {
+            @builtin(position) position: vec4<f32>,
+            @location(0) uv: vec2<f32>,
+        };
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+            let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+            var out: VertexOutput;
+            out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+            // Vulkan's clip space points down, wgpu's points up.
+            out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+            return out;
+        }
+
+        @fragment
+        fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+            let tint = vec4<f32>(1.0, 0.8, 0.2, 1.0);
+            return textureSample(shared_texture, shared_sampler, in.uv) * tint;
+        }
+    ";
+
+    /// The image rendered to by vulkano, along with what wgpu needs to import it.
+    struct SharedImage {
+        image: Arc<Image>,
+        handle: platform::Handle,
+        allocation_size: u64,
+        memory_type_index: u32,
+    }
+
+    // Destroys the wgpu side of the shared image once wgpu is done with the texture.
+    struct ImportedImage {
+        device: ash::Device,
+        image: vk::Image,
+        memory: vk::DeviceMemory,
+    }
+
+    impl Drop for ImportedImage {
+        fn drop(&mut self) {
+            unsafe {
+                self.device.destroy_image(self.image, None);
+                self.device.free_memory(self.memory, None);
+            }
+        }
+    }
+
+    fn create_device() -> (Arc<PhysicalDevice>, Arc<Device>, Arc<Queue>) {
+        let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(library, InstanceCreateInfo::default())
+            .expect("failed to create instance");
+
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .find(|p| {
+                p.supported_extensions()
+                    .contains(&platform::device_extensions())
+            })
+            .expect("no device supports exporting memory");
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::GRAPHICS))
+            .expect("couldn't find a graphical queue family")
+            as u32;
+
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                enabled_extensions: platform::device_extensions(),
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        (physical_device, device, queues.next().unwrap())
+    }
+
+    /// Creates an image in exportable memory. The memory is allocated directly instead of going
+    /// through the `StandardMemoryAllocator`, since sharing a block would share the other
+    /// allocations it contains as well.
+    fn create_shared_image(physical_device: &PhysicalDevice, device: &Arc<Device>) -> SharedImage {
+        let raw_image = RawImage::new(
+            device.clone(),
+            ImageCreateInfo {
+                dimensions: ImageDimensions::Dim2d {
+                    width: WIDTH,
+                    height: HEIGHT,
+                    array_layers: 1,
+                },
+                format: Some(Format::R8G8B8A8_UNORM),
+                usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                external_memory_handle_types: platform::HANDLE_TYPES,
+                ..Default::default()
+            },
+        )
+        .expect("failed to create image");
+
+        let requirements = raw_image.memory_requirements()[0];
+        let memory_type_index = physical_device
+            .memory_properties()
+            .memory_types
+            .iter()
+            .enumerate()
+            .position(|(i, memory_type)| {
+                requirements.memory_type_bits & (1 << i) != 0
+                    && memory_type
+                        .property_flags
+                        .intersects(MemoryPropertyFlags::DEVICE_LOCAL)
+            })
+            .expect("no device-local memory type for the image")
+            as u32;
+        let allocation_size = requirements.layout.size();
+
+        let memory = DeviceMemory::allocate(
+            device.clone(),
+            MemoryAllocateInfo {
+                allocation_size,
+                memory_type_index,
+                export_handle_types: platform::HANDLE_TYPES,
+                ..MemoryAllocateInfo::dedicated_allocation(DedicatedAllocation::Image(&raw_image))
+            },
+        )
+        .expect("failed to allocate memory");
+
+        let handle = platform::export(&memory);
+
+        let allocation = MemoryAlloc::new(memory).unwrap();
+        let image = raw_image
+            .bind_memory([allocation])
+            .map_err(|(err, _, _)| err)
+            .expect("failed to bind memory");
+
+        SharedImage {
+            image: Arc::new(image),
+            handle,
+            allocation_size,
+            memory_type_index,
+        }
+    }
+
+    /// Renders the triangle into the shared image and waits for the GPU to be done.
+    fn render_triangle(device: &Arc<Device>, queue: &Arc<Queue>, image: Arc<Image>) {
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+
+        let vertex_buffer = Buffer::from_iter(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            [[-0.5, -0.5], [0.0, 0.5], [0.5, -0.25]].map(|position| MyVertex { position }),
+        )
+        .unwrap();
+
+        let render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: Format::R8G8B8A8_UNORM,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+        .unwrap();
+
+        let view = ImageView::new_default(image).unwrap();
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![view],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [WIDTH as f32, HEIGHT as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(MyVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .render_pass(Subpass::from(render_pass, 0).unwrap())
+            .build(device.clone())
+            .unwrap();
+
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .bind_pipeline_graphics(pipeline)
+            .bind_vertex_buffers(0, vertex_buffer)
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        // Waiting on the CPU is the simplest way to make wgpu see the finished image. A real
+        // application would export a semaphore as well, see the `semaphore_sharing` example.
+        sync::now(device.clone())
+            .then_execute(queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+    }
+
+    /// Opens a wgpu device on the same physical device as vulkano, with the extensions needed to
+    /// import memory enabled on top of the ones wgpu needs.
+    fn create_wgpu_device(physical_device: &PhysicalDevice) -> (wgpu::Device, wgpu::Queue) {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::VULKAN,
+            ..Default::default()
+        });
+
+        // Opaque handles can only be imported on the same physical device they were exported from.
+        let properties = physical_device.properties();
+        let adapter = instance
+            .enumerate_adapters(wgpu::Backends::VULKAN)
+            .find(|adapter| {
+                let info = adapter.get_info();
+                info.vendor == properties.vendor_id as _ && info.device == properties.device_id as _
+            })
+            .expect("wgpu doesn't see the device used by vulkano");
+
+        let features = wgpu::Features::empty();
+        let open_device = unsafe {
+            adapter.as_hal::<Vulkan, _, _>(|hal_adapter| {
+                let hal_adapter = hal_adapter.unwrap();
+
+                let mut extensions = hal_adapter.required_device_extensions(features);
+                for extension in platform::wgpu_device_extensions() {
+                    if !extensions.contains(&extension) {
+                        extensions.push(extension);
+                    }
+                }
+                let extension_names: Vec<_> = extensions.iter().map(|name| name.as_ptr()).collect();
+                let mut physical_features =
+                    hal_adapter.physical_device_features(&extensions, features);
+
+                // wgpu always uses the first queue of the first queue family.
+                let family_index = 0;
+                let queue_info = vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(family_index)
+                    .queue_priorities(&[1.0])
+                    .build();
+                let create_info = physical_features.add_to_device_create_builder(
+                    vk::DeviceCreateInfo::builder()
+                        .queue_create_infos(std::slice::from_ref(&queue_info))
+                        .enabled_extension_names(&extension_names),
+                );
+
+                let raw_device = hal_adapter
+                    .shared_instance()
+                    .raw_instance()
+                    .create_device(hal_adapter.raw_physical_device(), &create_info, None)
+                    .expect("failed to create wgpu device");
+
+                hal_adapter
+                    .device_from_raw(raw_device, true, &extensions, features, family_index, 0)
+                    .expect("failed to open wgpu device")
+            })
+        };
+
+        unsafe {
+            adapter.create_device_from_hal(
+                open_device,
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features,
+                    limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+        }
+        .expect("failed to create wgpu device")
+    }
+
+    /// Creates a wgpu texture backed by the memory of the shared image.
+    fn import_texture(device: &wgpu::Device, shared: &SharedImage) -> wgpu::Texture {
+        let size = wgpu::Extent3d {
+            width: WIDTH,
+            height: HEIGHT,
+            depth_or_array_layers: 1,
+        };
+
+        let hal_texture = unsafe {
+            device.as_hal::<Vulkan, _, _>(|hal_device| {
+                let raw_device = hal_device.unwrap().raw_device();
+
+                // Must be created with the same parameters as the vulkano image.
+                let mut external_info = vk::ExternalMemoryImageCreateInfo::builder()
+                    .handle_types(platform::ASH_HANDLE_TYPE);
+                let image_info = vk::ImageCreateInfo::builder()
+                    .push_next(&mut external_info)
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(vk::Format::R8G8B8A8_UNORM)
+                    .extent(vk::Extent3D {
+                        width: WIDTH,
+                        height: HEIGHT,
+                        depth: 1,
+                    })
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED);
+                let image = raw_device
+                    .create_image(&image_info, None)
+                    .expect("failed to create wgpu image");
+
+                let memory = platform::import(
+                    raw_device,
+                    shared.handle,
+                    image,
+                    shared.allocation_size,
+                    shared.memory_type_index,
+                );
+                raw_device
+                    .bind_image_memory(image, memory, 0)
+                    .expect("failed to bind imported memory");
+
+                wgpu_hal::vulkan::Device::texture_from_raw(
+                    image,
+                    &wgpu_hal::TextureDescriptor {
+                        label: Some("shared texture"),
+                        size,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        usage: wgpu_hal::TextureUses::RESOURCE,
+                        memory_flags: wgpu_hal::MemoryFlags::empty(),
+                        view_formats: vec![],
+                    },
+                    Some(Box::new(ImportedImage {
+                        device: raw_device.clone(),
+                        image,
+                        memory,
+                    })),
+                )
+            })
+        };
+
+        // wgpu transitions the texture out of the `UNDEFINED` layout the first time it is used.
+        // The Vulkan spec allows that to discard the content; a complete implementation would
+        // acquire the image from `VK_QUEUE_FAMILY_EXTERNAL` first, which wgpu doesn't expose.
+        unsafe {
+            device.create_texture_from_hal::<Vulkan>(
+                hal_texture,
+                &wgpu::TextureDescriptor {
+                    label: Some("shared texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+            )
+        }
+    }
+
+    /// Samples `texture` with a tint into a new texture and returns its content.
+    fn tint(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Vec<u8> {
+        let output = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("output"),
+            size: texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output.create_view(&Default::default());
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("tint"),
+            source: wgpu::ShaderSource::Wgsl(TINT_SHADER.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tint"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            }),
+            multiview: None,
+        });
+
+        let texture_view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&Default::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (WIDTH * HEIGHT * 4) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &output_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            output.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(WIDTH * 4),
+                    rows_per_image: None,
+                },
+            },
+            output.size(),
+        );
+        queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("failed to map buffer")
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        let content = slice.get_mapped_range().to_vec();
+        readback.unmap();
+        content
+    }
+
+    pub fn main() {
+        let (physical_device, device, queue) = create_device();
+
+        let shared = create_shared_image(&physical_device, &device);
+        render_triangle(&device, &queue, shared.image.clone());
+
+        let (wgpu_device, wgpu_queue) = create_wgpu_device(&physical_device);
+        let texture = import_texture(&wgpu_device, &shared);
+        let pixels = tint(&wgpu_device, &wgpu_queue, &texture);
+
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(WIDTH, HEIGHT, pixels).unwrap();
+        image.save("image.png").unwrap();
+
+        println!("Everything succeeded!");
+    }
+}
+
+#[cfg(all(feature = "wgpu-interop", any(target_os = "linux", windows)))]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(all(feature = "wgpu-interop", any(target_os = "linux", windows))))]
+fn main() {
+    println!(
+        "This example is only available on Linux and Windows, with the `wgpu-interop` feature"
+    );
+}
@@ -13,6 +13,7 @@
 
 use std::sync::Arc;
 
+use chapter_code::{is_headless, HEADLESS_FRAME_COUNT};
 use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
 use vulkano::command_buffer::{
@@ -344,6 +345,9 @@ fn main() {
     let mut fences: Vec<Option<Arc<FenceSignalFuture<_>>>> = vec![None; frames_in_flight];
     let mut previous_fence_i = 0;
 
+    let headless = is_headless();
+    let mut frame_count = 0;
+
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             event: WindowEvent::CloseRequested,
@@ -449,6 +453,11 @@ fn main() {
             };
 
             previous_fence_i = image_i;
+
+            frame_count += 1;
+            if headless && frame_count == HEADLESS_FRAME_COUNT {
+                *control_flow = ControlFlow::Exit;
+            }
         }
         _ => (),
     });
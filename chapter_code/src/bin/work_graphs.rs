@@ -0,0 +1,205 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Skeleton of a work graphs example (`VK_AMDX_shader_enqueue`).
+//!
+//! With work graphs, shaders are nodes of a graph, and a node can enqueue work for other nodes
+//! directly on the GPU, with a payload for each invocation. The graph this example is meant to
+//! run has two nodes:
+//!
+//! - a `spawn` compute node, dispatched once by the CPU, which enqueues 16 `process` nodes, each
+//!   with a different payload (its index and an input value);
+//! - a `process` compute node, which reads its payload and writes a result to a storage buffer,
+//!   which the CPU then reads back.
+//!
+//! # Status
+//!
+//! `VK_AMDX_shader_enqueue` is a provisional extension, only exposed by recent AMD drivers, and
+//! is newer than the Vulkan headers of vulkano 0.33 and ash 0.37. The shader compiler used by
+//! vulkano-shaders can't produce node shaders (`SPV_AMDX_shader_enqueue`) either. For now, this
+//! example only checks whether the extension is supported, queries its features and limits with
+//! structures declared here, and checks that the graph above fits in them. Creating the execution
+//! graph pipeline and dispatching it are left for when the bindings are available.
+//!
+//! ```bash
+//! cargo run --bin work_graphs --features work-graphs
+//! ```
+
+#[cfg(feature = "work-graphs")]
+mod example {
+    use std::ffi::c_void;
+    use std::ptr;
+
+    use ash::vk;
+    use vulkano::device::physical::PhysicalDevice;
+    use vulkano::instance::{Instance, InstanceCreateInfo};
+    use vulkano::{Version, VulkanLibrary, VulkanObject};
+
+    const EXTENSION_NAME: &str = "VK_AMDX_shader_enqueue";
+
+    // Not in the Vulkan headers used by ash 0.37.
+    const STRUCTURE_TYPE_SHADER_ENQUEUE_FEATURES: i32 = 1_000_134_000;
+    const STRUCTURE_TYPE_SHADER_ENQUEUE_PROPERTIES: i32 = 1_000_134_001;
+
+    // The graph described at the top of the file.
+    const GRAPH_DEPTH: u32 = 2;
+    const PROCESS_NODE_COUNT: u32 = 16;
+    // The payload of a `process` node: its index and an input value.
+    const PROCESS_PAYLOAD_SIZE: u32 = 2 * std::mem::size_of::<u32>() as u32;
+
+    /// `VkPhysicalDeviceShaderEnqueueFeaturesAMDX`
+    // Some fields are only written by the driver.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ShaderEnqueueFeatures {
+        s_type: vk::StructureType,
+        p_next: *mut c_void,
+        shader_enqueue: vk::Bool32,
+        // Added in a later revision of the extension. Declared so that drivers implementing it
+        // don't write past the end of the structure.
+        shader_mesh_enqueue: vk::Bool32,
+    }
+
+    /// `VkPhysicalDeviceShaderEnqueuePropertiesAMDX`
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ShaderEnqueueProperties {
+        s_type: vk::StructureType,
+        p_next: *mut c_void,
+        max_execution_graph_depth: u32,
+        max_execution_graph_shader_output_nodes: u32,
+        max_execution_graph_shader_payload_size: u32,
+        max_execution_graph_shader_payload_count: u32,
+        execution_graph_dispatch_address_alignment: u32,
+        // Added in a later revision of the extension, like `shader_mesh_enqueue`.
+        max_execution_graph_workgroup_count: [u32; 3],
+        max_execution_graph_workgroups: u32,
+    }
+
+    fn supports_extension(physical_device: &PhysicalDevice) -> bool {
+        physical_device
+            .extension_properties()
+            .iter()
+            .any(|extension| extension.extension_name == EXTENSION_NAME)
+    }
+
+    /// Queries the features and limits of the extension, chained to the core structures as
+    /// vulkano doesn't know about them.
+    fn query_shader_enqueue(
+        physical_device: &PhysicalDevice,
+    ) -> (ShaderEnqueueFeatures, ShaderEnqueueProperties) {
+        let fns = &physical_device.instance().fns().v1_1;
+
+        let mut features = ShaderEnqueueFeatures {
+            s_type: vk::StructureType::from_raw(STRUCTURE_TYPE_SHADER_ENQUEUE_FEATURES),
+            p_next: ptr::null_mut(),
+            shader_enqueue: vk::FALSE,
+            shader_mesh_enqueue: vk::FALSE,
+        };
+        let mut features2 = vk::PhysicalDeviceFeatures2 {
+            p_next: &mut features as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        unsafe { (fns.get_physical_device_features2)(physical_device.handle(), &mut features2) };
+
+        let mut properties = ShaderEnqueueProperties {
+            s_type: vk::StructureType::from_raw(STRUCTURE_TYPE_SHADER_ENQUEUE_PROPERTIES),
+            p_next: ptr::null_mut(),
+            max_execution_graph_depth: 0,
+            max_execution_graph_shader_output_nodes: 0,
+            max_execution_graph_shader_payload_size: 0,
+            max_execution_graph_shader_payload_count: 0,
+            execution_graph_dispatch_address_alignment: 0,
+            max_execution_graph_workgroup_count: [0; 3],
+            max_execution_graph_workgroups: 0,
+        };
+        let mut properties2 = vk::PhysicalDeviceProperties2 {
+            p_next: &mut properties as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        unsafe {
+            (fns.get_physical_device_properties2)(physical_device.handle(), &mut properties2)
+        };
+
+        (features, properties)
+    }
+
+    pub fn main() {
+        let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let instance = Instance::new(library, InstanceCreateInfo::default())
+            .expect("failed to create instance");
+
+        // Chaining extension structures needs `vkGetPhysicalDeviceFeatures2`, part of Vulkan 1.1.
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .filter(|p| p.api_version() >= Version::V1_1 && supports_extension(p))
+            .find(|p| query_shader_enqueue(p).0.shader_enqueue == vk::TRUE);
+
+        let physical_device = match physical_device {
+            Some(p) => p,
+            None => {
+                println!(
+                    "No device supports {EXTENSION_NAME}, work graphs are only available on \
+                     recent AMD drivers"
+                );
+                return;
+            }
+        };
+
+        println!(
+            "Using device: {} (type: {:?})",
+            physical_device.properties().device_name,
+            physical_device.properties().device_type,
+        );
+
+        let (_, properties) = query_shader_enqueue(&physical_device);
+        println!("Max graph depth: {}", properties.max_execution_graph_depth);
+        println!(
+            "Max output nodes per shader: {}",
+            properties.max_execution_graph_shader_output_nodes
+        );
+        println!(
+            "Max payload size: {} bytes",
+            properties.max_execution_graph_shader_payload_size
+        );
+        println!(
+            "Max payloads per shader: {}",
+            properties.max_execution_graph_shader_payload_count
+        );
+        println!(
+            "Dispatch address alignment: {}",
+            properties.execution_graph_dispatch_address_alignment
+        );
+
+        let fits = properties.max_execution_graph_depth >= GRAPH_DEPTH
+            && properties.max_execution_graph_shader_output_nodes >= 1
+            && properties.max_execution_graph_shader_payload_size >= PROCESS_PAYLOAD_SIZE
+            && properties.max_execution_graph_shader_payload_count >= PROCESS_NODE_COUNT;
+        if !fits {
+            println!("The spawn/process graph doesn't fit in the limits of this device");
+            return;
+        }
+
+        println!(
+            "The spawn/process graph fits in the limits of this device, but creating execution \
+             graphs isn't supported by this version of vulkano"
+        );
+    }
+}
+
+#[cfg(feature = "work-graphs")]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(feature = "work-graphs"))]
+fn main() {
+    println!("Work graphs not compiled in");
+}
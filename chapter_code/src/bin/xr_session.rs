@@ -0,0 +1,767 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Skeleton of an OpenXR application rendering with vulkano, as a starting point for VR and AR.
+//!
+//! The OpenXR runtime owns the headset: it decides which GPU to use, which Vulkan extensions must
+//! be enabled, and it hands out the swapchain images the compositor displays. The example goes
+//! through the usual steps of the Vulkan graphics binding (`XR_KHR_vulkan_enable`):
+//!
+//! - create an OpenXR instance and get the head-mounted display system,
+//! - create the Vulkan instance with the extensions the runtime asks for, and the device on the
+//!   physical device it picks,
+//! - create a session from the raw Vulkan handles (`XrGraphicsBindingVulkanKHR`),
+//! - run the frame loop: wait for the frame, begin it, render both eyes and end it with a
+//!   projection layer.
+//!
+//! The rendering is the triangle of the `restructuring` chapter, drawn once per eye with the same
+//! render pass, pipeline and command buffers. Since vulkano can't wrap images it doesn't own, each
+//! eye is rendered offscreen and copied into the OpenXR swapchain with raw Vulkan commands.
+//!
+//! After a few seconds the example asks the runtime to end the session and exits.
+//!
+//! # Requirements
+//!
+//! This example needs the `openxr` feature and an OpenXR loader and runtime (SteamVR, Monado,
+//! the Oculus runtime...). The loader is loaded at runtime, so it only prints a message if none
+//! is installed:
+//!
+//! ```bash
+//! cargo run --bin xr_session --features openxr
+//! ```
+//!
+//! Monado's simulated headset (`XRT_COMPOSITOR_FORCE_XCB=1 monado-service`) can be used without
+//! any hardware.
+
+#[cfg(feature = "openxr")]
+mod example {
+    use std::ptr;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use ash::vk;
+    use chapter_code::shaders::static_triangle;
+    use chapter_code::vulkano_objects::allocators::Allocators;
+    use chapter_code::vulkano_objects::instance::get_instance_with_extensions;
+    use chapter_code::{vulkano_objects, Vertex2d};
+    use openxr as xr;
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+    use vulkano::command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo,
+        PrimaryAutoCommandBuffer,
+    };
+    use vulkano::device::{
+        Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
+    };
+    use vulkano::format::Format;
+    use vulkano::image::view::ImageView;
+    use vulkano::image::{AttachmentImage, ImageUsage};
+    use vulkano::instance::InstanceExtensions;
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+    use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+    use vulkano::sync::{self, GpuFuture};
+    use vulkano::{DeviceSize, Handle, Version, VulkanObject};
+
+    const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+    const VIEW_COUNT: u32 = 2;
+
+    // The session is ended after this many rendered frames, about 5 seconds at 120 Hz.
+    const FRAME_COUNT: u32 = 600;
+
+    // Formats with 4 bytes per texel, so that a rendered eye can be copied to the swapchain
+    // through a buffer.
+    const SUPPORTED_FORMATS: &[Format] = &[Format::R8G8B8A8_SRGB, Format::B8G8R8A8_SRGB];
+
+    /// Creates the OpenXR instance and gets the headset, or returns `None` if there is no
+    /// runtime or no headset.
+    fn create_xr_instance() -> Option<(xr::Instance, xr::SystemId)> {
+        let entry = match unsafe { xr::Entry::load() } {
+            Ok(entry) => entry,
+            Err(err) => {
+                println!("No OpenXR loader found: {}", err);
+                return None;
+            }
+        };
+
+        // The loader needs an active runtime to list the extensions.
+        let available_extensions = match entry.enumerate_extensions() {
+            Ok(extensions) => extensions,
+            Err(err) => {
+                println!("No OpenXR runtime found: {}", err);
+                return None;
+            }
+        };
+
+        if !available_extensions.khr_vulkan_enable {
+            println!("The OpenXR runtime doesn't support Vulkan");
+            return None;
+        }
+
+        let mut enabled_extensions = xr::ExtensionSet::default();
+        enabled_extensions.khr_vulkan_enable = true;
+
+        let xr_instance = entry
+            .create_instance(
+                &xr::ApplicationInfo {
+                    application_name: "vulkano xr_session",
+                    application_version: 0,
+                    engine_name: "vulkano",
+                    engine_version: 0,
+                },
+                &enabled_extensions,
+                &[],
+            )
+            .expect("failed to create OpenXR instance");
+
+        let properties = xr_instance
+            .properties()
+            .expect("failed to get the OpenXR runtime properties");
+        println!(
+            "XR runtime found: {} {}",
+            properties.runtime_name, properties.runtime_version
+        );
+
+        match xr_instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY) {
+            Ok(system) => Some((xr_instance, system)),
+            Err(err) => {
+                println!("No headset available: {}", err);
+                None
+            }
+        }
+    }
+
+    fn create_device(
+        xr_instance: &xr::Instance,
+        system: xr::SystemId,
+    ) -> (Arc<Device>, Arc<Queue>) {
+        // OpenXR requires this call before creating the session.
+        let requirements = xr_instance
+            .graphics_requirements::<xr::Vulkan>(system)
+            .expect("failed to get the Vulkan requirements");
+
+        let instance_extensions = xr_instance
+            .vulkan_legacy_instance_extensions(system)
+            .expect("failed to get the Vulkan instance extensions");
+        let instance = get_instance_with_extensions(InstanceExtensions::from_iter(
+            instance_extensions.split_ascii_whitespace(),
+        ))
+        .expect("failed to create instance");
+
+        let min_version = requirements.min_api_version_supported;
+        assert!(
+            instance.api_version()
+                >= Version::major_minor(min_version.major().into(), min_version.minor().into()),
+            "the OpenXR runtime needs Vulkan {}.{}",
+            min_version.major(),
+            min_version.minor()
+        );
+
+        // The runtime picks the GPU the headset is connected to.
+        let raw_physical_device = xr_instance
+            .vulkan_graphics_device(system, instance.handle().as_raw() as _)
+            .expect("failed to get the Vulkan device of the headset");
+        let physical_device = instance
+            .enumerate_physical_devices()
+            .expect("could not enumerate devices")
+            .find(|p| p.handle().as_raw() == raw_physical_device as u64)
+            .expect("the OpenXR runtime returned an unknown device");
+
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::GRAPHICS))
+            .expect("couldn't find a graphical queue family")
+            as u32;
+
+        let device_extensions = xr_instance
+            .vulkan_legacy_device_extensions(system)
+            .expect("failed to get the Vulkan device extensions");
+
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: DeviceExtensions::from_iter(
+                    device_extensions.split_ascii_whitespace(),
+                ),
+                ..Default::default()
+            },
+        )
+        .expect("failed to create device");
+
+        (device, queues.next().unwrap())
+    }
+
+    fn create_render_pass(device: Arc<Device>, format: Format) -> Arc<RenderPass> {
+        vulkano::single_pass_renderpass!(
+            device,
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                },
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {},
+            },
+        )
+        .unwrap()
+    }
+
+    /// Renders the triangle for both eyes, the same way the `restructuring` chapter's
+    /// `RenderLoop` does for a window, and leaves the pixels of each eye in a buffer.
+    struct EyeRenderer {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        eye_buffers: Vec<Subbuffer<[u8]>>,
+        command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+        copy_command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>>,
+    }
+
+    impl EyeRenderer {
+        fn new(device: Arc<Device>, queue: Arc<Queue>, format: Format, extent: [u32; 2]) -> Self {
+            let allocators = Allocators::new(device.clone());
+
+            let render_pass = create_render_pass(device.clone(), format);
+
+            let images: Vec<_> = (0..VIEW_COUNT)
+                .map(|_| {
+                    AttachmentImage::with_usage(
+                        &allocators.memory,
+                        extent,
+                        format,
+                        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+                    )
+                    .unwrap()
+                })
+                .collect();
+
+            let framebuffers: Vec<_> = images
+                .iter()
+                .map(|image| {
+                    Framebuffer::new(
+                        render_pass.clone(),
+                        FramebufferCreateInfo {
+                            attachments: vec![ImageView::new_default(image.clone()).unwrap()],
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap()
+                })
+                .collect();
+
+            let vertex_shader =
+                static_triangle::vs::load(device.clone()).expect("failed to create shader module");
+            let fragment_shader =
+                static_triangle::fs::load(device.clone()).expect("failed to create shader module");
+
+            let pipeline = vulkano_objects::pipeline::create_pipeline(
+                device.clone(),
+                vertex_shader,
+                fragment_shader,
+                render_pass,
+            )
+            .expect("failed to create pipeline");
+
+            let vertex_buffer = Buffer::from_iter(
+                &allocators.memory,
+                BufferCreateInfo {
+                    usage: BufferUsage::VERTEX_BUFFER,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    usage: MemoryUsage::Upload,
+                    ..Default::default()
+                },
+                [
+                    Vertex2d {
+                        position: [-0.5, -0.25],
+                    },
+                    Vertex2d {
+                        position: [0.0, 0.5],
+                    },
+                    Vertex2d {
+                        position: [0.25, -0.1],
+                    },
+                ],
+            )
+            .unwrap();
+
+            let command_buffers =
+                vulkano_objects::command_buffers::create_only_vertex_command_buffers(
+                    &allocators,
+                    queue.clone(),
+                    pipeline,
+                    &framebuffers,
+                    vertex_buffer,
+                    vulkano_objects::command_buffers::DEFAULT_CLEAR_COLOR,
+                );
+
+            let eye_buffers: Vec<_> = (0..VIEW_COUNT)
+                .map(|_| {
+                    Buffer::new_slice::<u8>(
+                        &allocators.memory,
+                        BufferCreateInfo {
+                            usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+                            ..Default::default()
+                        },
+                        AllocationCreateInfo {
+                            usage: MemoryUsage::DeviceOnly,
+                            ..Default::default()
+                        },
+                        extent[0] as DeviceSize * extent[1] as DeviceSize * 4,
+                    )
+                    .unwrap()
+                })
+                .collect();
+
+            let copy_command_buffers = images
+                .iter()
+                .zip(&eye_buffers)
+                .map(|(image, buffer)| {
+                    let mut builder = AutoCommandBufferBuilder::primary(
+                        &allocators.command_buffer,
+                        queue.queue_family_index(),
+                        CommandBufferUsage::MultipleSubmit,
+                    )
+                    .unwrap();
+
+                    builder
+                        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                            image.clone(),
+                            buffer.clone(),
+                        ))
+                        .unwrap();
+
+                    Arc::new(builder.build().unwrap())
+                })
+                .collect();
+
+            EyeRenderer {
+                device,
+                queue,
+                eye_buffers,
+                command_buffers,
+                copy_command_buffers,
+            }
+        }
+
+        fn render(&self) {
+            let mut future = sync::now(self.device.clone()).boxed();
+            for (render, copy) in self.command_buffers.iter().zip(&self.copy_command_buffers) {
+                future = future
+                    .then_execute(self.queue.clone(), render.clone())
+                    .unwrap()
+                    .then_execute(self.queue.clone(), copy.clone())
+                    .unwrap()
+                    .boxed();
+            }
+
+            future
+                .then_signal_fence_and_flush()
+                .unwrap()
+                .wait(None)
+                .unwrap();
+        }
+    }
+
+    /// Copies the rendered eyes into the layers of an OpenXR swapchain image.
+    ///
+    /// The swapchain images belong to the runtime, so this uses raw Vulkan commands instead of
+    /// vulkano's command buffers.
+    struct SwapchainCopier {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+        fence: vk::Fence,
+    }
+
+    impl SwapchainCopier {
+        fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+            let fns = &device.fns().v1_0;
+
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(queue.queue_family_index());
+            let mut command_pool = vk::CommandPool::null();
+            unsafe {
+                (fns.create_command_pool)(
+                    device.handle(),
+                    &*pool_info,
+                    ptr::null(),
+                    &mut command_pool,
+                )
+            }
+            .result()
+            .expect("failed to create command pool");
+
+            let allocate_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let mut command_buffer = vk::CommandBuffer::null();
+            unsafe {
+                (fns.allocate_command_buffers)(
+                    device.handle(),
+                    &*allocate_info,
+                    &mut command_buffer,
+                )
+            }
+            .result()
+            .expect("failed to allocate command buffer");
+
+            let fence_info = vk::FenceCreateInfo::builder();
+            let mut fence = vk::Fence::null();
+            unsafe { (fns.create_fence)(device.handle(), &*fence_info, ptr::null(), &mut fence) }
+                .result()
+                .expect("failed to create fence");
+
+            SwapchainCopier {
+                device,
+                queue,
+                command_pool,
+                command_buffer,
+                fence,
+            }
+        }
+
+        fn copy(&self, eye_buffers: &[Subbuffer<[u8]>], image: vk::Image, extent: [u32; 2]) {
+            let fns = &self.device.fns().v1_0;
+            let layers = vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: VIEW_COUNT,
+            };
+
+            unsafe {
+                (fns.reset_command_pool)(
+                    self.device.handle(),
+                    self.command_pool,
+                    vk::CommandPoolResetFlags::empty(),
+                )
+                .result()
+                .unwrap();
+
+                let begin_info = vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+                (fns.begin_command_buffer)(self.command_buffer, &*begin_info)
+                    .result()
+                    .unwrap();
+
+                // Make the copies done by vulkano visible, and discard the previous content of
+                // the swapchain image.
+                let memory_barrier = vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+                let to_transfer = vk::ImageMemoryBarrier::builder()
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image)
+                    .subresource_range(layers);
+                (fns.cmd_pipeline_barrier)(
+                    self.command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    1,
+                    &*memory_barrier,
+                    0,
+                    ptr::null(),
+                    1,
+                    &*to_transfer,
+                );
+
+                for (layer, buffer) in eye_buffers.iter().enumerate() {
+                    let region = vk::BufferImageCopy {
+                        buffer_offset: buffer.offset(),
+                        buffer_row_length: 0,
+                        buffer_image_height: 0,
+                        image_subresource: vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: 0,
+                            base_array_layer: layer as u32,
+                            layer_count: 1,
+                        },
+                        image_offset: vk::Offset3D::default(),
+                        image_extent: vk::Extent3D {
+                            width: extent[0],
+                            height: extent[1],
+                            depth: 1,
+                        },
+                    };
+                    (fns.cmd_copy_buffer_to_image)(
+                        self.command_buffer,
+                        buffer.buffer().handle(),
+                        image,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        1,
+                        &region,
+                    );
+                }
+
+                // OpenXR expects the image to be in the color attachment layout when it's released.
+                let to_attachment = vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                    .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(image)
+                    .subresource_range(layers);
+                (fns.cmd_pipeline_barrier)(
+                    self.command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    vk::DependencyFlags::empty(),
+                    0,
+                    ptr::null(),
+                    0,
+                    ptr::null(),
+                    1,
+                    &*to_attachment,
+                );
+
+                (fns.end_command_buffer)(self.command_buffer)
+                    .result()
+                    .unwrap();
+
+                let command_buffers = [self.command_buffer];
+                let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+                self.queue
+                    .with(|_queue| {
+                        (fns.queue_submit)(self.queue.handle(), 1, &*submit_info, self.fence)
+                    })
+                    .result()
+                    .expect("failed to submit the swapchain copy");
+
+                (fns.wait_for_fences)(self.device.handle(), 1, &self.fence, vk::TRUE, u64::MAX)
+                    .result()
+                    .unwrap();
+                (fns.reset_fences)(self.device.handle(), 1, &self.fence)
+                    .result()
+                    .unwrap();
+            }
+        }
+    }
+
+    impl Drop for SwapchainCopier {
+        fn drop(&mut self) {
+            let fns = &self.device.fns().v1_0;
+            unsafe {
+                (fns.destroy_fence)(self.device.handle(), self.fence, ptr::null());
+                (fns.destroy_command_pool)(self.device.handle(), self.command_pool, ptr::null());
+            }
+        }
+    }
+
+    pub fn main() {
+        let (xr_instance, system) = match create_xr_instance() {
+            Some(xr) => xr,
+            None => {
+                println!("Nothing to render to, exiting");
+                return;
+            }
+        };
+
+        let (device, queue) = create_device(&xr_instance, system);
+
+        // The graphics binding: OpenXR uses the Vulkan objects created by vulkano.
+        let (session, mut frame_waiter, mut frame_stream) = unsafe {
+            xr_instance.create_session::<xr::Vulkan>(
+                system,
+                &xr::vulkan::SessionCreateInfo {
+                    instance: device.instance().handle().as_raw() as _,
+                    physical_device: device.physical_device().handle().as_raw() as _,
+                    device: device.handle().as_raw() as _,
+                    queue_family_index: queue.queue_family_index(),
+                    queue_index: queue.id_within_family(),
+                },
+            )
+        }
+        .expect("failed to create OpenXR session");
+
+        let views = xr_instance
+            .enumerate_view_configuration_views(system, VIEW_TYPE)
+            .unwrap();
+        assert_eq!(views.len(), VIEW_COUNT as usize);
+        let extent = [
+            views[0].recommended_image_rect_width,
+            views[0].recommended_image_rect_height,
+        ];
+
+        let runtime_formats = session.enumerate_swapchain_formats().unwrap();
+        let format = SUPPORTED_FORMATS
+            .iter()
+            .copied()
+            .find(|&format| runtime_formats.contains(&(vk::Format::from(format).as_raw() as u32)))
+            .expect("the OpenXR runtime doesn't support any of the formats used by the example");
+
+        // A single swapchain with one layer per eye.
+        let mut swapchain = session
+            .create_swapchain(&xr::SwapchainCreateInfo {
+                create_flags: xr::SwapchainCreateFlags::EMPTY,
+                usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT
+                    | xr::SwapchainUsageFlags::TRANSFER_DST,
+                format: vk::Format::from(format).as_raw() as u32,
+                sample_count: 1,
+                width: extent[0],
+                height: extent[1],
+                face_count: 1,
+                array_size: VIEW_COUNT,
+                mip_count: 1,
+            })
+            .expect("failed to create OpenXR swapchain");
+        let swapchain_images: Vec<_> = swapchain
+            .enumerate_images()
+            .unwrap()
+            .into_iter()
+            .map(vk::Image::from_raw)
+            .collect();
+
+        let space = session
+            .create_reference_space(xr::ReferenceSpaceType::LOCAL, xr::Posef::IDENTITY)
+            .unwrap();
+        let blend_mode = xr_instance
+            .enumerate_environment_blend_modes(system, VIEW_TYPE)
+            .unwrap()[0];
+
+        let eye_renderer = EyeRenderer::new(device.clone(), queue.clone(), format, extent);
+        let copier = SwapchainCopier::new(device, queue);
+
+        let mut event_storage = xr::EventDataBuffer::new();
+        let mut session_running = false;
+        let mut frames_rendered = 0;
+
+        'main_loop: loop {
+            while let Some(event) = xr_instance.poll_event(&mut event_storage).unwrap() {
+                match event {
+                    xr::Event::SessionStateChanged(event) => {
+                        println!("Session state: {:?}", event.state());
+                        match event.state() {
+                            xr::SessionState::READY => {
+                                session.begin(VIEW_TYPE).unwrap();
+                                session_running = true;
+                            }
+                            xr::SessionState::STOPPING => {
+                                session.end().unwrap();
+                                session_running = false;
+                            }
+                            xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
+                                break 'main_loop;
+                            }
+                            _ => {}
+                        }
+                    }
+                    xr::Event::InstanceLossPending(_) => break 'main_loop,
+                    _ => {}
+                }
+            }
+
+            if !session_running {
+                // Don't spin while the runtime gets the headset ready.
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+
+            // Blocks until the runtime wants the next frame, this replaces waiting for the
+            // swapchain image in a windowed render loop.
+            let frame_state = frame_waiter.wait().unwrap();
+            frame_stream.begin().unwrap();
+
+            if !frame_state.should_render {
+                frame_stream
+                    .end(frame_state.predicted_display_time, blend_mode, &[])
+                    .unwrap();
+                continue;
+            }
+
+            let image_index = swapchain.acquire_image().unwrap();
+            swapchain.wait_image(xr::Duration::INFINITE).unwrap();
+
+            // Both eyes see the same triangle. A real application would compute a view and
+            // projection matrix for each eye from `views` below.
+            eye_renderer.render();
+            copier.copy(
+                &eye_renderer.eye_buffers,
+                swapchain_images[image_index as usize],
+                extent,
+            );
+
+            swapchain.release_image().unwrap();
+
+            let (_, views) = session
+                .locate_views(VIEW_TYPE, frame_state.predicted_display_time, &space)
+                .unwrap();
+            let rect = xr::Rect2Di {
+                offset: xr::Offset2Di { x: 0, y: 0 },
+                extent: xr::Extent2Di {
+                    width: extent[0] as i32,
+                    height: extent[1] as i32,
+                },
+            };
+            let projection_views: Vec<_> = views
+                .iter()
+                .enumerate()
+                .map(|(layer, view)| {
+                    xr::CompositionLayerProjectionView::new()
+                        .pose(view.pose)
+                        .fov(view.fov)
+                        .sub_image(
+                            xr::SwapchainSubImage::new()
+                                .swapchain(&swapchain)
+                                .image_array_index(layer as u32)
+                                .image_rect(rect),
+                        )
+                })
+                .collect();
+
+            frame_stream
+                .end(
+                    frame_state.predicted_display_time,
+                    blend_mode,
+                    &[&xr::CompositionLayerProjection::new()
+                        .space(&space)
+                        .views(&projection_views)],
+                )
+                .unwrap();
+
+            frames_rendered += 1;
+            if frames_rendered == FRAME_COUNT {
+                // The runtime answers with the STOPPING and EXITING states.
+                session.request_exit().unwrap();
+            }
+        }
+
+        println!("Everything succeeded!");
+    }
+}
+
+#[cfg(feature = "openxr")]
+fn main() {
+    example::main();
+}
+
+#[cfg(not(feature = "openxr"))]
+fn main() {
+    println!("OpenXR support not compiled in, run with `--features openxr`");
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+/// Averages the frame rate over periods of `interval`, for showing it in the window title.
+///
+/// Changing the title goes through the windowing system, so `record_frame` only returns a new title
+/// at the end of each period, and only if it differs from the one shown.
+pub struct FpsCounter {
+    name: String,
+    interval: Duration,
+    elapsed: Duration,
+    frame_count: u32,
+    gpu_elapsed: Duration,
+    gpu_frame_count: u32,
+    title: String,
+}
+
+impl FpsCounter {
+    /// Creates a counter for a window titled `name`, like "Movable Square".
+    pub fn new(name: &str, interval: Duration) -> Self {
+        Self {
+            name: name.to_owned(),
+            interval,
+            elapsed: Duration::ZERO,
+            frame_count: 0,
+            gpu_elapsed: Duration::ZERO,
+            gpu_frame_count: 0,
+            title: name.to_owned(),
+        }
+    }
+
+    /// To be called once per frame with the time since the previous one. Returns the title to set
+    /// when the displayed frame rate changes.
+    pub fn record_frame(&mut self, frame_time: Duration) -> Option<&str> {
+        self.record_frame_with_gpu_time(frame_time, None)
+    }
+
+    /// Same as `record_frame`, but the title also shows the average of the `gpu_time`s, which are
+    /// the times the GPU took to render the frames, as measured by a `GpuTimer`. Frames without
+    /// one, for example while the GPU is still rendering them, are left out of the average.
+    pub fn record_frame_with_gpu_time(
+        &mut self,
+        frame_time: Duration,
+        gpu_time: Option<Duration>,
+    ) -> Option<&str> {
+        self.elapsed += frame_time;
+        self.frame_count += 1;
+        if let Some(gpu_time) = gpu_time {
+            self.gpu_elapsed += gpu_time;
+            self.gpu_frame_count += 1;
+        }
+
+        if self.elapsed < self.interval {
+            return None;
+        }
+
+        let average_frame_time = self.elapsed / self.frame_count;
+        let mut title = format!(
+            "{} — {:.0} fps ({:.1} ms",
+            self.name,
+            1.0 / average_frame_time.as_secs_f64(),
+            average_frame_time.as_secs_f64() * 1000.0,
+        );
+        if self.gpu_frame_count > 0 {
+            let average_gpu_time = self.gpu_elapsed / self.gpu_frame_count;
+            title += &format!(", GPU {:.2} ms", average_gpu_time.as_secs_f64() * 1000.0);
+        }
+        title.push(')');
+
+        self.elapsed = Duration::ZERO;
+        self.frame_count = 0;
+        self.gpu_elapsed = Duration::ZERO;
+        self.gpu_frame_count = 0;
+
+        if title == self.title {
+            return None;
+        }
+
+        self.title = title;
+        Some(&self.title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_updates() {
+        let mut counter = FpsCounter::new("Movable Square", Duration::from_millis(500));
+
+        for _ in 0..49 {
+            assert_eq!(counter.record_frame(Duration::from_millis(10)), None);
+        }
+        assert_eq!(
+            counter.record_frame(Duration::from_millis(10)),
+            Some("Movable Square — 100 fps (10.0 ms)")
+        );
+
+        // Same frame rate, same title.
+        for _ in 0..50 {
+            assert_eq!(counter.record_frame(Duration::from_millis(10)), None);
+        }
+
+        for _ in 0..24 {
+            counter.record_frame(Duration::from_millis(20));
+        }
+        assert_eq!(
+            counter.record_frame(Duration::from_millis(20)),
+            Some("Movable Square — 50 fps (20.0 ms)")
+        );
+    }
+
+    #[test]
+    fn title_with_gpu_time() {
+        let mut counter = FpsCounter::new("Movable Square", Duration::from_millis(500));
+
+        // The first frames haven't been measured yet.
+        for i in 0..50 {
+            let gpu_time = (i >= 2).then(|| Duration::from_micros(1500 + i % 2 * 1000));
+            counter.record_frame_with_gpu_time(Duration::from_millis(10), gpu_time);
+        }
+        assert_eq!(
+            counter.title,
+            "Movable Square — 100 fps (10.0 ms, GPU 2.00 ms)"
+        );
+
+        // Without a GPU timer.
+        for _ in 0..49 {
+            counter.record_frame_with_gpu_time(Duration::from_millis(10), None);
+        }
+        assert_eq!(
+            counter.record_frame_with_gpu_time(Duration::from_millis(10), None),
+            Some("Movable Square — 100 fps (10.0 ms)")
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Measures the time between frames, averaged over the last few frames so that the displayed
+/// value doesn't flicker.
+pub struct FrameTimer {
+    last_frame: Option<Instant>,
+    frame_times: VecDeque<Duration>,
+    window_size: usize,
+}
+
+impl FrameTimer {
+    /// Creates a timer averaging over the last `window_size` frames.
+    pub fn new(window_size: usize) -> Self {
+        assert!(window_size > 0, "the averaging window can't be empty");
+
+        Self {
+            last_frame: None,
+            frame_times: VecDeque::with_capacity(window_size),
+            window_size,
+        }
+    }
+
+    /// To be called once per frame.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == self.window_size {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(now - last_frame);
+        }
+
+        self.last_frame = Some(now);
+    }
+
+    /// The average frame time, or `None` before the second frame.
+    pub fn average_frame_time(&self) -> Option<Duration> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+
+        let total: Duration = self.frame_times.iter().sum();
+        Some(total / self.frame_times.len() as u32)
+    }
+
+    pub fn fps(&self) -> Option<f64> {
+        self.average_frame_time()
+            .map(|frame_time| 1.0 / frame_time.as_secs_f64())
+    }
+}
@@ -0,0 +1,22 @@
+use winit::window::{Fullscreen, Window};
+
+/// Switches `window` between windowed and borderless fullscreen mode, on the monitor the window is
+/// on or else on the primary one.
+///
+/// The window is resized by the switch, and receives a `Resized` event like any other resize, so
+/// the swapchain is recreated the usual way. If no monitor can be queried, as on some Wayland
+/// compositors, the window stays as it is.
+pub fn toggle_fullscreen(window: &Window) {
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+        return;
+    }
+
+    match window
+        .current_monitor()
+        .or_else(|| window.primary_monitor())
+    {
+        Some(monitor) => window.set_fullscreen(Some(Fullscreen::Borderless(Some(monitor)))),
+        None => println!("Warning: no monitor could be queried, staying in windowed mode"),
+    }
+}
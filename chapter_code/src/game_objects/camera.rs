@@ -0,0 +1,141 @@
+use glam::{Mat4, Vec2, Vec3};
+
+// Distance between the camera of `Camera::orthographic_2d` and the plane z = 0.
+const ORTHOGRAPHIC_DISTANCE: f32 = 1.0;
+
+pub enum Projection {
+    /// Parallel projection, showing `height` world units vertically whatever the distance.
+    Orthographic { height: f32 },
+    /// Projection with foreshortening, with a vertical field of view of `fov_y` radians.
+    Perspective { fov_y: f32 },
+}
+
+/// Where the scene is seen from, and how it is projected onto the window.
+///
+/// The projections follow Vulkan's conventions: depths go from 0 at the near plane to 1 at the far
+/// plane, and the y axis of the window points down, like in the rest of the examples.
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+    /// Width divided by height of the window, which keeps shapes from being stretched.
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    /// A camera looking at the plane z = 0, which shows the y range [-1, 1], and a wider or
+    /// narrower x range depending on `aspect_ratio`. With a square window, world coordinates are
+    /// the same as clip coordinates.
+    pub fn orthographic_2d(aspect_ratio: f32) -> Self {
+        Self {
+            eye: Vec3::new(0.0, 0.0, ORTHOGRAPHIC_DISTANCE),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            projection: Projection::Orthographic { height: 2.0 },
+            aspect_ratio,
+            near: 0.0,
+            far: 2.0 * ORTHOGRAPHIC_DISTANCE,
+        }
+    }
+
+    /// Sets the aspect ratio from the size of the window, for example after it was resized.
+    pub fn set_viewport_size(&mut self, [width, height]: [f32; 2]) {
+        // A minimized window has a height of 0.
+        if height > 0.0 {
+            self.aspect_ratio = width / height;
+        }
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self) -> Mat4 {
+        match self.projection {
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect_ratio;
+
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.near,
+                    self.far,
+                )
+            }
+            Projection::Perspective { fov_y } => {
+                Mat4::perspective_rh(fov_y, self.aspect_ratio, self.near, self.far)
+            }
+        }
+    }
+
+    /// The view and projection matrices composed, to be multiplied with a model matrix.
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+
+    /// The point of the plane z = 0 under the cursor, which is at `cursor` pixels from the top left
+    /// corner of a window of `window_size` pixels. Both must be in the same unit, like the physical
+    /// pixels of winit's `CursorMoved` event and `Window::inner_size`.
+    ///
+    /// Returns `None` if the window is empty, or if the camera looks along the plane.
+    pub fn cursor_to_world(&self, [x, y]: [f32; 2], [width, height]: [f32; 2]) -> Option<Vec3> {
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+
+        // The y axis of the window and of Vulkan's normalized device coordinates both point down.
+        let ndc = Vec2::new(2.0 * x / width - 1.0, 2.0 * y / height - 1.0);
+
+        // The cursor covers a ray from the near plane to the far plane, which crosses z = 0 once.
+        let inverse = self.view_projection().inverse();
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+
+        if near.z == far.z {
+            return None;
+        }
+
+        Some(near + (far - near) * (near.z / (near.z - far.z)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orthographic_keeps_aspect_ratio() {
+        let mut camera = Camera::orthographic_2d(1.0);
+        camera.set_viewport_size([800.0, 400.0]);
+
+        let view_projection = camera.view_projection();
+        let right = view_projection.project_point3(Vec3::new(1.0, 0.0, 0.0));
+        let down = view_projection.project_point3(Vec3::new(0.0, 1.0, 0.0));
+
+        // The window is twice as wide as it is high, so a unit covers half as much of its width.
+        assert!((right.x - 0.5).abs() < 1e-6);
+        assert!((down.y - 1.0).abs() < 1e-6);
+        assert!((0.0..=1.0).contains(&right.z));
+    }
+
+    #[test]
+    fn cursor_to_world() {
+        let mut camera = Camera::orthographic_2d(1.0);
+        camera.set_viewport_size([800.0, 400.0]);
+        let window_size = [800.0, 400.0];
+
+        let center = camera.cursor_to_world([400.0, 200.0], window_size).unwrap();
+        let top_right = camera.cursor_to_world([800.0, 0.0], window_size).unwrap();
+
+        assert!(center.abs_diff_eq(Vec3::ZERO, 1e-6));
+        // The y axis points down, and the x range is twice as wide as the y one.
+        assert!(top_right.abs_diff_eq(Vec3::new(2.0, -1.0, 0.0), 1e-6));
+        assert!(camera.cursor_to_world([0.0, 0.0], [0.0, 0.0]).is_none());
+    }
+}
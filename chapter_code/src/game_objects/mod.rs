@@ -1,3 +1,7 @@
+mod camera;
 mod square;
+mod square_grid;
 
+pub use camera::{Camera, Projection};
 pub use square::Square;
+pub use square_grid::{GridSquare, SquareGrid, GRID_SQUARE_SCALE};
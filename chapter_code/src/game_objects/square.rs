@@ -1,9 +1,22 @@
+use std::f32::consts::PI;
+
+use glam::{Mat4, Quat, Vec2, Vec3};
 use rand::Rng;
 
+// In radians per second.
+const ROTATION_SPEED: f32 = PI;
+// The scale is multiplied or divided by this every second.
+const SCALE_SPEED: f32 = 2.0;
+const MIN_SCALE: f32 = 0.25;
+const MAX_SCALE: f32 = 4.0;
+
 pub struct Square {
     pub color: [f32; 3],
     pub position: [f32; 2],
     pub speed: f32,
+    /// In radians, around the center of the square.
+    pub rotation: f32,
+    pub scale: f32,
 }
 
 impl Square {
@@ -13,6 +26,8 @@ impl Square {
             color: [1.0, 0.0, 0.0],
             position: [0.0, 0.0],
             speed: 1.3,
+            rotation: 0.0,
+            scale: 1.0,
         }
     }
 
@@ -21,19 +36,89 @@ impl Square {
         self.color = [get_random_float(), get_random_float(), get_random_float()];
     }
 
-    pub fn move_right(&mut self, seconds_passed: f32) {
-        self.position[0] += seconds_passed * self.speed
+    /// Moves the square at its speed in `direction`, which is normalized so that moving diagonally
+    /// isn't faster than along an axis. The square doesn't move if `direction` is zero.
+    ///
+    /// The Y axis points down, so moving up is moving towards negative Y.
+    pub fn move_in_direction(&mut self, direction: Vec2, seconds_passed: f32) {
+        let offset = direction.normalize_or_zero() * self.speed * seconds_passed;
+
+        self.position[0] += offset.x;
+        self.position[1] += offset.y;
+    }
+
+    /// Rotates the square around its center, clockwise on the screen if `direction` is positive
+    /// and counterclockwise if it is negative.
+    pub fn rotate(&mut self, direction: f32, seconds_passed: f32) {
+        self.rotation += direction * ROTATION_SPEED * seconds_passed;
+    }
+
+    /// Makes the square bigger if `direction` is positive and smaller if it is negative, up to a
+    /// limit. The scale changes by a factor rather than an amount, so it feels as fast either way.
+    pub fn grow(&mut self, direction: f32, seconds_passed: f32) {
+        self.scale =
+            (self.scale * SCALE_SPEED.powf(direction * seconds_passed)).clamp(MIN_SCALE, MAX_SCALE);
+    }
+
+    /// The transformation from the coordinates of the model to the ones of the world: the model is
+    /// scaled and rotated around its center, which is its origin, then moved to `position`.
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            Vec3::new(self.scale, self.scale, 1.0),
+            Quat::from_rotation_z(self.rotation),
+            Vec3::new(self.position[0], self.position[1], 0.0),
+        )
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance_moved(direction: Vec2) -> f32 {
+        let mut square = Square::new();
+        square.move_in_direction(direction, 0.5);
 
-    pub fn move_left(&mut self, seconds_passed: f32) {
-        self.position[0] -= seconds_passed * self.speed
+        Vec2::from(square.position).length()
     }
 
-    pub fn move_up(&mut self, seconds_passed: f32) {
-        self.position[1] -= seconds_passed * self.speed
+    #[test]
+    fn diagonal_movement_speed() {
+        let speed = Square::new().speed;
+
+        assert!((distance_moved(Vec2::new(1.0, 0.0)) - speed * 0.5).abs() < 1e-6);
+        assert!((distance_moved(Vec2::new(1.0, -1.0)) - speed * 0.5).abs() < 1e-6);
+        assert_eq!(distance_moved(Vec2::ZERO), 0.0);
     }
 
-    pub fn move_down(&mut self, seconds_passed: f32) {
-        self.position[1] += seconds_passed * self.speed
+    #[test]
+    fn model_transform() {
+        let mut square = Square::new();
+        square.position = [1.0, 2.0];
+        square.rotation = PI / 2.0;
+        square.scale = 2.0;
+
+        let matrix = square.model_matrix();
+
+        // The center stays at the position.
+        assert!(matrix
+            .transform_point3(Vec3::ZERO)
+            .abs_diff_eq(Vec3::new(1.0, 2.0, 0.0), 1e-6));
+        // The Y axis points down, so this is a quarter turn clockwise on the screen.
+        assert!(matrix
+            .transform_point3(Vec3::new(0.25, 0.0, 0.0))
+            .abs_diff_eq(Vec3::new(1.0, 2.5, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn scale_limits() {
+        let mut square = Square::new();
+
+        square.grow(1.0, 1.0);
+        assert!((square.scale - SCALE_SPEED).abs() < 1e-6);
+        square.grow(-1.0, 100.0);
+        assert_eq!(square.scale, MIN_SCALE);
+        square.grow(1.0, 100.0);
+        assert_eq!(square.scale, MAX_SCALE);
     }
 }
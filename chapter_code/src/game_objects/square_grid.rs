@@ -0,0 +1,81 @@
+use rand::Rng;
+
+/// Size of the squares of a `SquareGrid`, as a fraction of the size of `SquareModel`.
+pub const GRID_SQUARE_SCALE: f32 = 0.2;
+
+// Half of the width of a square of the grid, which is 0.5 for `SquareModel`.
+const HALF_SIZE: f32 = 0.25 * GRID_SQUARE_SCALE;
+
+const MAX_SPEED: f32 = 0.3;
+
+pub struct GridSquare {
+    pub color: [f32; 3],
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+}
+
+/// Squares laid out in a grid covering the window, each moving on its own in a random direction
+/// and bouncing off the edges of the window.
+pub struct SquareGrid {
+    pub squares: Vec<GridSquare>,
+}
+
+impl SquareGrid {
+    pub fn new(columns: u32, rows: u32) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut squares = Vec::with_capacity((columns * rows) as usize);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                // The centers of the cells of a grid splitting [-1, 1] in both directions.
+                let x = -1.0 + (2 * column + 1) as f32 / columns as f32;
+                let y = -1.0 + (2 * row + 1) as f32 / rows as f32;
+
+                squares.push(GridSquare {
+                    color: [rng.gen(), rng.gen(), rng.gen()],
+                    position: [x, y],
+                    velocity: [
+                        rng.gen_range(-MAX_SPEED..MAX_SPEED),
+                        rng.gen_range(-MAX_SPEED..MAX_SPEED),
+                    ],
+                });
+            }
+        }
+
+        Self { squares }
+    }
+
+    pub fn update(&mut self, seconds_passed: f32) {
+        for square in &mut self.squares {
+            for axis in 0..2 {
+                square.position[axis] += square.velocity[axis] * seconds_passed;
+
+                let limit = 1.0 - HALF_SIZE;
+                if square.position[axis].abs() > limit {
+                    square.position[axis] = square.position[axis].clamp(-limit, limit);
+                    square.velocity[axis] = -square.velocity[axis];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squares_stay_in_the_window() {
+        let mut grid = SquareGrid::new(10, 10);
+        assert_eq!(grid.squares.len(), 100);
+
+        for _ in 0..1000 {
+            grid.update(0.1);
+        }
+
+        for square in &grid.squares {
+            assert!(square.position[0].abs() <= 1.0 - HALF_SIZE);
+            assert!(square.position[1].abs() <= 1.0 - HALF_SIZE);
+        }
+    }
+}
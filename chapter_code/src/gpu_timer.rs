@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use vulkano::command_buffer::allocator::CommandBufferAllocator;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Device;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::PipelineStage;
+
+/// Measures the time taken by the GPU to execute part of a command buffer, with two timestamp
+/// queries.
+///
+/// The timer can be reused once the previous command buffer using it has finished executing.
+pub struct GpuTimer {
+    query_pool: Arc<QueryPool>,
+    valid_bits: u32,
+    timestamp_period: f32,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the queue family doesn't support timestamps.
+    pub fn new(device: Arc<Device>, queue_family_index: u32) -> Option<Self> {
+        let physical_device = device.physical_device();
+        let valid_bits = physical_device.queue_family_properties()[queue_family_index as usize]
+            .timestamp_valid_bits?;
+        let timestamp_period = physical_device.properties().timestamp_period;
+
+        let query_pool = QueryPool::new(
+            device.clone(),
+            QueryPoolCreateInfo {
+                query_count: 2,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            },
+        )
+        .expect("failed to create query pool");
+
+        Some(Self {
+            query_pool,
+            valid_bits,
+            timestamp_period,
+        })
+    }
+
+    /// Records the start of the measured commands. Must be outside of a render pass.
+    pub fn begin<L, A: CommandBufferAllocator>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L, A>,
+    ) {
+        // Safety: the queries aren't in use, as the previous measurement has been read back.
+        unsafe {
+            builder
+                .reset_query_pool(self.query_pool.clone(), 0..2)
+                .unwrap()
+                .write_timestamp(self.query_pool.clone(), 0, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+    }
+
+    /// Records the end of the measured commands.
+    pub fn end<L, A: CommandBufferAllocator>(&self, builder: &mut AutoCommandBufferBuilder<L, A>) {
+        // Safety: the query was reset by `begin`.
+        unsafe {
+            builder
+                .write_timestamp(self.query_pool.clone(), 1, PipelineStage::BottomOfPipe)
+                .unwrap();
+        }
+    }
+
+    /// The pool of the two queries, for command buffers that aren't recorded with vulkano. Query 0
+    /// must be reset and written before the measured commands, and query 1 after them.
+    pub fn query_pool(&self) -> &Arc<QueryPool> {
+        &self.query_pool
+    }
+
+    /// The time between `begin` and `end`, waiting for the command buffer to finish executing.
+    pub fn elapsed(&self) -> Duration {
+        let mut timestamps = [0u64; 2];
+        self.query_pool
+            .queries_range(0..2)
+            .unwrap()
+            .get_results(&mut timestamps, QueryResultFlags::WAIT)
+            .expect("failed to read the timestamps");
+
+        ticks_to_duration(
+            timestamps[0],
+            timestamps[1],
+            self.valid_bits,
+            self.timestamp_period,
+        )
+    }
+}
+
+/// Converts the difference between two timestamps to a duration. Only the lowest `valid_bits` of
+/// the timestamps are meaningful, and the counter may have wrapped around between them.
+fn ticks_to_duration(start: u64, end: u64, valid_bits: u32, timestamp_period: f32) -> Duration {
+    let mask = if valid_bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << valid_bits) - 1
+    };
+    let ticks = end.wrapping_sub(start) & mask;
+
+    Duration::from_nanos((ticks as f64 * timestamp_period as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_wrap_around() {
+        assert_eq!(
+            ticks_to_duration(100, 350, 64, 1.0),
+            Duration::from_nanos(250)
+        );
+        assert_eq!(
+            ticks_to_duration(100, 350, 64, 2.5),
+            Duration::from_nanos(625)
+        );
+        // The counter wrapped around after 36 bits.
+        assert_eq!(
+            ticks_to_duration((1 << 36) - 10, 5, 36, 1.0),
+            Duration::from_nanos(15)
+        );
+    }
+}
@@ -0,0 +1,16 @@
+use std::env;
+
+/// Command line flag making the windowed examples exit on their own.
+pub const HEADLESS_FLAG: &str = "--headless";
+
+/// Number of frames rendered by a windowed example started with `--headless` before it exits.
+pub const HEADLESS_FRAME_COUNT: u64 = 10;
+
+/// Whether the example was started with `--headless`.
+///
+/// The windowed examples still open a window in that mode, but render a fixed number of frames
+/// and exit instead of waiting for the window to be closed. This lets them run unattended, for
+/// example in the integration tests on a CI machine with a virtual X server.
+pub fn is_headless() -> bool {
+    env::args().skip(1).any(|arg| arg == HEADLESS_FLAG)
+}
@@ -1,12 +1,38 @@
+use std::env;
 use std::io;
 
+mod fps_counter;
+mod frame_timer;
+mod fullscreen;
 pub mod game_objects;
+mod gpu_timer;
+mod headless;
 pub mod models;
+mod render_stats;
+#[cfg(feature = "shader-hot-reload")]
+mod shader_reloader;
 pub mod shaders;
 mod vertex_data;
 pub mod vulkano_objects;
 
-pub use vertex_data::{Vertex2d, Vertex3d};
+pub use fps_counter::FpsCounter;
+pub use frame_timer::FrameTimer;
+pub use fullscreen::toggle_fullscreen;
+pub use gpu_timer::GpuTimer;
+pub use headless::{is_headless, HEADLESS_FLAG, HEADLESS_FRAME_COUNT};
+pub use render_stats::RenderStats;
+#[cfg(feature = "shader-hot-reload")]
+pub use shader_reloader::ShaderReloader;
+pub use vertex_data::{Instance2d, Vertex2d, Vertex3d};
+
+// Android loads applications as shared libraries, so the Android example is built as part of the
+// library there. It refers to the library as `chapter_code`, like the other binaries.
+#[cfg(target_os = "android")]
+extern crate self as chapter_code;
+
+#[cfg(target_os = "android")]
+#[path = "bin/android_surface.rs"]
+mod android_surface;
 
 #[cfg(test)]
 mod tests {
@@ -17,22 +43,22 @@ mod tests {
     }
 }
 
-pub fn select_example_to_run(examples: &Vec<&str>, execute: fn(&str)) {
-    println!("Select example to run: (default 0)");
+/// Runs the example of `examples` selected by its name or its index, with `execute`. Each example
+/// is given as its name and a short description of what it does, which is shown in the list.
+///
+/// The selection is the first command line argument, for example
+/// `cargo run --bin images -- mandelbrot`, so that the examples can be run from scripts. Without
+/// one, the examples are listed and the selection is read from the standard input instead.
+pub fn select_example_to_run(examples: &[(&str, &str)], execute: fn(&str)) {
+    let selection = match env::args().nth(1) {
+        Some(selection) => selection,
+        None => read_selection(examples),
+    };
 
-    for (i, example) in examples.iter().enumerate() {
-        println!("{} {}", i, example);
-    }
-
-    let mut selection = String::new();
-    io::stdin()
-        .read_line(&mut selection)
-        .expect("Failed to read line");
-
-    selection = selection.trim().to_string();
+    let selection = selection.trim();
 
     if selection.is_empty() {
-        execute(examples[0]);
+        execute(examples[0].0);
     // else if selection is numeric
     } else if let Ok(i) = selection.parse::<usize>() {
         if i >= examples.len() {
@@ -41,12 +67,12 @@ pub fn select_example_to_run(examples: &Vec<&str>, execute: fn(&str)) {
                 selection
             );
         } else {
-            execute(examples[i]);
+            execute(examples[i].0);
         }
     } else {
-        match examples.iter().position(|&s| s == selection) {
+        match examples.iter().position(|&(name, _)| name == selection) {
             Some(i) => {
-                execute(examples[i]);
+                execute(examples[i].0);
             }
             None => {
                 println!("\"{}\" doesn't correspond to any known example", selection);
@@ -54,3 +80,19 @@ pub fn select_example_to_run(examples: &Vec<&str>, execute: fn(&str)) {
         }
     }
 }
+
+// Lists the `examples` and reads the name or the index of the one to run from the standard input.
+fn read_selection(examples: &[(&str, &str)]) -> String {
+    println!("Select example to run: (default 0)");
+
+    for (i, (name, description)) in examples.iter().enumerate() {
+        println!("{}  {} — {}", i, name, description);
+    }
+
+    let mut selection = String::new();
+    io::stdin()
+        .read_line(&mut selection)
+        .expect("Failed to read line");
+
+    selection
+}
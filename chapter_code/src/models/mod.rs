@@ -1,5 +1,7 @@
+mod obj;
 mod square;
 mod traits;
 
+pub use obj::parse_obj;
 pub use square::SquareModel;
 pub use traits::Model;
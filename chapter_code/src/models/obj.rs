@@ -0,0 +1,148 @@
+use crate::Vertex3d;
+
+/// Reads the positions and faces of a Wavefront OBJ file, like one exported from Blender, into
+/// vertices and `u32` indices, three for each triangle. Faces with more than three corners are
+/// split into triangles around their first corner, which is correct for the convex quads that
+/// most files use.
+///
+/// A file without faces is a list of triangles, each made of the next three vertices. Texture
+/// coordinates, normals, materials and groups are ignored, as `Vertex3d` only has a position.
+///
+/// Panics with the line number if the file is invalid or a face refers to a vertex that doesn't
+/// exist.
+pub fn parse_obj(source: &str) -> (Vec<Vertex3d>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for (line_i, line) in source.lines().enumerate() {
+        let line_number = line_i + 1;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                // An optional fourth coordinate, the weight, is only used by curves.
+                let coordinates: Vec<f32> = tokens
+                    .take(3)
+                    .map(|token| {
+                        token.parse().unwrap_or_else(|_| {
+                            panic!(
+                                "line {}: invalid vertex coordinate {:?}",
+                                line_number, token
+                            )
+                        })
+                    })
+                    .collect();
+                let position: [f32; 3] = coordinates.try_into().unwrap_or_else(|_| {
+                    panic!("line {}: a vertex must have 3 coordinates", line_number)
+                });
+
+                vertices.push(Vertex3d { position });
+            }
+            Some("f") => {
+                let corners: Vec<u32> = tokens
+                    .map(|token| parse_corner(token, vertices.len(), line_number))
+                    .collect();
+                assert!(
+                    corners.len() >= 3,
+                    "line {}: a face must have at least 3 corners",
+                    line_number,
+                );
+
+                for i in 1..corners.len() - 1 {
+                    indices.extend([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if indices.is_empty() {
+        assert!(
+            vertices.len() % 3 == 0,
+            "a file without faces must have 3 vertices for each triangle",
+        );
+        indices = (0..vertices.len() as u32).collect();
+    }
+
+    (vertices, indices)
+}
+
+// A corner is `v`, `v/vt`, `v//vn` or `v/vt/vn`, of which only the vertex index `v` is used. It
+// counts from 1, or from the last vertex read so far if it is negative.
+fn parse_corner(token: &str, vertex_count: usize, line_number: usize) -> u32 {
+    let vertex = token.split('/').next().unwrap();
+    let index: i64 = vertex
+        .parse()
+        .unwrap_or_else(|_| panic!("line {}: invalid face corner {:?}", line_number, token));
+
+    let index = if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        index - 1
+    };
+
+    assert!(
+        (0..vertex_count as i64).contains(&index),
+        "line {}: face corner {:?} refers to a vertex that doesn't exist",
+        line_number,
+        token,
+    );
+
+    index as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(vertices: &[Vertex3d]) -> Vec<[f32; 3]> {
+        vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn quads_are_triangulated() {
+        let (vertices, indices) = parse_obj(
+            "# a unit square
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vn 0 0 1
+            f 1//1 2//1 3//1 4//1",
+        );
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn corner_formats() {
+        let (vertices, indices) = parse_obj(
+            "v 0 0 0
+            v 1 0 0
+            v 0 1 0 1.0
+            vt 0 0
+            f 1/1 2/1/1 -1",
+        );
+
+        assert_eq!(
+            positions(&vertices),
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn file_without_faces() {
+        let (vertices, indices) = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\n");
+
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(indices, [0, 1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "line 2")]
+    fn missing_vertex() {
+        parse_obj("v 0 0 0\nf 1 2 3");
+    }
+}
@@ -1,3 +1,5 @@
+use glam::Mat4;
+
 use crate::models::Model;
 use crate::shaders::movable_square;
 use crate::Vertex2d;
@@ -30,8 +32,8 @@ impl Model<Vertex2d, UniformData> for SquareModel {
 
     fn get_initial_uniform_data() -> UniformData {
         UniformData {
+            mvp: Mat4::IDENTITY.to_cols_array_2d(),
             color: [0.0, 0.0, 0.0].into(),
-            position: [0.0, 0.0],
         }
     }
 }
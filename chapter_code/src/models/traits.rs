@@ -1,7 +1,10 @@
 use vulkano::buffer::BufferContents;
+use vulkano::pipeline::graphics::input_assembly::Index;
 
-pub trait Model<V: BufferContents, U: BufferContents> {
-    fn get_indices() -> Vec<u16>;
+/// A mesh with vertices of type `V`, indices of type `Ix` (`u16`, or `u32` for meshes with more
+/// than 65536 vertices) and uniforms of type `U`.
+pub trait Model<V: BufferContents, U: BufferContents, Ix: Index = u16> {
+    fn get_indices() -> Vec<Ix>;
     fn get_vertices() -> Vec<V>;
     fn get_initial_uniform_data() -> U;
 }
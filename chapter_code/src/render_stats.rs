@@ -0,0 +1,92 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+// Number of frames the statistics are computed over.
+const WINDOW_SIZE: usize = 1000;
+
+/// Frame time statistics over the last frames.
+///
+/// Besides the average, it gives percentiles of the frame time: a few slow frames are barely
+/// visible in the average, but they are what makes the rendering stutter.
+#[derive(Default)]
+pub struct RenderStats {
+    // Frame times in microseconds, in the order they were recorded, to know which one to forget.
+    frame_times: VecDeque<u64>,
+    // The same frame times sorted, as a count of frames for each frame time.
+    sorted_frame_times: BTreeMap<u64, usize>,
+    total_us: u64,
+}
+
+impl RenderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == WINDOW_SIZE {
+            let oldest = self.frame_times.pop_front().unwrap();
+            self.total_us -= oldest;
+
+            let count = self.sorted_frame_times.get_mut(&oldest).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.sorted_frame_times.remove(&oldest);
+            }
+        }
+
+        let frame_time_us = frame_time.as_micros() as u64;
+        self.frame_times.push_back(frame_time_us);
+        self.total_us += frame_time_us;
+        *self.sorted_frame_times.entry(frame_time_us).or_insert(0) += 1;
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_times.len()
+    }
+
+    /// The average frame time, or 0 if no frame was recorded.
+    pub fn average_frame_time_us(&self) -> u64 {
+        self.total_us
+            .checked_div(self.frame_times.len() as u64)
+            .unwrap_or(0)
+    }
+
+    /// The frame time that `percentile` percent of the frames are faster than or equal to, or 0
+    /// if no frame was recorded.
+    pub fn percentile_frame_time_us(&self, percentile: f64) -> u64 {
+        assert!((0.0..=100.0).contains(&percentile));
+
+        // Nearest-rank method: the smallest frame time with at least `rank` frames up to it.
+        let rank = ((percentile * self.frame_times.len() as f64 / 100.0).ceil() as usize).max(1);
+
+        let mut frames = 0;
+        for (&frame_time_us, &count) in &self.sorted_frame_times {
+            frames += count;
+            if frames >= rank {
+                return frame_time_us;
+            }
+        }
+
+        0
+    }
+
+    pub fn p99_frame_time_us(&self) -> u64 {
+        self.percentile_frame_time_us(99.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let mut stats = RenderStats::new();
+        for ms in 1..=100 {
+            stats.record_frame(Duration::from_millis(ms));
+        }
+
+        assert_eq!(stats.p99_frame_time_us(), 99_000);
+        assert_eq!(stats.percentile_frame_time_us(50.0), 50_000);
+    }
+}
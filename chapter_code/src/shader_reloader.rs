@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use shaderc::{Compiler, ShaderKind};
+use vulkano::device::Device;
+use vulkano::shader::ShaderModule;
+
+/// Watches the GLSL sources of a vertex and a fragment shader, and compiles them again when one of
+/// them changes, so that they can be edited while an example is running.
+///
+/// Only available with the `shader-hot-reload` feature. Otherwise, the examples only have the
+/// shaders compiled at build time by `vulkano_shaders::shader!`.
+pub struct ShaderReloader {
+    device: Arc<Device>,
+    compiler: Compiler,
+    vertex_path: PathBuf,
+    fragment_path: PathBuf,
+    // The files stop being watched when it is dropped.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl ShaderReloader {
+    /// Starts watching the shaders at `vertex_path` and `fragment_path`.
+    ///
+    /// Panics if one of the files doesn't exist or can't be watched, or if shaderc can't be loaded.
+    pub fn new(device: Arc<Device>, vertex_path: &Path, fragment_path: &Path) -> Self {
+        // The watcher reports the paths under the watched directories, so both are canonicalized
+        // to be compared with them.
+        let canonicalize = |path: &Path| {
+            fs::canonicalize(path)
+                .unwrap_or_else(|e| panic!("failed to find {}: {}", path.display(), e))
+        };
+        let vertex_path = canonicalize(vertex_path);
+        let fragment_path = canonicalize(fragment_path);
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).expect("failed to create watcher");
+
+        // Editors often save a file by replacing it, after which the file itself would no longer
+        // be watched, so the directories containing the shaders are watched instead.
+        for path in [&vertex_path, &fragment_path] {
+            let directory = path.parent().unwrap();
+            watcher
+                .watch(directory, RecursiveMode::NonRecursive)
+                .unwrap_or_else(|e| panic!("failed to watch {}: {}", directory.display(), e));
+        }
+
+        println!(
+            "Watching {} and {} for changes",
+            vertex_path.display(),
+            fragment_path.display()
+        );
+
+        Self {
+            device,
+            compiler: Compiler::new().expect("failed to load shaderc"),
+            vertex_path,
+            fragment_path,
+            _watcher: watcher,
+            events,
+        }
+    }
+
+    /// Compiles both shaders again if one of them changed since the last call, and returns the
+    /// new vertex and fragment shaders, in that order.
+    ///
+    /// Returns `None` if neither changed, or if one failed to compile, in which case the error is
+    /// printed and the previous shaders should be kept until it is fixed.
+    pub fn reload_if_changed(&self) -> Option<(Arc<ShaderModule>, Arc<ShaderModule>)> {
+        let shader_paths = [self.vertex_path.as_path(), self.fragment_path.as_path()];
+
+        // Saving a file usually sends several events, which are all taken at once.
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) => changed |= is_shader_change(&event, &shader_paths),
+                Err(e) => println!("Warning: failed to watch the shaders: {}", e),
+            }
+        }
+
+        if !changed {
+            return None;
+        }
+
+        let shaders = self
+            .load(&self.vertex_path, ShaderKind::Vertex)
+            .and_then(|vs| Ok((vs, self.load(&self.fragment_path, ShaderKind::Fragment)?)));
+
+        match shaders {
+            Ok(shaders) => {
+                println!("Reloaded the shaders");
+                Some(shaders)
+            }
+            Err(e) => {
+                println!("Warning: {}\nKeeping the previous shaders", e);
+                None
+            }
+        }
+    }
+
+    fn load(&self, path: &Path, kind: ShaderKind) -> Result<Arc<ShaderModule>, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let words = compile(&self.compiler, &source, kind, path)?;
+
+        // Safety: shaderc only outputs valid SPIR-V.
+        unsafe { ShaderModule::from_words(self.device.clone(), &words) }
+            .map_err(|e| format!("failed to create the module of {}: {}", path.display(), e))
+    }
+}
+
+// Whether `event` is a change to one of the files at `shader_paths`.
+fn is_shader_change(event: &Event, shader_paths: &[&Path]) -> bool {
+    (event.kind.is_modify() || event.kind.is_create())
+        && event
+            .paths
+            .iter()
+            .any(|path| shader_paths.contains(&path.as_path()))
+}
+
+// Compiles the GLSL `source` of the file at `path` to SPIR-V. The errors start with the path and
+// the line they are on, like the ones of `vulkano_shaders::shader!`.
+fn compile(
+    compiler: &Compiler,
+    source: &str,
+    kind: ShaderKind,
+    path: &Path,
+) -> Result<Vec<u32>, String> {
+    compiler
+        .compile_into_spirv(source, kind, &path.display().to_string(), "main", None)
+        .map(|artifact| artifact.as_binary().to_vec())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::event::{AccessKind, EventKind, ModifyKind};
+
+    use super::*;
+
+    #[test]
+    fn shader_changes() {
+        let vertex_path = Path::new("/shaders/vertex.glsl");
+        let fragment_path = Path::new("/shaders/fragment.glsl");
+        let shader_paths = [vertex_path, fragment_path];
+
+        let modified = |path: &Path| {
+            Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path.to_path_buf())
+        };
+
+        assert!(is_shader_change(&modified(fragment_path), &shader_paths));
+        assert!(!is_shader_change(
+            &modified(Path::new("/shaders/vertex.glsl.swp")),
+            &shader_paths
+        ));
+
+        let read = Event::new(EventKind::Access(AccessKind::Any)).add_path(vertex_path.into());
+        assert!(!is_shader_change(&read, &shader_paths));
+    }
+
+    #[test]
+    fn compile_errors() {
+        let compiler = Compiler::new().unwrap();
+
+        let compile_vertex_shader = |source: &str, path: &str| {
+            compile(&compiler, source, ShaderKind::Vertex, Path::new(path))
+        };
+
+        let source = include_str!("shaders/static_triangle/vertex.glsl");
+        assert!(compile_vertex_shader(source, "vertex.glsl").is_ok());
+
+        let source = "#version 460\n\nvoid main() {\n    undeclared = 1.0;\n}\n";
+        let error = compile_vertex_shader(source, "broken.glsl").unwrap_err();
+        assert!(error.contains("broken.glsl:4"), "{}", error);
+    }
+}
@@ -1,9 +1,12 @@
 #version 460
 
 layout(location = 0) in vec3 color;
+layout(location = 1) in vec2 uv;
+
+layout(set = 0, binding = 1) uniform sampler2D tex;
 
 layout(location = 0) out vec4 f_color;
 
 void main() {
-    f_color = vec4(color, 1.0);
+    f_color = texture(tex, uv) * vec4(color, 1.0);
 }
@@ -11,3 +11,20 @@ pub mod fs {
         path: "src/shaders/movable_square/fragment.glsl",
     }
 }
+
+/// Same as `vs`, but for a single square whose transformation and color are push constants
+/// rather than a uniform buffer.
+pub mod vs_push_constants {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/movable_square/vertex_push_constants.glsl",
+    }
+}
+
+/// Same as `fs`, without the texture.
+pub mod fs_push_constants {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/movable_square/fragment_push_constants.glsl",
+    }
+}
@@ -2,19 +2,23 @@
 
 layout(location = 0) in vec2 position;
 
+// Per-instance attributes, one set for each square.
+layout(location = 1) in vec2 offset;
+layout(location = 2) in float scale;
+layout(location = 3) in vec3 color;
+
+// Shared by all the squares: transforms them all together and tints them.
 layout(set = 0, binding = 0) uniform Data {
+    mat4 mvp;
     vec3 color;
-    vec2 position;
 } uniforms;
 
 layout(location = 0) out vec3 outColor;
+// The corners of the square model are at +-0.25, which this maps to the corners of the texture.
+layout(location = 1) out vec2 uv;
 
 void main() {
-    outColor = uniforms.color;
-    gl_Position = vec4(
-        position.x + uniforms.position.x, 
-        position.y + uniforms.position.y, 
-        0.0, 
-        1.0
-    );
+    outColor = mix(color, uniforms.color, 0.5);
+    uv = position * 2.0 + 0.5;
+    gl_Position = uniforms.mvp * vec4(position * scale + offset, 0.0, 1.0);
 }
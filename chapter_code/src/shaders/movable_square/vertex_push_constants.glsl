@@ -0,0 +1,17 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+
+// Written with `push_constants` when recording the command buffer, instead of being read from a
+// uniform buffer. 128 bytes are always available for push constants, this block takes 76.
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    vec3 color;
+} push_constants;
+
+layout(location = 0) out vec3 outColor;
+
+void main() {
+    outColor = push_constants.color;
+    gl_Position = push_constants.mvp * vec4(position, 0.0, 1.0);
+}
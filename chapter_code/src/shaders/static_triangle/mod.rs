@@ -11,3 +11,15 @@ pub mod fs {
         path: "src/shaders/static_triangle/fragment.glsl",
     }
 }
+
+/// The source of `vs`, which the `shader-hot-reload` feature compiles again when it is edited.
+pub const VERTEX_SHADER_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/shaders/static_triangle/vertex.glsl"
+);
+
+/// The source of `fs`, which the `shader-hot-reload` feature compiles again when it is edited.
+pub const FRAGMENT_SHADER_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/shaders/static_triangle/fragment.glsl"
+);
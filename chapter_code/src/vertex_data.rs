@@ -14,3 +14,16 @@ pub struct Vertex3d {
     #[format(R32G32B32_SFLOAT)]
     pub position: [f32; 3],
 }
+
+/// Per-instance data of a 2D shape drawn many times with instanced rendering: where the copy is,
+/// how big it is compared to the model, and its color.
+#[derive(BufferContents, Vertex, Clone)]
+#[repr(C)]
+pub struct Instance2d {
+    #[format(R32G32_SFLOAT)]
+    pub offset: [f32; 2],
+    #[format(R32_SFLOAT)]
+    pub scale: f32,
+    #[format(R32G32B32_SFLOAT)]
+    pub color: [f32; 3],
+}
@@ -1,20 +1,38 @@
 use std::sync::Arc;
 
+use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
+use vulkano::buffer::BufferUsage;
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
 use vulkano::device::Device;
-use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
 
 pub struct Allocators {
-    pub memory: StandardMemoryAllocator,
+    pub memory: Arc<StandardMemoryAllocator>,
     pub command_buffer: StandardCommandBufferAllocator,
     pub descriptor_set: StandardDescriptorSetAllocator,
+    /// Hands out a new uniform buffer every frame from a ring of host-visible buffers. A buffer is
+    /// only reused once nothing refers to it anymore, including the command buffers that the GPU
+    /// may still be executing, so the host never writes to a buffer that is being read.
+    pub uniform_buffer: SubbufferAllocator,
 }
 
 impl Allocators {
     pub fn new(device: Arc<Device>) -> Self {
+        let memory = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
         Allocators {
-            memory: StandardMemoryAllocator::new_default(device.clone()),
+            // Uniform buffers must have the `UNIFORM_BUFFER` usage to be bound to a descriptor of
+            // type `UniformBuffer`.
+            uniform_buffer: SubbufferAllocator::new(
+                memory.clone(),
+                SubbufferAllocatorCreateInfo {
+                    buffer_usage: BufferUsage::UNIFORM_BUFFER,
+                    memory_usage: MemoryUsage::Upload,
+                    ..Default::default()
+                },
+            ),
+            memory,
             command_buffer: StandardCommandBufferAllocator::new(device.clone(), Default::default()),
             descriptor_set: StandardDescriptorSetAllocator::new(device),
         }
@@ -1,3 +1,4 @@
+use std::marker::PhantomData;
 use std::sync::Arc;
 
 use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
@@ -9,86 +10,158 @@ use vulkano::descriptor_set::layout::DescriptorSetLayout;
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::Queue;
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::pipeline::graphics::input_assembly::Index;
 use vulkano::sync::future::NowFuture;
-use vulkano::sync::GpuFuture;
+use vulkano::sync::{GpuFuture, Sharing};
 use vulkano::DeviceSize;
 
 use super::allocators::Allocators;
+use super::texture::Texture;
+use super::VulkanoObjectsError;
 use crate::models::Model;
 
-pub type Uniform<U> = (Subbuffer<U>, Arc<PersistentDescriptorSet>);
-
-/// Struct with a vertex, index and uniform buffer, with generic (V)ertices and (U)niforms.
-pub struct Buffers<V: BufferContents, U: BufferContents> {
+/// Struct with a vertex and index buffer, with generic (V)ertices, and a per-instance vertex
+/// buffer of (I)nstances for drawing the model several times at once. The (U)niforms are written
+/// to a new buffer every frame with `create_uniform_descriptor_set`.
+///
+/// The indices are `u16` by default, and can be `u32` for models with too many vertices for them.
+///
+/// There is an instance buffer for each frame in flight, so that one can be written to while the
+/// others are in use by the GPU.
+pub struct Buffers<V: BufferContents, U: BufferContents, I: BufferContents, Ix: Index = u16> {
     pub vertex: Subbuffer<[V]>,
-    pub index: Subbuffer<[u16]>,
-    pub uniforms: Vec<Uniform<U>>,
+    pub index: Subbuffer<[Ix]>,
+    pub instances: Vec<Subbuffer<[I]>>,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    texture: Option<Texture>,
+    uniform: PhantomData<U>,
 }
 
-impl<V: BufferContents, U: BufferContents> Buffers<V, U> {
-    pub fn initialize_host_accessible<M: Model<V, U>>(
+impl<V, U, I, Ix> Buffers<V, U, I, Ix>
+where
+    V: BufferContents,
+    U: BufferContents,
+    I: BufferContents + Clone,
+    Ix: Index,
+{
+    pub fn initialize_host_accessible<M: Model<V, U, Ix>>(
         allocators: &Allocators,
         descriptor_set_layout: Arc<DescriptorSetLayout>,
-        uniform_buffer_count: usize,
-    ) -> Self {
-        Self {
-            vertex: create_cpu_accessible_vertex::<V, U, M>(allocators),
-            index: create_cpu_accessible_index::<V, U, M>(allocators),
-            uniforms: create_cpu_accessible_uniforms::<V, U, M>(
+        instance_buffer_count: usize,
+        texture: Option<&Texture>,
+        instances: &[I],
+    ) -> Result<Self, VulkanoObjectsError> {
+        Ok(Self {
+            vertex: create_cpu_accessible_vertex::<V, U, Ix, M>(allocators)?,
+            index: create_cpu_accessible_index::<V, U, Ix, M>(allocators)?,
+            instances: create_cpu_accessible_instances(
                 allocators,
-                descriptor_set_layout,
-                uniform_buffer_count,
-            ),
-        }
+                instances,
+                instance_buffer_count,
+            )?,
+            descriptor_set_layout,
+            texture: texture.cloned(),
+            uniform: PhantomData,
+        })
     }
 
-    pub fn initialize_device_local<M: Model<V, U>>(
+    /// Same as `initialize_host_accessible`, but the vertex and index buffers are in device-local
+    /// memory, uploaded through staging buffers on `transfer_queue`. This can be a queue of a
+    /// dedicated transfer family, as returned by `select_transfer_queue_family`, or the graphics
+    /// queue itself.
+    ///
+    /// Waits for the uploads to finish, so the buffers can be used on the graphics queue at once.
+    pub fn initialize_device_local<M: Model<V, U, Ix>>(
         allocators: &Allocators,
         descriptor_set_layout: Arc<DescriptorSetLayout>,
-        uniform_buffer_count: usize,
+        instance_buffer_count: usize,
+        texture: Option<&Texture>,
+        instances: &[I],
         transfer_queue: Arc<Queue>,
-    ) -> Self {
-        let (vertex, vertex_future) =
-            create_device_local_vertex::<V, U, M>(allocators, transfer_queue.clone());
-        let (index, index_future) =
-            create_device_local_index::<V, U, M>(allocators, transfer_queue);
+        graphics_queue_family_index: u32,
+    ) -> Result<Self, VulkanoObjectsError> {
+        let (vertex, vertex_future) = create_device_local_vertex::<V, U, Ix, M>(
+            allocators,
+            transfer_queue.clone(),
+            graphics_queue_family_index,
+        )?;
+        let (index, index_future) = create_device_local_index::<V, U, Ix, M>(
+            allocators,
+            transfer_queue,
+            graphics_queue_family_index,
+        )?;
 
         let fence = vertex_future
             .join(index_future)
-            .then_signal_fence_and_flush()
-            .unwrap();
+            .then_signal_fence_and_flush()?;
 
-        fence.wait(None).unwrap();
+        fence.wait(None)?;
 
-        Self {
+        Ok(Self {
             vertex,
             index,
-            uniforms: create_cpu_accessible_uniforms::<V, U, M>(
+            instances: create_cpu_accessible_instances(
                 allocators,
-                descriptor_set_layout,
-                uniform_buffer_count,
-            ),
-        }
+                instances,
+                instance_buffer_count,
+            )?,
+            descriptor_set_layout,
+            texture: texture.cloned(),
+            uniform: PhantomData,
+        })
     }
 
     pub fn get_vertex(&self) -> Subbuffer<[V]> {
         self.vertex.clone()
     }
 
-    pub fn get_index(&self) -> Subbuffer<[u16]> {
+    pub fn get_index(&self) -> Subbuffer<[Ix]> {
         self.index.clone()
     }
 
-    pub fn get_uniform_descriptor_set(&self, i: usize) -> Arc<PersistentDescriptorSet> {
-        self.uniforms[i].1.clone()
+    /// Writes `data` to a new uniform buffer, and returns a descriptor set binding it at binding 0,
+    /// and the texture given to `initialize_*`, if any, at binding 1.
+    ///
+    /// The buffer comes from `allocators.uniform_buffer`, so the uniforms of the frames that the
+    /// GPU is still rendering aren't overwritten.
+    pub fn create_uniform_descriptor_set(
+        &self,
+        allocators: &Allocators,
+        data: U,
+    ) -> Result<Arc<PersistentDescriptorSet>, VulkanoObjectsError> {
+        let buffer = allocators.uniform_buffer.allocate_sized()?;
+        *buffer.write().map_err(VulkanoObjectsError::BufferWrite)? = data;
+
+        let mut writes = vec![WriteDescriptorSet::buffer(0, buffer)];
+        if let Some(texture) = &self.texture {
+            writes.push(WriteDescriptorSet::image_view_sampler(
+                1,
+                texture.view.clone(),
+                texture.sampler.clone(),
+            ));
+        }
+
+        PersistentDescriptorSet::new(
+            &allocators.descriptor_set,
+            self.descriptor_set_layout.clone(),
+            writes,
+        )
+        .map_err(VulkanoObjectsError::from)
+    }
+
+    pub fn get_instance(&self, i: usize) -> Subbuffer<[I]> {
+        self.instances[i].clone()
     }
 }
 
-fn create_cpu_accessible_vertex<V, U, M>(allocators: &Allocators) -> Subbuffer<[V]>
+fn create_cpu_accessible_vertex<V, U, Ix, M>(
+    allocators: &Allocators,
+) -> Result<Subbuffer<[V]>, VulkanoObjectsError>
 where
     V: BufferContents,
     U: BufferContents,
-    M: Model<V, U>,
+    Ix: Index,
+    M: Model<V, U, Ix>,
 {
     Buffer::from_iter(
         &allocators.memory,
@@ -102,33 +175,35 @@ where
         },
         M::get_vertices(),
     )
-    .unwrap()
+    .map_err(VulkanoObjectsError::from)
 }
 
-fn create_device_local_vertex<V, U, M>(
+fn create_device_local_vertex<V, U, Ix, M>(
     allocators: &Allocators,
     queue: Arc<Queue>,
-) -> (Subbuffer<[V]>, CommandBufferExecFuture<NowFuture>)
+    graphics_queue_family_index: u32,
+) -> Result<(Subbuffer<[V]>, CommandBufferExecFuture<NowFuture>), VulkanoObjectsError>
 where
     V: BufferContents,
     U: BufferContents,
-    M: Model<V, U>,
+    Ix: Index,
+    M: Model<V, U, Ix>,
 {
     let vertices = M::get_vertices();
 
     let buffer = Buffer::new_slice(
         &allocators.memory,
-        BufferCreateInfo {
-            usage: BufferUsage::VERTEX_BUFFER | BufferUsage::TRANSFER_DST,
-            ..Default::default()
-        },
+        device_local_buffer_info(
+            BufferUsage::VERTEX_BUFFER | BufferUsage::TRANSFER_DST | BufferUsage::TRANSFER_SRC,
+            &queue,
+            graphics_queue_family_index,
+        ),
         AllocationCreateInfo {
             usage: MemoryUsage::DeviceOnly,
             ..Default::default()
         },
         vertices.len() as DeviceSize,
-    )
-    .unwrap();
+    )?;
 
     let staging_buffer = Buffer::from_iter(
         &allocators.memory,
@@ -141,29 +216,28 @@ where
             ..Default::default()
         },
         vertices,
-    )
-    .unwrap();
+    )?;
 
     let mut builder = AutoCommandBufferBuilder::primary(
         &allocators.command_buffer,
         queue.queue_family_index(),
         CommandBufferUsage::OneTimeSubmit,
-    )
-    .unwrap();
-    builder
-        .copy_buffer(CopyBufferInfo::buffers(staging_buffer, buffer.clone()))
-        .unwrap();
+    )?;
+    builder.copy_buffer(CopyBufferInfo::buffers(staging_buffer, buffer.clone()))?;
 
-    let future = builder.build().unwrap().execute(queue).unwrap();
+    let future = builder.build()?.execute(queue)?;
 
-    (buffer, future)
+    Ok((buffer, future))
 }
 
-fn create_cpu_accessible_index<V, U, M>(allocators: &Allocators) -> Subbuffer<[u16]>
+fn create_cpu_accessible_index<V, U, Ix, M>(
+    allocators: &Allocators,
+) -> Result<Subbuffer<[Ix]>, VulkanoObjectsError>
 where
     V: BufferContents,
     U: BufferContents,
-    M: Model<V, U>,
+    Ix: Index,
+    M: Model<V, U, Ix>,
 {
     Buffer::from_iter(
         &allocators.memory,
@@ -177,33 +251,35 @@ where
         },
         M::get_indices(),
     )
-    .unwrap()
+    .map_err(VulkanoObjectsError::from)
 }
 
-fn create_device_local_index<V, U, M>(
+fn create_device_local_index<V, U, Ix, M>(
     allocators: &Allocators,
     queue: Arc<Queue>,
-) -> (Subbuffer<[u16]>, CommandBufferExecFuture<NowFuture>)
+    graphics_queue_family_index: u32,
+) -> Result<(Subbuffer<[Ix]>, CommandBufferExecFuture<NowFuture>), VulkanoObjectsError>
 where
     V: BufferContents,
     U: BufferContents,
-    M: Model<V, U>,
+    Ix: Index,
+    M: Model<V, U, Ix>,
 {
     let indices = M::get_indices();
 
     let buffer = Buffer::new_slice(
         &allocators.memory,
-        BufferCreateInfo {
-            usage: BufferUsage::INDEX_BUFFER | BufferUsage::TRANSFER_DST,
-            ..Default::default()
-        },
+        device_local_buffer_info(
+            BufferUsage::INDEX_BUFFER | BufferUsage::TRANSFER_DST | BufferUsage::TRANSFER_SRC,
+            &queue,
+            graphics_queue_family_index,
+        ),
         AllocationCreateInfo {
             usage: MemoryUsage::DeviceOnly,
             ..Default::default()
         },
         indices.len() as DeviceSize,
-    )
-    .unwrap();
+    )?;
 
     let staging_buffer = Buffer::from_iter(
         &allocators.memory,
@@ -216,58 +292,290 @@ where
             ..Default::default()
         },
         indices,
-    )
-    .unwrap();
+    )?;
 
     let mut builder = AutoCommandBufferBuilder::primary(
         &allocators.command_buffer,
         queue.queue_family_index(),
         CommandBufferUsage::OneTimeSubmit,
-    )
-    .unwrap();
+    )?;
+    builder.copy_buffer(CopyBufferInfo::buffers(staging_buffer, buffer.clone()))?;
+
+    let future = builder.build()?.execute(queue)?;
+
+    Ok((buffer, future))
+}
+
+/// Copies `buffer` to a new host-visible buffer on `queue`, waits for the copy, and returns the
+/// contents, so that device-local buffers, like the ones of `Buffers::initialize_device_local` or
+/// the results of a compute shader, can still be checked by the host.
+///
+/// Like for the uploads, `queue` can be a queue of a dedicated transfer family, as returned by
+/// `select_transfer_queue_family`, if `buffer` is shared with it, or otherwise a queue of the
+/// family that owns `buffer`. The buffer must have been created with `BufferUsage::TRANSFER_SRC`,
+/// and the GPU must be done writing to it.
+pub fn read_device_local_buffer<T>(
+    allocators: &Allocators,
+    buffer: Subbuffer<[T]>,
+    queue: Arc<Queue>,
+) -> Result<Vec<T>, VulkanoObjectsError>
+where
+    T: BufferContents + Clone,
+{
+    let staging_buffer = Buffer::new_slice::<T>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        buffer.len(),
+    )?;
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &allocators.command_buffer,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )?;
+    builder.copy_buffer(CopyBufferInfo::buffers(buffer, staging_buffer.clone()))?;
+
     builder
-        .copy_buffer(CopyBufferInfo::buffers(staging_buffer, buffer.clone()))
-        .unwrap();
+        .build()?
+        .execute(queue)?
+        .then_signal_fence_and_flush()?
+        .wait(None)?;
 
-    let future = builder.build().unwrap().execute(queue).unwrap();
+    // The copy is done, so nothing else is using the buffer.
+    let contents = staging_buffer.read().unwrap().to_vec();
 
-    (buffer, future)
+    Ok(contents)
 }
 
-fn create_cpu_accessible_uniforms<V, U, M>(
+// The device-local buffers are written on `transfer_queue` and then read on the graphics queue. If
+// these are of different families, an exclusive buffer would have to be released by one and
+// acquired by the other with a pair of barriers, which vulkano's command buffer builder can't
+// record, so the buffer is shared concurrently by both families instead.
+fn device_local_buffer_info(
+    usage: BufferUsage,
+    transfer_queue: &Queue,
+    graphics_queue_family_index: u32,
+) -> BufferCreateInfo {
+    let transfer_queue_family_index = transfer_queue.queue_family_index();
+
+    let sharing = if transfer_queue_family_index == graphics_queue_family_index {
+        Sharing::Exclusive
+    } else {
+        Sharing::Concurrent(
+            [transfer_queue_family_index, graphics_queue_family_index]
+                .into_iter()
+                .collect(),
+        )
+    };
+
+    BufferCreateInfo {
+        sharing,
+        usage,
+        ..Default::default()
+    }
+}
+
+// The instances are rewritten by the host every frame, so they are kept in host-visible memory.
+fn create_cpu_accessible_instances<I>(
     allocators: &Allocators,
-    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    instances: &[I],
     buffer_count: usize,
-) -> Vec<Uniform<U>>
+) -> Result<Vec<Subbuffer<[I]>>, VulkanoObjectsError>
 where
-    V: BufferContents,
-    U: BufferContents,
-    M: Model<V, U>,
+    I: BufferContents + Clone,
 {
     (0..buffer_count)
         .map(|_| {
-            let buffer = Buffer::from_data(
+            Buffer::from_iter(
                 &allocators.memory,
                 BufferCreateInfo {
-                    usage: BufferUsage::INDEX_BUFFER,
+                    usage: BufferUsage::VERTEX_BUFFER,
                     ..Default::default()
                 },
                 AllocationCreateInfo {
                     usage: MemoryUsage::Upload,
                     ..Default::default()
                 },
-                M::get_initial_uniform_data(),
+                instances.iter().cloned(),
             )
+            .map_err(VulkanoObjectsError::from)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use vulkano::descriptor_set::layout::{
+        DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType,
+    };
+    use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+    use vulkano::shader::ShaderStages;
+
+    use super::*;
+    use crate::models::SquareModel;
+    use crate::shaders::movable_square;
+    use crate::vulkano_objects::instance::get_headless_instance;
+    use crate::{Instance2d, Vertex2d};
+
+    // A grid of `GRID_SIZE` by `GRID_SIZE` vertices, too many to be indexed with `u16`.
+    const GRID_SIZE: u32 = 300;
+
+    struct GridModel;
+
+    impl Model<Vertex2d, movable_square::vs::Data, u32> for GridModel {
+        fn get_indices() -> Vec<u32> {
+            let mut indices = Vec::new();
+            for y in 0..GRID_SIZE - 1 {
+                for x in 0..GRID_SIZE - 1 {
+                    let i = y * GRID_SIZE + x;
+                    indices.extend([
+                        i,
+                        i + 1,
+                        i + GRID_SIZE,
+                        i + 1,
+                        i + GRID_SIZE,
+                        i + GRID_SIZE + 1,
+                    ]);
+                }
+            }
+            indices
+        }
+
+        fn get_vertices() -> Vec<Vertex2d> {
+            let step = 2.0 / (GRID_SIZE - 1) as f32;
+            (0..GRID_SIZE * GRID_SIZE)
+                .map(|i| Vertex2d {
+                    position: [
+                        (i % GRID_SIZE) as f32 * step - 1.0,
+                        (i / GRID_SIZE) as f32 * step - 1.0,
+                    ],
+                })
+                .collect()
+        }
+
+        fn get_initial_uniform_data() -> movable_square::vs::Data {
+            SquareModel::get_initial_uniform_data()
+        }
+    }
+
+    fn create_device() -> (Arc<Device>, Arc<Queue>) {
+        let physical_device = get_headless_instance()
+            .unwrap()
+            .enumerate_physical_devices()
+            .unwrap()
+            .next()
+            .expect("no devices available");
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::GRAPHICS))
+            .unwrap() as u32;
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        (device, queues.next().unwrap())
+    }
+
+    // The uniform buffer binding of the set used by the `movable_square` shaders.
+    fn create_descriptor_set_layout(device: Arc<Device>) -> Arc<DescriptorSetLayout> {
+        DescriptorSetLayout::new(
+            device,
+            DescriptorSetLayoutCreateInfo {
+                bindings: BTreeMap::from([(
+                    0,
+                    DescriptorSetLayoutBinding {
+                        stages: ShaderStages::VERTEX,
+                        ..DescriptorSetLayoutBinding::descriptor_type(DescriptorType::UniformBuffer)
+                    },
+                )]),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn grid_model_needs_u32_indices() {
+        let indices = GridModel::get_indices();
+
+        assert_eq!(indices.len() as u32, (GRID_SIZE - 1) * (GRID_SIZE - 1) * 6);
+        assert!(indices.iter().any(|&i| i > u16::MAX as u32));
+        assert!(indices.iter().all(|&i| i < GRID_SIZE * GRID_SIZE));
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan driver"]
+    fn square_buffers() {
+        let (device, _) = create_device();
+        let descriptor_set_layout = create_descriptor_set_layout(device.clone());
+
+        let allocators = Allocators::new(device);
+        let instances = [Instance2d {
+            offset: [0.0, 0.0],
+            scale: 1.0,
+            color: [1.0, 0.0, 0.0],
+        }];
+        let buffers =
+            Buffers::<Vertex2d, movable_square::vs::Data, Instance2d>::initialize_host_accessible::<
+                SquareModel,
+            >(&allocators, descriptor_set_layout, 2, None, &instances)
             .unwrap();
 
-            let descriptor_set = PersistentDescriptorSet::new(
-                &allocators.descriptor_set,
-                descriptor_set_layout.clone(),
-                [WriteDescriptorSet::buffer(0, buffer.clone())],
-            )
+        assert_eq!(buffers.instances.len(), 2);
+        // Fails if the uniform buffer doesn't have the usage that the descriptor needs.
+        buffers
+            .create_uniform_descriptor_set(&allocators, SquareModel::get_initial_uniform_data())
             .unwrap();
+    }
 
-            (buffer, descriptor_set)
-        })
-        .collect()
+    #[test]
+    #[ignore = "needs a Vulkan driver"]
+    fn u32_indexed_buffers() {
+        let (device, queue) = create_device();
+        let descriptor_set_layout = create_descriptor_set_layout(device.clone());
+
+        let allocators = Allocators::new(device);
+        let instances = [Instance2d {
+            offset: [0.0, 0.0],
+            scale: 1.0,
+            color: [1.0, 0.0, 0.0],
+        }];
+        let buffers = Buffers::<_, _, _, u32>::initialize_device_local::<GridModel>(
+            &allocators,
+            descriptor_set_layout,
+            1,
+            None,
+            &instances,
+            queue.clone(),
+            queue.queue_family_index(),
+        )
+        .unwrap();
+
+        assert_eq!(buffers.vertex.len(), (GRID_SIZE * GRID_SIZE) as DeviceSize);
+        assert_eq!(
+            buffers.get_index().len(),
+            GridModel::get_indices().len() as DeviceSize
+        );
+
+        let indices = read_device_local_buffer(&allocators, buffers.get_index(), queue).unwrap();
+        assert_eq!(indices, GridModel::get_indices());
+    }
 }
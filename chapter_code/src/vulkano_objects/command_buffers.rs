@@ -5,20 +5,30 @@ use vulkano::command_buffer::{
     AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
     SubpassContents,
 };
+use vulkano::descriptor_set::PersistentDescriptorSet;
 use vulkano::device::Queue;
+use vulkano::format::ClearValue;
+use vulkano::image::ImageAspects;
+use vulkano::pipeline::graphics::input_assembly::Index;
+use vulkano::pipeline::graphics::viewport::Viewport;
 use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
-use vulkano::render_pass::Framebuffer;
+use vulkano::render_pass::{Framebuffer, LoadOp};
 
 use super::allocators::Allocators;
 use crate::vulkano_objects::buffers::Buffers;
-use crate::Vertex2d;
+use crate::GpuTimer;
 
-pub fn create_only_vertex_command_buffers(
+/// The dark grey that the color attachments are cleared with by the examples that don't pick
+/// another color.
+pub const DEFAULT_CLEAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+pub fn create_only_vertex_command_buffers<V: BufferContents>(
     allocators: &Allocators,
     queue: Arc<Queue>,
     pipeline: Arc<GraphicsPipeline>,
     framebuffers: &[Arc<Framebuffer>],
-    vertex_buffer: Subbuffer<[Vertex2d]>,
+    vertex_buffer: Subbuffer<[V]>,
+    clear_color: [f32; 4],
 ) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
     framebuffers
         .iter()
@@ -33,13 +43,14 @@ pub fn create_only_vertex_command_buffers(
             builder
                 .begin_render_pass(
                     RenderPassBeginInfo {
-                        clear_values: vec![Some([0.1, 0.1, 0.1, 1.0].into())],
+                        clear_values: clear_values(framebuffer, clear_color),
                         ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
                     },
                     SubpassContents::Inline,
                 )
                 .unwrap()
                 .bind_pipeline_graphics(pipeline.clone())
+                .set_viewport(0, [viewport(framebuffer)])
                 .bind_vertex_buffers(0, vertex_buffer.clone())
                 .draw(vertex_buffer.len() as u32, 1, 0, 0)
                 .unwrap()
@@ -51,51 +62,164 @@ pub fn create_only_vertex_command_buffers(
         .collect()
 }
 
-pub fn create_simple_command_buffers<V: BufferContents, U: BufferContents>(
+/// Creates a command buffer drawing every instance of the model of `buffers` to `framebuffer`,
+/// with the instance buffer at `instance_buffer_i` and the uniforms bound by `uniform_set`.
+///
+/// The uniforms are in a new buffer every frame, so a new command buffer is recorded every frame
+/// as well.
+///
+/// If `gpu_timer` is given, it measures how long the GPU takes to execute the command buffer.
+pub fn create_simple_command_buffer<V, U, I, Ix>(
     allocators: &Allocators,
     queue: Arc<Queue>,
     pipeline: Arc<GraphicsPipeline>,
-    framebuffers: &[Arc<Framebuffer>],
-    buffers: &Buffers<V, U>,
-) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
-    framebuffers
-        .iter()
-        .enumerate()
-        .map(|(i, framebuffer)| {
-            let mut builder = AutoCommandBufferBuilder::primary(
-                &allocators.command_buffer,
-                queue.queue_family_index(),
-                CommandBufferUsage::MultipleSubmit,
-            )
-            .unwrap();
+    framebuffer: Arc<Framebuffer>,
+    buffers: &Buffers<V, U, I, Ix>,
+    instance_buffer_i: usize,
+    uniform_set: Arc<PersistentDescriptorSet>,
+    clear_color: [f32; 4],
+    gpu_timer: Option<&GpuTimer>,
+) -> Arc<PrimaryAutoCommandBuffer>
+where
+    V: BufferContents,
+    U: BufferContents,
+    I: BufferContents + Clone,
+    Ix: Index,
+{
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &allocators.command_buffer,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
 
-            let index_buffer = buffers.get_index();
-            let index_buffer_length = index_buffer.len();
+    if let Some(gpu_timer) = gpu_timer {
+        gpu_timer.begin(&mut builder);
+    }
 
-            builder
-                .begin_render_pass(
-                    RenderPassBeginInfo {
-                        clear_values: vec![Some([0.1, 0.1, 0.1, 1.0].into())],
-                        ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                    },
-                    SubpassContents::Inline,
-                )
-                .unwrap()
-                .bind_pipeline_graphics(pipeline.clone())
-                .bind_descriptor_sets(
-                    PipelineBindPoint::Graphics,
-                    pipeline.layout().clone(),
-                    0,
-                    buffers.get_uniform_descriptor_set(i),
-                )
-                .bind_vertex_buffers(0, buffers.get_vertex())
-                .bind_index_buffer(index_buffer)
-                .draw_indexed(index_buffer_length as u32, 1, 0, 0, 0)
-                .unwrap()
-                .end_render_pass()
-                .unwrap();
+    let index_buffer = buffers.get_index();
+    let index_buffer_length = index_buffer.len();
+    let instance_buffer = buffers.get_instance(instance_buffer_i);
+    let instance_count = instance_buffer.len();
 
-            Arc::new(builder.build().unwrap())
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: clear_values(&framebuffer, clear_color),
+                ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .set_viewport(0, [viewport(&framebuffer)])
+        .bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            uniform_set,
+        )
+        .bind_vertex_buffers(0, (buffers.get_vertex(), instance_buffer))
+        .bind_index_buffer(index_buffer)
+        .draw_indexed(index_buffer_length as u32, instance_count as u32, 0, 0, 0)
+        .unwrap()
+        .end_render_pass()
+        .unwrap();
+
+    if let Some(gpu_timer) = gpu_timer {
+        gpu_timer.end(&mut builder);
+    }
+
+    Arc::new(builder.build().unwrap())
+}
+
+/// Same as `create_simple_command_buffer`, but for a single object whose data, like its
+/// transformation and color, is passed as push constants instead of through a uniform buffer and a
+/// descriptor set.
+///
+/// The pushed values are part of the command buffer, so a new one is recorded every frame.
+/// `push_constants` must match the `layout(push_constant)` block of
+/// the shaders, from which the push constant range of the pipeline layout is taken.
+pub fn create_push_constants_command_buffer<V, Ix, P>(
+    allocators: &Allocators,
+    queue: Arc<Queue>,
+    pipeline: Arc<GraphicsPipeline>,
+    framebuffer: Arc<Framebuffer>,
+    vertex_buffer: Subbuffer<[V]>,
+    index_buffer: Subbuffer<[Ix]>,
+    push_constants: P,
+    clear_color: [f32; 4],
+) -> Arc<PrimaryAutoCommandBuffer>
+where
+    V: BufferContents,
+    Ix: Index,
+    P: BufferContents,
+{
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &allocators.command_buffer,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+
+    let index_buffer_length = index_buffer.len();
+
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: clear_values(&framebuffer, clear_color),
+                ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .set_viewport(0, [viewport(&framebuffer)])
+        .push_constants(pipeline.layout().clone(), 0, push_constants)
+        .bind_vertex_buffers(0, vertex_buffer)
+        .bind_index_buffer(index_buffer)
+        .draw_indexed(index_buffer_length as u32, 1, 0, 0, 0)
+        .unwrap()
+        .end_render_pass()
+        .unwrap();
+
+    Arc::new(builder.build().unwrap())
+}
+
+// The values the attachments of `framebuffer` are cleared with: `clear_color` for the color ones,
+// and the far plane for the depth one if the render pass has one. The attachments that aren't
+// cleared, like the resolve attachment of a multisampled render pass, must not be given a value.
+fn clear_values(framebuffer: &Framebuffer, clear_color: [f32; 4]) -> Vec<Option<ClearValue>> {
+    framebuffer
+        .render_pass()
+        .attachments()
+        .iter()
+        .map(|attachment| {
+            if attachment.load_op != LoadOp::Clear {
+                return None;
+            }
+
+            let is_depth = attachment.format.map_or(false, |format| {
+                format.aspects().intersects(ImageAspects::DEPTH)
+            });
+
+            if is_depth {
+                Some(1.0.into())
+            } else {
+                Some(clear_color.into())
+            }
         })
         .collect()
 }
+
+// The pipelines of `vulkano_objects::pipeline` have a dynamic viewport, which covers the whole
+// framebuffer. It is set when recording, so that the pipelines don't depend on the window size.
+fn viewport(framebuffer: &Framebuffer) -> Viewport {
+    let [width, height] = framebuffer.extent();
+
+    Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [width as f32, height as f32],
+        depth_range: 0.0..1.0,
+    }
+}
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use vulkano::buffer::Subbuffer;
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::{ComputePipeline, Pipeline};
+use vulkano::shader::EntryPoint;
+
+use super::VulkanoObjectsError;
+
+/// Creates a pipeline running the compute shader `entry_point`, with a layout made of the
+/// descriptor sets and push constants that the shader uses.
+pub fn create_compute_pipeline(
+    device: Arc<Device>,
+    entry_point: EntryPoint,
+) -> Result<Arc<ComputePipeline>, VulkanoObjectsError> {
+    create_compute_pipeline_with_cache(device, entry_point, None)
+}
+
+/// Same as `create_compute_pipeline`, but the compiled shader is looked up in and added to
+/// `pipeline_cache`, if given, like with `pipeline::create_pipeline_with_cache`.
+pub fn create_compute_pipeline_with_cache(
+    device: Arc<Device>,
+    entry_point: EntryPoint,
+    pipeline_cache: Option<Arc<PipelineCache>>,
+) -> Result<Arc<ComputePipeline>, VulkanoObjectsError> {
+    ComputePipeline::new(device, entry_point, &(), pipeline_cache, |_| {})
+        .map_err(VulkanoObjectsError::from)
+}
+
+/// Creates the descriptor set 0 of `pipeline` with `buffer` bound to binding 0, for the compute
+/// shaders that work on a single storage buffer, like the one of the `compute_pipeline` example:
+///
+/// ```glsl
+/// layout(set = 0, binding = 0) buffer Data {
+///     uint data[];
+/// } buf;
+/// ```
+///
+/// The buffer must have been created with `BufferUsage::STORAGE_BUFFER`.
+///
+/// Panics if the shader of `pipeline` doesn't use any descriptor set.
+pub fn create_storage_buffer_descriptor_set<T: ?Sized>(
+    allocator: &StandardDescriptorSetAllocator,
+    pipeline: &ComputePipeline,
+    buffer: Subbuffer<T>,
+) -> Result<Arc<PersistentDescriptorSet>, VulkanoObjectsError> {
+    let layout = pipeline
+        .layout()
+        .set_layouts()
+        .get(0)
+        .expect("the compute shader has no descriptor set");
+
+    PersistentDescriptorSet::new(
+        allocator,
+        layout.clone(),
+        [WriteDescriptorSet::buffer(0, buffer)],
+    )
+    .map_err(VulkanoObjectsError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
+    use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+    use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+    use vulkano::device::{DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+    use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+    use vulkano::pipeline::PipelineBindPoint;
+    use vulkano::sync::{self, GpuFuture};
+
+    use super::*;
+    use crate::vulkano_objects::instance::get_headless_instance;
+
+    mod cs {
+        vulkano_shaders::shader! {
+            ty: "compute",
+            src: r"
+                #version 460
+
+                layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+                layout(set = 0, binding = 0) buffer Data {
+                    uint data[];
+                } buf;
+
+                void main() {
+                    uint idx = gl_GlobalInvocationID.x;
+                    buf.data[idx] *= 12;
+                }
+            ",
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Vulkan driver"]
+    fn multiply_storage_buffer() {
+        let physical_device = get_headless_instance()
+            .unwrap()
+            .enumerate_physical_devices()
+            .unwrap()
+            .next()
+            .expect("no devices available");
+        let queue_family_index = physical_device
+            .queue_family_properties()
+            .iter()
+            .position(|q| q.queue_flags.contains(QueueFlags::COMPUTE))
+            .unwrap() as u32;
+        let (device, mut queues) = Device::new(
+            physical_device,
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let queue = queues.next().unwrap();
+
+        let memory_allocator = StandardMemoryAllocator::new_default(device.clone());
+        let buffer = Buffer::from_iter(
+            &memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+            0..128u32,
+        )
+        .unwrap();
+
+        let shader = cs::load(device.clone()).unwrap();
+        let pipeline =
+            create_compute_pipeline(device.clone(), shader.entry_point("main").unwrap()).unwrap();
+
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+        let descriptor_set = create_storage_buffer_descriptor_set(
+            &descriptor_set_allocator,
+            &pipeline,
+            buffer.clone(),
+        )
+        .unwrap();
+
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue_family_index,
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipeline.layout().clone(),
+                0,
+                descriptor_set,
+            )
+            .dispatch([2, 1, 1])
+            .unwrap();
+
+        sync::now(device)
+            .then_execute(queue, builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let content = buffer.read().unwrap();
+        assert!(content.iter().enumerate().all(|(i, &x)| x == i as u32 * 12));
+    }
+}
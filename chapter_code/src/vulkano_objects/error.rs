@@ -0,0 +1,78 @@
+use thiserror::Error;
+use vulkano::buffer::BufferError;
+use vulkano::command_buffer::{
+    BuildError, CommandBufferBeginError, CommandBufferExecError, CopyError,
+};
+use vulkano::descriptor_set::DescriptorSetCreationError;
+use vulkano::device::physical::PhysicalDeviceError;
+use vulkano::instance::InstanceCreationError;
+use vulkano::memory::allocator::MemoryAllocatorError;
+use vulkano::pipeline::compute::ComputePipelineCreationError;
+use vulkano::pipeline::graphics::GraphicsPipelineCreationError;
+use vulkano::render_pass::RenderPassCreationError;
+use vulkano::swapchain::SwapchainCreationError;
+use vulkano::sync::FlushError;
+use vulkano::{LoadingError, VulkanError};
+
+/// Error returned by the helpers of `vulkano_objects` that create the instance, the swapchain,
+/// the render passes, the pipelines, the descriptor sets and the `Buffers`, saying which step
+/// failed along with the error vulkano returned.
+///
+/// The examples can't do much but stop when one of these fails, so they `expect` them, but an
+/// application could for example try again with other settings.
+#[derive(Debug, Error)]
+pub enum VulkanoObjectsError {
+    #[error("failed to load the Vulkan library, is a Vulkan driver installed? ({0})")]
+    LibraryLoading(#[from] LoadingError),
+
+    #[error("failed to create the instance: {0}")]
+    InstanceCreation(#[from] InstanceCreationError),
+
+    #[error("failed to query the surface: {0}")]
+    SurfaceQuery(#[from] PhysicalDeviceError),
+
+    #[error("failed to create the swapchain: {0}")]
+    SwapchainCreation(#[from] SwapchainCreationError),
+
+    #[error("failed to create the render pass: {0}")]
+    RenderPassCreation(#[from] RenderPassCreationError),
+
+    #[error("the {0} shader has no `main` entry point")]
+    MissingEntryPoint(&'static str),
+
+    #[error("failed to create the graphics pipeline: {0}")]
+    PipelineCreation(#[from] GraphicsPipelineCreationError),
+
+    #[error("failed to create the compute pipeline: {0}")]
+    ComputePipelineCreation(#[from] ComputePipelineCreationError),
+
+    #[error("failed to create a descriptor set: {0}")]
+    DescriptorSetCreation(#[from] DescriptorSetCreationError),
+
+    #[error("failed to create a buffer: {0}")]
+    BufferCreation(#[from] BufferError),
+
+    #[error("failed to allocate a uniform buffer: {0}")]
+    UniformBufferAllocation(#[from] MemoryAllocatorError),
+
+    #[error("failed to write to a buffer: {0}")]
+    BufferWrite(BufferError),
+
+    #[error("failed to begin a command buffer: {0}")]
+    CommandBufferBegin(#[from] CommandBufferBeginError),
+
+    #[error("failed to record a copy: {0}")]
+    Copy(#[from] CopyError),
+
+    #[error("failed to build a command buffer: {0}")]
+    CommandBufferBuild(#[from] BuildError),
+
+    #[error("failed to execute a command buffer: {0}")]
+    CommandBufferExecution(#[from] CommandBufferExecError),
+
+    #[error("failed to submit to or wait for the GPU: {0}")]
+    Flush(#[from] FlushError),
+
+    #[error("a Vulkan function failed: {0}")]
+    Vulkan(#[from] VulkanError),
+}
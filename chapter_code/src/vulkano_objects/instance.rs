@@ -1,17 +1,50 @@
+use std::env;
 use std::sync::Arc;
 
-use vulkano::instance::{Instance, InstanceCreateInfo, LayerProperties};
+use vulkano::instance::debug::{
+    DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+    DebugUtilsMessengerCreateInfo, Message,
+};
+use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions, LayerProperties};
+use vulkano::{VulkanError, VulkanLibrary};
+
+use super::VulkanoObjectsError;
 
 const LIST_AVAILABLE_LAYERS: bool = false;
-const ENABLE_VALIDATION_LAYERS: bool = false;
-const VALIDATION_LAYERS: &[&str] = &["VK_LAYER_LUNARG_api_dump"];
 
-pub fn get_instance() -> Arc<Instance> {
-    let library = vulkano::VulkanLibrary::new().expect("no local Vulkan library/DLL");
-    let required_extensions = vulkano_win::required_extensions(&library);
+/// Environment variable enabling the validation layers when set to `1`, for example with
+/// `VULKANO_VALIDATION=1 cargo run --bin windowing`. Their messages are printed by the messenger
+/// of `create_debug_messenger`.
+pub const VALIDATION_ENV_VAR: &str = "VULKANO_VALIDATION";
+
+/// Environment variable with a comma-separated list of the layers to enable instead of
+/// `DEFAULT_VALIDATION_LAYERS` when the validation layers are enabled.
+pub const VALIDATION_LAYERS_ENV_VAR: &str = "VULKANO_VALIDATION_LAYERS";
+
+/// The Khronos validation layer, which checks the API calls against the specification. Other
+/// layers, like `VK_LAYER_LUNARG_api_dump` to log every call, can be enabled with
+/// `VALIDATION_LAYERS_ENV_VAR`.
+pub const DEFAULT_VALIDATION_LAYERS: &[&str] = &["VK_LAYER_KHRONOS_validation"];
+
+/// Creates an instance with the extensions needed to present to a window, and the validation
+/// layers if `VALIDATION_ENV_VAR` is set. Fails if there is no Vulkan driver.
+pub fn get_instance() -> Result<Arc<Instance>, VulkanoObjectsError> {
+    get_instance_with_extensions(InstanceExtensions::empty())
+}
+
+/// Same as `get_instance`, but also enables `extensions`, for example the ones an OpenXR runtime
+/// asks for.
+pub fn get_instance_with_extensions(
+    extensions: InstanceExtensions,
+) -> Result<Arc<Instance>, VulkanoObjectsError> {
+    let library = vulkano::VulkanLibrary::new()?;
+    let required_extensions = surface_extensions(&library)
+        .union(&extensions)
+        .union(&debug_extensions(&library))
+        .union(&portability_extensions(&library));
 
     if LIST_AVAILABLE_LAYERS {
-        let layers: Vec<_> = library.layer_properties().unwrap().collect();
+        let layers: Vec<_> = library.layer_properties()?.collect();
         let layer_names = layers.iter().map(LayerProperties::name);
         println!(
             "Available layers:\n {:?}",
@@ -19,14 +52,214 @@ pub fn get_instance() -> Arc<Instance> {
         );
     }
 
-    let mut create_info = InstanceCreateInfo {
+    let create_info = InstanceCreateInfo {
         enabled_extensions: required_extensions,
+        enabled_layers: validation_layers(&library)?,
+        enumerate_portability: supports_portability_enumeration(&library),
         ..Default::default()
     };
 
-    if ENABLE_VALIDATION_LAYERS {
-        create_info.enabled_layers = VALIDATION_LAYERS.iter().map(|s| s.to_string()).collect();
+    Instance::new(library, create_info).map_err(VulkanoObjectsError::from)
+}
+
+// The layers to enable according to `VALIDATION_ENV_VAR` and `VALIDATION_LAYERS_ENV_VAR`. The ones
+// that aren't installed are left out with a warning, as creating the instance would fail otherwise.
+fn validation_layers(library: &VulkanLibrary) -> Result<Vec<String>, VulkanError> {
+    if !validation_enabled() {
+        return Ok(Vec::new());
     }
 
-    Instance::new(library, create_info).unwrap()
+    let requested = requested_layers(env::var(VALIDATION_LAYERS_ENV_VAR).ok().as_deref());
+    let available: Vec<_> = library.layer_properties()?.collect();
+    let available_names: Vec<_> = available.iter().map(LayerProperties::name).collect();
+    let (layers, missing) = split_available_layers(requested, &available_names);
+
+    for layer in missing {
+        println!(
+            "Warning: {} is set but the {} layer isn't installed, continuing without it",
+            VALIDATION_ENV_VAR, layer,
+        );
+    }
+
+    Ok(layers)
+}
+
+fn requested_layers(env_value: Option<&str>) -> Vec<String> {
+    match env_value {
+        Some(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        None => DEFAULT_VALIDATION_LAYERS
+            .iter()
+            .map(|&layer| layer.to_owned())
+            .collect(),
+    }
+}
+
+// Returns the layers of `requested` that are in `available`, and the ones that aren't.
+fn split_available_layers(
+    requested: Vec<String>,
+    available: &[&str],
+) -> (Vec<String>, Vec<String>) {
+    requested
+        .into_iter()
+        .partition(|layer| available.contains(&layer.as_str()))
+}
+
+/// Whether the validation layers were enabled with `VALIDATION_ENV_VAR`.
+pub fn validation_enabled() -> bool {
+    env::var(VALIDATION_ENV_VAR).as_deref() == Ok("1")
+}
+
+// `ext_debug_utils`, which `create_debug_messenger` needs, when the validation layers are enabled
+// and the driver supports it.
+fn debug_extensions(library: &VulkanLibrary) -> InstanceExtensions {
+    InstanceExtensions {
+        ext_debug_utils: validation_enabled() && library.supported_extensions().ext_debug_utils,
+        ..InstanceExtensions::empty()
+    }
+}
+
+/// Creates a messenger printing the messages of the validation layers, if they were enabled with
+/// `VALIDATION_ENV_VAR` when `instance` was created. Otherwise, returns `None`.
+///
+/// Messages are only printed while the messenger is alive, so it must be kept for as long as the
+/// instance, for example in a field of the renderer.
+pub fn create_debug_messenger(instance: &Arc<Instance>) -> Option<DebugUtilsMessenger> {
+    if !instance.enabled_extensions().ext_debug_utils {
+        return None;
+    }
+
+    // Safety: the callback doesn't call any Vulkan function.
+    let messenger = unsafe {
+        DebugUtilsMessenger::new(
+            instance.clone(),
+            DebugUtilsMessengerCreateInfo {
+                message_severity: DebugUtilsMessageSeverity::ERROR
+                    | DebugUtilsMessageSeverity::WARNING
+                    | DebugUtilsMessageSeverity::INFO,
+                message_type: DebugUtilsMessageType::GENERAL
+                    | DebugUtilsMessageType::VALIDATION
+                    | DebugUtilsMessageType::PERFORMANCE,
+                ..DebugUtilsMessengerCreateInfo::user_callback(Arc::new(print_message))
+            },
+        )
+    };
+
+    Some(messenger.expect("failed to create debug messenger"))
+}
+
+fn print_message(message: &Message) {
+    let severity = if message
+        .severity
+        .intersects(DebugUtilsMessageSeverity::ERROR)
+    {
+        "error"
+    } else if message
+        .severity
+        .intersects(DebugUtilsMessageSeverity::WARNING)
+    {
+        "warning"
+    } else if message.severity.intersects(DebugUtilsMessageSeverity::INFO) {
+        "info"
+    } else {
+        "verbose"
+    };
+
+    let ty = if message.ty.intersects(DebugUtilsMessageType::VALIDATION) {
+        "validation"
+    } else if message.ty.intersects(DebugUtilsMessageType::PERFORMANCE) {
+        "performance"
+    } else {
+        "general"
+    };
+
+    println!(
+        "[{} {} {}] {}",
+        message.layer_prefix.unwrap_or("unknown"),
+        ty,
+        severity,
+        message.description,
+    );
+}
+
+// Portability implementations, like MoltenVK on macOS, don't support all of Vulkan. Their devices
+// are only listed if the instance is created with `khr_portability_enumeration` and
+// `enumerate_portability`, so both are enabled when the loader supports them.
+fn supports_portability_enumeration(library: &VulkanLibrary) -> bool {
+    library.supported_extensions().khr_portability_enumeration
+}
+
+fn portability_extensions(library: &VulkanLibrary) -> InstanceExtensions {
+    InstanceExtensions {
+        khr_portability_enumeration: supports_portability_enumeration(library),
+        ..InstanceExtensions::empty()
+    }
+}
+
+// The extensions needed to create a surface for a window.
+#[cfg(not(any(target_os = "android", target_os = "macos")))]
+fn surface_extensions(library: &VulkanLibrary) -> InstanceExtensions {
+    vulkano_win::required_extensions(library)
+}
+
+// On Android the window comes from the activity instead of winit.
+#[cfg(target_os = "android")]
+fn surface_extensions(_library: &VulkanLibrary) -> InstanceExtensions {
+    InstanceExtensions {
+        khr_surface: true,
+        khr_android_surface: true,
+        ..InstanceExtensions::empty()
+    }
+}
+
+// On macOS, surfaces are created from a `CAMetalLayer`, which MoltenVK renders to.
+#[cfg(target_os = "macos")]
+fn surface_extensions(_library: &VulkanLibrary) -> InstanceExtensions {
+    InstanceExtensions {
+        khr_surface: true,
+        ext_metal_surface: true,
+        ..InstanceExtensions::empty()
+    }
+}
+
+/// Same as `get_instance`, but without the extensions needed to present to a window, for
+/// examples that only render offscreen.
+pub fn get_headless_instance() -> Result<Arc<Instance>, VulkanoObjectsError> {
+    let library = vulkano::VulkanLibrary::new()?;
+
+    let create_info = InstanceCreateInfo {
+        enabled_extensions: debug_extensions(&library).union(&portability_extensions(&library)),
+        enabled_layers: validation_layers(&library)?,
+        enumerate_portability: supports_portability_enumeration(&library),
+        ..Default::default()
+    };
+
+    Instance::new(library, create_info).map_err(VulkanoObjectsError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_layer_list() {
+        assert_eq!(requested_layers(None), ["VK_LAYER_KHRONOS_validation"]);
+        assert_eq!(
+            requested_layers(Some(
+                "VK_LAYER_KHRONOS_validation, VK_LAYER_LUNARG_api_dump,"
+            )),
+            ["VK_LAYER_KHRONOS_validation", "VK_LAYER_LUNARG_api_dump"]
+        );
+
+        let (layers, missing) = split_available_layers(
+            requested_layers(Some("VK_LAYER_KHRONOS_validation,VK_LAYER_LUNARG_api_dump")),
+            &["VK_LAYER_KHRONOS_validation"],
+        );
+        assert_eq!(layers, ["VK_LAYER_KHRONOS_validation"]);
+        assert_eq!(missing, ["VK_LAYER_LUNARG_api_dump"]);
+    }
 }
@@ -1,8 +1,15 @@
 pub mod allocators;
 pub mod buffers;
 pub mod command_buffers;
+pub mod compute;
+pub mod error;
 pub mod instance;
 pub mod physical_device;
 pub mod pipeline;
+pub mod pipeline_cache;
 pub mod render_pass;
+pub mod screenshot;
 pub mod swapchain;
+pub mod texture;
+
+pub use error::VulkanoObjectsError;
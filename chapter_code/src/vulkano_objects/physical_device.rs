@@ -1,20 +1,55 @@
+use std::env;
 use std::sync::Arc;
 
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::device::{DeviceExtensions, QueueFlags};
+use vulkano::device::{DeviceExtensions, Features, QueueFlags};
+use vulkano::image::{SampleCount, SampleCounts};
 use vulkano::instance::Instance;
 use vulkano::swapchain::Surface;
 
+use super::instance::validation_enabled;
+
+/// Environment variable forcing the device `select_physical_device` picks, for example on a laptop
+/// with two GPUs. It is either the index of the device in the list printed by
+/// `select_physical_device`, or a part of its name, like `VULKANO_DEVICE=nvidia`.
+pub const DEVICE_ENV_VAR: &str = "VULKANO_DEVICE";
+
+/// Returns the device supporting `device_extensions` and presenting to `surface` that
+/// `DEVICE_ENV_VAR` names, or if it is unset or names no such device, the one that is likely the
+/// most powerful, along with the index of its graphics queue family.
 pub fn select_physical_device(
     instance: &Arc<Instance>,
     surface: Arc<Surface>,
     device_extensions: &DeviceExtensions,
 ) -> (Arc<PhysicalDevice>, u32) {
-    instance
+    select_physical_device_with_features(instance, surface, device_extensions, &Features::empty())
+}
+
+/// Same as `select_physical_device`, but only among the devices supporting `required_features`,
+/// for examples that need some, like `fill_mode_non_solid` to draw wireframes. The same features
+/// must be given to `DeviceCreateInfo::enabled_features` when creating the device.
+///
+/// Panics with the names of the missing features if they are what no device supports.
+pub fn select_physical_device_with_features(
+    instance: &Arc<Instance>,
+    surface: Arc<Surface>,
+    device_extensions: &DeviceExtensions,
+    required_features: &Features,
+) -> (Arc<PhysicalDevice>, u32) {
+    let physical_devices: Vec<_> = instance
         .enumerate_physical_devices()
         .expect("failed to enumerate physical devices")
-        .filter(|p| p.supported_extensions().contains(device_extensions))
-        .filter_map(|p| {
+        .collect();
+
+    for (i, p) in physical_devices.iter().enumerate() {
+        println!("Device {}: {}", i, p.properties().device_name);
+    }
+
+    let candidates: Vec<_> = physical_devices
+        .into_iter()
+        .enumerate()
+        .filter(|(_, p)| p.supported_extensions().contains(device_extensions))
+        .filter_map(|(device_i, p)| {
             p.queue_family_properties()
                 .iter()
                 .enumerate()
@@ -22,14 +57,268 @@ pub fn select_physical_device(
                     q.queue_flags.contains(QueueFlags::GRAPHICS)
                         && p.surface_support(i as u32, &surface).unwrap_or(false)
                 })
-                .map(|q| (p, q as u32))
+                .map(|q| (device_i, p, q as u32))
+        })
+        .collect();
+
+    if let Some(missing) = missing_features(&candidates, required_features) {
+        panic!(
+            "no device supports the required features, missing: {:?}",
+            missing
+        );
+    }
+
+    let candidates: Vec<_> = candidates
+        .into_iter()
+        .filter(|(_, p, _)| p.supported_features().contains(required_features))
+        .collect();
+
+    let forced = env::var(DEVICE_ENV_VAR).ok().and_then(|selector| {
+        let forced = candidates
+            .iter()
+            .find(|(i, p, _)| matches_device(&selector, *i, &p.properties().device_name))
+            .map(|(_, p, q)| (p.clone(), *q));
+
+        if forced.is_none() {
+            println!(
+                "Warning: no suitable device matches {}={}, selecting one automatically",
+                DEVICE_ENV_VAR, selector,
+            );
+        }
+
+        forced
+    });
+
+    let (physical_device, queue_family_index) = forced.unwrap_or_else(|| {
+        candidates
+            .into_iter()
+            .map(|(_, p, q)| (p, q))
+            .min_by_key(|(p, _)| match p.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => 0,
+                PhysicalDeviceType::IntegratedGpu => 1,
+                PhysicalDeviceType::VirtualGpu => 2,
+                PhysicalDeviceType::Cpu => 3,
+                _ => 4,
+            })
+            .expect("no device available")
+    });
+
+    print_physical_device(&physical_device);
+
+    (physical_device, queue_family_index)
+}
+
+// If some devices are suitable except for `required_features` but none supports all of them, the
+// features the first of these devices is missing.
+fn missing_features(
+    candidates: &[(usize, Arc<PhysicalDevice>, u32)],
+    required_features: &Features,
+) -> Option<Features> {
+    let all_missing = !candidates.is_empty()
+        && candidates
+            .iter()
+            .all(|(_, p, _)| !p.supported_features().contains(required_features));
+
+    all_missing.then(|| required_features.difference(candidates[0].1.supported_features()))
+}
+
+// Prints which device is used, on one line, or with more details when the validation layers are
+// enabled, as when debugging an issue that only happens with some GPUs or drivers.
+fn print_physical_device(physical_device: &PhysicalDevice) {
+    let properties = physical_device.properties();
+
+    println!(
+        "Using {} ({:?}, Vulkan {}, driver version {:#x})",
+        properties.device_name,
+        properties.device_type,
+        properties.api_version,
+        properties.driver_version,
+    );
+
+    if validation_enabled() {
+        println!("  Vendor ID: {:#06x}", properties.vendor_id);
+        println!("  Device ID: {:#06x}", properties.device_id);
+        if let Some(driver_name) = &properties.driver_name {
+            println!("  Driver: {}", driver_name);
+        }
+        if let Some(driver_info) = &properties.driver_info {
+            println!("  Driver info: {}", driver_info);
+        }
+        println!("  Max 2D image size: {}", properties.max_image_dimension2_d);
+        for (i, heap) in physical_device
+            .memory_properties()
+            .memory_heaps
+            .iter()
+            .enumerate()
+        {
+            println!(
+                "  Memory heap {}: {} MiB ({:?})",
+                i,
+                heap.size / (1024 * 1024),
+                heap.flags,
+            );
+        }
+    }
+}
+
+// Whether `selector`, the value of `DEVICE_ENV_VAR`, is `index` or a part of `name`, ignoring case.
+fn matches_device(selector: &str, index: usize, name: &str) -> bool {
+    let selector = selector.trim();
+
+    match selector.parse::<usize>() {
+        Ok(selected_index) => selected_index == index,
+        Err(_) => name.to_lowercase().contains(&selector.to_lowercase()),
+    }
+}
+
+/// Returns the index of a queue family of `physical_device`, other than the graphics one, on which
+/// uploads to device-local memory can run without waiting behind the rendering. A family that only
+/// supports transfers is preferred, as it is usually backed by a dedicated DMA engine.
+///
+/// Returns `None` if the graphics family is the only one supporting transfers, in which case the
+/// graphics queue should be used for the uploads as well.
+pub fn select_transfer_queue_family(
+    physical_device: &PhysicalDevice,
+    graphics_queue_family_index: u32,
+) -> Option<u32> {
+    let queue_flags: Vec<_> = physical_device
+        .queue_family_properties()
+        .iter()
+        .map(|q| q.queue_flags)
+        .collect();
+
+    let transfer_queue_family_index =
+        choose_transfer_queue_family(&queue_flags, graphics_queue_family_index);
+
+    match transfer_queue_family_index {
+        Some(i) => println!("Using queue family {} for transfers", i),
+        None => {
+            println!("No separate transfer queue family, using the graphics queue for transfers")
+        }
+    }
+
+    transfer_queue_family_index
+}
+
+// Graphics and compute queues support transfers even if they don't report it.
+fn choose_transfer_queue_family(
+    queue_flags: &[QueueFlags],
+    graphics_queue_family_index: u32,
+) -> Option<u32> {
+    queue_flags
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i as u32 != graphics_queue_family_index)
+        .filter(|(_, flags)| {
+            flags.intersects(QueueFlags::TRANSFER | QueueFlags::GRAPHICS | QueueFlags::COMPUTE)
         })
-        .min_by_key(|(p, _)| match p.properties().device_type {
-            PhysicalDeviceType::DiscreteGpu => 0,
-            PhysicalDeviceType::IntegratedGpu => 1,
-            PhysicalDeviceType::VirtualGpu => 2,
-            PhysicalDeviceType::Cpu => 3,
-            _ => 4,
+        .min_by_key(|(_, flags)| flags.intersects(QueueFlags::GRAPHICS | QueueFlags::COMPUTE))
+        .map(|(i, _)| i as u32)
+}
+
+/// Returns `device_extensions` with `khr_portability_subset` added if `physical_device` supports
+/// it, as it must then be enabled when creating the device. This is the case of the devices of
+/// portability implementations like MoltenVK on macOS, which don't support all of Vulkan.
+pub fn enabled_device_extensions(
+    physical_device: &PhysicalDevice,
+    device_extensions: &DeviceExtensions,
+) -> DeviceExtensions {
+    DeviceExtensions {
+        khr_portability_subset: physical_device
+            .supported_extensions()
+            .khr_portability_subset,
+        ..*device_extensions
+    }
+}
+
+// The sample counts that can be asked of `select_sample_count`.
+const SAMPLE_COUNTS: [SampleCount; 4] = [
+    SampleCount::Sample1,
+    SampleCount::Sample2,
+    SampleCount::Sample4,
+    SampleCount::Sample8,
+];
+
+/// Returns the number of samples per pixel to use for multisample anti-aliasing: `requested` if
+/// the device supports it for color attachments, and otherwise the supported count that is the
+/// closest to it.
+///
+/// Panics if `requested` isn't 1, 2, 4 or 8.
+pub fn select_sample_count(physical_device: &PhysicalDevice, requested: u32) -> SampleCount {
+    nearest_sample_count(
+        requested,
+        physical_device.properties().framebuffer_color_sample_counts,
+    )
+}
+
+// When two supported counts are as close to `requested`, the lower one is cheaper and is chosen.
+fn nearest_sample_count(requested: u32, supported: SampleCounts) -> SampleCount {
+    assert!(
+        SAMPLE_COUNTS.iter().any(|&count| count as u32 == requested),
+        "the sample count must be 1, 2, 4 or 8, not {}",
+        requested,
+    );
+
+    SAMPLE_COUNTS
+        .into_iter()
+        .filter(|&count| supported.contains_enum(count))
+        .min_by_key(|&count| {
+            (count as u32)
+                .trailing_zeros()
+                .abs_diff(requested.trailing_zeros())
         })
-        .expect("no device available")
+        .unwrap_or(SampleCount::Sample1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_selector() {
+        assert!(matches_device("1", 1, "AMD Radeon RX 6600"));
+        assert!(!matches_device("0", 1, "AMD Radeon RX 6600"));
+        assert!(matches_device("radeon", 1, "AMD Radeon RX 6600"));
+        assert!(matches_device(" NVIDIA ", 0, "NVIDIA GeForce RTX 3060"));
+        assert!(!matches_device("intel", 0, "NVIDIA GeForce RTX 3060"));
+    }
+
+    #[test]
+    fn transfer_queue_family_priority() {
+        let graphics = QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER;
+        let compute = QueueFlags::COMPUTE | QueueFlags::TRANSFER;
+
+        assert_eq!(
+            choose_transfer_queue_family(&[graphics, compute, QueueFlags::TRANSFER], 0),
+            Some(2)
+        );
+        assert_eq!(
+            choose_transfer_queue_family(&[graphics, compute], 0),
+            Some(1)
+        );
+        // Compute queues support transfers even without the flag.
+        assert_eq!(
+            choose_transfer_queue_family(&[QueueFlags::COMPUTE, graphics], 1),
+            Some(0)
+        );
+        assert_eq!(choose_transfer_queue_family(&[graphics], 0), None);
+        assert_eq!(
+            choose_transfer_queue_family(&[graphics, QueueFlags::SPARSE_BINDING], 0),
+            None
+        );
+    }
+
+    #[test]
+    fn sample_count_fallback() {
+        let supported = SampleCounts::SAMPLE_1 | SampleCounts::SAMPLE_2 | SampleCounts::SAMPLE_8;
+
+        assert_eq!(nearest_sample_count(2, supported), SampleCount::Sample2);
+        assert_eq!(nearest_sample_count(8, supported), SampleCount::Sample8);
+        // 2 and 8 are as close to 4.
+        assert_eq!(nearest_sample_count(4, supported), SampleCount::Sample2);
+        assert_eq!(
+            nearest_sample_count(8, SampleCounts::SAMPLE_1 | SampleCounts::SAMPLE_4),
+            SampleCount::Sample4
+        );
+    }
 }
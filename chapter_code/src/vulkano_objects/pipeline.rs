@@ -1,29 +1,194 @@
 use std::sync::Arc;
 
 use vulkano::device::Device;
+use vulkano::image::SampleCount;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
 use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
-use vulkano::pipeline::graphics::vertex_input::Vertex;
-use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::graphics::multisample::MultisampleState;
+use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexInputState};
+use vulkano::pipeline::graphics::viewport::ViewportState;
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::render_pass::{RenderPass, Subpass};
-use vulkano::shader::ShaderModule;
+use vulkano::shader::{EntryPoint, ShaderModule};
 
-use crate::Vertex2d;
+use super::VulkanoObjectsError;
+use crate::{Instance2d, Vertex2d, Vertex3d};
 
+/// Creates the pipeline drawing `Vertex2d` vertices in the first subpass of `render_pass`.
+///
+/// The pipeline uses as many samples per pixel as the attachments of the subpass, so it works
+/// with the multisampled render pass of `create_render_pass_with_msaa` as well.
+///
+/// The viewport is dynamic: it isn't part of the pipeline but is set by the command buffers, so the
+/// pipeline doesn't need to be recreated when the window is resized.
 pub fn create_pipeline(
     device: Arc<Device>,
     vs: Arc<ShaderModule>,
     fs: Arc<ShaderModule>,
     render_pass: Arc<RenderPass>,
-    viewport: Viewport,
-) -> Arc<GraphicsPipeline> {
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    create_pipeline_with_cache::<Vertex2d>(device, vs, fs, render_pass, None)
+}
+
+/// Same as `create_pipeline`, but for vertices of any type `V`, and the compiled shaders are looked
+/// up in and added to `pipeline_cache`, if given, which is much faster than compiling them when
+/// they are found. See `pipeline_cache::load_pipeline_cache` for keeping them between runs.
+pub fn create_pipeline_with_cache<V: Vertex>(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+    pipeline_cache: Option<Arc<PipelineCache>>,
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    build_pipeline::<V>(device, vs, fs, render_pass, pipeline_cache, false)
+}
+
+/// Same as `create_pipeline`, but the colors written by the fragment shader are blended with the
+/// ones already in the attachment according to their alpha, instead of replacing them:
+///
+/// ```text
+/// result = source * source_alpha + destination * (1 - source_alpha)
+/// ```
+///
+/// Only what is already drawn shows through, so translucent shapes must be drawn after the ones
+/// behind them.
+pub fn create_pipeline_with_alpha_blending(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    build_pipeline::<Vertex2d>(device, vs, fs, render_pass, None, true)
+}
+
+fn build_pipeline<V: Vertex>(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+    pipeline_cache: Option<Arc<PipelineCache>>,
+    alpha_blending: bool,
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    let subpass = Subpass::from(render_pass, 0).unwrap();
+
+    let mut builder = GraphicsPipeline::start()
+        .vertex_input_state(V::per_vertex())
+        .vertex_shader(entry_point(&vs, "vertex")?, ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(entry_point(&fs, "fragment")?, ())
+        .multisample_state(multisample_state(&subpass))
+        .color_blend_state(color_blend_state(&subpass, alpha_blending))
+        .render_pass(subpass);
+
+    if let Some(pipeline_cache) = pipeline_cache {
+        builder = builder.build_with_cache(pipeline_cache);
+    }
+
+    builder.build(device).map_err(VulkanoObjectsError::from)
+}
+
+/// Same as `create_pipeline`, but with a second vertex buffer binding holding an `Instance2d` for
+/// each instance, for drawing many copies of a model with a single draw call.
+pub fn create_instanced_pipeline(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    let subpass = Subpass::from(render_pass, 0).unwrap();
+
+    GraphicsPipeline::start()
+        .vertex_input_state([Vertex2d::per_vertex(), Instance2d::per_instance()])
+        .vertex_shader(entry_point(&vs, "vertex")?, ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(entry_point(&fs, "fragment")?, ())
+        .multisample_state(multisample_state(&subpass))
+        .render_pass(subpass)
+        .build(device)
+        .map_err(VulkanoObjectsError::from)
+}
+
+/// Same as `create_pipeline`, but for `Vertex3d` vertices and with depth testing enabled: a
+/// fragment is only drawn if it is closer than what was already drawn at its position. The
+/// render pass must have a depth attachment, like the one from `create_render_pass_with_depth`.
+pub fn create_pipeline_with_depth(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    let subpass = Subpass::from(render_pass, 0).unwrap();
+
+    GraphicsPipeline::start()
+        .vertex_input_state(Vertex3d::per_vertex())
+        .vertex_shader(entry_point(&vs, "vertex")?, ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(entry_point(&fs, "fragment")?, ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .multisample_state(multisample_state(&subpass))
+        .render_pass(subpass)
+        .build(device)
+        .map_err(VulkanoObjectsError::from)
+}
+
+/// Same as `create_pipeline`, but without vertex buffers, for full-screen passes like the
+/// post-processing ones. The vertex shader computes the positions of a triangle covering the whole
+/// framebuffer from `gl_VertexIndex`, and the pipeline is drawn with 3 vertices:
+///
+/// ```glsl
+/// vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+/// gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+/// ```
+pub fn create_fullscreen_pipeline(
+    device: Arc<Device>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>, VulkanoObjectsError> {
+    let subpass = Subpass::from(render_pass, 0).unwrap();
+
     GraphicsPipeline::start()
-        .vertex_input_state(Vertex2d::per_vertex())
-        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .vertex_input_state(VertexInputState::new())
+        .vertex_shader(entry_point(&vs, "vertex")?, ())
         .input_assembly_state(InputAssemblyState::new())
-        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
-        .fragment_shader(fs.entry_point("main").unwrap(), ())
-        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(entry_point(&fs, "fragment")?, ())
+        .multisample_state(multisample_state(&subpass))
+        .render_pass(subpass)
         .build(device)
-        .unwrap()
+        .map_err(VulkanoObjectsError::from)
+}
+
+// The `main` entry point of `shader`, which is the `stage` shader of the pipeline.
+fn entry_point<'a>(
+    shader: &'a ShaderModule,
+    stage: &'static str,
+) -> Result<EntryPoint<'a>, VulkanoObjectsError> {
+    shader
+        .entry_point("main")
+        .ok_or(VulkanoObjectsError::MissingEntryPoint(stage))
+}
+
+// Opaque colors replace what is in the attachments of `subpass`, unless `alpha_blending` is set.
+fn color_blend_state(subpass: &Subpass, alpha_blending: bool) -> ColorBlendState {
+    let color_blend_state = ColorBlendState::new(subpass.num_color_attachments());
+
+    if alpha_blending {
+        color_blend_state.blend_alpha()
+    } else {
+        color_blend_state
+    }
+}
+
+// Rasterizes with as many samples as the attachments of `subpass` have.
+fn multisample_state(subpass: &Subpass) -> MultisampleState {
+    MultisampleState {
+        rasterization_samples: subpass.num_samples().unwrap_or(SampleCount::Sample1),
+        ..Default::default()
+    }
 }
@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::pipeline::cache::PipelineCache;
+
+// The header that drivers put at the start of the data of a pipeline cache, as of version one:
+// its length, its version, the vendor and device IDs and the pipeline cache UUID of the device.
+const HEADER_LENGTH: usize = 32;
+const HEADER_VERSION_ONE: u32 = 1;
+
+/// Creates a pipeline cache with the data saved by `save_pipeline_cache` at `path`, so that the
+/// pipelines that were built with it in a previous run don't have to be compiled again. The cache
+/// is empty if there is no such file, or if it was saved with another device or driver version.
+///
+/// The cache is given to the pipelines with `build_with_cache`, like in
+/// `pipeline::create_pipeline_with_cache`.
+pub fn load_pipeline_cache(device: Arc<Device>, path: &Path) -> Arc<PipelineCache> {
+    let data = fs::read(path)
+        .ok()
+        .filter(|data| is_valid_cache_data(data, device.physical_device()));
+
+    match data {
+        Some(data) => {
+            println!("Loaded the pipeline cache from {}", path.display());
+
+            // Safety: the header was checked, and drivers check the rest of the data themselves.
+            unsafe { PipelineCache::with_data(device, &data) }.unwrap()
+        }
+        None => {
+            if path.exists() {
+                println!(
+                    "Warning: {} isn't a pipeline cache of this device, ignoring it",
+                    path.display()
+                );
+            }
+
+            PipelineCache::empty(device).unwrap()
+        }
+    }
+}
+
+/// Writes the data of `pipeline_cache` to `path`, to be loaded by `load_pipeline_cache` in the
+/// next run. Failing to save the cache only makes that run slower to start, so it isn't an error.
+pub fn save_pipeline_cache(pipeline_cache: &PipelineCache, path: &Path) {
+    let result = pipeline_cache
+        .get_data()
+        .map_err(|e| e.to_string())
+        .and_then(|data| fs::write(path, data).map_err(|e| e.to_string()));
+
+    if let Err(e) = result {
+        println!(
+            "Warning: failed to save the pipeline cache to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// Drivers are supposed to ignore the data of other devices, but some crash on it instead.
+fn is_valid_cache_data(data: &[u8], physical_device: &PhysicalDevice) -> bool {
+    let properties = physical_device.properties();
+
+    matches_header(
+        data,
+        properties.vendor_id,
+        properties.device_id,
+        &properties.pipeline_cache_uuid,
+    )
+}
+
+fn matches_header(data: &[u8], vendor_id: u32, device_id: u32, uuid: &[u8; 16]) -> bool {
+    if data.len() < HEADER_LENGTH {
+        return false;
+    }
+
+    let read_u32 = |offset: usize| u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    read_u32(0) as usize >= HEADER_LENGTH
+        && read_u32(4) == HEADER_VERSION_ONE
+        && read_u32(8) == vendor_id
+        && read_u32(12) == device_id
+        && data[16..32] == uuid[..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(vendor_id: u32, device_id: u32, uuid: [u8; 16]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend((HEADER_LENGTH as u32).to_ne_bytes());
+        data.extend(HEADER_VERSION_ONE.to_ne_bytes());
+        data.extend(vendor_id.to_ne_bytes());
+        data.extend(device_id.to_ne_bytes());
+        data.extend(uuid);
+        // Driver specific data.
+        data.extend([0xab; 8]);
+        data
+    }
+
+    #[test]
+    fn cache_header_validation() {
+        let uuid = [7; 16];
+        let data = header(0x10de, 0x2504, uuid);
+
+        assert!(matches_header(&data, 0x10de, 0x2504, &uuid));
+        assert!(!matches_header(&data, 0x1002, 0x2504, &uuid));
+        assert!(!matches_header(&data, 0x10de, 0x2487, &uuid));
+        // Another driver version.
+        assert!(!matches_header(&data, 0x10de, 0x2504, &[8; 16]));
+        assert!(!matches_header(&data[..20], 0x10de, 0x2504, &uuid));
+        assert!(!matches_header(&[], 0x10de, 0x2504, &uuid));
+    }
+}
@@ -1,10 +1,23 @@
 use std::sync::Arc;
 
 use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::SampleCount;
 use vulkano::render_pass::RenderPass;
 use vulkano::swapchain::Swapchain;
 
-pub fn create_render_pass(device: Arc<Device>, swapchain: Arc<Swapchain>) -> Arc<RenderPass> {
+use super::VulkanoObjectsError;
+
+/// Format of the depth attachment added by `create_render_pass_with_depth`. Every device supports
+/// it as a depth attachment.
+pub const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
+/// Creates a render pass with a single subpass drawing to a color attachment of the format of the
+/// swapchain images, which is cleared at the start.
+pub fn create_render_pass(
+    device: Arc<Device>,
+    swapchain: Arc<Swapchain>,
+) -> Result<Arc<RenderPass>, VulkanoObjectsError> {
     vulkano::single_pass_renderpass!(
         device,
         attachments: {
@@ -20,5 +33,74 @@ pub fn create_render_pass(device: Arc<Device>, swapchain: Arc<Swapchain>) -> Arc
             depth_stencil: {},
         },
     )
-    .unwrap()
+    .map_err(VulkanoObjectsError::from)
+}
+
+/// Same as `create_render_pass`, but with a depth attachment of format `DEPTH_FORMAT`, for scenes
+/// where what is closer to the camera must hide what is behind it.
+///
+/// The depth attachment is cleared at the start of the render pass and isn't kept afterwards.
+pub fn create_render_pass_with_depth(
+    device: Arc<Device>,
+    swapchain: Arc<Swapchain>,
+) -> Result<Arc<RenderPass>, VulkanoObjectsError> {
+    vulkano::single_pass_renderpass!(
+        device,
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: swapchain.image_format(),
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: DEPTH_FORMAT,
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {depth},
+        },
+    )
+    .map_err(VulkanoObjectsError::from)
+}
+
+/// Same as `create_render_pass`, but for multisample anti-aliasing: the subpass draws to a
+/// multisampled color attachment with `samples` samples per pixel, which is then resolved into the
+/// swapchain image.
+///
+/// The multisampled attachment comes first and the swapchain image second, which is the order
+/// `create_framebuffers_with_msaa` gives them in.
+pub fn create_render_pass_with_msaa(
+    device: Arc<Device>,
+    swapchain: Arc<Swapchain>,
+    samples: SampleCount,
+) -> Result<Arc<RenderPass>, VulkanoObjectsError> {
+    vulkano::single_pass_renderpass!(
+        device,
+        attachments: {
+            // Only needed during the subpass, its content is discarded once it has been resolved.
+            multisampled: {
+                load: Clear,
+                store: DontCare,
+                format: swapchain.image_format(),
+                samples: samples,
+            },
+            color: {
+                load: DontCare,
+                store: Store,
+                format: swapchain.image_format(),
+                samples: 1,
+            },
+        },
+        pass: {
+            color: [multisampled],
+            depth_stencil: {},
+            resolve: [color],
+        },
+    )
+    .map_err(VulkanoObjectsError::from)
 }
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::{ImageBuffer, Rgba};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::{ImageAccess, SwapchainImage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage};
+use vulkano::DeviceSize;
+
+use super::allocators::Allocators;
+
+/// Whether images of `format` can be saved by `save_screenshot`, which only handles 8-bit RGBA
+/// and BGRA pixels.
+pub fn supports_screenshots(format: Format) -> bool {
+    matches!(
+        format,
+        Format::B8G8R8A8_SRGB
+            | Format::B8G8R8A8_UNORM
+            | Format::R8G8B8A8_SRGB
+            | Format::R8G8B8A8_UNORM
+    )
+}
+
+/// Records the copy of a swapchain image to a buffer that can be read by the CPU, to be executed
+/// after the image is rendered and before it is presented.
+///
+/// The swapchain must have been created with the `TRANSFER_SRC` image usage, like the ones of
+/// `create_swapchain`.
+pub fn create_screenshot_copy(
+    allocators: &Allocators,
+    queue: &Queue,
+    image: Arc<SwapchainImage>,
+) -> (Arc<PrimaryAutoCommandBuffer>, Subbuffer<[u8]>) {
+    let [width, height] = image.dimensions().width_height();
+
+    let buffer = Buffer::new_slice::<u8>(
+        &allocators.memory,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            usage: MemoryUsage::Download,
+            ..Default::default()
+        },
+        width as DeviceSize * height as DeviceSize * 4,
+    )
+    .unwrap();
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        &allocators.command_buffer,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+    builder
+        .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+        .unwrap();
+
+    (Arc::new(builder.build().unwrap()), buffer)
+}
+
+/// Saves the pixels copied by `create_screenshot_copy`, once the copy is done, to a PNG file in
+/// the current directory, and returns its path.
+///
+/// The pixels are written as they are stored. With an `_SRGB` format, they were gamma encoded
+/// when rendered, which is what PNG files expect. With a `_UNORM` format, the display shows the
+/// stored values as if they were gamma encoded anyway. Either way, the file looks like the window.
+///
+/// Panics if `supports_screenshots(format)` is false.
+pub fn save_screenshot(
+    buffer: &Subbuffer<[u8]>,
+    [width, height]: [u32; 2],
+    format: Format,
+) -> String {
+    assert!(
+        supports_screenshots(format),
+        "can't save a screenshot of a {:?} image",
+        format,
+    );
+
+    let mut pixels = buffer.read().unwrap().to_vec();
+
+    // Most swapchains are BGRA, while PNG files are RGBA.
+    if matches!(format, Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let path = format!("screenshot_{timestamp}.png");
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, pixels).unwrap();
+    image.save(&path).unwrap();
+
+    path
+}
@@ -2,35 +2,66 @@ use std::sync::Arc;
 
 use vulkano::device::physical::PhysicalDevice;
 use vulkano::device::Device;
+use vulkano::format::Format;
 use vulkano::image::view::ImageView;
-use vulkano::image::{ImageUsage, SwapchainImage};
+use vulkano::image::{AttachmentImage, ImageAccess, ImageUsage, SampleCount, SwapchainImage};
+use vulkano::memory::allocator::StandardMemoryAllocator;
 use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
-use vulkano::swapchain::{Surface, Swapchain, SwapchainCreateInfo};
+use vulkano::swapchain::{ColorSpace, PresentMode, Surface, Swapchain, SwapchainCreateInfo};
 use winit::window::Window;
 
+use super::render_pass::DEPTH_FORMAT;
+use super::VulkanoObjectsError;
+
+/// The present modes `create_swapchain` picks from, best first: `Mailbox` replaces the queued image
+/// with the newest one instead of waiting for it to be presented, which gives the lowest latency
+/// without tearing, and `FifoRelaxed` only tears when a frame is late.
+pub const PREFERRED_PRESENT_MODES: [PresentMode; 3] = [
+    PresentMode::Mailbox,
+    PresentMode::FifoRelaxed,
+    PresentMode::Fifo,
+];
+
+/// Creates a swapchain with the format returned by `select_surface_format`, presenting with the
+/// first of `PREFERRED_PRESENT_MODES` that the surface supports.
+///
+/// The render pass helpers take their color format from `Swapchain::image_format`, so they always
+/// match the format chosen here.
 pub fn create_swapchain(
     physical_device: &Arc<PhysicalDevice>,
     device: Arc<Device>,
     surface: Arc<Surface>,
-) -> (Arc<Swapchain>, Vec<Arc<SwapchainImage>>) {
-    let caps = physical_device
-        .surface_capabilities(&surface, Default::default())
-        .expect("failed to get surface capabilities");
+) -> Result<(Arc<Swapchain>, Vec<Arc<SwapchainImage>>), VulkanoObjectsError> {
+    create_swapchain_with_present_modes(physical_device, device, surface, &PREFERRED_PRESENT_MODES)
+}
+
+/// Same as `create_swapchain`, but with the first of `preferred_present_modes` that the surface
+/// supports, for example to put `PresentMode::Immediate` first. Falls back to `PresentMode::Fifo`,
+/// which every surface supports, if none of them is.
+pub fn create_swapchain_with_present_modes(
+    physical_device: &Arc<PhysicalDevice>,
+    device: Arc<Device>,
+    surface: Arc<Surface>,
+    preferred_present_modes: &[PresentMode],
+) -> Result<(Arc<Swapchain>, Vec<Arc<SwapchainImage>>), VulkanoObjectsError> {
+    let caps = physical_device.surface_capabilities(&surface, Default::default())?;
 
     let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
-    let image_format = Some(
-        physical_device
-            .surface_formats(&surface, Default::default())
-            .unwrap()[0]
-            .0,
+    let (image_format, image_color_space) = select_surface_format(physical_device, &surface)?;
+
+    let present_mode = choose_present_mode(
+        preferred_present_modes,
+        physical_device.surface_present_modes(&surface)?,
     );
+    println!("Using present mode {:?}", present_mode);
 
     Swapchain::new(
         device,
         surface.clone(),
         SwapchainCreateInfo {
             min_image_count: caps.min_image_count,
-            image_format,
+            image_format: Some(image_format),
+            image_color_space,
             image_extent: surface
                 .object()
                 .unwrap()
@@ -39,12 +70,60 @@ pub fn create_swapchain(
                 .unwrap()
                 .inner_size()
                 .into(),
-            image_usage: ImageUsage::COLOR_ATTACHMENT,
+            // `TRANSFER_SRC` is what allows copying the images for screenshots.
+            image_usage: ImageUsage::COLOR_ATTACHMENT
+                | (caps.supported_usage_flags & ImageUsage::TRANSFER_SRC),
             composite_alpha,
+            present_mode,
             ..Default::default()
         },
     )
-    .unwrap()
+    .map_err(VulkanoObjectsError::from)
+}
+
+/// Returns the format and color space that `create_swapchain` uses for the images of `surface`:
+/// `B8G8R8A8_SRGB` or `R8G8B8A8_SRGB` in the `SrgbNonLinear` color space if the surface supports
+/// one of them, and otherwise the first format it supports.
+pub fn select_surface_format(
+    physical_device: &PhysicalDevice,
+    surface: &Surface,
+) -> Result<(Format, ColorSpace), VulkanoObjectsError> {
+    let formats = physical_device.surface_formats(surface, Default::default())?;
+    let (format, color_space) = choose_surface_format(&formats);
+    println!("Using surface format {:?} ({:?})", format, color_space);
+
+    Ok((format, color_space))
+}
+
+// The fragment shaders output linear colors, which is what blending and interpolation need to be
+// correct. With an `_SRGB` format, the hardware applies the sRGB gamma curve when writing to the
+// image, and the display shows the colors as intended. With a `_UNORM` format, the linear values
+// are stored as they are and displayed as if they were gamma encoded, so everything but black and
+// white looks too dark.
+const PREFERRED_SURFACE_FORMATS: [Format; 2] = [Format::B8G8R8A8_SRGB, Format::R8G8B8A8_SRGB];
+
+fn choose_surface_format(formats: &[(Format, ColorSpace)]) -> (Format, ColorSpace) {
+    PREFERRED_SURFACE_FORMATS
+        .iter()
+        .find_map(|&preferred| {
+            formats.iter().copied().find(|&(format, color_space)| {
+                format == preferred && color_space == ColorSpace::SrgbNonLinear
+            })
+        })
+        .unwrap_or(formats[0])
+}
+
+fn choose_present_mode(
+    preferred: &[PresentMode],
+    supported: impl IntoIterator<Item = PresentMode>,
+) -> PresentMode {
+    let supported: Vec<_> = supported.into_iter().collect();
+
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
 }
 
 pub fn create_framebuffers_from_swapchain_images(
@@ -66,3 +145,187 @@ pub fn create_framebuffers_from_swapchain_images(
         })
         .collect::<Vec<_>>()
 }
+
+/// Same as `create_framebuffers_from_swapchain_images`, for a render pass created with
+/// `create_render_pass_with_depth`. Each framebuffer gets its own depth image, so that frames in
+/// flight don't write to the same one.
+pub fn create_framebuffers_with_depth(
+    images: &[Arc<SwapchainImage>],
+    render_pass: Arc<RenderPass>,
+    memory_allocator: &StandardMemoryAllocator,
+) -> Vec<Arc<Framebuffer>> {
+    images
+        .iter()
+        .map(|image| {
+            let view = ImageView::new_default(image.clone()).unwrap();
+            let depth_image = AttachmentImage::transient(
+                memory_allocator,
+                image.dimensions().width_height(),
+                DEPTH_FORMAT,
+            )
+            .unwrap();
+            let depth_view = ImageView::new_default(depth_image).unwrap();
+
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![view, depth_view],
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Same as `create_framebuffers_from_swapchain_images`, for a render pass created with
+/// `create_render_pass_with_msaa`. Each framebuffer gets its own multisampled image with
+/// `samples` samples per pixel, which must be the count the render pass was created with.
+///
+/// The multisampled images are transient: they are only used during the render pass, so the
+/// driver may not need to allocate memory for them at all.
+pub fn create_framebuffers_with_msaa(
+    images: &[Arc<SwapchainImage>],
+    render_pass: Arc<RenderPass>,
+    memory_allocator: &StandardMemoryAllocator,
+    samples: SampleCount,
+) -> Vec<Arc<Framebuffer>> {
+    images
+        .iter()
+        .map(|image| {
+            let multisampled_image = AttachmentImage::transient_multisampled(
+                memory_allocator,
+                image.dimensions().width_height(),
+                samples,
+                image.format(),
+            )
+            .unwrap();
+            let multisampled_view = ImageView::new_default(multisampled_image).unwrap();
+            let view = ImageView::new_default(image.clone()).unwrap();
+
+            Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![multisampled_view, view],
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>()
+}
+
+/// A color image that a scene is rendered to instead of a swapchain image, so that another pass
+/// can read it afterwards, for example to apply a post-processing effect while drawing it to the
+/// swapchain image.
+pub struct OffscreenTarget {
+    /// The image, to be bound as a sampled image by the pass reading it.
+    pub view: Arc<ImageView<AttachmentImage>>,
+    /// Draws to the image with the render pass given to `create_offscreen_targets`.
+    pub framebuffer: Arc<Framebuffer>,
+}
+
+/// Creates an `OffscreenTarget` for each of the swapchain `images`, with their size and format, to
+/// be drawn to with `render_pass`, like the one of `create_render_pass`. Each swapchain image gets
+/// its own target, so that frames in flight don't write to the same one.
+///
+/// The targets have the size of the swapchain images, so they must be created again along with the
+/// framebuffers when the swapchain is recreated.
+pub fn create_offscreen_targets(
+    images: &[Arc<SwapchainImage>],
+    render_pass: Arc<RenderPass>,
+    memory_allocator: &StandardMemoryAllocator,
+) -> Vec<OffscreenTarget> {
+    images
+        .iter()
+        .map(|image| {
+            // Can be both a color attachment and sampled.
+            let offscreen_image = AttachmentImage::sampled(
+                memory_allocator,
+                image.dimensions().width_height(),
+                image.format(),
+            )
+            .unwrap();
+            let view = ImageView::new_default(offscreen_image).unwrap();
+
+            let framebuffer = Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![view.clone()],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            OffscreenTarget { view, framebuffer }
+        })
+        .collect::<Vec<_>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_format_priority() {
+        let srgb = ColorSpace::SrgbNonLinear;
+
+        assert_eq!(
+            choose_surface_format(&[
+                (Format::B8G8R8A8_UNORM, srgb),
+                (Format::R8G8B8A8_SRGB, srgb),
+                (Format::B8G8R8A8_SRGB, srgb),
+            ]),
+            (Format::B8G8R8A8_SRGB, srgb)
+        );
+        assert_eq!(
+            choose_surface_format(&[
+                (Format::B8G8R8A8_SRGB, ColorSpace::DisplayP3NonLinear),
+                (Format::R8G8B8A8_SRGB, srgb),
+            ]),
+            (Format::R8G8B8A8_SRGB, srgb)
+        );
+        assert_eq!(
+            choose_surface_format(&[
+                (Format::A2B10G10R10_UNORM_PACK32, srgb),
+                (Format::B8G8R8A8_UNORM, srgb),
+            ]),
+            (Format::A2B10G10R10_UNORM_PACK32, srgb)
+        );
+    }
+
+    #[test]
+    fn present_mode_priority() {
+        let all = [
+            PresentMode::Immediate,
+            PresentMode::Mailbox,
+            PresentMode::Fifo,
+            PresentMode::FifoRelaxed,
+        ];
+
+        assert_eq!(
+            choose_present_mode(&PREFERRED_PRESENT_MODES, all),
+            PresentMode::Mailbox
+        );
+        assert_eq!(
+            choose_present_mode(
+                &PREFERRED_PRESENT_MODES,
+                [PresentMode::Fifo, PresentMode::FifoRelaxed]
+            ),
+            PresentMode::FifoRelaxed
+        );
+        assert_eq!(
+            choose_present_mode(&PREFERRED_PRESENT_MODES, [PresentMode::Fifo]),
+            PresentMode::Fifo
+        );
+        // Immediate isn't in the default list, so it is only picked when asked for.
+        assert_eq!(
+            choose_present_mode(&[PresentMode::Immediate], all),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            choose_present_mode(&[PresentMode::Immediate], [PresentMode::Fifo]),
+            PresentMode::Fifo
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use image::ImageFormat;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract,
+};
+use vulkano::device::{DeviceOwned, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::sync::GpuFuture;
+
+use super::allocators::Allocators;
+
+/// An image in device-local memory, with the sampler it is read with by the shaders. It is bound
+/// as a combined image sampler, next to the uniform buffer of `Buffers`.
+#[derive(Clone)]
+pub struct Texture {
+    pub view: Arc<ImageView<ImmutableImage>>,
+    pub sampler: Arc<Sampler>,
+}
+
+impl Texture {
+    /// Decodes a PNG image and uploads it to the device through `queue`, waiting for the upload to
+    /// finish.
+    ///
+    /// Panics if `png` isn't a valid PNG image.
+    pub fn from_png(allocators: &Allocators, queue: Arc<Queue>, png: &[u8]) -> Self {
+        let image = image::load_from_memory_with_format(png, ImageFormat::Png)
+            .expect("failed to decode the texture")
+            .to_rgba8();
+        let dimensions = ImageDimensions::Dim2d {
+            width: image.width(),
+            height: image.height(),
+            array_layers: 1,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &allocators.command_buffer,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        // Records the copy from a staging buffer into the image, which is executed below.
+        let image = ImmutableImage::from_iter(
+            &allocators.memory,
+            image.into_raw(),
+            dimensions,
+            MipmapsCount::One,
+            Format::R8G8B8A8_SRGB,
+            &mut builder,
+        )
+        .unwrap();
+
+        builder
+            .build()
+            .unwrap()
+            .execute(queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let sampler = Sampler::new(
+            queue.device().clone(),
+            SamplerCreateInfo::simple_repeat_linear(),
+        )
+        .unwrap();
+
+        Self {
+            view: ImageView::new_default(image).unwrap(),
+            sampler,
+        }
+    }
+}
@@ -0,0 +1,423 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Runs the examples and checks that they succeed and write the files they are expected to.
+//!
+//! The tests are ignored by default, as they need a Vulkan driver, and a display server for the
+//! windowed examples, which are started with `--headless` so that they exit on their own. On a
+//! CI machine they can run on a software driver (Lavapipe or SwiftShader) with a virtual X
+//! server:
+//!
+//! ```bash
+//! xvfb-run cargo test --test integration -- --ignored
+//! ```
+//!
+//! Unless `VK_ICD_FILENAMES` is already set, it's pointed at the first software driver found in
+//! the usual install locations, so that the results don't depend on the GPU of the machine.
+//!
+//! The examples are the binaries `cargo run --bin` would run, built by cargo for the tests. Each
+//! one runs in its own directory, so that the files they write don't overwrite each other.
+//!
+//! The examples that need a cargo feature, a specific platform or a real display aren't covered,
+//! and neither are the `perf_*` benchmarks, which would only measure the software driver.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chapter_code::HEADLESS_FLAG;
+
+const SOFTWARE_ICDS: &[&str] = &[
+    "/usr/share/vulkan/icd.d/lvp_icd.x86_64.json",
+    "/usr/share/vulkan/icd.d/lvp_icd.aarch64.json",
+    "/usr/share/vulkan/icd.d/vk_swiftshader_icd.json",
+    "/usr/local/share/vulkan/icd.d/vk_swiftshader_icd.json",
+];
+
+fn software_icd() -> Option<&'static str> {
+    SOFTWARE_ICDS
+        .iter()
+        .copied()
+        .find(|path| Path::new(path).exists())
+}
+
+/// Runs an example in an empty directory named after the test, writing `stdin` to its standard
+/// input if it isn't empty, and returns the directory.
+fn run_example(test_name: &str, executable: &str, args: &[&str], stdin: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test_name);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut command = Command::new(executable);
+    command
+        .args(args)
+        .current_dir(&dir)
+        .stdin(if stdin.is_empty() {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if env::var_os("VK_ICD_FILENAMES").is_none() {
+        if let Some(icd) = software_icd() {
+            command.env("VK_ICD_FILENAMES", icd);
+        }
+    }
+
+    let mut child = command.spawn().expect("failed to start the example");
+    if let Some(mut child_stdin) = child.stdin.take() {
+        child_stdin.write_all(stdin.as_bytes()).unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+
+    assert!(
+        output.status.success(),
+        "{} exited with {}\nstdout:\n{}\nstderr:\n{}",
+        test_name,
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+
+    dir
+}
+
+fn assert_non_empty_file(path: &Path) {
+    let metadata =
+        fs::metadata(path).unwrap_or_else(|_| panic!("{} wasn't created", path.display()));
+    assert!(metadata.len() > 0, "{} is empty", path.display());
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn buffer_creation() {
+    run_example(
+        "buffer_creation",
+        env!("CARGO_BIN_EXE_buffer_creation"),
+        &[],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn compute_pipeline() {
+    run_example(
+        "compute_pipeline",
+        env!("CARGO_BIN_EXE_compute_pipeline"),
+        &[],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn graphics_pipeline() {
+    let dir = run_example(
+        "graphics_pipeline",
+        env!("CARGO_BIN_EXE_graphics_pipeline"),
+        &[],
+        "",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn images_image_clear() {
+    let dir = run_example(
+        "images_image_clear",
+        env!("CARGO_BIN_EXE_images"),
+        &[],
+        "image_clear\n",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn images_mandelbrot() {
+    let dir = run_example(
+        "images_mandelbrot",
+        env!("CARGO_BIN_EXE_images"),
+        &["mandelbrot"],
+        "",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn depth_readback() {
+    let dir = run_example(
+        "depth_readback",
+        env!("CARGO_BIN_EXE_depth_readback"),
+        &[],
+        "",
+    );
+    assert_non_empty_file(&dir.join("depth.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn image_resolve() {
+    let dir = run_example(
+        "image_resolve",
+        env!("CARGO_BIN_EXE_image_resolve"),
+        &[],
+        "",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn headless_egl() {
+    let dir = run_example("headless_egl", env!("CARGO_BIN_EXE_headless_egl"), &[], "");
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn draw_indirect_fill() {
+    let dir = run_example(
+        "draw_indirect_fill",
+        env!("CARGO_BIN_EXE_draw_indirect_fill"),
+        &[],
+        "",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn stream_compaction() {
+    run_example(
+        "stream_compaction",
+        env!("CARGO_BIN_EXE_stream_compaction"),
+        &[],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn compute_scan_inclusive() {
+    run_example(
+        "compute_scan_inclusive",
+        env!("CARGO_BIN_EXE_compute_scan_inclusive"),
+        &[],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn hierarchical_z() {
+    let dir = run_example(
+        "hierarchical_z",
+        env!("CARGO_BIN_EXE_hierarchical_z"),
+        &[],
+        "",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn blend_constants() {
+    let dir = run_example(
+        "blend_constants",
+        env!("CARGO_BIN_EXE_blend_constants"),
+        &[],
+        "",
+    );
+    assert_non_empty_file(&dir.join("image.png"));
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn robust_buffer_access() {
+    run_example(
+        "robust_buffer_access",
+        env!("CARGO_BIN_EXE_robust_buffer_access"),
+        &[],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver"]
+fn render_loop_metrics() {
+    run_example(
+        "render_loop_metrics",
+        env!("CARGO_BIN_EXE_render_loop_metrics"),
+        &[],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn windowing() {
+    run_example(
+        "windowing",
+        env!("CARGO_BIN_EXE_windowing"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn restructuring() {
+    run_example(
+        "restructuring",
+        env!("CARGO_BIN_EXE_restructuring"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn restructuring_square() {
+    run_example(
+        "restructuring_square",
+        env!("CARGO_BIN_EXE_restructuring"),
+        &[HEADLESS_FLAG, "--square"],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn more_on_buffers() {
+    run_example(
+        "more_on_buffers",
+        env!("CARGO_BIN_EXE_more_on_buffers"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn alpha_blending() {
+    run_example(
+        "alpha_blending",
+        env!("CARGO_BIN_EXE_alpha_blending"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn obj_model() {
+    run_example(
+        "obj_model",
+        env!("CARGO_BIN_EXE_obj_model"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn fps_counter_window_title() {
+    run_example(
+        "fps_counter_window_title",
+        env!("CARGO_BIN_EXE_fps_counter_window_title"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn depth_buffer() {
+    run_example(
+        "depth_buffer",
+        env!("CARGO_BIN_EXE_depth_buffer"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn push_constants() {
+    run_example(
+        "push_constants",
+        env!("CARGO_BIN_EXE_push_constants"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn mandelbrot_viewer() {
+    run_example(
+        "mandelbrot_viewer",
+        env!("CARGO_BIN_EXE_mandelbrot_viewer"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn particles() {
+    run_example(
+        "particles",
+        env!("CARGO_BIN_EXE_particles"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn post_processing() {
+    run_example(
+        "post_processing",
+        env!("CARGO_BIN_EXE_post_processing"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan driver and a display"]
+fn screenshot() {
+    let dir = run_example(
+        "screenshot",
+        env!("CARGO_BIN_EXE_screenshot"),
+        &[HEADLESS_FLAG],
+        "",
+    );
+
+    let screenshot = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("screenshot_") && name.ends_with(".png")
+        })
+        .expect("no screenshot was saved");
+    assert_non_empty_file(&screenshot);
+}
@@ -0,0 +1,102 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Callouts in the guide, written as a blockquote whose first line is a marker:
+//!
+//! ```markdown
+//! > [!WARNING]
+//! > The buffer must not be in use by the GPU.
+//! ```
+//!
+//! The supported markers are `[!NOTE]`, `[!WARNING]` and `[!TIP]`. A blockquote with any other
+//! first line is left alone.
+
+use pulldown_cmark::{Event, Tag};
+
+// The markers, with the CSS class and the title of the callouts that they start.
+const KINDS: &[(&str, &str, &str)] = &[
+    ("[!NOTE]", "note", "Note"),
+    ("[!WARNING]", "warning", "Warning"),
+    ("[!TIP]", "tip", "Tip"),
+];
+
+/// Turns the blockquotes that start with a marker into `<div class="admonition KIND">` blocks.
+pub fn convert(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output = Vec::with_capacity(events.len());
+    // For each blockquote that is open, whether it was turned into a callout.
+    let mut blockquotes = Vec::new();
+    let mut i = 0;
+
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::BlockQuote) => match marker(&events[i + 1..]) {
+                Some((class, title, marker_len)) => {
+                    output.push(Event::Html(
+                        format!(
+                            "<div class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n",
+                            class, title,
+                        )
+                        .into(),
+                    ));
+                    blockquotes.push(true);
+                    // Skips the marker, keeping the rest of its paragraph if there is one.
+                    let after_marker = i + 2 + marker_len;
+                    i = match events[after_marker] {
+                        Event::End(Tag::Paragraph) => after_marker + 1,
+                        _ => {
+                            output.push(Event::Start(Tag::Paragraph));
+                            after_marker + 1
+                        }
+                    };
+                    continue;
+                }
+                None => {
+                    blockquotes.push(false);
+                    output.push(events[i].clone());
+                }
+            },
+            Event::End(Tag::BlockQuote) => {
+                if blockquotes.pop() == Some(true) {
+                    output.push(Event::Html("</div>\n".into()));
+                } else {
+                    output.push(events[i].clone());
+                }
+            }
+            event => output.push(event.clone()),
+        }
+
+        i += 1;
+    }
+
+    output
+}
+
+// Checks whether the content of a blockquote starts with a marker alone on its line. Returns the
+// class and title of the callout, and the number of text events that make up the marker.
+fn marker(events: &[Event<'_>]) -> Option<(&'static str, &'static str, usize)> {
+    if !matches!(events.first(), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut len = 0;
+    for event in &events[1..] {
+        match event {
+            Event::Text(t) => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak | Event::End(Tag::Paragraph) => break,
+            _ => return None,
+        }
+        len += 1;
+    }
+
+    KINDS
+        .iter()
+        .find(|(marker, _, _)| text.trim() == *marker)
+        .map(|&(_, class, title)| (class, title, len))
+}
@@ -8,9 +8,70 @@
 // according to those terms.
 
 use std::env;
+use std::net::ToSocketAddrs;
+use std::process;
+
+use vulkano_www::StartConfig;
+
+const DEFAULT_ADDR: &str = "0.0.0.0:8000";
 
 fn main() {
-    let addr = env::var("ADDR").unwrap_or("0.0.0.0:8000".to_owned());
+    let addr = match addr() {
+        Ok(addr) => addr,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("Usage: vulkano-www [--addr ADDRESS]");
+            process::exit(1);
+        }
+    };
+
+    let mut config = StartConfig {
+        precompile: env::var("PRERENDER").map_or(true, |v| v != "0"),
+        dev_mode: env::var("VULKANO_WWW_DEV").map_or(false, |v| v == "1"),
+        deny_broken_links: env::var("VULKANO_WWW_DENY_BROKEN_LINKS").map_or(false, |v| v == "1"),
+        minify_html: env::var("VULKANO_WWW_MINIFY").map_or(true, |v| v != "0"),
+        ..StartConfig::default()
+    };
+    if let Ok(capacity) = env::var("CACHE_CAPACITY") {
+        config.cache_capacity = capacity
+            .parse()
+            .expect("CACHE_CAPACITY must be a number of pages");
+    }
+
     println!("Listening on {}", addr);
-    vulkano_www::start(&addr)
+    if let Err(err) = vulkano_www::start(&addr, config) {
+        eprintln!("Failed to start the server on {}: {}", addr, err);
+        process::exit(1);
+    }
+}
+
+// The address to listen on, from `--addr`/`-a`, or else the `VULKANO_WWW_ADDR` or `ADDR`
+// environment variables, or else `DEFAULT_ADDR`.
+fn addr() -> Result<String, String> {
+    let mut args = env::args().skip(1);
+    let mut addr = None;
+
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--addr=") {
+            addr = Some(value.to_owned());
+        } else if arg == "--addr" || arg == "-a" {
+            addr = Some(
+                args.next()
+                    .ok_or_else(|| format!("Missing value after {}", arg))?,
+            );
+        } else {
+            return Err(format!("Unknown argument: {}", arg));
+        }
+    }
+
+    let addr = addr
+        .or_else(|| env::var("VULKANO_WWW_ADDR").ok())
+        .or_else(|| env::var("ADDR").ok())
+        .unwrap_or_else(|| DEFAULT_ADDR.to_owned());
+
+    match addr.to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(_)) => Ok(addr),
+        Ok(None) => Err(format!("The address {} doesn't resolve to anything", addr)),
+        Err(err) => Err(format!("Invalid address {}: {}", addr, err)),
+    }
 }
@@ -0,0 +1,116 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Bounded caches of rendered HTML.
+//!
+//! Entries are keyed on a hash of what was rendered rather than on the input itself, so that a
+//! cache doesn't hold a second copy of every page. When a cache is full, the entry that was used
+//! the least recently is evicted.
+
+use lru::LruCache;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Number of entries of each cache, unless changed with `set_capacity`.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_CAPACITY);
+
+/// Sets the capacity of the caches created by `RenderCache::new` from now on. Caches that already
+/// exist keep their capacity.
+pub fn set_capacity(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::Relaxed);
+}
+
+pub struct RenderCache<V> {
+    entries: Mutex<LruCache<u64, V>>,
+}
+
+impl<V: Clone> RenderCache<V> {
+    /// Creates a cache with the capacity given to `set_capacity`.
+    pub fn new() -> Self {
+        Self::with_capacity(CAPACITY.load(Ordering::Relaxed))
+    }
+
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).expect("the cache capacity must not be 0");
+
+        RenderCache {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Returns a clone of the value cached for `key`, calling `render` to compute it if it isn't
+    /// cached.
+    ///
+    /// The cache isn't locked while `render` runs, so that several pages can be rendered at once.
+    /// If two threads render the same page at the same time, both results are the same and the
+    /// second one replaces the first.
+    pub fn get_or_insert_with<K, F>(&self, key: &K, render: F) -> V
+    where
+        K: Hash + ?Sized,
+        F: FnOnce() -> V,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if let Some(value) = self.entries.lock().unwrap().get(&hash) {
+            return value.clone();
+        }
+
+        let value = render();
+        self.entries.lock().unwrap().put(hash, value.clone());
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = RenderCache::with_capacity(2);
+        cache.get_or_insert_with("a", || 1);
+        cache.get_or_insert_with("b", || 2);
+        // Makes "b" the oldest entry.
+        assert_eq!(cache.get_or_insert_with("a", || unreachable!()), 1);
+        cache.get_or_insert_with("c", || 3);
+
+        assert_eq!(cache.get_or_insert_with("a", || unreachable!()), 1);
+        assert_eq!(cache.get_or_insert_with("c", || unreachable!()), 3);
+        assert_eq!(cache.get_or_insert_with("b", || 4), 4);
+    }
+
+    #[test]
+    fn render_can_use_the_cache() {
+        let cache = RenderCache::with_capacity(2);
+        let value =
+            cache.get_or_insert_with("outer", || cache.get_or_insert_with("inner", || 1) + 1);
+
+        assert_eq!(value, 2);
+        assert_eq!(cache.get_or_insert_with("inner", || unreachable!()), 1);
+    }
+
+    #[test]
+    fn panic_in_render_keeps_the_cache_usable() {
+        let cache = RenderCache::with_capacity(2);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cache.get_or_insert_with("a", || panic!("render failed"))
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(cache.get_or_insert_with("a", || 1), 1);
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Anchors on the headings of the guide, so that a section can be linked to with
+//! `/guide/PAGE#SLUG`, and the table of contents built from them.
+
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::{Event, HeadingLevel, Tag};
+use std::collections::HashSet;
+
+/// A heading found by `add_anchors`.
+pub struct Heading {
+    pub level: HeadingLevel,
+    pub text: String,
+    pub slug: String,
+}
+
+/// Gives an `id` to every heading of a document, plus a trailing `#` link to the heading.
+///
+/// The id is the slug of the heading's text. When several headings of the same document have the
+/// same slug, the later ones are suffixed with `-1`, `-2`, and so on.
+///
+/// Returns the modified events and the headings of the document, in order.
+pub fn add_anchors<'a, I>(events: I) -> (Vec<Event<'a>>, Vec<Heading>)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut used_slugs = HashSet::new();
+    let mut output = Vec::new();
+    let mut headings = Vec::new();
+    // Position of the start of the heading being read in `output`, and its text.
+    let mut heading: Option<(usize, String)> = None;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading(..)) => {
+                heading = Some((output.len(), String::new()));
+                output.push(event);
+            }
+            Event::Text(ref text) | Event::Code(ref text) if heading.is_some() => {
+                heading.as_mut().unwrap().1.push_str(text);
+                output.push(event);
+            }
+            Event::End(Tag::Heading(level, _, _)) => {
+                let (start, text) = heading.take().unwrap();
+                let slug = unique_slug(&text, &mut used_slugs);
+                output[start] = Event::Html(format!("<{} id=\"{}\">", level, slug).into());
+                let anchor = format!(" <a href=\"#{}\" class=\"anchor\">#</a>", slug);
+                output.push(Event::Html(format!("{}</{}>\n", anchor, level).into()));
+                headings.push(Heading { level, text, slug });
+            }
+            event => output.push(event),
+        }
+    }
+
+    (output, headings)
+}
+
+/// Renders the `##` and `###` headings of a document as a nested list of links. Returns an empty
+/// string if there are fewer than two of them, as such a list wouldn't help navigating the page.
+pub fn table_of_contents(headings: &[Heading]) -> String {
+    let headings: Vec<_> = headings
+        .iter()
+        .filter(|h| matches!(h.level, HeadingLevel::H2 | HeadingLevel::H3))
+        .collect();
+
+    if headings.len() < 2 {
+        return String::new();
+    }
+
+    let mut html = String::from("<nav class=\"toc\">\n<h2>Contents</h2>\n<ul>\n");
+    // Whether a `##` item is open, and whether it contains an open list of `###` items. A `###`
+    // heading that comes before any `##` heading is put at the top level.
+    let mut item_open = false;
+    let mut sublist_open = false;
+
+    for heading in headings {
+        if heading.level == HeadingLevel::H3 && item_open {
+            if !sublist_open {
+                html.push_str("<ul>\n");
+                sublist_open = true;
+            }
+            html.push_str("<li>");
+            push_link(&mut html, heading);
+            html.push_str("</li>\n");
+        } else {
+            if sublist_open {
+                html.push_str("</ul>\n");
+                sublist_open = false;
+            }
+            if item_open {
+                html.push_str("</li>\n");
+            }
+            html.push_str("<li>");
+            push_link(&mut html, heading);
+            item_open = true;
+        }
+    }
+
+    if sublist_open {
+        html.push_str("</ul>\n");
+    }
+    if item_open {
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n</nav>\n");
+    html
+}
+
+fn push_link(html: &mut String, heading: &Heading) {
+    html.push_str("<a href=\"#");
+    html.push_str(&heading.slug);
+    html.push_str("\">");
+    escape_html(&mut *html, &heading.text).unwrap();
+    html.push_str("</a>");
+}
+
+// Turns "Example operation" into "example-operation", keeping only the characters that are safe in
+// both a URL fragment and an HTML attribute.
+fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() || c == '_' {
+            slug.extend(c.to_lowercase());
+        } else if c.is_whitespace() || c == '-' {
+            slug.push('-');
+        }
+    }
+
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+
+    slug
+}
+
+fn unique_slug(text: &str, used_slugs: &mut HashSet<String>) -> String {
+    let base = slugify(text);
+    let mut slug = base.clone();
+    let mut suffix = 1;
+
+    while used_slugs.contains(&slug) {
+        slug = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+
+    used_slugs.insert(slug.clone());
+    slug
+}
@@ -0,0 +1,136 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Syntax highlighting of the fenced code blocks of the guide.
+//!
+//! The highlighting is done once when a page is rendered, so that the browser receives spans that
+//! are already colored instead of having to run a highlighter on every page load.
+
+use pulldown_cmark::escape::escape_html;
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use std::fmt::Write;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+lazy_static::lazy_static! {
+    static ref SYNTAXES: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME: Theme = ThemeSet::load_defaults()
+        .themes
+        .remove("base16-ocean.dark")
+        .expect("missing highlighting theme");
+}
+
+/// Same as `pulldown_cmark::html::push_html`, except that fenced code blocks with a known language
+/// are highlighted.
+///
+/// A code block whose info string contains `linenos`, for example ` ```rust,linenos `, also gets
+/// line numbers.
+pub fn push_html<'a, I>(html: &mut String, events: I)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    // The code block being read, if any.
+    let mut code_block: Option<CodeBlock> = None;
+
+    let events = events.filter_map(|event| match event {
+        Event::Start(Tag::CodeBlock(kind)) => {
+            code_block = Some(match kind {
+                CodeBlockKind::Fenced(info) => CodeBlock::new(&info),
+                CodeBlockKind::Indented => CodeBlock::new(""),
+            });
+            None
+        }
+        Event::End(Tag::CodeBlock(_)) => {
+            let code_block = code_block.take().unwrap();
+            Some(Event::Html(code_block.to_html().into()))
+        }
+        Event::Text(text) if code_block.is_some() => {
+            code_block.as_mut().unwrap().code.push_str(&text);
+            None
+        }
+        event => Some(event),
+    });
+
+    pulldown_cmark::html::push_html(html, events);
+}
+
+struct CodeBlock {
+    language: String,
+    line_numbers: bool,
+    code: String,
+}
+
+impl CodeBlock {
+    // The info string is the language followed by options, separated by commas or spaces.
+    fn new(info: &str) -> Self {
+        let mut words = info.split(|c: char| c == ',' || c.is_whitespace());
+        let language = words.next().unwrap_or("").to_owned();
+        let line_numbers = words.any(|word| word == "linenos");
+
+        CodeBlock {
+            language,
+            line_numbers,
+            code: String::new(),
+        }
+    }
+
+    // Unknown or missing languages are written as plain text.
+    //
+    // Line numbers are empty spans whose number is shown by the stylesheet, so that they aren't
+    // part of the text that is copied from the page.
+    fn to_html(&self) -> String {
+        let mut html = String::from(if self.line_numbers {
+            "<pre class=\"highlight linenos\"><code>"
+        } else {
+            "<pre class=\"highlight\"><code>"
+        });
+
+        let mut highlighter =
+            find_syntax(&self.language).map(|syntax| HighlightLines::new(syntax, &THEME));
+
+        for (index, line) in LinesWithEndings::from(&self.code).enumerate() {
+            if self.line_numbers {
+                write!(html, "<span class=\"ln\" data-ln=\"{}\"></span>", index + 1).unwrap();
+            }
+
+            match &mut highlighter {
+                Some(highlighter) => {
+                    let regions = highlighter
+                        .highlight_line(line, &SYNTAXES)
+                        .expect("failed to highlight code block");
+                    let line = styled_line_to_highlighted_html(&regions, IncludeBackground::No)
+                        .expect("failed to highlight code block");
+                    html.push_str(&line);
+                }
+                None => escape_html(&mut html, line).unwrap(),
+            }
+        }
+
+        html.push_str("</code></pre>\n");
+        html
+    }
+}
+
+fn find_syntax(language: &str) -> Option<&'static SyntaxReference> {
+    // The default syntaxes don't include GLSL, whose syntax is close enough to C for the shaders of
+    // the guide.
+    let language = match language {
+        "glsl" => "c",
+        language => language,
+    };
+
+    if language.is_empty() {
+        return None;
+    }
+
+    SYNTAXES.find_syntax_by_token(language)
+}
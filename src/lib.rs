@@ -10,21 +10,114 @@
 #[macro_use]
 extern crate rouille;
 
+use cache::RenderCache;
+use pulldown_cmark::{Event, Tag};
 use rouille::Request;
 use rouille::Response;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use rouille::ResponseBody;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::net::ToSocketAddrs;
-use std::sync::Mutex;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// Runs the HTTP server forever on the given address.
-pub fn start<A>(addr: A)
+mod admonitions;
+mod cache;
+mod headings;
+mod highlight;
+mod minify;
+mod snippets;
+
+/// Options for `start`.
+pub struct StartConfig {
+    /// If true, every page is rendered in the background when the server starts, so that the first
+    /// request to each page doesn't have to wait for the markdown and templates. `/readyz` fails
+    /// until then.
+    pub precompile: bool,
+    /// Maximum number of rendered pages kept by each of the rendering caches.
+    pub cache_capacity: usize,
+    /// If true, the markdown of the guide is read from the disk on every request instead of being
+    /// embedded in the binary, so that changes show up without recompiling.
+    pub dev_mode: bool,
+    /// If true, `start` fails when a guide page links to a page that doesn't exist, instead of
+    /// only printing a warning.
+    pub deny_broken_links: bool,
+    /// If true, comments and needless whitespace are removed from the HTML pages.
+    pub minify_html: bool,
+}
+
+impl Default for StartConfig {
+    fn default() -> Self {
+        StartConfig {
+            precompile: true,
+            cache_capacity: cache::DEFAULT_CAPACITY,
+            dev_mode: false,
+            deny_broken_links: false,
+            minify_html: true,
+        }
+    }
+}
+
+// Value of `StartConfig::dev_mode`.
+static DEV_MODE: AtomicBool = AtomicBool::new(false);
+
+// Value of `StartConfig::minify_html`.
+static MINIFY_HTML: AtomicBool = AtomicBool::new(true);
+
+/// Runs the HTTP server on the given address until the process receives SIGINT or SIGTERM.
+/// Returns an error if the server can't listen on that address, if a guide page includes a code
+/// snippet that doesn't exist, or if a guide page has a broken link and `deny_broken_links` is
+/// set.
+///
+/// On shutdown, the requests being handled are given `SHUTDOWN_TIMEOUT` to finish before this
+/// function returns anyway.
+pub fn start<A>(addr: A, config: StartConfig) -> Result<(), Box<dyn Error + Send + Sync>>
 where
     A: ToSocketAddrs,
 {
-    rouille::start_server(addr, move |request| {
-        rouille::content_encoding::apply(
+    cache::set_capacity(config.cache_capacity);
+    DEV_MODE.store(config.dev_mode, Ordering::Relaxed);
+    MINIFY_HTML.store(config.minify_html, Ordering::Relaxed);
+
+    let guide_routes: Vec<_> = GUIDE_ROUTES.iter().chain(WIP_GUIDE_ROUTES).collect();
+    let other_paths: Vec<_> = OTHER_PAGES
+        .iter()
+        .copied()
+        .chain(REDIRECTS.iter().map(|&(from, _)| from))
+        .collect();
+    let broken_links = check_links(&guide_routes, &other_paths);
+    for link in &broken_links {
+        println!(
+            "Warning: {} links to {}, which doesn't exist",
+            link.page, link.target
+        );
+    }
+    if config.deny_broken_links && !broken_links.is_empty() {
+        return Err(format!("{} broken links in the guide", broken_links.len()).into());
+    }
+
+    // Rendering a page with a broken include would fail on every request to it.
+    for route in &guide_routes {
+        if let Err(err) = snippets::expand_includes(route.markdown) {
+            return Err(format!("{}: {}", route.file, err).into());
+        }
+    }
+
+    let server = rouille::Server::new(addr, move |request| {
+        let _active = ActiveRequest::new();
+
+        // The probes of the load balancer aren't logged, as they would drown the other requests.
+        if let Some(response) = probes(request) {
+            return response;
+        }
+
+        let response = rouille::content_encoding::apply(
             request,
             rouille::log(request, io::stdout(), || {
                 {
@@ -34,18 +127,430 @@ where
                             "Cache-Control".into(),
                             format!("max-age={}", 2 * 60 * 60).into(),
                         ));
-                        return r;
+                        return with_file_etag(r, request.url());
                     }
                 }
 
                 routes(request)
             }),
-        )
+        );
+
+        // Done after the encoding, which would otherwise give a body to the 304 response.
+        with_security_headers(not_modified(request, response))
+    })?;
+
+    // Requests that arrive in the meantime are rendered on demand, and `/readyz` fails until the
+    // caches are filled.
+    if config.precompile {
+        thread::spawn(|| {
+            precompile();
+            READY.store(true, Ordering::SeqCst);
+        });
+    } else {
+        READY.store(true, Ordering::SeqCst);
+    }
+
+    ctrlc::set_handler(|| SHUTDOWN.store(true, Ordering::SeqCst))?;
+    while !SHUTDOWN.load(Ordering::SeqCst) {
+        server.poll_timeout(Duration::from_millis(100));
+    }
+
+    println!(
+        "Shutting down, waiting for {} requests",
+        ACTIVE_REQUESTS.load(Ordering::SeqCst)
+    );
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    while ACTIVE_REQUESTS.load(Ordering::SeqCst) > 0 {
+        if Instant::now() >= deadline {
+            println!(
+                "Stopping with {} requests still running",
+                ACTIVE_REQUESTS.load(Ordering::SeqCst)
+            );
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+// Set once the server is ready to serve every page quickly.
+static READY: AtomicBool = AtomicBool::new(false);
+
+// Answers `/healthz`, which succeeds as long as the server is running, and `/readyz`, which
+// succeeds once the caches are filled.
+fn probes(request: &Request) -> Option<Response> {
+    if request.method() != "GET" {
+        return None;
+    }
+
+    match request.url().as_str() {
+        "/healthz" => Some(Response::text("ok")),
+        "/readyz" if READY.load(Ordering::SeqCst) => Some(Response::text("ready")),
+        "/readyz" => Some(Response::text("warming up").with_status_code(503)),
+        _ => None,
+    }
+}
+
+// How long the requests being handled have to finish when the server shuts down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Set by the signal handler when the process is asked to terminate.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+// Number of requests being handled.
+static ACTIVE_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+// Counts as an active request as long as it's alive, even if the handler panics.
+struct ActiveRequest;
+
+impl ActiveRequest {
+    fn new() -> Self {
+        ACTIVE_REQUESTS.fetch_add(1, Ordering::SeqCst);
+        ActiveRequest
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        ACTIVE_REQUESTS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Origins from which pages may load content. Besides the site itself, pages use:
+//
+// - Inline scripts and styles, including the colors of the highlighted code.
+// - Font Awesome for the icons, and Google Fonts from the stylesheets.
+// - The GitHub ribbon, served through GitHub's image proxy.
+// - The Patreon buttons of the donation page, a script that creates frames.
+const CONTENT_SECURITY_POLICY: &str = "\
+    default-src 'self'; \
+    script-src 'self' 'unsafe-inline' https://use.fontawesome.com https://c6.patreon.com; \
+    style-src 'self' 'unsafe-inline' https://fonts.googleapis.com; \
+    font-src 'self' https://fonts.gstatic.com; \
+    img-src 'self' data: https://camo.githubusercontent.com https://c6.patreon.com; \
+    frame-src https://www.patreon.com; \
+    frame-ancestors 'none'; \
+    base-uri 'self'; \
+    form-action 'self'";
+
+// Adds the headers that restrict what browsers allow a page to do. Only HTML pages get them, as
+// they don't mean anything for images or stylesheets.
+fn with_security_headers(response: Response) -> Response {
+    let is_html = response.headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("Content-Type") && value.starts_with("text/html")
+    });
+    if !is_html {
+        return response;
+    }
+
+    response
+        .with_unique_header("Content-Security-Policy", CONTENT_SECURITY_POLICY)
+        .with_unique_header("X-Content-Type-Options", "nosniff")
+        .with_unique_header("Referrer-Policy", "strict-origin-when-cross-origin")
+        .with_unique_header("X-Frame-Options", "DENY")
+}
+
+// Gives a static file an `ETag` that changes when the file is modified.
+fn with_file_etag(response: Response, url: &str) -> Response {
+    let path = Path::new("./static").join(url.trim_start_matches('/'));
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    response.with_unique_header("ETag", content_etag(&(url, modified)))
+}
+
+// Computes the value of an `ETag` header from something that identifies the content of a response.
+fn content_etag<T: Hash + ?Sized>(content: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+// Replaces a successful response with an empty `304 Not Modified` if its `ETag` is one of those in
+// the `If-None-Match` header of the request, meaning that the client already has the content.
+fn not_modified(request: &Request, response: Response) -> Response {
+    if response.status_code != 200 {
+        return response;
+    }
+
+    let etag = match response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("ETag"))
+    {
+        Some((_, etag)) => etag.clone(),
+        None => return response,
+    };
+
+    let matches = request.header("If-None-Match").map_or(false, |tags| {
+        tags.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
     });
+
+    if !matches {
+        return response;
+    }
+
+    Response {
+        status_code: 304,
+        headers: response.headers,
+        data: ResponseBody::empty(),
+        upgrade: None,
+    }
+}
+
+// Every page served by `routes` that isn't part of the guide.
+const OTHER_PAGES: &[&str] = &["/", "/donate", "/guide/all"];
+
+// Paths that used to serve a page, and the path where the page now is.
+const REDIRECTS: &[(&str, &str)] = &[("/guide/windowing", "/guide/windowing/introduction")];
+
+// A page of the guide.
+struct GuideRoute {
+    // Title of the group of pages in the navigation of the guide.
+    section: &'static str,
+    path: &'static str,
+    title: &'static str,
+    // Path of the markdown file, relative to the root of the crate.
+    file: &'static str,
+    // Content of `file` at compile time.
+    markdown: &'static str,
+}
+
+macro_rules! guide_route {
+    ($section:expr, $path:expr, $title:expr, $file:literal) => {
+        GuideRoute {
+            section: $section,
+            path: $path,
+            title: $title,
+            file: $file,
+            markdown: include_str!(concat!("../", $file)),
+        }
+    };
+}
+
+// The chapters of the guide, in reading order.
+static GUIDE_ROUTES: &[GuideRoute] = &[
+    guide_route!(
+        "Introduction",
+        "/guide/introduction",
+        "Introduction",
+        "content/guide/introduction/introduction.md"
+    ),
+    guide_route!(
+        "Initialization",
+        "/guide/initialization",
+        "Initialization",
+        "content/guide/initialization/initialization.md"
+    ),
+    guide_route!(
+        "Initialization",
+        "/guide/device-creation",
+        "Device creation",
+        "content/guide/initialization/device-creation.md"
+    ),
+    guide_route!(
+        "Buffer creation",
+        "/guide/buffer-creation",
+        "Creating a buffer",
+        "content/guide/buffer_creation/buffer_creation.md"
+    ),
+    guide_route!(
+        "Buffer creation",
+        "/guide/example-operation",
+        "Example operation",
+        "content/guide/buffer_creation/example_operation.md"
+    ),
+    guide_route!(
+        "Compute pipeline",
+        "/guide/compute-intro",
+        "Introduction to compute operations",
+        "content/guide/compute_pipeline/compute_intro.md"
+    ),
+    guide_route!(
+        "Compute pipeline",
+        "/guide/compute-pipeline",
+        "Compute pipelines",
+        "content/guide/compute_pipeline/compute_pipeline.md"
+    ),
+    guide_route!(
+        "Compute pipeline",
+        "/guide/descriptor-sets",
+        "Descriptor sets",
+        "content/guide/compute_pipeline/descriptor_sets.md"
+    ),
+    guide_route!(
+        "Compute pipeline",
+        "/guide/dispatch",
+        "Dispatch",
+        "content/guide/compute_pipeline/dispatch.md"
+    ),
+    guide_route!(
+        "Using images",
+        "/guide/image-creation",
+        "Image creation",
+        "content/guide/images/image_creation.md"
+    ),
+    guide_route!(
+        "Using images",
+        "/guide/image-clear",
+        "Clearing an image",
+        "content/guide/images/image_clear.md"
+    ),
+    guide_route!(
+        "Using images",
+        "/guide/image-export",
+        "Exporting the result",
+        "content/guide/images/image_export.md"
+    ),
+    guide_route!(
+        "Using images",
+        "/guide/mandelbrot",
+        "Drawing a fractal with a compute shader",
+        "content/guide/images/mandelbrot.md"
+    ),
+    guide_route!(
+        "Graphics pipeline",
+        "/guide/what-graphics-pipeline",
+        "What is the graphics pipeline?",
+        "content/guide/graphics_pipeline/introduction.md"
+    ),
+    guide_route!(
+        "Graphics pipeline",
+        "/guide/vertex-input",
+        "Vertex input",
+        "content/guide/graphics_pipeline/vertex_shader.md"
+    ),
+    guide_route!(
+        "Graphics pipeline",
+        "/guide/fragment-shader",
+        "Fragment shader",
+        "content/guide/graphics_pipeline/fragment_shader.md"
+    ),
+    guide_route!(
+        "Graphics pipeline",
+        "/guide/render-pass-framebuffer",
+        "Render passes and framebuffers",
+        "content/guide/graphics_pipeline/render_pass_framebuffer.md"
+    ),
+    guide_route!(
+        "Graphics pipeline",
+        "/guide/graphics-pipeline-creation",
+        "Putting it all together",
+        "content/guide/graphics_pipeline/pipeline_creation.md"
+    ),
+    guide_route!(
+        "Windowing",
+        "/guide/windowing/introduction",
+        "Window creation",
+        "content/guide/windowing/introduction.md"
+    ),
+    guide_route!(
+        "Windowing",
+        "/guide/windowing/swapchain-creation",
+        "Swapchain creation",
+        "content/guide/windowing/swapchain_creation.md"
+    ),
+    guide_route!(
+        "Windowing",
+        "/guide/windowing/other-initialization",
+        "Other initialization",
+        "content/guide/windowing/other_initialization.md"
+    ),
+    guide_route!(
+        "Windowing",
+        "/guide/windowing/event-handling",
+        "Event Handling: Acquiring and presenting",
+        "content/guide/windowing/event_handling.md"
+    ),
+];
+
+// Guide pages that are still being written. They are served, but aren't part of the chapters.
+// `/api/guide` only lists `GUIDE_ROUTES`, so their section isn't shown anywhere yet.
+static WIP_GUIDE_ROUTES: &[GuideRoute] = &[guide_route!(
+    "Work in progress",
+    "/guide/memory",
+    "Introduction to memory",
+    "content/guide/wip/memory.md"
+)];
+
+// A link from a guide page to a page of the site that doesn't exist.
+#[derive(Debug, PartialEq, Eq)]
+struct BrokenLink {
+    page: &'static str,
+    target: String,
+}
+
+// Checks that the links of the guide pages to other pages of the site, like `/guide/dispatch`,
+// point to one of `routes` or to one of `other_paths`. Links to other sites aren't checked.
+fn check_links(routes: &[&GuideRoute], other_paths: &[&str]) -> Vec<BrokenLink> {
+    let mut broken_links = Vec::new();
+
+    for route in routes {
+        for event in pulldown_cmark::Parser::new(route.markdown) {
+            let target = match event {
+                Event::Start(Tag::Link(_, target, _)) => target,
+                _ => continue,
+            };
+
+            // `//host/path` is a link to another site.
+            if !target.starts_with('/') || target.starts_with("//") {
+                continue;
+            }
+
+            let path = target.split(|c: char| c == '#' || c == '?').next().unwrap();
+            let exists = routes.iter().any(|route| route.path == path)
+                || other_paths.iter().any(|&other| other == path);
+            if !exists {
+                broken_links.push(BrokenLink {
+                    page: route.path,
+                    target: target.to_string(),
+                });
+            }
+        }
+    }
+
+    broken_links
+}
+
+// Requests every page once so that the rendering caches are filled.
+fn precompile() {
+    let start = Instant::now();
+
+    let guide_pages = GUIDE_ROUTES
+        .iter()
+        .chain(WIP_GUIDE_ROUTES)
+        .map(|route| route.path);
+    let pages = OTHER_PAGES.iter().copied().chain(guide_pages);
+    for page in pages.clone() {
+        let response = routes(&Request::fake_http("GET", page, vec![], vec![]));
+        if !response.is_success() {
+            println!("Failed to pre-render {}: {}", page, response.status_code);
+        }
+    }
+
+    println!(
+        "Warmed the caches with {} pages in {:?}",
+        pages.count(),
+        start.elapsed()
+    );
 }
 
 // Handles all the non-static routes.
 fn routes(request: &Request) -> Response {
+    if request.method() == "GET" {
+        let url = request.url();
+        if let Some(&(_, to)) = REDIRECTS.iter().find(|&&(from, _)| from == url) {
+            return Response::redirect_301(to);
+        }
+
+        let mut guide_routes = GUIDE_ROUTES.iter().chain(WIP_GUIDE_ROUTES);
+        if let Some(route) = guide_routes.find(|route| route.path == url) {
+            return guide_template_markdown(route);
+        }
+    }
+
     router!(request,
         (GET) (/) => {
             main_template(include_str!("../content/home.html"))
@@ -53,98 +558,13 @@ fn routes(request: &Request) -> Response {
         (GET) (/donate) => {
             main_template(include_str!("../content/donate.html"))
         },
-
-        (GET) (/guide/introduction) => {
-            guide_template_markdown(include_str!("../content/guide/introduction/introduction.md"))
-        },
-        (GET) (/guide/initialization) => {
-            guide_template_markdown(include_str!("../content/guide/initialization/initialization.md"))
-        },
-        (GET) (/guide/device-creation) => {
-            guide_template_markdown(include_str!("../content/guide/initialization/device-creation.md"))
-        },
-
-        (GET) (/guide/buffer-creation) => {
-            guide_template_markdown(include_str!("../content/guide/buffer_creation/buffer_creation.md"))
-        },
-        (GET) (/guide/example-operation) => {
-            guide_template_markdown(include_str!("../content/guide/buffer_creation/example_operation.md"))
-        },
-
-        (GET) (/guide/compute-intro) => {
-            guide_template_markdown(include_str!("../content/guide/compute_pipeline/compute_intro.md"))
-        },
-        (GET) (/guide/compute-pipeline) => {
-            guide_template_markdown(include_str!("../content/guide/compute_pipeline/compute_pipeline.md"))
-        },
-        (GET) (/guide/descriptor-sets) => {
-            guide_template_markdown(include_str!("../content/guide/compute_pipeline/descriptor_sets.md"))
-        },
-        (GET) (/guide/dispatch) => {
-            guide_template_markdown(include_str!("../content/guide/compute_pipeline/dispatch.md"))
-        },
-
-        (GET) (/guide/image-creation) => {
-            guide_template_markdown(include_str!("../content/guide/images/image_creation.md"))
-        },
-        (GET) (/guide/image-clear) => {
-            guide_template_markdown(include_str!("../content/guide/images/image_clear.md"))
-        },
-        (GET) (/guide/image-export) => {
-            guide_template_markdown(include_str!("../content/guide/images/image_export.md"))
-        },
-        (GET) (/guide/mandelbrot) => {
-            guide_template_markdown(include_str!("../content/guide/images/mandelbrot.md"))
-        },
-
-        (GET) (/guide/what-graphics-pipeline) => {
-            guide_template_markdown(include_str!("../content/guide/graphics_pipeline/introduction.md"))
-        },
-        (GET) (/guide/vertex-input) => {
-            guide_template_markdown(include_str!("../content/guide/graphics_pipeline/vertex_shader.md"))
-        },
-        (GET) (/guide/fragment-shader) => {
-            guide_template_markdown(include_str!("../content/guide/graphics_pipeline/fragment_shader.md"))
-        },
-        (GET) (/guide/render-pass-framebuffer) => {
-            guide_template_markdown({
-                include_str!("../content/guide/graphics_pipeline/render_pass_framebuffer.md")
-            })
-        },
-        (GET) (/guide/graphics-pipeline-creation) => {
-            guide_template_markdown({
-                include_str!("../content/guide/graphics_pipeline/pipeline_creation.md")
-            })
-        },
-
-        // todo: redirect to the other url
-        (GET) (/guide/windowing) => {
-            guide_template_markdown({
-                include_str!("../content/guide/windowing/introduction.md")
-            })
-        },
-        (GET) (/guide/windowing/introduction) => {
-            guide_template_markdown({
-                include_str!("../content/guide/windowing/introduction.md")
-            })
-        },
-        (GET) (/guide/windowing/swapchain-creation) => {
-            guide_template_markdown({
-                include_str!("../content/guide/windowing/swapchain_creation.md")
-            })
-        },
-        (GET) (/guide/windowing/other-initialization) => {
-            guide_template_markdown({
-                include_str!("../content/guide/windowing/other_initialization.md")
-            })
+        (GET) (/api/guide) => {
+            guide_api()
         },
-        (GET) (/guide/windowing/event-handling) => {
-            guide_template_markdown(include_str!("../content/guide/windowing/event_handling.md"))
+        (GET) (/guide/all) => {
+            guide_all()
         },
 
-        (GET) (/guide/memory) => {
-            guide_template_markdown(include_str!("../content/guide/wip/memory.md"))
-        },
         _ => {
             main_template(include_str!("../content/404.html"))
                 .with_status_code(404)
@@ -152,8 +572,30 @@ fn routes(request: &Request) -> Response {
     )
 }
 
+// An entry of `/api/guide`.
+#[derive(Serialize)]
+struct GuideEntry {
+    path: &'static str,
+    title: &'static str,
+    section: &'static str,
+}
+
+// Lists the chapters of the guide as JSON, in reading order.
+fn guide_api() -> Response {
+    let entries: Vec<_> = GUIDE_ROUTES
+        .iter()
+        .map(|route| GuideEntry {
+            path: route.path,
+            title: route.title,
+            section: route.section,
+        })
+        .collect();
+
+    Response::from_data("application/json", serde_json::to_string(&entries).unwrap())
+}
+
 // `body` is expected to be HTML code. Puts `body` inside of the main template and builds a
-// `Response` that contains the whole.
+// `Response` that contains the whole, with an `ETag` computed from the resulting page.
 fn main_template<S>(body: S) -> Response
 where
     S: Into<String>,
@@ -163,81 +605,351 @@ where
             mustache::compile_str(&include_str!("../content/template_main.html")).unwrap()
         };
 
-        static ref CACHE: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+        // Maps the body to the page and its `ETag`.
+        static ref CACHE: RenderCache<(String, String)> = RenderCache::new();
     }
 
     let body = body.into();
 
-    let mut compil_cache = CACHE.lock().unwrap();
-    let html = match compil_cache.entry(body) {
-        Entry::Occupied(e) => e.into_mut(),
-        Entry::Vacant(e) => {
-            let data = mustache::MapBuilder::new()
-                .insert_str("body", e.key().as_str())
-                .build();
-
-            let mut out = Vec::new();
-            MAIN_TEMPLATE.render_data(&mut out, &data).unwrap();
-            e.insert(String::from_utf8(out).unwrap())
+    let (html, etag) = CACHE.get_or_insert_with(&body, || {
+        let data = mustache::MapBuilder::new()
+            .insert_str("body", body.as_str())
+            .build();
+
+        let mut out = Vec::new();
+        MAIN_TEMPLATE.render_data(&mut out, &data).unwrap();
+        let mut html = String::from_utf8(out).unwrap();
+        if MINIFY_HTML.load(Ordering::Relaxed) {
+            html = minify::minify(&html);
         }
-    };
+        let etag = content_etag(&html);
+        (html, etag)
+    });
 
-    Response::html(html.clone())
+    Response::html(html).with_unique_header("ETag", etag)
 }
 
-// `body` is expected to be HTML code. Puts `body` inside of the guide template and builds a
-// `Response` that contains the whole.
-fn guide_template<S>(body: S) -> Response
-where
-    S: Into<String>,
-{
+// Puts a rendered guide page inside of the guide template and builds a `Response` that contains
+// the whole. `page` is the path of the page, used to link to the previous and next chapters.
+fn guide_template(page: &str, rendered: &RenderedMarkdown) -> Response {
     lazy_static::lazy_static! {
         static ref GUIDE_TEMPLATE: mustache::Template = {
             mustache::compile_str(&include_str!("../content/guide/template.html")).unwrap()
         };
 
-        static ref CACHE: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+        static ref CACHE: RenderCache<String> = RenderCache::new();
     }
 
-    let body = body.into();
+    let html = CACHE.get_or_insert_with(&(page, rendered), || {
+        let mut data = mustache::MapBuilder::new()
+            .insert_str("body", rendered.html.as_str())
+            .insert_str("toc", rendered.toc.as_str())
+            .insert_str("reading_time", rendered.reading_time.to_string());
 
-    let mut compil_cache = CACHE.lock().unwrap();
-    let html = match compil_cache.entry(body) {
-        Entry::Occupied(e) => e.into_mut(),
-        Entry::Vacant(e) => {
-            let data = mustache::MapBuilder::new()
-                .insert_str("body", e.key().as_str())
-                .build();
-
-            let mut out = Vec::new();
-            GUIDE_TEMPLATE.render_data(&mut out, &data).unwrap();
-            e.insert(String::from_utf8(out).unwrap())
+        // Pages that aren't chapters, like the work in progress ones, have no links.
+        if let Some(index) = GUIDE_ROUTES.iter().position(|route| route.path == page) {
+            if let Some(prev) = index.checked_sub(1).map(|i| &GUIDE_ROUTES[i]) {
+                data = data
+                    .insert_str("prev_url", prev.path)
+                    .insert_str("prev_title", prev.title);
+            }
+            if let Some(next) = GUIDE_ROUTES.get(index + 1) {
+                data = data
+                    .insert_str("next_url", next.path)
+                    .insert_str("next_title", next.title);
+            }
         }
+
+        let mut out = Vec::new();
+        GUIDE_TEMPLATE.render_data(&mut out, &data.build()).unwrap();
+        String::from_utf8(out).unwrap()
+    });
+
+    main_template(html)
+}
+
+// Turns the markdown of a guide page into HTML and calls `guide_template`.
+fn guide_template_markdown(route: &GuideRoute) -> Response {
+    guide_template(route.path, &render_guide_route(route))
+}
+
+// Renders the markdown of a guide page, or returns it from the cache.
+fn render_guide_route(route: &GuideRoute) -> RenderedMarkdown {
+    lazy_static::lazy_static! {
+        static ref CACHE: RenderCache<RenderedMarkdown> = RenderCache::new();
+    }
+
+    // The includes of the embedded pages are checked by `start`, so this can only fail in dev
+    // mode, after a page was edited.
+    let expand_and_render = |body: &str| {
+        let markdown = snippets::expand_includes(body)
+            .unwrap_or_else(|err| panic!("failed to expand guide snippets: {}", err));
+        render_markdown(&markdown)
     };
 
-    main_template(html.clone())
+    if DEV_MODE.load(Ordering::Relaxed) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(route.file);
+        let body = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("failed to read {}: {}", path.display(), err));
+        expand_and_render(&body)
+    } else {
+        CACHE.get_or_insert_with(route.markdown, || expand_and_render(route.markdown))
+    }
 }
 
-// `body` is expected to be markdown. Turns it into HTML and calls `guide_template`.
-fn guide_template_markdown<S>(body: S) -> Response
-where
-    S: Into<String>,
-{
+// Puts every chapter of the guide in a single page meant to be read offline or printed. The links
+// between chapters are turned into links within the page.
+fn guide_all() -> Response {
     lazy_static::lazy_static! {
-        static ref CACHE: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+        static ref ALL_TEMPLATE: mustache::Template = {
+            mustache::compile_str(&include_str!("../content/guide/all.html")).unwrap()
+        };
+
+        static ref CACHE: RenderCache<(String, String)> = RenderCache::new();
     }
 
-    let body = body.into();
+    let render = || {
+        let chapters: Vec<_> = GUIDE_ROUTES
+            .iter()
+            .map(|route| (route, scope_anchors(&render_guide_route(route).html, route)))
+            .collect();
+
+        let data = mustache::MapBuilder::new()
+            .insert_vec("chapters", |mut vec| {
+                for (index, (route, html)) in chapters.iter().enumerate() {
+                    vec = vec.push_map(|chapter| {
+                        chapter
+                            .insert_str("id", chapter_id(route))
+                            .insert_str("number", (index + 1).to_string())
+                            .insert_str("section", route.section)
+                            .insert_str("title", route.title)
+                            .insert_str("html", html.as_str())
+                    });
+                }
+                vec
+            })
+            .build();
 
-    let mut compil_cache = CACHE.lock().unwrap();
-    let html = match compil_cache.entry(body) {
-        Entry::Occupied(e) => e.into_mut(),
-        Entry::Vacant(e) => {
-            let mut html = String::new();
-            pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(e.key()));
-            e.insert(html)
+        let mut out = Vec::new();
+        ALL_TEMPLATE.render_data(&mut out, &data).unwrap();
+        let mut html = String::from_utf8(out).unwrap();
+        if MINIFY_HTML.load(Ordering::Relaxed) {
+            html = minify::minify(&html);
         }
+        let etag = content_etag(&html);
+        (html, etag)
     };
 
-    guide_template(html.clone())
+    // The chapters are read from the disk on every request in dev mode, so the page can't be
+    // cached.
+    let (html, etag) = if DEV_MODE.load(Ordering::Relaxed) {
+        render()
+    } else {
+        CACHE.get_or_insert_with("/guide/all", render)
+    };
+
+    Response::html(html).with_unique_header("ETag", etag)
+}
+
+// The `id` of a chapter in `/guide/all`, like `windowing-introduction`.
+fn chapter_id(route: &GuideRoute) -> String {
+    route.path.trim_start_matches("/guide/").replace('/', "-")
+}
+
+// Prepares the HTML of the chapter `route` to be put in `/guide/all` with the other chapters. The
+// ids of its headings are prefixed with the id of the chapter, as several chapters have headings
+// with the same name, and the links to chapters or to their headings become links within the
+// page, like `#dispatch` for `/guide/dispatch` and `#dispatch-usage` for `/guide/dispatch#usage`.
+fn scope_anchors(html: &str, route: &GuideRoute) -> String {
+    let id = chapter_id(route);
+    let mut html = html
+        .replace("id=\"", &format!("id=\"{}-", id))
+        .replace("href=\"#", &format!("href=\"#{}-", id));
+
+    for other in GUIDE_ROUTES {
+        let other_id = chapter_id(other);
+        html = html
+            .replace(
+                &format!("href=\"{}#", other.path),
+                &format!("href=\"#{}-", other_id),
+            )
+            .replace(
+                &format!("href=\"{}\"", other.path),
+                &format!("href=\"#{}\"", other_id),
+            );
+    }
+
+    html
+}
+
+// A guide page rendered from markdown.
+#[derive(Clone, Hash)]
+struct RenderedMarkdown {
+    html: String,
+    // HTML of the table of contents of the page.
+    toc: String,
+    // Estimated number of minutes needed to read the page.
+    reading_time: usize,
+}
+
+// Reading speed used to estimate `RenderedMarkdown::reading_time`.
+const WORDS_PER_MINUTE: usize = 200;
+
+// Turns markdown into the HTML of a page and of its table of contents.
+fn render_markdown(markdown: &str) -> RenderedMarkdown {
+    let mut options = pulldown_cmark::Options::empty();
+    options.insert(pulldown_cmark::Options::ENABLE_TABLES);
+    options.insert(pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
+    options.insert(pulldown_cmark::Options::ENABLE_TASKLISTS);
+    options.insert(pulldown_cmark::Options::ENABLE_FOOTNOTES);
+
+    let parser = pulldown_cmark::Parser::new_ext(markdown, options);
+    let (events, sections) = headings::add_anchors(parser);
+    let events = admonitions::convert(events);
+    let reading_time = reading_time(&events);
+    let mut html = String::new();
+    highlight::push_html(&mut html, events.into_iter());
+    let toc = headings::table_of_contents(&sections);
+
+    RenderedMarkdown {
+        html,
+        toc,
+        reading_time,
+    }
+}
+
+// Counts the words of a page, except for those in code blocks, and turns that into a number of
+// minutes. Pages that are almost only code still take a minute.
+fn reading_time(events: &[Event]) -> usize {
+    let mut in_code_block = false;
+    let mut words = 0;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Text(text) | Event::Code(text) if !in_code_block => {
+                words += text.split_whitespace().count();
+            }
+            _ => {}
+        }
+    }
+
+    ((words + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn broken_links() {
+        let chapter = GuideRoute {
+            section: "Test",
+            path: "/guide/chapter",
+            title: "Chapter",
+            file: "",
+            markdown: "[home](/) [next](/guide/next#section) [typo](/guide/nxet)\n\
+                       [other](https://example.com/x) [local](#s) [donate](/donate?x=1)",
+        };
+        let next = GuideRoute {
+            section: "Test",
+            path: "/guide/next",
+            title: "Next",
+            file: "",
+            markdown: "[back](/guide/chapter)",
+        };
+
+        assert_eq!(
+            check_links(&[&chapter, &next], &["/", "/donate"]),
+            vec![BrokenLink {
+                page: "/guide/chapter",
+                target: "/guide/nxet".to_owned(),
+            }],
+        );
+    }
+
+    #[test]
+    fn guide_api_lists_chapters() {
+        let request = Request::fake_http("GET", "/api/guide", vec![], vec![]);
+        let response = routes(&request);
+        assert!(response
+            .headers
+            .iter()
+            .any(|(name, value)| name == "Content-Type" && value == "application/json"));
+
+        let mut body = String::new();
+        let (mut reader, _) = response.data.into_reader_and_size();
+        reader.read_to_string(&mut body).unwrap();
+        let entries: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(entries[0]["path"], "/guide/introduction");
+        assert_eq!(entries[0]["title"], "Introduction");
+        assert_eq!(entries[0]["section"], "Introduction");
+        assert_eq!(entries.as_array().unwrap().len(), GUIDE_ROUTES.len());
+    }
+
+    #[test]
+    fn guide_all_anchors() {
+        let dispatch = GUIDE_ROUTES
+            .iter()
+            .find(|route| route.path == "/guide/dispatch")
+            .unwrap();
+        let html = scope_anchors(
+            "<h2 id=\"usage\"><a href=\"#usage\" class=\"anchor\">#</a></h2>\n\
+             <a href=\"/guide/compute-pipeline\">a</a> \
+             <a href=\"/guide/windowing/introduction#setup\">b</a> \
+             <a href=\"/donate\">c</a>",
+            dispatch,
+        );
+
+        assert_eq!(
+            html,
+            "<h2 id=\"dispatch-usage\"><a href=\"#dispatch-usage\" class=\"anchor\">#</a></h2>\n\
+             <a href=\"#compute-pipeline\">a</a> \
+             <a href=\"#windowing-introduction-setup\">b</a> \
+             <a href=\"/donate\">c</a>",
+        );
+    }
+
+    #[test]
+    fn redirects() {
+        let request = Request::fake_http("GET", "/guide/windowing", vec![], vec![]);
+        let response = routes(&request);
+        assert_eq!(response.status_code, 301);
+        assert!(response
+            .headers
+            .iter()
+            .any(|(name, value)| name == "Location" && value == "/guide/windowing/introduction"));
+    }
+
+    #[test]
+    fn markdown_extensions() {
+        let html = render_markdown(
+            "| Usage | Host visible |\n\
+             |-------|--------------|\n\
+             | `Upload` | yes |\n\
+             \n\
+             ~~removed~~\n\
+             \n\
+             - [x] done\n",
+        )
+        .html;
+
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<td><code>Upload</code></td>"));
+        assert!(html.contains("<del>removed</del>"));
+        assert!(html.contains("<input disabled=\"\" type=\"checkbox\" checked=\"\"/>"));
+    }
+
+    #[test]
+    fn reading_time_ignores_code() {
+        let code = "let x = 5;\n".repeat(1000);
+        let rendered = render_markdown(&format!("# Title\n\n```rust\n{}```\n", code));
+        assert_eq!(rendered.reading_time, 1);
+
+        let prose = "word ".repeat(450);
+        let rendered = render_markdown(&format!("{}\n\n```rust\n{}```\n", prose, code));
+        assert_eq!(rendered.reading_time, 3);
+    }
 }
@@ -0,0 +1,85 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Shrinking of the generated HTML.
+
+// Elements whose content is written as is, because whitespace matters in them.
+const PRESERVED_ELEMENTS: &[&str] = &["pre", "code", "script", "style", "textarea"];
+
+/// Removes the comments of an HTML document and collapses each run of whitespace into a single
+/// space, except inside of the elements where whitespace matters, like `<pre>`.
+pub fn minify(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("<!--") {
+            rest = match rest.find("-->") {
+                Some(end) => &rest[end + 3..],
+                None => "",
+            };
+        } else if let Some(len) = preserved_element_len(rest) {
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+        } else if c.is_whitespace() {
+            if !output.ends_with(' ') {
+                output.push(' ');
+            }
+            rest = rest.trim_start();
+        } else {
+            output.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    output
+}
+
+// If `html` starts with one of `PRESERVED_ELEMENTS`, returns the length of the element up to its
+// closing tag included.
+fn preserved_element_len(html: &str) -> Option<usize> {
+    let tag = html.strip_prefix('<')?;
+    let name_len = tag
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(tag.len());
+    let name = tag[..name_len].to_ascii_lowercase();
+
+    if !PRESERVED_ELEMENTS.contains(&name.as_str()) {
+        return None;
+    }
+    if !tag[name_len..].starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+        return None;
+    }
+
+    // Elements of the same kind aren't nested in the pages of the site, so the first closing tag
+    // is the right one.
+    let closing_tag = format!("</{}", name);
+    let close = html.to_ascii_lowercase().find(&closing_tag)?;
+    let end = html[close..].find('>')?;
+
+    Some(close + end + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_preformatted_text() {
+        let html = "<div>\n    <!-- navigation -->\n    <p>Some   text</p>\n\n\
+                    <pre class=\"highlight\"><code>fn main() {\n    foo();\n}\n</code></pre>\n\
+                    </div>\n";
+
+        assert_eq!(
+            minify(html),
+            "<div> <p>Some text</p> \
+             <pre class=\"highlight\"><code>fn main() {\n    foo();\n}\n</code></pre> </div> ",
+        );
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (c) 2017 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+//! Code snippets taken from the compiled examples in `chapter_code`.
+//!
+//! A guide page can contain `{{include:FILE#REGION}}`, where `FILE` is a path relative to
+//! `chapter_code/src/bin` and `REGION` is the name of a region in that file:
+//!
+//! ```ignore
+//! // region: example-operation
+//! let source = ...;
+//! // endregion
+//! ```
+//!
+//! The directive is replaced with the lines between the two markers, so that the code shown in the
+//! guide is always the code that actually compiles.
+
+const INCLUDE_START: &str = "{{include:";
+const INCLUDE_END: &str = "}}";
+
+const REGION_START: &str = "// region:";
+const REGION_END: &str = "// endregion";
+
+// Sources that can be included from the guide.
+const CHAPTER_SOURCES: &[(&str, &str)] = &[
+    (
+        "buffer_creation.rs",
+        include_str!("../chapter_code/src/bin/buffer_creation.rs"),
+    ),
+    (
+        "compute_pipeline.rs",
+        include_str!("../chapter_code/src/bin/compute_pipeline.rs"),
+    ),
+    (
+        "graphics_pipeline.rs",
+        include_str!("../chapter_code/src/bin/graphics_pipeline.rs"),
+    ),
+    (
+        "windowing.rs",
+        include_str!("../chapter_code/src/bin/windowing.rs"),
+    ),
+    (
+        "images/image_clear.rs",
+        include_str!("../chapter_code/src/bin/images/image_clear.rs"),
+    ),
+    (
+        "images/mandelbrot.rs",
+        include_str!("../chapter_code/src/bin/images/mandelbrot.rs"),
+    ),
+];
+
+/// Replaces every `{{include:FILE#REGION}}` in `markdown` with the content of the region.
+///
+/// Returns an error naming the directive if the file or the region doesn't exist.
+pub fn expand_includes(markdown: &str) -> Result<String, String> {
+    let mut output = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+
+    while let Some(start) = rest.find(INCLUDE_START) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start + INCLUDE_START.len()..];
+
+        let end = rest
+            .find(INCLUDE_END)
+            .ok_or_else(|| format!("unterminated `{}` directive", INCLUDE_START))?;
+        let directive = &rest[..end];
+        rest = &rest[end + INCLUDE_END.len()..];
+
+        let (file, region) = directive.split_once('#').ok_or_else(|| {
+            format!(
+                "`{}{}{}` is missing a region, expected FILE#REGION",
+                INCLUDE_START, directive, INCLUDE_END
+            )
+        })?;
+
+        let source = CHAPTER_SOURCES
+            .iter()
+            .find(|(name, _)| *name == file)
+            .map(|(_, source)| *source)
+            .ok_or_else(|| format!("unknown chapter source file `{}`", file))?;
+
+        output.push_str(
+            &extract_region(source, region)
+                .ok_or_else(|| format!("region `{}` not found in `{}`", region, file))?,
+        );
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+// Returns the lines between `// region: NAME` and the matching `// endregion`, without their
+// common indentation. Markers of nested regions are left out.
+fn extract_region(source: &str, name: &str) -> Option<String> {
+    let mut lines = source.lines();
+    lines.find(|line| line.trim().strip_prefix(REGION_START).map(str::trim) == Some(name))?;
+
+    let mut depth = 0;
+    let mut region = Vec::new();
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.starts_with(REGION_START) {
+            depth += 1;
+        } else if trimmed.starts_with(REGION_END) {
+            if depth == 0 {
+                return Some(dedent(&region));
+            }
+            depth -= 1;
+        } else {
+            region.push(line);
+        }
+    }
+
+    // The region was never closed.
+    None
+}
+
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i != 0 {
+            output.push('\n');
+        }
+        output.push_str(line.get(indent..).unwrap_or("").trim_end());
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+fn main() {
+    // region: outer
+    let a = 1;
+    // region: inner
+    if a == 1 {
+        println!(\"one\");
+    }
+    // endregion
+
+    let b = 2;
+    // endregion
+    // region: unterminated
+    let c = 3;
+}";
+
+    #[test]
+    fn region() {
+        assert_eq!(
+            extract_region(SOURCE, "inner").unwrap(),
+            "if a == 1 {\n    println!(\"one\");\n}"
+        );
+    }
+
+    #[test]
+    fn nested_region_markers_are_left_out() {
+        assert_eq!(
+            extract_region(SOURCE, "outer").unwrap(),
+            "let a = 1;\nif a == 1 {\n    println!(\"one\");\n}\n\nlet b = 2;"
+        );
+    }
+
+    #[test]
+    fn missing_region() {
+        assert_eq!(extract_region(SOURCE, "missing"), None);
+    }
+
+    #[test]
+    fn unterminated_region() {
+        assert_eq!(extract_region(SOURCE, "unterminated"), None);
+    }
+
+    #[test]
+    fn dedent_keeps_relative_indentation() {
+        assert_eq!(
+            dedent(&["        a", "", "            b  ", "        c"]),
+            "a\n\n    b\nc"
+        );
+        assert_eq!(dedent(&[]), "");
+    }
+
+    #[test]
+    fn unknown_include() {
+        assert!(expand_includes("{{include:missing.rs#region}}").is_err());
+        assert!(expand_includes("{{include:windowing.rs#missing}}").is_err());
+        assert!(expand_includes("{{include:windowing.rs}}").is_err());
+    }
+}